@@ -0,0 +1,71 @@
+// Reports how far the visitor got in the video of the watch page, so that it can be resumed:
+// through the IFrame API for embedded YouTube players and through the element for downloaded
+// copies. Players of privacy frontends don't have an API, so they are left alone.
+(function () {
+    const player = document.querySelector(".player[data-history]");
+    if (!player) {
+        return;
+    }
+    // How often the position is reported while playing, in milliseconds.
+    const interval = 15000;
+    let reported = Number(player.dataset.position);
+
+    function report(position) {
+        position = Math.floor(position);
+        if (position === reported) {
+            return;
+        }
+        reported = position;
+        fetch(player.dataset.history, {
+            method: "POST",
+            body: new URLSearchParams({ position: position }),
+            keepalive: true,
+        });
+    }
+
+    const native = player.querySelector("video");
+    if (native) {
+        native.addEventListener("loadedmetadata", function () {
+            native.currentTime = Number(player.dataset.position);
+        }, { once: true });
+        native.addEventListener("pause", function () {
+            report(native.currentTime);
+        });
+        native.addEventListener("ended", function () {
+            report(0);
+        });
+        setInterval(function () {
+            if (!native.paused) {
+                report(native.currentTime);
+            }
+        }, interval);
+        return;
+    }
+
+    const iframe = document.getElementById("embedded-player");
+    if (!iframe || !iframe.src.startsWith("https://www.youtube.com/")) {
+        return;
+    }
+    window.onYouTubeIframeAPIReady = function () {
+        const youtube = new YT.Player(iframe, {
+            events: {
+                onStateChange: function (event) {
+                    if (event.data === YT.PlayerState.ENDED) {
+                        report(0);
+                    } else if (event.data === YT.PlayerState.PAUSED) {
+                        report(youtube.getCurrentTime());
+                    }
+                },
+            },
+        });
+        setInterval(function () {
+            if (youtube.getPlayerState && youtube.getPlayerState() === YT.PlayerState.PLAYING) {
+                report(youtube.getCurrentTime());
+            }
+        }, interval);
+    };
+
+    const script = document.createElement("script");
+    script.src = "https://www.youtube.com/iframe_api";
+    document.head.appendChild(script);
+})();
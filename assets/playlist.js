@@ -0,0 +1,93 @@
+// Plays the entries of #playlist one after another: YouTube videos through the IFrame API and
+// direct media files through a native <video> element.
+(function () {
+    const entries = Array.from(document.querySelectorAll("#playlist li"));
+    const native = document.getElementById("native-player");
+    // The IFrame API replaces #youtube-player with its iframe, so toggle its container instead.
+    const youtubeContainer = document.getElementById("youtube-container");
+
+    let current = -1;
+    let youtube = null;
+    let youtubeReady = false;
+    let pendingYoutubeId = null;
+
+    function play(index) {
+        if (index < 0 || index >= entries.length) {
+            return;
+        }
+
+        if (current >= 0) {
+            entries[current].classList.remove("playing");
+        }
+        current = index;
+        const entry = entries[current];
+        entry.classList.add("playing");
+        entry.scrollIntoView({ block: "nearest" });
+
+        const youtubeId = entry.dataset.youtubeId;
+        if (youtubeId) {
+            native.pause();
+            native.hidden = true;
+            youtubeContainer.hidden = false;
+            playYoutube(youtubeId);
+        } else {
+            if (youtube) {
+                youtube.stopVideo();
+            }
+            youtubeContainer.hidden = true;
+            native.hidden = false;
+            native.src = entry.dataset.src;
+            native.play();
+        }
+    }
+
+    function playYoutube(id) {
+        if (!youtubeReady) {
+            pendingYoutubeId = id;
+            return;
+        }
+        if (youtube) {
+            youtube.loadVideoById(id);
+            return;
+        }
+        youtube = new YT.Player("youtube-player", {
+            videoId: id,
+            playerVars: { autoplay: 1 },
+            events: {
+                onStateChange: function (event) {
+                    if (event.data === YT.PlayerState.ENDED) {
+                        play(current + 1);
+                    }
+                },
+            },
+        });
+    }
+
+    window.onYouTubeIframeAPIReady = function () {
+        youtubeReady = true;
+        if (pendingYoutubeId) {
+            playYoutube(pendingYoutubeId);
+        }
+    };
+
+    native.addEventListener("ended", function () {
+        play(current + 1);
+    });
+    document.getElementById("playlist-previous").addEventListener("click", function () {
+        play(current - 1);
+    });
+    document.getElementById("playlist-next").addEventListener("click", function () {
+        play(current + 1);
+    });
+    entries.forEach(function (entry, index) {
+        entry.addEventListener("dblclick", function () {
+            play(index);
+        });
+    });
+
+    const script = document.createElement("script");
+    script.src = "https://www.youtube.com/iframe_api";
+    document.head.appendChild(script);
+
+    play(0);
+})();
@@ -0,0 +1,27 @@
+// Follow the progress of a refresh through server-sent events.
+//
+// Elements with a `data-progress-events` URL get their `<progress>` bar and `.progress-label`
+// updated. Elements that also have `data-reload` reload the page once the refresh is over, so
+// that the fresh video list shows up.
+document.querySelectorAll("[data-progress-events]").forEach((element) => {
+    const source = new EventSource(element.dataset.progressEvents);
+    const bar = element.querySelector("progress");
+    const label = element.querySelector(".progress-label");
+
+    source.addEventListener("progress", (event) => {
+        const status = JSON.parse(event.data);
+        if (bar) {
+            bar.max = status.total || 1;
+            bar.value = status.done;
+        }
+        if (label) {
+            label.textContent = `${status.done}/${status.total}`;
+        }
+        if (status.status !== "running") {
+            source.close();
+            if (element.dataset.reload !== undefined) {
+                location.reload();
+            }
+        }
+    });
+});
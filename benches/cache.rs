@@ -0,0 +1,57 @@
+//! Benchmarks of the SQLite response cache.
+use criterion::{criterion_group, criterion_main, Criterion};
+use hnv::cache::Cache;
+use tokio::runtime::Runtime;
+
+const ITEM: &str = r#"{"by":"someone","descendants":42,"id":40000000,"score":123,"time":1714000000,"title":"A talk about Rust","type":"story","url":"https://www.youtube.com/watch?v=dQw4w9WgXcQ"}"#;
+
+/// How many items the cache is filled with before reading from it.
+const ITEMS: usize = 500;
+
+fn item_url(id: usize) -> String {
+    format!("https://hacker-news.firebaseio.com/v0/item/{}.json", id)
+}
+
+fn cache(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let path = std::env::temp_dir().join(format!("hnv-bench-{}.db", std::process::id()));
+    let cache = runtime.block_on(async {
+        let cache = Cache::open(&path).await.unwrap();
+        for id in 0..ITEMS {
+            cache.set(&item_url(id), ITEM).await.unwrap();
+        }
+        cache.flush().await.unwrap();
+        cache
+    });
+
+    c.bench_function("cache_get_hit", |b| {
+        let mut id = 0;
+        b.to_async(&runtime).iter(|| {
+            id = (id + 1) % ITEMS;
+            let url = item_url(id);
+            let cache = &cache;
+            async move { cache.get(&url).await.unwrap() }
+        })
+    });
+
+    c.bench_function("cache_get_miss", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { cache.get(&item_url(ITEMS + 1)).await.unwrap() })
+    });
+
+    c.bench_function("cache_set", |b| {
+        let mut id = ITEMS;
+        b.to_async(&runtime).iter(|| {
+            id += 1;
+            let url = item_url(id);
+            let cache = &cache;
+            async move { cache.set(&url, ITEM).await.unwrap() }
+        })
+    });
+
+    drop(cache);
+    let _ = std::fs::remove_file(&path);
+}
+
+criterion_group!(benches, cache);
+criterion_main!(benches);
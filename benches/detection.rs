@@ -0,0 +1,38 @@
+//! Benchmarks of classifying Hacker News items.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use hnv::hacker_news::{detect, is_video, youtube_id, Item};
+
+const YOUTUBE_ITEM: &str = r#"{"by":"someone","descendants":42,"id":40000000,"kids":[40000001,40000002],"score":123,"time":1714000000,"title":"A talk about Rust","type":"story","url":"https://www.youtube.com/watch?v=dQw4w9WgXcQ"}"#;
+
+const ARTICLE_ITEM: &str = r#"{"by":"someone","descendants":7,"id":40000003,"kids":[40000004],"score":45,"time":1714000000,"title":"An article about Rust","type":"story","url":"https://example.com/blog/rust"}"#;
+
+const ASK_ITEM: &str = r#"{"by":"someone","descendants":3,"id":40000005,"score":12,"text":"What are you working on?","time":1714000000,"title":"Ask HN: What are you working on?","type":"story"}"#;
+
+fn detection(c: &mut Criterion) {
+    let mut group = c.benchmark_group("detect");
+    for (name, json) in [
+        ("youtube", YOUTUBE_ITEM),
+        ("article", ARTICLE_ITEM),
+        ("ask", ASK_ITEM),
+    ] {
+        group.bench_function(name, |b| b.iter(|| detect(black_box(json)).unwrap()));
+    }
+    group.finish();
+
+    c.bench_function("is_video", |b| {
+        b.iter(|| is_video(black_box(YOUTUBE_ITEM)).unwrap())
+    });
+}
+
+fn parsing(c: &mut Criterion) {
+    c.bench_function("parse_item", |b| {
+        b.iter(|| Item::parse(black_box(YOUTUBE_ITEM)).unwrap())
+    });
+
+    c.bench_function("youtube_id", |b| {
+        b.iter(|| youtube_id(black_box("https://www.youtube.com/watch?v=dQw4w9WgXcQ")))
+    });
+}
+
+criterion_group!(benches, detection, parsing);
+criterion_main!(benches);
@@ -0,0 +1,49 @@
+//! Embed the commit and the time of the build, for `/version` and the page footer.
+//!
+//! Packagers building outside of a git checkout can set `HNV_GIT_HASH` themselves, and
+//! `SOURCE_DATE_EPOCH` pins the build time for reproducible builds.
+use std::{process::Command, time::SystemTime};
+
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=HNV_GIT_HASH");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    // Rebuilt on every commit and checkout, rather than on every change of any file.
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/logs/HEAD");
+
+    let git_hash = std::env::var("HNV_GIT_HASH")
+        .ok()
+        .or_else(|| {
+            let output = Command::new("git")
+                .args(["rev-parse", "--short=12", "HEAD"])
+                .output()
+                .ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let built_at = match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => OffsetDateTime::from_unix_timestamp(
+            epoch
+                .parse()
+                .expect("SOURCE_DATE_EPOCH must be a number of seconds"),
+        )
+        .expect("SOURCE_DATE_EPOCH is out of range"),
+        Err(_) => OffsetDateTime::from(SystemTime::now()),
+    };
+
+    println!("cargo:rustc-env=HNV_GIT_HASH={}", git_hash);
+    println!(
+        "cargo:rustc-env=HNV_BUILT_AT={}",
+        built_at
+            .replace_nanosecond(0)
+            .unwrap()
+            .format(&Rfc3339)
+            .unwrap()
+    );
+}
@@ -0,0 +1,733 @@
+/// Publishing new videos to the Fediverse as an ActivityPub actor.
+///
+/// The instance is a single actor, `@videos@host` by default, which Fediverse users find through
+/// WebFinger and follow from their own accounts. Follows are accepted right away, and every new
+/// video is delivered to the inboxes of the followers as a Note linking to it.
+///
+/// Requests between servers are signed with HTTP signatures, as Mastodon and most others
+/// require. The RSA key of the actor is made on the first start and kept in the store, so
+/// followers keep trusting it across restarts. Signatures on requests to the inbox are only
+/// accepted for a few minutes after their date, and the actors that made them are only fetched
+/// over HTTPS and cached for a while. Hosts that resolve to loopback, private or link-local
+/// addresses are never connected to, nor are redirects followed.
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use axum::http::HeaderMap;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use openssl::{
+    hash::MessageDigest,
+    pkey::{PKey, Private},
+    rsa::Rsa,
+    sign::{Signer, Verifier},
+};
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect::Policy,
+    Client, Method, Url,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    config::{ActivityPubConfig, NotifyRule},
+    notify::Notifier,
+    store::Store,
+    video::Video,
+};
+
+/// The media type of ActivityPub documents.
+pub const CONTENT_TYPE: &str = "application/activity+json";
+
+/// The name the signing key is kept under in the store.
+const KEY_NAME: &str = "activitypub";
+
+const CONTEXT: [&str; 2] = [
+    "https://www.w3.org/ns/activitystreams",
+    "https://w3id.org/security/v1",
+];
+
+/// The audience of public posts.
+const PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+/// How far the date of a signed request may be from now, to allow for clocks that are off and
+/// slow deliveries, but not for replaying requests.
+const MAX_DATE_SKEW: Duration = Duration::from_secs(5 * 60);
+
+/// How long a fetched actor, with its key, is used to check signatures.
+const ACTOR_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// How many actors are cached at most.
+const MAX_CACHED_ACTORS: usize = 1000;
+
+#[derive(Clone)]
+pub struct ActivityPub {
+    client: Client,
+    config: ActivityPubConfig,
+    store: Store,
+    /// The public URL of the site, without a trailing slash.
+    base: String,
+    /// The host in the address of the actor, e.g. `videos.example.com`.
+    host: String,
+    key: PKey<Private>,
+    public_key_pem: String,
+    /// The remote actors that signed requests recently, by the URL they were fetched from.
+    actors: Arc<Mutex<HashMap<String, (Instant, RemoteActor)>>>,
+}
+
+/// The parts of a remote actor needed to deliver to it and check its signatures.
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteActor {
+    id: String,
+    inbox: String,
+    #[serde(default)]
+    endpoints: Endpoints,
+    public_key: PublicKey,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Endpoints {
+    /// The inbox of the whole server, which saves delivering once per follower.
+    shared_inbox: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PublicKey {
+    id: String,
+    public_key_pem: String,
+}
+
+impl ActivityPub {
+    pub async fn new(
+        config: ActivityPubConfig,
+        public_url: &str,
+        store: Store,
+    ) -> anyhow::Result<Self> {
+        let url =
+            Url::parse(public_url).with_context(|| format!("Invalid public URL {}", public_url))?;
+        let mut host = url
+            .host_str()
+            .context("The public URL has no host")?
+            .to_string();
+        if let Some(port) = url.port() {
+            host = format!("{}:{}", host, port);
+        }
+
+        let pem = match store.key(KEY_NAME).await? {
+            Some(pem) => pem,
+            None => {
+                info!("Making the signing key of the ActivityPub actor");
+                let key = PKey::from_rsa(Rsa::generate(2048)?)?;
+                let pem = String::from_utf8(key.private_key_to_pem_pkcs8()?)?;
+                store.add_key(KEY_NAME, pem).await?
+            }
+        };
+        let key = PKey::private_key_from_pem(pem.as_bytes())?;
+        let public_key_pem = String::from_utf8(key.public_key_to_pem()?)?;
+
+        let mut client = Client::builder().redirect(Policy::none());
+        if !config.allow_private_hosts {
+            client = client.dns_resolver(Arc::new(PublicResolver));
+        }
+
+        Ok(Self {
+            client: client.build()?,
+            config,
+            store,
+            base: public_url.trim_end_matches('/').to_string(),
+            host,
+            key,
+            public_key_pem,
+            actors: Arc::default(),
+        })
+    }
+
+    fn actor_id(&self) -> String {
+        format!("{}/ap/actor", self.base)
+    }
+
+    fn followers_id(&self) -> String {
+        format!("{}/ap/followers", self.base)
+    }
+
+    /// The WebFinger description of the actor, if `resource` is its address.
+    pub fn webfinger(&self, resource: &str) -> Option<Value> {
+        let address = format!("acct:{}@{}", self.config.username, self.host);
+        if !resource.eq_ignore_ascii_case(&address) && resource != self.actor_id() {
+            return None;
+        }
+        Some(json!({
+            "subject": address,
+            "aliases": [self.actor_id()],
+            "links": [{
+                "rel": "self",
+                "type": CONTENT_TYPE,
+                "href": self.actor_id(),
+            }],
+        }))
+    }
+
+    /// The document of the actor.
+    pub fn actor(&self, name: &str) -> Value {
+        json!({
+            "@context": CONTEXT,
+            "id": self.actor_id(),
+            "type": "Service",
+            "preferredUsername": self.config.username,
+            "name": name,
+            "summary": format!("<p>New videos on <a href=\"{0}/\">{0}</a></p>", self.base),
+            "url": format!("{}/", self.base),
+            "inbox": format!("{}/ap/inbox", self.base),
+            "outbox": format!("{}/ap/outbox", self.base),
+            "followers": self.followers_id(),
+            "manuallyApprovesFollowers": false,
+            "discoverable": true,
+            "publicKey": {
+                "id": format!("{}#main-key", self.actor_id()),
+                "owner": self.actor_id(),
+                "publicKeyPem": self.public_key_pem,
+            },
+        })
+    }
+
+    /// The collection of the followers, which only tells how many there are.
+    pub async fn followers(&self) -> anyhow::Result<Value> {
+        Ok(json!({
+            "@context": CONTEXT[0],
+            "id": self.followers_id(),
+            "type": "OrderedCollection",
+            "totalItems": self.store.follower_count().await?,
+        }))
+    }
+
+    /// The collection of the posts of the actor, i.e. the current videos with the times they
+    /// were first listed.
+    pub fn outbox(&self, videos: &[(&Video, i64)]) -> Value {
+        let items: Vec<_> = videos
+            .iter()
+            .map(|(video, published)| self.create(video, *published))
+            .collect();
+        json!({
+            "@context": CONTEXT[0],
+            "id": format!("{}/ap/outbox", self.base),
+            "type": "OrderedCollection",
+            "totalItems": items.len(),
+            "orderedItems": items,
+        })
+    }
+
+    /// The Note of a video first listed at `published`, in seconds since the Unix epoch.
+    pub fn note(&self, video: &Video, published: i64) -> Value {
+        let content = format!(
+            "<p>{}</p><p><a href=\"{}\">{}</a></p><p><a href=\"{}\">{}</a></p>",
+            escape(&video.title),
+            escape(&video.url),
+            escape(&video.url),
+            escape(&video.discussion_url),
+            escape(video.source_label),
+        );
+        json!({
+            "@context": CONTEXT[0],
+            "id": format!("{}/ap/notes/{}", self.base, video.id),
+            "type": "Note",
+            "attributedTo": self.actor_id(),
+            "content": content,
+            "url": format!("{}{}", self.base, video.watch_link),
+            "published": timestamp(published),
+            "to": [PUBLIC],
+            "cc": [self.followers_id()],
+        })
+    }
+
+    /// The activity publishing the Note of a video.
+    fn create(&self, video: &Video, published: i64) -> Value {
+        let note = self.note(video, published);
+        json!({
+            "@context": CONTEXT[0],
+            "id": format!("{}/activity", note["id"].as_str().unwrap_or_default()),
+            "type": "Create",
+            "actor": self.actor_id(),
+            "published": timestamp(published),
+            "to": [PUBLIC],
+            "cc": [self.followers_id()],
+            "object": note,
+        })
+    }
+
+    /// Check the signature of a request to the inbox, returning the actor who signed it.
+    pub async fn verify(
+        &self,
+        path: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> anyhow::Result<RemoteActorId> {
+        let header = |name: &str| -> anyhow::Result<&str> {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .with_context(|| format!("Missing {} header", name))
+        };
+        let signature = header("signature")?;
+        let fields: Vec<(&str, &str)> = signature
+            .split(',')
+            .filter_map(|field| {
+                let (name, value) = field.trim().split_once('=')?;
+                Some((name, value.trim_matches('"')))
+            })
+            .collect();
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(field, _)| *field == name)
+                .map(|(_, value)| *value)
+                .with_context(|| format!("The signature has no {}", name))
+        };
+        let key_id = field("keyId")?;
+        let signed_headers: Vec<&str> = field("headers")?.split(' ').collect();
+        for required in ["(request-target)", "host", "date", "digest"] {
+            if !signed_headers.contains(&required) {
+                bail!("The signature doesn't cover {}", required);
+            }
+        }
+        if header("digest")? != digest(body) {
+            bail!("The digest doesn't match the body");
+        }
+        let date = httpdate::parse_http_date(header("date")?).context("Invalid date")?;
+        let skew = match date.duration_since(SystemTime::now()) {
+            Ok(ahead) => ahead,
+            Err(behind) => behind.duration(),
+        };
+        if skew > MAX_DATE_SKEW {
+            bail!("The signature is dated {}", header("date")?);
+        }
+
+        let signing_string = signed_headers
+            .iter()
+            .map(|name| match *name {
+                "(request-target)" => Ok(format!("(request-target): post {}", path)),
+                name => Ok(format!("{}: {}", name, header(name)?)),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .join("\n");
+
+        let actor_url = key_id.split('#').next().unwrap_or(key_id);
+        let actor = self.actor_of(actor_url).await?;
+        if actor.public_key.id != key_id {
+            bail!("{} is not the key of {}", key_id, actor.id);
+        }
+        let key = PKey::public_key_from_pem(actor.public_key.public_key_pem.as_bytes())?;
+        let mut verifier = Verifier::new(MessageDigest::sha256(), &key)?;
+        verifier.update(signing_string.as_bytes())?;
+        if !verifier.verify(&STANDARD.decode(field("signature")?)?)? {
+            bail!("Invalid signature by {}", key_id);
+        }
+        let inbox = actor.endpoints.shared_inbox.unwrap_or(actor.inbox);
+        Ok(RemoteActorId {
+            id: actor.id,
+            inbox,
+        })
+    }
+
+    /// Handle an activity sent to the inbox by `sender`, whose signature was checked.
+    pub async fn receive(&self, sender: RemoteActorId, activity: Value) -> anyhow::Result<()> {
+        if activity["actor"].as_str() != Some(&sender.id) {
+            bail!("{} sent an activity of someone else", sender.id);
+        }
+        match activity["type"].as_str() {
+            Some("Follow") if activity["object"].as_str() == Some(&self.actor_id()) => {
+                self.store.add_follower(&sender.id, &sender.inbox).await?;
+                info!(follower = %sender.id, "New ActivityPub follower");
+                let accept = json!({
+                    "@context": CONTEXT[0],
+                    "id": format!("{}#accepts/{}", self.actor_id(), Uuid::new_v4()),
+                    "type": "Accept",
+                    "actor": self.actor_id(),
+                    "object": activity,
+                });
+                self.deliver(&sender.inbox, &accept).await?;
+            }
+            Some("Undo") if activity["object"]["type"] == "Follow" => {
+                self.store.remove_follower(&sender.id).await?;
+                info!(follower = %sender.id, "Lost an ActivityPub follower");
+            }
+            // Likes, replies and the rest are of no use here.
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Get a remote actor, from the cache if it was fetched recently.
+    async fn actor_of(&self, url: &str) -> anyhow::Result<RemoteActor> {
+        let cached = self.actors.lock().unwrap().get(url).cloned();
+        if let Some((fetched, actor)) = cached {
+            if fetched.elapsed() < ACTOR_TTL {
+                return Ok(actor);
+            }
+        }
+
+        let actor = self.fetch_actor(url).await?;
+        let mut actors = self.actors.lock().unwrap();
+        actors.retain(|_, (fetched, _)| fetched.elapsed() < ACTOR_TTL);
+        if actors.len() >= MAX_CACHED_ACTORS {
+            let oldest = actors
+                .iter()
+                .min_by_key(|(_, (fetched, _))| *fetched)
+                .map(|(url, _)| url.clone());
+            if let Some(oldest) = oldest {
+                actors.remove(&oldest);
+            }
+        }
+        actors.insert(url.to_string(), (Instant::now(), actor.clone()));
+        Ok(actor)
+    }
+
+    /// Fetch a remote actor. Only HTTPS URLs are fetched, unless plain HTTP is allowed, so
+    /// that signatures can't have the server make requests to arbitrary services.
+    async fn fetch_actor(&self, url: &str) -> anyhow::Result<RemoteActor> {
+        let url = Url::parse(url).with_context(|| format!("Invalid actor {}", url))?;
+        let allowed = url.scheme() == "https" || (self.config.allow_http && url.scheme() == "http");
+        if !allowed {
+            bail!("The actor {} isn't served over HTTPS", url);
+        }
+        self.check_host(&url)?;
+        let request = self
+            .signed(Method::GET, &url, None)?
+            .header("Accept", CONTENT_TYPE);
+        let actor = request
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .with_context(|| format!("Failed to read the actor {}", url))?;
+        Ok(actor)
+    }
+
+    /// POST an activity to an inbox.
+    async fn deliver(&self, inbox: &str, activity: &Value) -> anyhow::Result<()> {
+        let url = Url::parse(inbox).with_context(|| format!("Invalid inbox {}", inbox))?;
+        self.check_host(&url)?;
+        let body = activity.to_string();
+        self.signed(Method::POST, &url, Some(body.as_bytes()))?
+            .header("Content-Type", CONTENT_TYPE)
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Refuse URLs with a private address for a host. Names are checked when they are resolved,
+    /// by [`PublicResolver`].
+    fn check_host(&self, url: &Url) -> anyhow::Result<()> {
+        let host = url.host_str().unwrap_or_default();
+        let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse() else {
+            return Ok(());
+        };
+        if !self.config.allow_private_hosts && !is_public(ip) {
+            bail!("{} is on a private address", url);
+        }
+        Ok(())
+    }
+
+    /// A request signed by the actor, with the digest of its body if any.
+    fn signed(
+        &self,
+        method: Method,
+        url: &Url,
+        body: Option<&[u8]>,
+    ) -> anyhow::Result<reqwest::RequestBuilder> {
+        let mut host = url.host_str().context("The URL has no host")?.to_string();
+        if let Some(port) = url.port() {
+            host = format!("{}:{}", host, port);
+        }
+        let date = httpdate::fmt_http_date(SystemTime::now());
+        let mut path = url.path().to_string();
+        if let Some(query) = url.query() {
+            path = format!("{}?{}", path, query);
+        }
+
+        let mut names = vec!["(request-target)", "host", "date"];
+        let mut lines = vec![
+            format!(
+                "(request-target): {} {}",
+                method.as_str().to_lowercase(),
+                path
+            ),
+            format!("host: {}", host),
+            format!("date: {}", date),
+        ];
+        let digest = match body {
+            Some(body) => {
+                let digest = digest(body);
+                names.push("digest");
+                lines.push(format!("digest: {}", digest));
+                Some(digest)
+            }
+            None => None,
+        };
+
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.key)?;
+        signer.update(lines.join("\n").as_bytes())?;
+        let signature = format!(
+            "keyId=\"{}#main-key\",algorithm=\"rsa-sha256\",headers=\"{}\",signature=\"{}\"",
+            self.actor_id(),
+            names.join(" "),
+            STANDARD.encode(signer.sign_to_vec()?)
+        );
+
+        let mut request = self
+            .client
+            .request(method, url.clone())
+            .header("Host", host)
+            .header("Date", date)
+            .header("Signature", signature);
+        if let Some(digest) = digest {
+            request = request.header("Digest", digest);
+        }
+        Ok(request)
+    }
+}
+
+/// Resolves hosts like the system does, but fails for those with an address that isn't public,
+/// checked on every connection so that a name can't be pointed elsewhere once it was checked.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+                return Err(
+                    format!("{} is on the private address {}", name.as_str(), addr.ip()).into(),
+                );
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Whether an address is reachable from the internet, and not loopback, private, link-local or
+/// otherwise reserved.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                // Shared by carrier-grade NATs.
+                || (a == 100 && b & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_unspecified()
+                    || ip.is_loopback()
+                    // Unique local and link-local.
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+/// A remote actor who signed a request.
+pub struct RemoteActorId {
+    id: String,
+    /// Where activities for it are delivered.
+    inbox: String,
+}
+
+/// New videos are delivered to the followers like the posts of the notifiers, so each is only
+/// published once.
+#[async_trait]
+impl Notifier for ActivityPub {
+    fn name(&self) -> &'static str {
+        "activitypub"
+    }
+
+    fn rules(&self) -> &[NotifyRule] {
+        &self.config.rules
+    }
+
+    async fn send(&self, video: &Video) -> anyhow::Result<()> {
+        let activity = self.create(video, OffsetDateTime::now_utc().unix_timestamp());
+        for inbox in self.store.follower_inboxes().await? {
+            if let Err(err) = self.deliver(&inbox, &activity).await {
+                warn!(inbox, video = %video.id, "Failed to deliver a video: {:#}", err);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The value of the `Digest` header of a body.
+fn digest(body: &[u8]) -> String {
+    format!("SHA-256={}", STANDARD.encode(Sha256::digest(body)))
+}
+
+fn timestamp(seconds: i64) -> String {
+    OffsetDateTime::from_unix_timestamp(seconds)
+        .ok()
+        .and_then(|time| time.format(&Rfc3339).ok())
+        .unwrap_or_default()
+}
+
+/// Escape text for the HTML of a Note.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use tokio_rusqlite::Connection;
+
+    use super::*;
+
+    async fn activitypub(allow_http: bool, allow_private_hosts: bool) -> ActivityPub {
+        let conn = Connection::open_in_memory().await.unwrap();
+        let store = Store::new(conn).await.unwrap();
+        let config = ActivityPubConfig {
+            username: "videos".to_string(),
+            rules: Vec::new(),
+            allow_http,
+            allow_private_hosts,
+        };
+        ActivityPub::new(config, "https://videos.example", store)
+            .await
+            .unwrap()
+    }
+
+    /// The headers of a request to the inbox with a body of `{}`, whose signature is never
+    /// checked since the request is rejected before.
+    fn headers(key_id: &str, date: SystemTime) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let signature = format!(
+            "keyId=\"{}\",headers=\"(request-target) host date digest\",signature=\"\"",
+            key_id
+        );
+        let date = httpdate::fmt_http_date(date);
+        headers.insert("signature", HeaderValue::from_str(&signature).unwrap());
+        headers.insert("host", HeaderValue::from_static("videos.example"));
+        headers.insert("date", HeaderValue::from_str(&date).unwrap());
+        headers.insert("digest", HeaderValue::from_str(&digest(b"{}")).unwrap());
+        headers
+    }
+
+    #[test]
+    fn digests_are_base64_sha256() {
+        assert_eq!(
+            digest(b""),
+            "SHA-256=47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+        );
+    }
+
+    #[tokio::test]
+    async fn stale_signatures_are_rejected() {
+        let activitypub = activitypub(false, false).await;
+        let key_id = "https://remote.example/users/alice#main-key";
+        for date in [
+            SystemTime::UNIX_EPOCH,
+            SystemTime::now() - Duration::from_secs(10 * 60),
+            SystemTime::now() + Duration::from_secs(10 * 60),
+        ] {
+            let err = activitypub
+                .verify("/ap/inbox", &headers(key_id, date), b"{}")
+                .await
+                .err()
+                .unwrap();
+            assert!(err.to_string().contains("is dated"), "{}", err);
+        }
+    }
+
+    #[tokio::test]
+    async fn actors_are_only_fetched_over_https() {
+        let key_id = "http://127.0.0.1:1/users/alice#main-key";
+        let headers = headers(key_id, SystemTime::now());
+        let err = activitypub(false, true)
+            .await
+            .verify("/ap/inbox", &headers, b"{}")
+            .await
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string().contains("isn't served over HTTPS"),
+            "{}",
+            err
+        );
+
+        // Allowed, the request is made and fails since nothing listens there.
+        let err = activitypub(true, true)
+            .await
+            .verify("/ap/inbox", &headers, b"{}")
+            .await
+            .err()
+            .unwrap();
+        assert!(!err.to_string().contains("HTTPS"), "{}", err);
+    }
+    #[tokio::test]
+    async fn actors_on_private_addresses_are_not_fetched() {
+        let activitypub = activitypub(false, false).await;
+        for actor in [
+            "https://127.0.0.1/users/alice",
+            "https://10.0.0.1/users/alice",
+            "https://169.254.169.254/users/alice",
+            "https://[::1]/users/alice",
+            "https://[::ffff:192.168.0.1]/users/alice",
+            "https://localhost/users/alice",
+        ] {
+            let headers = headers(&format!("{}#main-key", actor), SystemTime::now());
+            let err = activitypub
+                .verify("/ap/inbox", &headers, b"{}")
+                .await
+                .err()
+                .unwrap();
+            assert!(
+                format!("{:#}", err).contains("private address"),
+                "{:#}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn addresses_are_public_unless_reserved() {
+        for ip in ["1.1.1.1", "100.128.0.1", "2606:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "0.0.0.0",
+            "127.0.0.1",
+            "172.16.0.1",
+            "100.64.0.1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:10.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+/// Static assets embedded into the binary.
+///
+/// The contents of `assets/` are compiled into the executable so that it can be started from any
+/// working directory. Debug builds read the files from disk on every access instead, so the
+/// stylesheet can be edited without recompiling.
+///
+/// The pages link to the assets under names carrying a fingerprint of their content, e.g.
+/// `main.3f2a1b9c0d.css`, which may be cached forever since a new version gets a new name.
+///
+/// A theme is a directory of files that replace the embedded assets of the same name, e.g. its
+/// own `main.css`, or add to them, e.g. fonts. Its files are read and hashed once, on start. The
+/// markup of the pages is compiled into the binary with the rest of the templates, so themes
+/// restyle the pages but can't bring templates of their own.
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap},
+    io,
+    path::Path,
+    sync::OnceLock,
+};
+
+use rust_embed::RustEmbed;
+use sha2::{Digest, Sha256};
+
+#[derive(RustEmbed)]
+#[folder = "assets/"]
+struct Embedded;
+
+/// The files of the theme in use, if it isn't the built-in one, by their paths relative to its
+/// directory.
+static THEME: OnceLock<HashMap<String, ThemeFile>> = OnceLock::new();
+
+/// A file of the theme, read on start.
+struct ThemeFile {
+    data: Vec<u8>,
+    mime_type: String,
+    hash: [u8; 32],
+}
+
+/// Read the files of a theme, to serve them in place of the embedded ones. Only the first theme
+/// loaded counts, since the fingerprints are computed once.
+pub fn load_theme(dir: &Path) -> io::Result<()> {
+    let mut paths = BTreeSet::new();
+    theme_paths(dir, dir, &mut paths)?;
+    let mut files = HashMap::new();
+    for path in paths {
+        let data = std::fs::read(dir.join(&path))?;
+        let file = ThemeFile {
+            mime_type: mime_guess::from_path(&path)
+                .first_or_octet_stream()
+                .to_string(),
+            hash: Sha256::digest(&data).into(),
+            data,
+        };
+        files.insert(path, file);
+    }
+    let _ = THEME.set(files);
+    Ok(())
+}
+
+/// An embedded asset ready to be served.
+pub struct Asset {
+    pub data: Cow<'static, [u8]>,
+    pub mime_type: String,
+    /// The SHA-256 digest of the content.
+    pub hash: [u8; 32],
+}
+
+/// Look up an asset by its path relative to the `assets/` directory, in the theme first.
+pub fn get(path: &str) -> Option<Asset> {
+    if let Some(file) = THEME.get().and_then(|files| files.get(path)) {
+        return Some(Asset {
+            data: Cow::Borrowed(&file.data),
+            mime_type: file.mime_type.clone(),
+            hash: file.hash,
+        });
+    }
+    let file = Embedded::get(path)?;
+    Some(Asset {
+        mime_type: file.metadata.mimetype().to_string(),
+        hash: file.metadata.sha256_hash(),
+        data: file.data,
+    })
+}
+
+/// The paths of the files in a directory and the ones below it, relative to `root`.
+fn theme_paths(root: &Path, dir: &Path, paths: &mut BTreeSet<String>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            theme_paths(root, &path, paths)?;
+        } else if let Some(relative) = path.strip_prefix(root).ok().and_then(Path::to_str) {
+            paths.insert(relative.replace('\\', "/"));
+        }
+    }
+    Ok(())
+}
+
+/// How many hex digits of the digest of an asset its fingerprint has.
+const FINGERPRINT_LENGTH: usize = 10;
+
+impl Asset {
+    /// The fingerprint of the content.
+    fn fingerprint(&self) -> String {
+        hex::encode(&self.hash[..FINGERPRINT_LENGTH / 2])
+    }
+}
+
+/// The fingerprinted path of an asset, e.g. `main.3f2a1b9c0d.css` for `main.css`, or the path
+/// itself if there is no such asset.
+///
+/// The fingerprints are computed once, so in debug builds an edited asset keeps its name until
+/// the server restarts, and is served by it all the same.
+pub fn fingerprinted(path: &str) -> Cow<'_, str> {
+    static FINGERPRINTED: OnceLock<HashMap<String, String>> = OnceLock::new();
+    let fingerprinted = FINGERPRINTED.get_or_init(|| {
+        paths()
+            .filter_map(|path| {
+                let asset = get(&path)?;
+                let fingerprinted = insert_fingerprint(&path, &asset.fingerprint());
+                Some((path.into_owned(), fingerprinted))
+            })
+            .collect()
+    });
+    match fingerprinted.get(path) {
+        Some(fingerprinted) => Cow::Borrowed(fingerprinted),
+        None => Cow::Borrowed(path),
+    }
+}
+
+/// Look up an asset by its path, fingerprinted or not, along with whether the fingerprint is that
+/// of its current content, which makes it safe to cache forever.
+pub fn resolve(path: &str) -> Option<(Asset, bool)> {
+    if let Some(asset) = get(path) {
+        return Some((asset, false));
+    }
+    let (path, fingerprint) = strip_fingerprint(path)?;
+    let asset = get(&path)?;
+    let current = asset.fingerprint() == fingerprint;
+    Some((asset, current))
+}
+
+/// Put a fingerprint before the extension of the file name of a path.
+fn insert_fingerprint(path: &str, fingerprint: &str) -> String {
+    let name_start = path.rfind('/').map_or(0, |slash| slash + 1);
+    match path[name_start..].rfind('.') {
+        Some(dot) => {
+            let (stem, extension) = path.split_at(name_start + dot);
+            format!("{}.{}{}", stem, fingerprint, extension)
+        }
+        None => path.to_string(),
+    }
+}
+
+/// Split a fingerprinted path into the path of the asset and the fingerprint.
+fn strip_fingerprint(path: &str) -> Option<(String, &str)> {
+    let name_start = path.rfind('/').map_or(0, |slash| slash + 1);
+    let name = &path[name_start..];
+    let end = name.rfind('.')?;
+    let start = name[..end].rfind('.')? + 1;
+    let fingerprint = &name[start..end];
+    let is_fingerprint = fingerprint.len() == FINGERPRINT_LENGTH
+        && fingerprint.bytes().all(|b| b.is_ascii_hexdigit());
+    if !is_fingerprint {
+        return None;
+    }
+    let stripped = format!(
+        "{}{}{}",
+        &path[..name_start],
+        &name[..start - 1],
+        &name[end..]
+    );
+    Some((stripped, fingerprint))
+}
+
+/// The paths of all assets, including those only the theme has.
+pub fn paths() -> impl Iterator<Item = Cow<'static, str>> {
+    let mut extra: BTreeSet<String> = THEME
+        .get()
+        .map(|files| files.keys().cloned().collect())
+        .unwrap_or_default();
+    extra.retain(|path| Embedded::get(path).is_none());
+    Embedded::iter().chain(extra.into_iter().map(Cow::Owned))
+}
@@ -0,0 +1,109 @@
+/// Authentication of operators and API clients.
+///
+/// API clients send one of the configured tokens as `Authorization: Bearer <token>`. Operators
+/// may use the admin credentials over HTTP basic auth instead, which browsers prompt for. Private
+/// instances require basic auth (or a token) for every page.
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    http::{header, HeaderValue, Request, Response, StatusCode},
+    response::IntoResponse,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use tower_http::validate_request::ValidateRequest;
+
+use crate::config::{Config, Credentials};
+
+/// The challenge sent when browsers should prompt for a user name and password.
+const BASIC_CHALLENGE: &str = "Basic realm=\"hnv\", charset=\"UTF-8\"";
+
+/// Requires one of a set of credentials in the `Authorization` header.
+#[derive(Clone)]
+pub struct RequireAuth {
+    /// The accepted values of the `Authorization` header.
+    accepted: Arc<Vec<Vec<u8>>>,
+    /// The `WWW-Authenticate` challenge sent to unauthenticated clients.
+    challenge: &'static str,
+}
+
+impl RequireAuth {
+    /// Accept the admin credentials and the API tokens, or `None` if neither is configured.
+    pub fn admin(config: &Config) -> Option<Self> {
+        let mut accepted = bearer(&config.api.tokens);
+        let challenge = match &config.admin {
+            Some(admin) => {
+                accepted.push(basic(admin));
+                BASIC_CHALLENGE
+            }
+            None => "Bearer",
+        };
+
+        (!accepted.is_empty()).then(|| Self {
+            accepted: Arc::new(accepted),
+            challenge,
+        })
+    }
+
+    /// Accept the API tokens, or `None` if the read API is open to everyone.
+    pub fn api(config: &Config) -> Option<Self> {
+        config.api.private.then(|| Self {
+            accepted: Arc::new(bearer(&config.api.tokens)),
+            challenge: "Bearer",
+        })
+    }
+
+    /// Accept the private instance credentials, or `None` if the site is public.
+    ///
+    /// The admin credentials and API tokens are accepted as well, so operators and API clients
+    /// don't need a second set of credentials.
+    pub fn site(config: &Config) -> Option<Self> {
+        let private = config.private.as_ref()?;
+        let mut accepted = bearer(&config.api.tokens);
+        accepted.extend(config.admin.iter().chain([private]).map(basic));
+
+        Some(Self {
+            accepted: Arc::new(accepted),
+            challenge: BASIC_CHALLENGE,
+        })
+    }
+
+    fn is_accepted(&self, value: &HeaderValue) -> bool {
+        // Check every candidate, so the time taken doesn't tell which one came close.
+        self.accepted.iter().fold(false, |found, accepted| {
+            constant_time_eq(value.as_bytes(), accepted) | found
+        })
+    }
+}
+
+impl<B> ValidateRequest<B> for RequireAuth {
+    type ResponseBody = Body;
+
+    fn validate(&mut self, request: &mut Request<B>) -> Result<(), Response<Self::ResponseBody>> {
+        match request.headers().get(header::AUTHORIZATION) {
+            Some(value) if self.is_accepted(value) => Ok(()),
+            _ => Err((
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, self.challenge)],
+            )
+                .into_response()),
+        }
+    }
+}
+
+fn basic(credentials: &Credentials) -> Vec<u8> {
+    let credentials = format!("{}:{}", credentials.username, credentials.password);
+    format!("Basic {}", STANDARD.encode(credentials)).into_bytes()
+}
+
+fn bearer(tokens: &[String]) -> Vec<Vec<u8>> {
+    tokens
+        .iter()
+        .map(|token| format!("Bearer {}", token).into_bytes())
+        .collect()
+}
+
+/// Compare two byte strings in time independent of where they differ.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
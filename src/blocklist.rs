@@ -0,0 +1,143 @@
+/// Keeping stories and whole domains off the instance.
+///
+/// Operators block a story by its ID, e.g. `1` or `lobsters-abc123`, or every story linking to a
+/// domain or its subdomains, through the admin page or API. Blocked videos are left out of the
+/// published list, and with it the index, the feeds and the API, and out of what is read from the
+/// archive, so a block applies to what was recorded before it too. Lifting it brings them back.
+///
+/// The entries are kept in the store, and in memory to check every video against.
+use std::{collections::HashSet, sync::RwLock};
+
+use reqwest::Url;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::{
+    store::{Block, Store},
+    video::VideoId,
+};
+
+/// A story, by its ID.
+pub const STORY: &str = "story";
+/// Every story linking to a domain or its subdomains.
+pub const DOMAIN: &str = "domain";
+
+/// A request to block a story or a domain.
+#[derive(Deserialize, ToSchema)]
+pub struct NewBlock {
+    /// `story` or `domain`.
+    pub kind: String,
+    /// The ID of the story, e.g. `1` or `lobsters-abc123`, or the domain, e.g. `example.com`.
+    pub value: String,
+}
+
+impl NewBlock {
+    /// The value as it is stored and matched, explaining what is wrong with it otherwise.
+    pub fn normalized(&self) -> Result<String, String> {
+        let value = self.value.trim();
+        match self.kind.as_str() {
+            STORY => value
+                .parse::<VideoId>()
+                .map(|id| id.to_string())
+                .map_err(|err| err.to_string()),
+            DOMAIN => {
+                let domain = value.trim_end_matches('.').to_ascii_lowercase();
+                let domain = domain.strip_prefix("www.").unwrap_or(&domain);
+                let valid = domain.contains('.')
+                    && domain
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'.');
+                match valid {
+                    true => Ok(domain.to_string()),
+                    false => Err(format!("{} isn't a domain, e.g. example.com", value)),
+                }
+            }
+            kind => Err(format!(
+                "Unknown kind {}, expected {} or {}",
+                kind, STORY, DOMAIN
+            )),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Entries {
+    stories: HashSet<String>,
+    domains: HashSet<String>,
+}
+
+pub struct Blocklist {
+    store: Store,
+    entries: RwLock<Entries>,
+}
+
+impl Blocklist {
+    /// Load the entries of the store.
+    pub async fn new(store: Store) -> anyhow::Result<Self> {
+        let blocklist = Self {
+            store,
+            entries: Default::default(),
+        };
+        blocklist.reload().await?;
+        Ok(blocklist)
+    }
+
+    /// The entries, the latest first.
+    pub async fn entries(&self) -> anyhow::Result<Vec<Block>> {
+        self.store.blocks().await
+    }
+
+    /// Block a story or a domain, returning the entry, or `None` if it was blocked already.
+    pub async fn add(&self, kind: &str, value: &str) -> anyhow::Result<Option<Block>> {
+        let block = self.store.add_block(kind, value).await?;
+        self.reload().await?;
+        Ok(block)
+    }
+
+    /// Lift a block, returning whether there was one.
+    pub async fn remove(&self, id: i64) -> anyhow::Result<bool> {
+        let removed = self.store.remove_block(id).await?;
+        self.reload().await?;
+        Ok(removed)
+    }
+
+    /// Whether a story or the domain it links to is blocked.
+    pub fn blocks(&self, id: &VideoId, url: &str) -> bool {
+        let entries = self.entries.read().unwrap();
+        if entries.stories.contains(&id.to_string()) {
+            return true;
+        }
+        if entries.domains.is_empty() {
+            return false;
+        }
+        let Some(host) = Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+        else {
+            return false;
+        };
+        // The domain itself and every parent of it, e.g. `b.example.com` and `example.com`.
+        let mut host = host.trim_end_matches('.').to_ascii_lowercase();
+        loop {
+            if entries.domains.contains(&host) {
+                return true;
+            }
+            match host.split_once('.') {
+                Some((_, parent)) if parent.contains('.') => host = parent.to_string(),
+                _ => return false,
+            }
+        }
+    }
+
+    async fn reload(&self) -> anyhow::Result<()> {
+        let mut entries = Entries::default();
+        for block in self.store.blocks().await? {
+            match block.kind.as_str() {
+                DOMAIN => entries.domains.insert(block.value),
+                _ => entries.stories.insert(block.value),
+            };
+        }
+        *self.entries.write().unwrap() = entries;
+        Ok(())
+    }
+}
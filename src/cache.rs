@@ -2,27 +2,130 @@
 ///
 /// This cache is used to store the results of Hacker News API requests so that we can serve them
 /// faster to users. This cache is backed by an SQLite database.
+///
+/// Every response is stored with the time it was fetched, so callers that want to pick up changes
+/// (e.g. a new title) can ask for responses younger than some age only.
+///
+/// Responses are written behind: they are queued and written in batches, one transaction each, so
+/// a refresh storing hundreds of items doesn't wait for SQLite to sync every single one.
+///
+/// The cache also keeps the progress of the refresh of the top stories, which items of the list
+/// were processed, so a refresh interrupted by a crash or a restart can be resumed. An item is
+/// marked processed in the same batch as its response, after it, so a processed item is cached.
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
 use tokio_rusqlite::{params, Connection};
+use tracing::{error, instrument};
+
+/// Write a batch once it holds this many responses.
+const BATCH_SIZE: usize = 256;
+
+/// Write a batch at the latest this long after its first response was queued.
+const BATCH_DELAY: Duration = Duration::from_millis(50);
+
+/// How many responses may be queued before writers have to wait.
+const QUEUE_SIZE: usize = 1024;
+
+/// Statistics about the cache.
+pub struct CacheStats {
+    /// The number of cached responses.
+    pub entries: u64,
+    /// The size of the database file in bytes.
+    pub size: u64,
+}
 
 /// The cache struct that stores the connection to the SQLite database.
+#[derive(Clone)]
 pub struct Cache {
     conn: Connection,
+    writes: mpsc::Sender<Write>,
+    /// Whether the database is `:memory:`, rather than a file.
+    in_memory: bool,
+}
+
+/// A request to the writer task.
+enum Write {
+    Set {
+        url: String,
+        response: String,
+    },
+    /// Mark an item of the top stories as processed by the refresh in progress.
+    Processed(i32),
+    /// Report back once everything queued before has been written, or failed to be.
+    Flush(oneshot::Sender<Result<(), String>>),
+}
+
+/// Where the database is kept by default: `cache.db` in the data directory of the platform, e.g.
+/// `~/.local/share/hnv` on Linux, `~/Library/Application Support/hnv` on macOS and
+/// `%APPDATA%\hnv\data` on Windows. Without a home directory, it is `db/cache.db`.
+pub fn default_path() -> PathBuf {
+    directories::ProjectDirs::from("", "", "hnv")
+        .map(|dirs| dirs.data_dir().join("cache.db"))
+        .unwrap_or_else(|| PathBuf::from("db/cache.db"))
 }
 
 impl Cache {
-    /// Create a new cache instance.
+    /// Create a new cache instance at the [`default_path`], creating its directory if needed.
     ///
     /// This function creates a new cache instance and initializes the SQLite database.
     pub async fn new() -> anyhow::Result<Self> {
+        let path = default_path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        Self::open(path).await
+    }
+
+    /// Create a new cache instance backed by the database at `path`.
+    pub async fn open(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let in_memory = path.as_ref() == Path::new(":memory:");
         // Call the asynchronous connect method using the runtime.
-        let conn = Connection::open("db/cache.db").await?;
+        let conn = Connection::open(path).await?;
 
         conn.call(|conn| {
             conn.execute(
                 "CREATE TABLE IF NOT EXISTS cache (
                 id INTEGER PRIMARY KEY,
                 url TEXT NOT NULL,
-                response TEXT NOT NULL
+                response TEXT NOT NULL,
+                fetched_at INTEGER NOT NULL DEFAULT 0
+            )",
+                [],
+            )?;
+
+            // Databases from before responses expired lack the fetch time, and could hold a URL
+            // more than once. Their responses count as fetched long ago.
+            let has_fetched_at = conn
+                .prepare("SELECT 1 FROM pragma_table_info('cache') WHERE name = 'fetched_at'")?
+                .exists([])?;
+            if !has_fetched_at {
+                conn.execute(
+                    "ALTER TABLE cache ADD COLUMN fetched_at INTEGER NOT NULL DEFAULT 0",
+                    [],
+                )?;
+            }
+            conn.execute(
+                "DELETE FROM cache WHERE id NOT IN (SELECT MAX(id) FROM cache GROUP BY url)",
+                [],
+            )?;
+            conn.execute(
+                "CREATE UNIQUE INDEX IF NOT EXISTS cache_url ON cache (url)",
+                [],
+            )?;
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS refresh_progress (
+                item_id INTEGER PRIMARY KEY,
+                rank INTEGER NOT NULL,
+                processed INTEGER NOT NULL DEFAULT 0,
+                started_at INTEGER NOT NULL
             )",
                 [],
             )?;
@@ -30,12 +133,48 @@ impl Cache {
             tokio_rusqlite::Result::Ok(())
         })
         .await?;
-        Ok(Self { conn })
+
+        let (writes, queue) = mpsc::channel(QUEUE_SIZE);
+        tokio::spawn(write_behind(conn.clone(), queue));
+        Ok(Self {
+            conn,
+            writes,
+            in_memory,
+        })
+    }
+
+    /// Whether the responses are held in memory, which [`Cache::trim`] is meant to bound.
+    pub fn in_memory(&self) -> bool {
+        self.in_memory
+    }
+
+    /// Get a handle to the underlying database connection, to share it with other tables.
+    pub fn connection(&self) -> Connection {
+        self.conn.clone()
+    }
+
+    /// Get statistics about the cache.
+    pub async fn stats(&self) -> anyhow::Result<CacheStats> {
+        let result = self
+            .conn
+            .call(|conn| {
+                let entries = conn.query_row("SELECT COUNT(*) FROM cache", [], |row| row.get(0))?;
+                let page_count: u64 = conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+                let page_size: u64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+                Ok(CacheStats {
+                    entries,
+                    size: page_count * page_size,
+                })
+            })
+            .await?;
+
+        Ok(result)
     }
 
     /// Get a cached response from the cache.
     ///
     /// This function retrieves a cached response from the cache based on the URL provided.
+    #[instrument(name = "cache.get", skip(self))]
     pub async fn get(&self, url: &str) -> anyhow::Result<Option<String>> {
         let url = url.to_string();
 
@@ -56,24 +195,335 @@ impl Cache {
         Ok(result)
     }
 
+    /// Get a cached response from the cache, unless it was fetched more than `max_age` ago.
+    #[instrument(name = "cache.get", skip(self))]
+    pub async fn get_fresh(&self, url: &str, max_age: Duration) -> anyhow::Result<Option<String>> {
+        let url = url.to_string();
+        let max_age = max_age.as_secs() as i64;
+
+        let result = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT response FROM cache WHERE url = ?1
+                    AND fetched_at >= CAST(strftime('%s', 'now') AS INTEGER) - ?2",
+                )?;
+                let mut rows = stmt.query(params![url, max_age])?;
+                if let Some(row) = rows.next()? {
+                    let response: String = row.get(0)?;
+                    Ok(Some(response))
+                } else {
+                    Ok(None)
+                }
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Evict the responses fetched longest ago until at most `max_entries` are left.
+    ///
+    /// Returns the number of evicted responses.
+    #[instrument(name = "cache.trim", skip(self))]
+    pub async fn trim(&self, max_entries: usize) -> anyhow::Result<usize> {
+        let max_entries = max_entries as i64;
+
+        let evicted = self
+            .conn
+            .call(move |conn| {
+                let evicted = conn.execute(
+                    "DELETE FROM cache WHERE id NOT IN (
+                        SELECT id FROM cache ORDER BY fetched_at DESC, id DESC LIMIT ?1
+                    )",
+                    params![max_entries],
+                )?;
+                Ok(evicted)
+            })
+            .await?;
+
+        Ok(evicted)
+    }
+
     /// Set a cached response in the cache.
     ///
     /// This function sets a cached response in the cache based on the URL and response provided.
+    ///
+    /// A response cached for the same URL before is replaced. The response is only queued, so it
+    /// may be missing from [`Cache::get`] for a moment; use [`Cache::flush`] to wait for it.
+    #[instrument(name = "cache.set", skip(self, response))]
     pub async fn set(&self, url: &str, response: &str) -> anyhow::Result<()> {
-        let url = url.to_string();
-        let response = response.to_string();
+        let write = Write::Set {
+            url: url.to_string(),
+            response: response.to_string(),
+        };
+        self.writes
+            .send(write)
+            .await
+            .map_err(|_| anyhow!("The cache writer has stopped"))
+    }
 
-        let result = self
+    /// Wait until every response set so far has been written.
+    ///
+    /// Fails if any of them couldn't be written since the last flush.
+    #[instrument(name = "cache.flush", skip(self))]
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        let (done, flushed) = oneshot::channel();
+        self.writes
+            .send(Write::Flush(done))
+            .await
+            .map_err(|_| anyhow!("The cache writer has stopped"))?;
+        flushed
+            .await
+            .context("The cache writer has stopped")?
+            .map_err(|err| anyhow!("Failed to write to the cache: {}", err))
+    }
+
+    /// Start tracking the progress of a refresh of the top stories `ids`, in rank order.
+    ///
+    /// If a refresh that started at most `max_age` ago was interrupted, it is resumed: the items
+    /// of the list it processed are returned, and it keeps its start. Otherwise, or with a
+    /// `max_age` of zero, the refresh starts over and nothing is returned.
+    #[instrument(name = "cache.resume_refresh", skip(self, ids))]
+    pub async fn resume_refresh(
+        &self,
+        ids: &[i32],
+        max_age: Duration,
+    ) -> anyhow::Result<HashSet<i32>> {
+        let ids = ids.to_vec();
+        let max_age = max_age.as_secs() as i64;
+
+        let processed = self
             .conn
             .call(move |conn| {
-                conn.execute(
-                    "INSERT INTO cache (url, response) VALUES (?1, ?2)",
-                    params![url, response],
-                )?;
+                let tx = conn.transaction()?;
+                let now: i64 =
+                    tx.query_row("SELECT CAST(strftime('%s', 'now') AS INTEGER)", [], |row| {
+                        row.get(0)
+                    })?;
+                let started: Option<i64> =
+                    tx.query_row("SELECT MIN(started_at) FROM refresh_progress", [], |row| {
+                        row.get(0)
+                    })?;
+                let started = started.filter(|started| max_age > 0 && *started >= now - max_age);
+
+                let mut processed = HashSet::new();
+                if started.is_some() {
+                    let mut stmt =
+                        tx.prepare("SELECT item_id FROM refresh_progress WHERE processed")?;
+                    let rows = stmt.query_map([], |row| row.get::<_, i32>(0))?;
+                    for id in rows {
+                        processed.insert(id?);
+                    }
+                }
+                processed.retain(|id| ids.contains(id));
+
+                tx.execute("DELETE FROM refresh_progress", [])?;
+                {
+                    let mut stmt = tx.prepare(
+                        "INSERT OR IGNORE INTO refresh_progress
+                        (item_id, rank, processed, started_at) VALUES (?1, ?2, ?3, ?4)",
+                    )?;
+                    for (rank, id) in ids.iter().enumerate() {
+                        let started = started.unwrap_or(now);
+                        stmt.execute(params![id, rank, processed.contains(id), started])?;
+                    }
+                }
+                tx.commit()?;
+                Ok(processed)
+            })
+            .await?;
+
+        Ok(processed)
+    }
+
+    /// Mark an item as processed by the refresh in progress, once its response is written.
+    pub async fn processed(&self, id: i32) -> anyhow::Result<()> {
+        self.writes
+            .send(Write::Processed(id))
+            .await
+            .map_err(|_| anyhow!("The cache writer has stopped"))
+    }
+
+    /// Forget the progress of the refresh in progress once it completed, so the next one starts
+    /// over.
+    #[instrument(name = "cache.finish_refresh", skip(self))]
+    pub async fn finish_refresh(&self) -> anyhow::Result<()> {
+        self.flush().await?;
+        self.conn
+            .call(|conn| {
+                conn.execute("DELETE FROM refresh_progress", [])?;
                 Ok(())
             })
             .await?;
+        Ok(())
+    }
 
-        Ok(result)
+    /// Write a consistent copy of the whole database, including the other tables sharing it, to
+    /// `path`, which must not exist yet.
+    ///
+    /// The copy is made with `VACUUM INTO` on the shared connection, so it doesn't need the
+    /// server to stop and comes out compacted. The queued responses are written first.
+    pub async fn backup(&self, path: &Path) -> anyhow::Result<()> {
+        if path.exists() {
+            bail!("{} exists already", path.display());
+        }
+        let target = path
+            .to_str()
+            .with_context(|| format!("{} isn't valid UTF-8", path.display()))?
+            .to_string();
+        self.flush().await?;
+        self.conn
+            .call(move |conn| {
+                conn.execute("VACUUM INTO ?1", [target])?;
+                Ok(())
+            })
+            .await
+            .with_context(|| format!("Failed to back up to {}", path.display()))
+    }
+}
+
+/// Write the queued responses and progress in batches until every [`Cache`] is dropped.
+async fn write_behind(conn: Connection, mut queue: mpsc::Receiver<Write>) {
+    // The first failure since the last flush, reported to it.
+    let mut failure = None;
+    while let Some(first) = queue.recv().await {
+        let deadline = Instant::now() + BATCH_DELAY;
+        let mut batch = Vec::new();
+        let mut flushes = Vec::new();
+        let mut next = Some(first);
+
+        while let Some(write) = next.take() {
+            match write {
+                Write::Flush(done) => {
+                    flushes.push(done);
+                    break;
+                }
+                write => batch.push(write),
+            }
+            if batch.len() >= BATCH_SIZE {
+                break;
+            }
+            next = tokio::time::timeout_at(deadline, queue.recv())
+                .await
+                .ok()
+                .flatten();
+        }
+
+        if !batch.is_empty() {
+            let size = batch.len();
+            if let Err(err) = write_batch(&conn, batch).await {
+                error!("Failed to write {} responses to the cache: {}", size, err);
+                failure.get_or_insert_with(|| format!("{:#}", err));
+            }
+        }
+        if !flushes.is_empty() {
+            let result = failure.take().map_or(Ok(()), Err);
+            for done in flushes {
+                let _ = done.send(result.clone());
+            }
+        }
+    }
+}
+
+/// Write responses and progress in a single transaction, in the order they were queued.
+#[instrument(name = "cache.write_batch", skip_all, fields(size = batch.len()))]
+async fn write_batch(conn: &Connection, batch: Vec<Write>) -> anyhow::Result<()> {
+    conn.call(move |conn| {
+        let tx = conn.transaction()?;
+        {
+            let mut set = tx.prepare_cached(
+                "INSERT INTO cache (url, response, fetched_at)
+                VALUES (?1, ?2, strftime('%s', 'now'))
+                ON CONFLICT (url) DO UPDATE
+                SET response = excluded.response, fetched_at = excluded.fetched_at",
+            )?;
+            let mut processed =
+                tx.prepare_cached("UPDATE refresh_progress SET processed = 1 WHERE item_id = ?1")?;
+            for write in &batch {
+                match write {
+                    Write::Set { url, response } => set.execute(params![url, response])?,
+                    Write::Processed(id) => processed.execute(params![id])?,
+                    // Flushes end a batch rather than being part of it.
+                    Write::Flush(_) => continue,
+                };
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    })
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn flushed_responses_are_read_back() {
+        let cache = Cache::open(":memory:").await.unwrap();
+        cache.set("https://a", "one").await.unwrap();
+        cache.set("https://b", "two").await.unwrap();
+        cache.set("https://a", "three").await.unwrap();
+        cache.flush().await.unwrap();
+
+        assert_eq!(
+            cache.get("https://a").await.unwrap().as_deref(),
+            Some("three")
+        );
+        assert_eq!(
+            cache.get("https://b").await.unwrap().as_deref(),
+            Some("two")
+        );
+        assert_eq!(cache.get("https://c").await.unwrap(), None);
+        let fresh = cache.get_fresh("https://b", Duration::from_secs(60));
+        assert_eq!(fresh.await.unwrap().as_deref(), Some("two"));
+        assert_eq!(cache.stats().await.unwrap().entries, 2);
+    }
+
+    #[tokio::test]
+    async fn a_flush_fails_when_writes_were_lost() {
+        let cache = Cache::open(":memory:").await.unwrap();
+        cache
+            .connection()
+            .call(|conn| {
+                conn.execute("DROP TABLE cache", [])?;
+                Ok(())
+            })
+            .await
+            .unwrap();
+
+        cache.set("https://a", "one").await.unwrap();
+        assert!(cache.flush().await.is_err());
+        // The failure is reported once, to the flush following it.
+        assert!(cache.flush().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn an_interrupted_refresh_is_resumed_within_its_age() {
+        let cache = Cache::open(":memory:").await.unwrap();
+        let hour = Duration::from_secs(60 * 60);
+        assert!(cache
+            .resume_refresh(&[1, 2, 3], hour)
+            .await
+            .unwrap()
+            .is_empty());
+        cache.processed(1).await.unwrap();
+        cache.processed(3).await.unwrap();
+        cache.flush().await.unwrap();
+
+        // Items that left the list are not resumed.
+        let processed = cache.resume_refresh(&[3, 1, 4], hour).await.unwrap();
+        assert_eq!(processed, HashSet::from([1, 3]));
+        assert!(cache
+            .resume_refresh(&[3, 1, 4], Duration::ZERO)
+            .await
+            .unwrap()
+            .is_empty());
+
+        cache.processed(4).await.unwrap();
+        cache.finish_refresh().await.unwrap();
+        assert!(cache.resume_refresh(&[4], hour).await.unwrap().is_empty());
     }
 }
@@ -0,0 +1,93 @@
+/// The command line interface.
+///
+/// Without a subcommand, `hnv` serves the site as it always did.
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+#[derive(Parser)]
+#[command(version = crate::version::LABEL, about = "Hacker News top videos")]
+pub struct Cli {
+    /// Log the progress of refreshes instead of drawing a progress bar, even on a terminal.
+    #[arg(long, global = true)]
+    pub no_progress: bool,
+
+    /// How log lines are written to stderr.
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Serve the site (the default).
+    Serve,
+    /// Fetch the top stories into the cache and exit.
+    Refresh,
+    /// Run the detection on the sources and print every video as soon as it is found, with its
+    /// ID, title and URL separated by tabs.
+    Fetch {
+        /// Print a JSON object per video and line instead, with its rank on its source.
+        #[arg(long)]
+        ndjson: bool,
+    },
+    /// Print the current videos.
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        /// Write the pages and feeds of the site to this directory instead, to host a snapshot
+        /// of it on any static host.
+        #[arg(long = "static", value_name = "DIR", conflicts_with = "format")]
+        dir: Option<PathBuf>,
+        /// Write a `.strm` file and an NFO for every video to this directory instead, for media
+        /// centers like Kodi or Jellyfin to list them as a library.
+        #[arg(long, value_name = "DIR", conflicts_with_all = ["format", "dir"])]
+        strm: Option<PathBuf>,
+    },
+    /// Write all the recorded videos with their details, for analysis in other tools.
+    Dump {
+        #[arg(long, value_enum, default_value_t = DumpFormat::Json)]
+        format: DumpFormat,
+        /// The file to write to, instead of stdout.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Print the current videos whose title contains a search term, ignoring case.
+    Search { query: String },
+    /// Answer LLM assistants over the Model Context Protocol, on stdin and stdout.
+    Mcp,
+    /// Browse the current videos in the terminal, printing the URL of the one picked with Enter.
+    Tui,
+    /// Play the current video at a rank, counting from 1, in the configured player.
+    Open { rank: usize },
+    /// Copy the database to a new file while the server keeps running, e.g. for a cron job.
+    Backup { path: PathBuf },
+    /// Check the configuration, the database, the Hacker News API and the assets, printing what
+    /// needs fixing.
+    Doctor,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    M3u,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum DumpFormat {
+    /// An array of objects.
+    Json,
+    /// A header row, then a row per video.
+    Csv,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum LogFormat {
+    /// Lines for humans.
+    Text,
+    /// One JSON object per line, with the fields of the line and its spans (e.g. the request
+    /// ID), for log shippers.
+    Json,
+}
@@ -0,0 +1,182 @@
+/// Emailing a digest of the top videos on a schedule.
+///
+/// Every day or week, the highest scoring videos first listed since the previous digest are sent
+/// to the recipients over SMTP, as HTML with a plain text alternative. Nothing is sent when there
+/// were no new videos.
+use std::{future::Future, str::FromStr};
+
+use anyhow::Context;
+use askama::Template;
+use chrono::{TimeDelta, Utc};
+use lettre::{
+    message::{Mailbox, MultiPart},
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::{DigestConfig, DigestPeriod},
+    i18n::Locale,
+    video::Video,
+};
+
+#[derive(Template)]
+#[template(path = "digest.html")]
+struct DigestHtml<'a> {
+    title: &'a str,
+    site_url: &'a str,
+    videos: &'a [Video],
+}
+
+#[derive(Template)]
+#[template(path = "digest.txt")]
+struct DigestText<'a> {
+    title: &'a str,
+    site_url: &'a str,
+    videos: &'a [Video],
+}
+
+pub struct Digest {
+    config: DigestConfig,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    schedule: cron::Schedule,
+    /// The index of the instance, linked at the end of the digest.
+    site_url: String,
+}
+
+impl Digest {
+    pub fn new(config: DigestConfig, site_url: String) -> anyhow::Result<Self> {
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::from_url(&config.smtp_url)
+            .context("Invalid digest SMTP URL")?
+            .build();
+        let from = config
+            .from
+            .parse()
+            .with_context(|| format!("Invalid digest sender {:?}", config.from))?;
+        let to = config
+            .to
+            .iter()
+            .map(|to| {
+                to.parse()
+                    .with_context(|| format!("Invalid digest recipient {:?}", to))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        if config.hour > 23 {
+            anyhow::bail!("Invalid digest hour {}", config.hour);
+        }
+        let days = match config.period {
+            DigestPeriod::Daily => "*",
+            DigestPeriod::Weekly => "Mon",
+        };
+        let schedule = cron::Schedule::from_str(&format!("0 0 {} * * {}", config.hour, days))?;
+
+        Ok(Self {
+            config,
+            transport,
+            from,
+            to,
+            schedule,
+            site_url,
+        })
+    }
+
+    /// Send the digests whenever they are due, forever, with the videos `top_videos` returns for
+    /// the start of the period, in seconds since the Unix epoch, and the most videos to list.
+    pub async fn run<F, Fut>(&self, top_videos: F)
+    where
+        F: Fn(i64, u32) -> Fut,
+        Fut: Future<Output = anyhow::Result<Vec<Video>>>,
+    {
+        let period = match self.config.period {
+            DigestPeriod::Daily => TimeDelta::days(1),
+            DigestPeriod::Weekly => TimeDelta::weeks(1),
+        };
+        while let Some(next) = self.schedule.upcoming(Utc).next() {
+            debug!("Next digest at {}", next);
+            tokio::time::sleep((next - Utc::now()).to_std().unwrap_or_default()).await;
+
+            let since = (next - period).timestamp();
+            let result = match top_videos(since, self.config.limit).await {
+                Ok(videos) if videos.is_empty() => {
+                    info!("Not sending the digest, there were no new videos");
+                    continue;
+                }
+                Ok(videos) => self.send(&videos).await,
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(()) => info!("Sent the digest to {} recipients", self.to.len()),
+                Err(err) => warn!("Failed to send the digest: {:#}", err),
+            }
+        }
+    }
+
+    async fn send(&self, videos: &[Video]) -> anyhow::Result<()> {
+        let site_title = Locale::default().message("site-title");
+        let title = match self.config.period {
+            DigestPeriod::Daily => format!("{}: the videos of the day", site_title),
+            DigestPeriod::Weekly => format!("{}: the videos of the week", site_title),
+        };
+        let html = DigestHtml {
+            title: &title,
+            site_url: &self.site_url,
+            videos,
+        }
+        .render()?;
+        let text = DigestText {
+            title: &title,
+            site_url: &self.site_url,
+            videos,
+        }
+        .render()?;
+
+        let mut message = Message::builder().from(self.from.clone()).subject(&title);
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        let message = message.multipart(MultiPart::alternative_plain_html(text, html))?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, Timelike, Weekday};
+
+    use super::*;
+
+    fn config(period: DigestPeriod, hour: u32) -> DigestConfig {
+        DigestConfig {
+            smtp_url: "smtp://localhost:25".to_string(),
+            from: "hnv <hnv@example.com>".to_string(),
+            to: vec!["alice@example.com".to_string()],
+            period,
+            hour,
+            limit: 10,
+        }
+    }
+
+    #[test]
+    fn weekly_digests_are_sent_on_mondays() {
+        let digest = Digest::new(config(DigestPeriod::Weekly, 7), String::new()).unwrap();
+        for next in digest.schedule.upcoming(Utc).take(3) {
+            assert_eq!(next.weekday(), Weekday::Mon);
+            assert_eq!((next.hour(), next.minute()), (7, 0));
+        }
+
+        let digest = Digest::new(config(DigestPeriod::Daily, 7), String::new()).unwrap();
+        let days: Vec<_> = digest.schedule.upcoming(Utc).take(2).collect();
+        assert_eq!(days[1] - days[0], TimeDelta::days(1));
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        assert!(Digest::new(config(DigestPeriod::Daily, 24), String::new()).is_err());
+        let mut invalid = config(DigestPeriod::Daily, 7);
+        invalid.to = vec!["not an address".to_string()];
+        assert!(Digest::new(invalid, String::new()).is_err());
+    }
+}
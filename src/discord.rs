@@ -0,0 +1,86 @@
+/// Posting new videos to a Discord channel through a webhook.
+///
+/// Every video is posted as an embed with its thumbnail, score and discussion, which Discord
+/// renders as a card.
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use crate::{
+    config::{DiscordConfig, NotifyRule},
+    hacker_news::HackerNews,
+    notify::Notifier,
+    video::Video,
+};
+
+/// The longest title Discord accepts in an embed.
+const MAX_TITLE: usize = 256;
+
+pub struct Discord {
+    client: Client,
+    config: DiscordConfig,
+    /// Used for the thumbnails, which come from the oEmbed metadata of the videos.
+    hn: Arc<HackerNews>,
+}
+
+impl Discord {
+    pub fn new(config: DiscordConfig, hn: Arc<HackerNews>) -> Self {
+        Self {
+            client: Client::new(),
+            config,
+            hn,
+        }
+    }
+
+    /// The embed of a video.
+    async fn embed(&self, video: &Video) -> Value {
+        let title = match video.title.char_indices().nth(MAX_TITLE - 1) {
+            Some((end, _)) => format!("{}…", &video.title[..end]),
+            None => video.title.clone(),
+        };
+        let mut fields = Vec::new();
+        if let Some(score) = video.score {
+            fields.push(json!({ "name": "Score", "value": score.to_string(), "inline": true }));
+        }
+        fields.push(json!({
+            "name": "Discussion",
+            "value": format!("[{}]({})", video.source_label, video.discussion_url),
+            "inline": true,
+        }));
+
+        let mut embed = json!({
+            "title": title,
+            "url": video.url,
+            "fields": fields,
+        });
+        let oembed = self.hn.oembed(&video.url).await;
+        if let Some(thumbnail) = oembed.and_then(|oembed| oembed.thumbnail_url) {
+            embed["thumbnail"] = json!({ "url": thumbnail });
+        }
+        embed
+    }
+}
+
+#[async_trait]
+impl Notifier for Discord {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    fn rules(&self) -> &[NotifyRule] {
+        &self.config.rules
+    }
+
+    async fn send(&self, video: &Video) -> anyhow::Result<()> {
+        let body = json!({ "embeds": [self.embed(video).await] });
+        self.client
+            .post(&self.config.webhook_url)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+/// Checking that everything `hnv` needs is in order, before filing a bug report.
+///
+/// Every check prints a line, and those that fail a hint at what to do about it. Checks go on
+/// after a failure, with the default configuration when the file can't be loaded, so a single
+/// run lists every problem. The command fails when any check does.
+use std::{path::Path, time::Duration};
+
+use anyhow::bail;
+use tokio_rusqlite::{Connection, OpenFlags};
+
+use crate::{assets, config::Config, store::SCHEMA_VERSION};
+
+/// How long the sources may take to answer.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// The outcome of a check.
+struct Finding {
+    status: Status,
+    message: String,
+    /// What to do about a warning or an error.
+    hint: Option<String>,
+}
+
+impl Finding {
+    fn ok(message: impl Into<String>) -> Self {
+        Self {
+            status: Status::Ok,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn warning(message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            status: Status::Warning,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn error(message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            status: Status::Error,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+/// Run every check, printing the findings.
+pub async fn run() -> anyhow::Result<()> {
+    let (config, finding) = check_config();
+    let mut findings = vec![("config", finding)];
+    findings.push(("database", check_database(&config.database).await));
+    if config.hacker_news.enabled {
+        findings.push((
+            "hacker news",
+            check_hacker_news(&config.hacker_news_url).await,
+        ));
+    }
+    findings.push(("assets", check_assets(config.assets_dir.as_deref())));
+
+    let mut errors = 0;
+    for (check, finding) in &findings {
+        let status = match finding.status {
+            Status::Ok => "ok",
+            Status::Warning => "warning",
+            Status::Error => {
+                errors += 1;
+                "error"
+            }
+        };
+        println!("{:<8} {:<12} {}", status, check, finding.message);
+        if let Some(hint) = &finding.hint {
+            println!("{:<21} {}", "", hint);
+        }
+    }
+    if errors > 0 {
+        bail!("{} of {} checks failed", errors, findings.len());
+    }
+    Ok(())
+}
+
+fn check_config() -> (Config, Finding) {
+    let config = match Config::load_unchecked() {
+        Ok(config) => config,
+        Err(err) => {
+            let finding = Finding::error(
+                format!("{:#}", err),
+                "Fix the file, the other checks use the defaults meanwhile",
+            );
+            return (Config::default(), finding);
+        }
+    };
+    let finding = match config.validate() {
+        Ok(()) => Finding::ok(match Config::path() {
+            Some(path) => format!("Loaded {}", path.display()),
+            None => "No configuration file, using the defaults".to_string(),
+        }),
+        Err(err) => Finding::error(format!("{:#}", err), "Fix the settings named above"),
+    };
+    (config, finding)
+}
+
+async fn check_database(path: &Path) -> Finding {
+    if path == Path::new(":memory:") {
+        return Finding::ok("In memory, discarded on exit");
+    }
+    if !path.exists() {
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        if !dir.is_dir() && path != crate::cache::default_path() {
+            return Finding::error(
+                format!("{} doesn't exist, nor its directory", path.display()),
+                format!("Create {} or point `database` elsewhere", dir.display()),
+            );
+        }
+        return Finding::ok(format!("{} is created on the first start", path.display()));
+    }
+
+    // Only look, migrating is left to the server.
+    let conn = match Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).await {
+        Ok(conn) => conn,
+        Err(err) => {
+            return Finding::error(
+                format!("Failed to open {}: {}", path.display(), err),
+                "Check that the file is a SQLite database and readable by this user",
+            )
+        }
+    };
+    let checked = conn
+        .call(|conn| {
+            let integrity: String = conn.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+            let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+            Ok((integrity, version))
+        })
+        .await;
+    let (integrity, version) = match checked {
+        Ok(checked) => checked,
+        Err(err) => {
+            return Finding::error(
+                format!("Failed to read {}: {}", path.display(), err),
+                "Check that the file is a SQLite database and not locked by another program",
+            )
+        }
+    };
+
+    if integrity != "ok" {
+        return Finding::error(
+            format!("{} is corrupt: {}", path.display(), integrity),
+            "Restore a backup, or remove the file to start over",
+        );
+    }
+    if std::fs::metadata(path).is_ok_and(|metadata| metadata.permissions().readonly()) {
+        return Finding::error(
+            format!("{} is read-only", path.display()),
+            "Make the file writable by this user",
+        );
+    }
+    match version.cmp(&SCHEMA_VERSION) {
+        std::cmp::Ordering::Less => Finding::warning(
+            format!(
+                "{} has schema version {} of {}",
+                path.display(),
+                version,
+                SCHEMA_VERSION
+            ),
+            "It is migrated on the next start, back it up first",
+        ),
+        std::cmp::Ordering::Equal => {
+            Finding::ok(format!("{} has schema version {}", path.display(), version))
+        }
+        std::cmp::Ordering::Greater => Finding::warning(
+            format!(
+                "{} has schema version {}, newer than this version's {}",
+                path.display(),
+                version,
+                SCHEMA_VERSION
+            ),
+            "A newer version of hnv wrote it, upgrade to use what it added",
+        ),
+    }
+}
+
+async fn check_hacker_news(base_url: &str) -> Finding {
+    let url = format!("{}/topstories.json", base_url.trim_end_matches('/'));
+    let client = reqwest::Client::new();
+    let response = match client.get(&url).timeout(TIMEOUT).send().await {
+        Ok(response) => response,
+        Err(err) => {
+            return Finding::error(
+                format!("Failed to reach {}: {:#}", url, anyhow::Error::from(err)),
+                "Check the network and `hacker_news_url`",
+            )
+        }
+    };
+    if !response.status().is_success() {
+        return Finding::error(
+            format!("{} answered {}", url, response.status()),
+            "Check `hacker_news_url`",
+        );
+    }
+    match response.json::<Vec<u64>>().await {
+        Ok(ids) => Finding::ok(format!("{} lists {} top stories", base_url, ids.len())),
+        Err(err) => Finding::error(
+            format!(
+                "{} answered something other than a list of stories: {}",
+                url, err
+            ),
+            "Check that `hacker_news_url` points at the Hacker News API",
+        ),
+    }
+}
+
+fn check_assets(dir: Option<&Path>) -> Finding {
+    let Some(dir) = dir else {
+        return Finding::ok(format!("{} embedded files", assets::paths().count()));
+    };
+    if !dir.is_dir() {
+        return Finding::error(
+            format!("{} isn't a directory", dir.display()),
+            "Point `assets_dir` at a copy of the assets, or remove it to use the embedded ones",
+        );
+    }
+    let missing: Vec<_> = assets::paths()
+        .filter(|path| !dir.join(path.as_ref()).is_file())
+        .collect();
+    if missing.is_empty() {
+        return Finding::ok(format!("{} has all the files", dir.display()));
+    }
+    Finding::warning(
+        format!("{} lacks {}", dir.display(), missing.join(", ")),
+        "Copy them from the assets of this version, the pages link to them",
+    )
+}
@@ -0,0 +1,408 @@
+/// Downloading videos with yt-dlp, to watch them offline.
+///
+/// Videos are queued from the admin pages, or automatically when they are new and match the
+/// configured rules. A single worker downloads them one after the other into the media directory,
+/// named after their IDs along with their subtitles, and the state of every download is kept in
+/// the store so the queue survives restarts.
+///
+/// After every download, and every hour, the oldest downloaded videos are removed while they
+/// exceed the quota or the retention window, except for the pinned ones.
+use std::{
+    io::ErrorKind,
+    path::Path,
+    process::Stdio,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use tokio::{process::Command, sync::Notify};
+use tracing::{info, warn};
+
+use crate::{
+    config::{DownloadsConfig, NotifyRule},
+    features::{Feature, Features},
+    notify::Notifier,
+    store::{Download, DownloadState, Store},
+    video::Video,
+};
+
+/// How often the downloads are cleaned up while nothing is downloaded.
+const CLEAN_UP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Clone)]
+pub struct Downloads {
+    config: DownloadsConfig,
+    store: Store,
+    /// Pauses the downloads while they are disabled.
+    features: Arc<Features>,
+    /// Wakes the worker up when a video is queued.
+    queued: Arc<Notify>,
+}
+
+impl Downloads {
+    pub fn new(
+        config: DownloadsConfig,
+        store: Store,
+        features: Arc<Features>,
+    ) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&config.dir).with_context(|| {
+            format!(
+                "Failed to create the media directory {}",
+                config.dir.display()
+            )
+        })?;
+        Ok(Self {
+            config,
+            store,
+            features,
+            queued: Default::default(),
+        })
+    }
+
+    /// The most bytes the downloaded videos may take, if limited.
+    pub fn max_size(&self) -> Option<u64> {
+        self.config.max_size
+    }
+
+    /// The size of a downloaded video, if it is still there.
+    pub fn size(&self, download: &Download) -> Option<u64> {
+        let file = download.file.as_ref()?;
+        let metadata = std::fs::metadata(self.config.dir.join(file)).ok()?;
+        Some(metadata.len())
+    }
+
+    /// The subtitles downloaded with a video, as their languages and the names of their files.
+    pub fn subtitles(&self, download: &Download) -> Vec<(String, String)> {
+        let prefix = format!("{}.", download.id);
+        let Ok(entries) = std::fs::read_dir(&self.config.dir) else {
+            return Vec::new();
+        };
+        let mut subtitles: Vec<_> = entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter_map(|file| {
+                // Named like `{id}.{language}.vtt`.
+                let language = file.strip_prefix(&prefix)?.strip_suffix(".vtt")?;
+                Some((language.to_string(), file.clone()))
+            })
+            .collect();
+        subtitles.sort();
+        subtitles
+    }
+
+    /// Whether new videos are downloaded automatically.
+    pub fn has_rules(&self) -> bool {
+        !self.config.rules.is_empty()
+    }
+
+    /// Queue a video for download, returning whether it was queued, i.e. it wasn't already.
+    pub async fn queue(&self, video: &Video) -> anyhow::Result<bool> {
+        let queued = self
+            .store
+            .queue_download(&video.id, &video.url, &video.title)
+            .await?;
+        if queued {
+            self.queued.notify_one();
+        }
+        Ok(queued)
+    }
+
+    /// Download the queued videos, forever, while the feature is enabled.
+    pub async fn run(&self) {
+        if let Err(err) = self.store.requeue_running_downloads().await {
+            warn!("Failed to resume the interrupted downloads: {:#}", err);
+        }
+        self.clean_up().await;
+        loop {
+            if !self.features.enabled(Feature::Downloads) {
+                info!("Downloads are disabled, pausing them");
+                self.features.wait_for(Feature::Downloads).await;
+                info!("Downloads are enabled, resuming them");
+            }
+            match self.store.next_download().await {
+                Ok(Some(download)) => {
+                    self.download(download).await;
+                    self.clean_up().await;
+                }
+                Ok(None) => {
+                    let queued = self.queued.notified();
+                    if tokio::time::timeout(CLEAN_UP_INTERVAL, queued)
+                        .await
+                        .is_err()
+                    {
+                        self.clean_up().await;
+                    }
+                }
+                Err(err) => {
+                    warn!("Failed to get the next download: {:#}", err);
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                }
+            }
+        }
+    }
+
+    /// Remove the oldest videos that aren't pinned, while they are too old or over the quota.
+    async fn clean_up(&self) {
+        if self.config.max_size.is_none() && self.config.keep_days.is_none() {
+            return;
+        }
+        let downloads = match self.store.finished_downloads().await {
+            Ok(downloads) => downloads,
+            Err(err) => {
+                warn!("Failed to clean up the downloads: {:#}", err);
+                return;
+            }
+        };
+
+        let sizes: Vec<_> = downloads
+            .iter()
+            .map(|download| self.size(download).unwrap_or(0))
+            .collect();
+        let mut total: u64 = sizes.iter().sum();
+        let expiry = self.config.keep_days.map(|days| {
+            let now = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default();
+            now.as_secs() as i64 - (days * 24 * 60 * 60) as i64
+        });
+        for (download, size) in downloads.iter().zip(sizes) {
+            let expired = expiry.is_some_and(|expiry| download.updated_at < expiry);
+            let over_quota = self
+                .config
+                .max_size
+                .is_some_and(|max_size| total > max_size);
+            if download.pinned || !(expired || over_quota) {
+                continue;
+            }
+            match self.remove(download).await {
+                Ok(()) => total -= size,
+                Err(err) => warn!(video = %download.id, "Failed to remove a download: {:#}", err),
+            }
+        }
+    }
+
+    /// Delete a downloaded video and its subtitles.
+    async fn remove(&self, download: &Download) -> anyhow::Result<()> {
+        let subtitles = self.subtitles(download).into_iter().map(|(_, file)| file);
+        for file in download.file.clone().into_iter().chain(subtitles) {
+            match tokio::fs::remove_file(self.config.dir.join(file)).await {
+                Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            }
+        }
+        self.store
+            .update_download(&download.id, DownloadState::Removed, None, None)
+            .await?;
+        info!(video = %download.id, "Removed the download of {}", download.url);
+        Ok(())
+    }
+
+    async fn download(&self, download: Download) {
+        let id = &download.id;
+        if let Err(err) = self
+            .store
+            .update_download(id, DownloadState::Running, None, None)
+            .await
+        {
+            warn!(video = %id, "Failed to start a download: {:#}", err);
+            return;
+        }
+
+        let (state, file, error) = match self.yt_dlp(&download).await {
+            Ok(file) => {
+                info!(video = %id, "Downloaded {}", file);
+                (DownloadState::Done, Some(file), None)
+            }
+            Err(err) => {
+                warn!(video = %id, "Failed to download {}: {:#}", download.url, err);
+                (DownloadState::Failed, None, Some(format!("{:#}", err)))
+            }
+        };
+        if let Err(err) = self.store.update_download(id, state, file, error).await {
+            warn!(video = %id, "Failed to record a download: {:#}", err);
+        }
+    }
+
+    /// Download a video and its subtitles, returning the name of the video file.
+    async fn yt_dlp(&self, download: &Download) -> anyhow::Result<String> {
+        let mut command = Command::new(&self.config.yt_dlp);
+        command
+            .args(["--no-playlist", "--no-progress", "--quiet"])
+            .args(["--print", "after_move:filepath"])
+            .arg("--paths")
+            .arg(&self.config.dir)
+            .args(["--output", &format!("{}.%(ext)s", download.id)]);
+        if let Some(format) = &self.config.format {
+            command.args(["--format", format]);
+        }
+        if !self.config.subtitles.is_empty() {
+            // Browsers only play WebVTT subtitles.
+            command
+                .args([
+                    "--write-subs",
+                    "--sub-format",
+                    "vtt/best",
+                    "--convert-subs",
+                    "vtt",
+                ])
+                .args(["--sub-langs", &self.config.subtitles.join(",")]);
+        }
+        let output = command
+            .arg("--")
+            .arg(&download.url)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await
+            .with_context(|| format!("Failed to run {}", self.config.yt_dlp.display()))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr.lines().rev().find(|line| !line.trim().is_empty());
+            bail!(
+                "yt-dlp failed with {}: {}",
+                output.status,
+                reason.unwrap_or("no output")
+            );
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        stdout
+            .lines()
+            .rev()
+            .find_map(|line| Path::new(line.trim()).file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .context("yt-dlp didn't report the downloaded file")
+    }
+}
+
+/// New videos matching the rules are queued like the posts of the notifiers, so each is only
+/// queued once.
+#[async_trait]
+impl Notifier for Downloads {
+    fn name(&self) -> &'static str {
+        "downloads"
+    }
+
+    fn rules(&self) -> &[NotifyRule] {
+        &self.config.rules
+    }
+
+    async fn send(&self, video: &Video) -> anyhow::Result<()> {
+        // Only the videos queued by hand wait for the downloads to be enabled again.
+        if !self.features.enabled(Feature::Downloads) {
+            return Ok(());
+        }
+        self.queue(video).await?;
+        Ok(())
+    }
+
+    async fn send_all(&self, videos: &[&Video]) -> anyhow::Result<()> {
+        for video in videos {
+            self.send(video).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use tokio_rusqlite::Connection;
+
+    use super::*;
+    use crate::{config::FeaturesConfig, video::VideoId};
+
+    /// Downloads in a directory of their own, with videos `1` to `4` of 100 bytes each downloaded
+    /// in that order and `2` pinned, along with the connection to their store.
+    async fn downloads(
+        name: &str,
+        max_size: Option<u64>,
+        keep_days: Option<u64>,
+    ) -> (Downloads, Connection) {
+        let dir =
+            std::env::temp_dir().join(format!("hnv-downloads-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = DownloadsConfig {
+            dir: dir.clone(),
+            yt_dlp: PathBuf::from("yt-dlp"),
+            format: None,
+            subtitles: Vec::new(),
+            max_size,
+            keep_days,
+            rules: Vec::new(),
+        };
+        let conn = Connection::open_in_memory().await.unwrap();
+        let store = Store::new(conn.clone()).await.unwrap();
+        let features = Arc::new(Features::new(&FeaturesConfig::default()));
+        let downloads = Downloads::new(config, store.clone(), features).unwrap();
+
+        for id in 1..=4 {
+            let id: VideoId = id.to_string().parse().unwrap();
+            let file = format!("{}.mp4", id);
+            std::fs::write(dir.join(&file), [0; 100]).unwrap();
+            store
+                .queue_download(&id, "https://youtu.be/x", "A talk")
+                .await
+                .unwrap();
+            store
+                .update_download(&id, DownloadState::Done, Some(file), None)
+                .await
+                .unwrap();
+        }
+        std::fs::write(dir.join("1.en.vtt"), "WEBVTT").unwrap();
+        store
+            .pin_download(&"2".parse().unwrap(), true)
+            .await
+            .unwrap();
+        (downloads, conn)
+    }
+
+    fn exists(downloads: &Downloads, file: &str) -> bool {
+        downloads.config.dir.join(file).exists()
+    }
+
+    async fn state(downloads: &Downloads, id: &str) -> DownloadState {
+        let download = downloads.store.download(&id.parse().unwrap()).await;
+        download.unwrap().unwrap().state
+    }
+
+    #[tokio::test]
+    async fn the_oldest_unpinned_videos_make_room() {
+        let (downloads, _) = downloads("quota", Some(250), None).await;
+
+        downloads.clean_up().await;
+
+        let kept =
+            ["1.mp4", "1.en.vtt", "2.mp4", "3.mp4", "4.mp4"].map(|file| exists(&downloads, file));
+        assert_eq!(kept, [false, false, true, false, true]);
+        assert_eq!(state(&downloads, "1").await, DownloadState::Removed);
+        assert_eq!(state(&downloads, "2").await, DownloadState::Done);
+        assert_eq!(state(&downloads, "3").await, DownloadState::Removed);
+        assert_eq!(state(&downloads, "4").await, DownloadState::Done);
+
+        std::fs::remove_dir_all(&downloads.config.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn videos_are_removed_once_too_old_unless_pinned() {
+        let (downloads, conn) = downloads("retention", None, Some(7)).await;
+        conn.call(|conn| {
+            conn.execute(
+                "UPDATE downloads SET updated_at = 0 WHERE item_id IN ('1', '2')",
+                [],
+            )?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        downloads.clean_up().await;
+
+        let kept = ["1.mp4", "2.mp4", "3.mp4", "4.mp4"].map(|file| exists(&downloads, file));
+        assert_eq!(kept, [false, true, true, true]);
+
+        std::fs::remove_dir_all(&downloads.config.dir).unwrap();
+    }
+}
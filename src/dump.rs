@@ -0,0 +1,104 @@
+/// Dumping the recorded videos, for analysis in tools like pandas or DuckDB.
+///
+/// Every video listed by a refresh is in the dump, not only the current ones, with the times it
+/// was first and last listed in RFC 3339 format, which both tools parse as timestamps.
+use std::io::Write;
+
+use serde::Serialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{cli::DumpFormat, store::ListedVideo};
+
+/// The columns of the CSV format, in the order of the fields of [`Row`].
+const COLUMNS: [&str; 8] = [
+    "id",
+    "source",
+    "title",
+    "url",
+    "discussion_url",
+    "score",
+    "first_seen",
+    "last_seen",
+];
+
+#[derive(Serialize)]
+struct Row<'a> {
+    id: String,
+    source: &'a str,
+    title: &'a str,
+    url: &'a str,
+    discussion_url: &'a str,
+    score: Option<i64>,
+    first_seen: String,
+    last_seen: String,
+}
+
+impl<'a> Row<'a> {
+    fn new(video: &'a ListedVideo) -> Self {
+        Self {
+            id: video.id.to_string(),
+            source: &video.id.source,
+            title: &video.title,
+            url: &video.url,
+            discussion_url: &video.discussion_url,
+            score: video.score,
+            first_seen: timestamp(video.first_seen),
+            last_seen: timestamp(video.last_seen),
+        }
+    }
+
+    fn fields(&self) -> [String; 8] {
+        [
+            self.id.clone(),
+            self.source.to_string(),
+            self.title.to_string(),
+            self.url.to_string(),
+            self.discussion_url.to_string(),
+            self.score
+                .map(|score| score.to_string())
+                .unwrap_or_default(),
+            self.first_seen.clone(),
+            self.last_seen.clone(),
+        ]
+    }
+}
+
+/// Write the videos in a format.
+pub fn write(
+    videos: &[ListedVideo],
+    format: DumpFormat,
+    mut out: impl Write,
+) -> anyhow::Result<()> {
+    let rows = videos.iter().map(Row::new);
+    match format {
+        DumpFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, &rows.collect::<Vec<_>>())?;
+            writeln!(out)?;
+        }
+        DumpFormat::Csv => {
+            writeln!(out, "{}", COLUMNS.join(","))?;
+            for row in rows {
+                let fields = row.fields().map(|field| csv_field(&field));
+                writeln!(out, "{}", fields.join(","))?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Quote a CSV field if it needs to be, as in RFC 4180.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn timestamp(seconds: i64) -> String {
+    OffsetDateTime::from_unix_timestamp(seconds)
+        .ok()
+        .and_then(|time| time.format(&Rfc3339).ok())
+        .unwrap_or_default()
+}
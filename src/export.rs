@@ -0,0 +1,238 @@
+/// Exporting the site to plain files, to host a snapshot of it on any static host.
+///
+/// The pages are rendered with the templates the server uses, as a visitor with the default
+/// preferences and language sees them. Every page is written as the `index.html` of a directory
+/// named after its path, e.g. `watch/123/index.html`, which static hosts serve at `/watch/123`.
+/// The forms that need the server, such as hiding videos, don't work in the snapshot.
+///
+/// The videos can also be exported as a library for media centers such as Kodi or Jellyfin: a
+/// `.strm` file per video, holding the URL to play, next to a `.nfo` file with its details. The
+/// URL is the downloaded copy of the video when there is one, since media centers only play
+/// links to YouTube with a plugin.
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::Path,
+};
+
+use anyhow::Context;
+use askama::Template;
+use tracing::info;
+
+use crate::{
+    assets,
+    preferences::View,
+    routes::{
+        feeds::{feed_template, sitemap},
+        index::{hot_discussions, IndexTemplate, Sort},
+        watch::WatchTemplate,
+        Layout,
+    },
+    state::State,
+    video::Video,
+};
+
+/// The longest title kept in the names of the files of the library.
+const MAX_NAME_LENGTH: usize = 100;
+
+/// The details of a video for media centers, as a Kodi movie NFO.
+#[derive(Template)]
+#[template(path = "movie.nfo", escape = "html")]
+struct NfoTemplate<'a> {
+    video: &'a Video,
+    /// Where the video is discussed, as its description.
+    plot: String,
+    thumbnail: Option<String>,
+}
+
+/// The layout of the exported pages, as of a visitor with the default preferences.
+fn layout(state: &State) -> Layout {
+    Layout {
+        base: state.config.base_path.clone().unwrap_or_default(),
+        privacy: state
+            .config
+            .privacy
+            .as_ref()
+            .map(|privacy| privacy.enabled_by_default),
+        time_zone: state.config.timezone,
+        ..Default::default()
+    }
+}
+
+/// The videos as listed on the exported pages.
+fn videos(state: &State, layout: &Layout) -> Vec<Video> {
+    state
+        .videos()
+        .iter()
+        .map(|video| video.clone().with_frontend(state.frontend(layout)))
+        .collect()
+}
+
+/// Render the index as it is exported, which the server shows when it is overloaded.
+pub fn render_index(state: &State) -> askama::Result<String> {
+    let layout = layout(state);
+    let videos = videos(state, &layout);
+    index_template(state, layout, videos).render()
+}
+
+/// The index as anyone sees it, without the visitor's own videos and preferences.
+fn index_template(state: &State, layout: Layout, videos: Vec<Video>) -> IndexTemplate {
+    IndexTemplate {
+        layout,
+        refresh: None,
+        hot: hot_discussions(&videos),
+        videos,
+        saved: Vec::new(),
+        hidden: Vec::new(),
+        hidden_count: 0,
+        show_hidden: false,
+        sources: state.source_links(),
+        source: None,
+        sort: Sort::Rank,
+        clicks: HashMap::new(),
+        loved: Vec::new(),
+        loves: HashMap::new(),
+        continue_watching: Vec::new(),
+        view: View::List,
+        // There are no other pages to link to, all of the videos are on this one.
+        page: 1,
+        more: false,
+    }
+}
+
+/// Write the index, the watch pages of the current videos, the feeds and the assets to `dir`.
+pub async fn write_site(state: &State, dir: &Path) -> anyhow::Result<()> {
+    let layout = layout(state);
+    let videos = videos(state, &layout);
+
+    let index = index_template(state, layout.clone(), videos.clone());
+    write(dir, "index.html", index.render()?)?;
+
+    for video in &videos {
+        let watch = WatchTemplate {
+            layout: layout.clone(),
+            page_url: state.config.absolute_url(&video.watch_link),
+            oembed: state.hn.oembed(&video.url).await,
+            video: video.clone(),
+            media_file: None,
+            subtitles: Vec::new(),
+            position: None,
+            playlists: Vec::new(),
+            sparkline: None,
+        };
+        let path = format!("{}/index.html", video.watch_link.trim_start_matches('/'));
+        write(dir, &path, watch.render()?)?;
+    }
+
+    write(
+        dir,
+        "feed.xml",
+        feed_template(state, None, |_| true).render()?,
+    )?;
+    let platforms: BTreeSet<_> = videos.iter().map(|video| video.platform()).collect();
+    for platform in platforms {
+        let feed = feed_template(state, Some(&platform), |video| video.platform() == platform);
+        write(dir, &format!("feed/{}.xml", platform), feed.render()?)?;
+    }
+    // Tags with slashes can't be file names, nor are they served.
+    let tags: BTreeSet<_> = videos
+        .iter()
+        .flat_map(|video| video.tags())
+        .filter(|tag| !tag.contains('/'))
+        .collect();
+    for tag in tags {
+        let feed = feed_template(state, Some(&tag), |video| video.tags().contains(&tag));
+        write(dir, &format!("feed/tag/{}.xml", tag), feed.render()?)?;
+    }
+    write(dir, "sitemap.xml", sitemap(state).render()?)?;
+
+    for path in assets::paths() {
+        let asset = assets::get(&path).expect("listed assets exist");
+        write(dir, &format!("assets/{}", path), asset.data)?;
+    }
+
+    info!("Exported {} videos to {}", videos.len(), dir.display());
+    Ok(())
+}
+
+/// Write a `.strm` and a `.nfo` file for every current video to `dir`, removing those of the
+/// videos that are gone.
+pub async fn write_library(state: &State, dir: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let videos = state.videos();
+    let mut files = BTreeSet::new();
+    for video in videos.iter() {
+        let name = file_name(video);
+        let url = match &state.downloads {
+            Some(_) => state.store.download(&video.id).await?.and_then(|d| d.file),
+            None => None,
+        }
+        .map(|file| state.config.absolute_url(&format!("/media/{}", file)))
+        .unwrap_or_else(|| video.url.clone());
+        let plot = match video.score {
+            Some(score) => format!(
+                "{} points on {}: {}",
+                score, video.source_label, video.discussion_url
+            ),
+            None => format!("On {}: {}", video.source_label, video.discussion_url),
+        };
+        let nfo = NfoTemplate {
+            video,
+            plot,
+            thumbnail: video
+                .youtube_id()
+                .map(|id| format!("https://i.ytimg.com/vi/{}/hqdefault.jpg", id)),
+        };
+
+        let strm = format!("{}.strm", name);
+        write(dir, &strm, format!("{}\n", url))?;
+        let nfo_name = format!("{}.nfo", name);
+        write(dir, &nfo_name, nfo.render()?)?;
+        files.insert(strm);
+        files.insert(nfo_name);
+    }
+
+    // Only the files of the library are removed, anything else in the directory is kept.
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_library = matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("strm" | "nfo")
+        );
+        let name = path.file_name().and_then(|name| name.to_str());
+        if is_library && name.is_some_and(|name| !files.contains(name)) {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+    }
+
+    info!("Exported {} videos to {}", videos.len(), dir.display());
+    Ok(())
+}
+
+/// The name of the files of a video, its title followed by its ID, without the characters file
+/// systems reject.
+fn file_name(video: &Video) -> String {
+    let title: String = video
+        .title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => ' ',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .take(MAX_NAME_LENGTH)
+        .collect();
+    // Names starting with a dot would be hidden.
+    let title = title.trim().trim_start_matches('.');
+    format!("{} [{}]", title, video.id)
+}
+
+/// Write a file of the site, creating the directories on its path.
+fn write(dir: &Path, path: &str, contents: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    let file = dir.join(path);
+    if let Some(parent) = file.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    std::fs::write(&file, contents).with_context(|| format!("Failed to write {}", file.display()))
+}
@@ -0,0 +1,91 @@
+/// Switching experimental behaviors on and off while the server runs.
+///
+/// Every [`Feature`] starts out as set in `[features]`, and operators can flip it on the admin
+/// page to try it out, or to stop it misbehaving, without a restart. Flipped flags only last
+/// until the server restarts, the configuration is where they are kept.
+use std::collections::BTreeMap;
+
+use tokio::sync::watch;
+
+use crate::config::FeaturesConfig;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Feature {
+    /// Downloading the queued videos, and queueing new ones by the rules.
+    Downloads,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 1] = [Feature::Downloads];
+
+    /// The name in the configuration and the URLs of the admin page.
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Downloads => "downloads",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|feature| feature.name() == name)
+    }
+
+    /// What the admin page calls it.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            Feature::Downloads => "admin-feature-downloads",
+        }
+    }
+}
+
+pub struct Features {
+    flags: watch::Sender<BTreeMap<Feature, bool>>,
+}
+
+impl Features {
+    pub fn new(config: &FeaturesConfig) -> Self {
+        let flags = Feature::ALL
+            .into_iter()
+            .map(|feature| {
+                let enabled = match feature {
+                    Feature::Downloads => config.downloads,
+                };
+                (feature, enabled)
+            })
+            .collect();
+        Self {
+            flags: watch::Sender::new(flags),
+        }
+    }
+
+    pub fn enabled(&self, feature: Feature) -> bool {
+        self.flags
+            .borrow()
+            .get(&feature)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Every feature, with whether it is enabled.
+    pub fn all(&self) -> Vec<(Feature, bool)> {
+        self.flags
+            .borrow()
+            .iter()
+            .map(|(feature, enabled)| (*feature, *enabled))
+            .collect()
+    }
+
+    pub fn set(&self, feature: Feature, enabled: bool) {
+        self.flags.send_modify(|flags| {
+            flags.insert(feature, enabled);
+        });
+    }
+
+    /// Wait until a feature is enabled.
+    pub async fn wait_for(&self, feature: Feature) {
+        let mut flags = self.flags.subscribe();
+        // The sender lives as long as `self`, so this only ends when the feature is enabled.
+        let _ = flags
+            .wait_for(|flags| flags.get(&feature).copied().unwrap_or_default())
+            .await;
+    }
+}
@@ -1,165 +1,1056 @@
 use std::{
-    collections::HashMap,
-    sync::{Arc, RwLock},
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
 /// Get data from the Hacker News API.
-use crate::cache::Cache;
-use reqwest::Client;
-
-use serde_json::Value;
-use tokio::task::JoinSet;
-use tracing::debug;
+use crate::cache::{Cache, CacheStats};
+use crate::oembed::{self, OEmbed};
+use crate::source::{Fetched, Source, Story};
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use futures_util::{stream, Stream, StreamExt};
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tracing::{debug, info, warn, Instrument, Span};
 
 /// The base URL for the Hacker News API.
-const BASE_URL: &str = "https://hacker-news.firebaseio.com/v0";
+pub const BASE_URL: &str = "https://hacker-news.firebaseio.com/v0";
+
+/// The name of Hacker News as a [`Source`].
+pub const NAME: &str = "hacker-news";
+
+/// The number of items fetched at the same time, unless configured otherwise.
+pub const DEFAULT_CONCURRENCY: usize = 20;
+
+/// The number of top stories on the front page, which are fetched before the deeper ones.
+pub const FRONT_PAGE: usize = 30;
+
+/// How long a fetched item is used before it is fetched again, unless configured otherwise.
+pub const DEFAULT_ITEM_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// How long after its start an interrupted refresh is resumed rather than started over, unless
+/// configured otherwise.
+pub const DEFAULT_RESUME_WITHIN: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// The number of oEmbed lookups made at the same time after a refresh, unless configured
+/// otherwise. Kept low, the platforms aren't ours to hammer.
+pub const DEFAULT_OEMBED_CONCURRENCY: usize = 4;
 
-const BATCH_SIZE: usize = 20;
+/// How long a video platform may take to answer an oEmbed lookup.
+const OEMBED_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// The client used to make requests to the Hacker News API.
 struct State {
     client: Client,
     cache: Cache,
+    /// The base URL of the API, [`BASE_URL`] unless talking to a stand-in.
+    base_url: String,
 }
 
-#[derive(Default)]
+/// The progress of a fetch of the top stories.
+///
+/// The fetch tasks bump the counts without locking, and every change is published on a watch
+/// channel so that consumers are woken up instead of having to poll.
 pub struct Counter {
+    pending: AtomicUsize,
+    done: AtomicUsize,
+    total: AtomicUsize,
+    progress: watch::Sender<Progress>,
+}
+
+/// A snapshot of a [`Counter`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of items whose fetch started.
     pub pending: usize,
+    /// The number of items whose fetch finished, successfully or not.
     pub done: usize,
+    /// The number of items to fetch, or 0 while the list of top stories is not known yet.
     pub total: usize,
 }
 
 impl Counter {
-    fn pending(&mut self) {
-        self.pending += 1;
+    pub fn new() -> Arc<Counter> {
+        Arc::new(Self {
+            pending: AtomicUsize::new(0),
+            done: AtomicUsize::new(0),
+            total: AtomicUsize::new(0),
+            progress: watch::Sender::new(Progress::default()),
+        })
+    }
+
+    fn set_total(&self, total: usize) {
+        self.total.store(total, Ordering::Relaxed);
+        self.publish();
+    }
+
+    fn pending(&self) {
+        self.pending.fetch_add(1, Ordering::Relaxed);
+        self.publish();
+    }
+
+    fn done(&self) {
+        self.done.fetch_add(1, Ordering::Relaxed);
+        self.publish();
+    }
+
+    /// The current progress.
+    pub fn progress(&self) -> Progress {
+        Progress {
+            pending: self.pending.load(Ordering::Relaxed),
+            done: self.done.load(Ordering::Relaxed),
+            total: self.total.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Get notified whenever the progress changes.
+    pub fn subscribe(&self) -> watch::Receiver<Progress> {
+        self.progress.subscribe()
+    }
+
+    fn publish(&self) {
+        self.progress.send_replace(self.progress());
+    }
+}
+
+/// The fields of a Hacker News item that we make use of.
+///
+/// Items are parsed once when they are fetched, and the rest of the fields are skipped.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Item {
+    pub id: i32,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub score: Option<i64>,
+    /// When the item was submitted, in seconds since the Unix epoch.
+    pub time: Option<i64>,
+    /// The number of comments on the item, counting replies.
+    pub descendants: Option<i64>,
+}
+
+impl Item {
+    /// Parse the JSON of an item. Unknown items are returned as `null` by the API.
+    pub fn parse(json: &str) -> anyhow::Result<Option<Self>> {
+        Ok(serde_json::from_str(json)?)
     }
 
-    fn done(&mut self) {
-        self.done += 1;
+    /// Classify the item by whether (and why) it is a video.
+    pub fn detection(&self) -> Detection {
+        self.url.as_deref().map_or(Detection::Rejected, detect_url)
     }
 
-    pub fn new() -> Arc<RwLock<Counter>> {
-        Default::default()
+    /// Turn the item into a story, if it has the fields of one.
+    pub fn story(&self) -> anyhow::Result<Story> {
+        Ok(Story {
+            id: self.id.to_string(),
+            title: self.title.clone().context("title not found")?,
+            url: self.url.clone().context("url not found")?,
+            discussion_url: discussion_url(self.id),
+            score: self.score,
+            submitted_at: self.time,
+            comments: self.descendants,
+        })
     }
+}
+
+/// A user, as far as following their submissions goes.
+#[derive(Deserialize)]
+struct User {
+    /// The IDs of the stories, comments and polls of the user, the latest first.
+    #[serde(default)]
+    submitted: Vec<i32>,
+}
+
+/// A comment on a story.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Comment {
+    pub id: i32,
+    /// The user who wrote it.
+    pub by: Option<String>,
+    /// The text, in HTML.
+    pub text: Option<String>,
+    /// When it was written, in seconds since the Unix epoch.
+    pub time: Option<i64>,
+    #[serde(default, skip_serializing)]
+    deleted: bool,
+    #[serde(default, skip_serializing)]
+    dead: bool,
+}
+
+/// The replies to an item.
+#[derive(Deserialize)]
+struct Thread {
+    #[serde(default)]
+    kids: Vec<i32>,
+}
+
+/// How an item was classified by the video detection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Detection {
+    /// The item links to YouTube.
+    YouTube,
+    /// The item is marked as a video, e.g. with a `[video]` tag.
+    VideoTag,
+    /// The item is not a video.
+    Rejected,
+}
 
-    pub fn counter(self: &Self) -> (usize, usize, usize) {
-        (self.pending, self.done, self.total)
+impl Detection {
+    /// The name of the rule that classified the item, as counted in the metrics.
+    pub fn rule(self) -> &'static str {
+        match self {
+            Detection::YouTube => "youtube",
+            Detection::VideoTag => "video-tag",
+            Detection::Rejected => "rejected",
+        }
+    }
+}
+
+/// The outcomes of the detection over the stories of a fetch, to notice when it drifts.
+#[derive(Clone, Debug, Default)]
+pub struct Detections {
+    /// By the rule and the platform of the video, which is empty for rejected stories.
+    pub counts: BTreeMap<(&'static str, String), usize>,
+}
+
+impl Detections {
+    pub fn record(&mut self, detection: Detection, url: &str) {
+        let platform = match detection {
+            Detection::Rejected => String::new(),
+            _ => platform(url),
+        };
+        *self.counts.entry((detection.rule(), platform)).or_default() += 1;
+    }
+}
+
+/// Statistics about the most recent fetch of the top stories.
+#[derive(Clone, Default)]
+pub struct Stats {
+    /// When the last fetch finished.
+    pub last_refresh: Option<SystemTime>,
+    /// How long the last fetch took.
+    pub last_duration: Option<Duration>,
+    /// The number of items that were fetched successfully.
+    pub items: usize,
+    /// The number of items that failed to be fetched or parsed.
+    pub errors: usize,
+    /// The number of items reused from the previous fetch without a request.
+    pub reused: usize,
+    pub youtube: usize,
+    pub video_tag: usize,
+    pub rejected: usize,
+}
+
+impl Stats {
+    fn record(&mut self, detection: Detection) {
+        self.items += 1;
+        match detection {
+            Detection::YouTube => self.youtube += 1,
+            Detection::VideoTag => self.video_tag += 1,
+            Detection::Rejected => self.rejected += 1,
+        }
     }
 }
 
+/// A client of the Hacker News API that caches responses and keeps statistics.
 pub struct HackerNews {
     state: Arc<State>,
+    stats: RwLock<Stats>,
+    /// The outcomes of the detection in the most recent fetch of the top stories.
+    detections: RwLock<Detections>,
+    /// The number of items fetched at the same time.
+    concurrency: usize,
+    /// How long a fetched item is used before it is fetched again.
+    item_ttl: Duration,
+    /// The top stories up to this rank are fetched by every fetch of the top stories, the deeper
+    /// ones only once a visitor pages to them.
+    eager_ranks: usize,
+    /// How long after its start an interrupted fetch of the top stories is resumed.
+    resume_within: Duration,
+    /// The items of the previous fetch of the top stories, which are reused until they expire.
+    known: Mutex<HashMap<i32, Known>>,
+    /// How many items are kept in `known` at most.
+    max_known: usize,
+    /// The top stories as of the previous fetch, in their order.
+    top_stories: Mutex<Vec<i32>>,
+    /// The ranks and IDs of the top stories the previous fetch left for later, the highest
+    /// ranked first.
+    deferred: Mutex<Vec<(usize, i32)>>,
+}
+
+/// An item of a previous fetch.
+struct Known {
+    fetched: Instant,
+    detection: Detection,
+    item: Item,
 }
 
 impl HackerNews {
-    pub async fn new() -> anyhow::Result<Self> {
+    pub fn new(cache: Cache) -> Self {
+        Self::with_base_url(cache, BASE_URL)
+    }
+
+    /// Talk to an API at another URL than the official one, e.g. a mirror or a fake for tests.
+    pub fn with_base_url(cache: Cache, base_url: &str) -> Self {
         let client = Client::new();
-        let cache = Cache::new().await?;
-        Ok(Self {
-            state: Arc::new(State { client, cache }),
-        })
+        let base_url = base_url.trim_end_matches('/').to_string();
+        Self {
+            state: Arc::new(State {
+                client,
+                cache,
+                base_url,
+            }),
+            stats: Default::default(),
+            detections: Default::default(),
+            concurrency: DEFAULT_CONCURRENCY,
+            item_ttl: DEFAULT_ITEM_TTL,
+            eager_ranks: usize::MAX,
+            resume_within: DEFAULT_RESUME_WITHIN,
+            known: Default::default(),
+            max_known: usize::MAX,
+            top_stories: Default::default(),
+            deferred: Default::default(),
+        }
+    }
+
+    /// Fetch up to `limit` items at the same time instead of [`DEFAULT_CONCURRENCY`].
+    pub fn with_concurrency(mut self, limit: usize) -> Self {
+        self.concurrency = limit.max(1);
+        self
+    }
+
+    /// Use fetched items for `ttl` instead of [`DEFAULT_ITEM_TTL`] before fetching them again.
+    pub fn with_item_ttl(mut self, ttl: Duration) -> Self {
+        self.item_ttl = ttl;
+        self
+    }
+
+    /// Only fetch the top stories up to `ranks` with the rest of the list. The deeper ones are
+    /// left for [`HackerNews::get_deeper_videos`], unless a previous fetch got them within the
+    /// TTL.
+    pub fn with_eager_ranks(mut self, ranks: usize) -> Self {
+        self.eager_ranks = ranks;
+        self
+    }
+
+    /// Keep at most `max` items of the previous fetch of the top stories to reuse them, evicting
+    /// those fetched longest ago.
+    pub fn with_max_known(mut self, max: usize) -> Self {
+        self.max_known = max;
+        self
+    }
+
+    /// Resume a fetch of the top stories that was interrupted at most `within` after it started,
+    /// instead of [`DEFAULT_RESUME_WITHIN`]. Zero to always start over.
+    pub fn with_resume_within(mut self, within: Duration) -> Self {
+        self.resume_within = within;
+        self
+    }
+
+    /// Get statistics about the response cache.
+    pub async fn cache_stats(&self) -> anyhow::Result<CacheStats> {
+        self.state.cache.stats().await
+    }
+
+    /// Get the statistics of the most recent fetch of the top stories.
+    pub fn stats(&self) -> Stats {
+        self.stats.read().unwrap().clone()
     }
 
     /// Get the top stories from the Hacker News API.
+    ///
+    /// Only the items that are new to the list, or whose previous fetch expired, are fetched. In
+    /// a steady state that is a handful of requests rather than one per top story.
+    ///
+    /// `on_fetched` is called with the rank of every video as soon as it is fetched, so callers
+    /// can show the videos before the whole list is done. The [`FRONT_PAGE`] is fetched first,
+    /// and reported as complete before the deeper ranks are fetched. The ranks below the eager
+    /// ones (see [`HackerNews::with_eager_ranks`]) are not fetched at all, only the items still
+    /// known from before are reused.
+    ///
+    /// The progress is kept in the cache. When the previous fetch was interrupted, e.g. by a
+    /// crash, the items it processed are taken from the cache rather than fetched again, however
+    /// old, unless it started too long ago (see [`HackerNews::with_resume_within`]).
     pub async fn get_top_videos(
         &self,
-        counter: Option<Arc<RwLock<Counter>>>,
-    ) -> anyhow::Result<Vec<String>> {
-        let url = format!("{}/topstories.json", BASE_URL);
+        counter: Option<Arc<Counter>>,
+        mut on_fetched: impl FnMut(Fetched<&Item>) + Send,
+    ) -> anyhow::Result<Vec<Item>> {
+        let url = format!("{}/topstories.json", self.state.base_url);
+        let started = Instant::now();
 
         debug!("Fetching fresh response for top stories");
         let top_stories: Vec<i32> = self.state.client.get(&url).send().await?.json().await?;
 
-        if let Some(counter) = counter.as_ref() {
-            counter.write().unwrap().total = top_stories.len();
+        let processed = match self
+            .state
+            .cache
+            .resume_refresh(&top_stories, self.resume_within)
+            .await
+        {
+            Ok(processed) => processed,
+            Err(err) => {
+                warn!("Failed to resume the previous refresh: {:#}", err);
+                HashSet::new()
+            }
+        };
+        if !processed.is_empty() {
+            info!(
+                "Resuming an interrupted refresh, {} of {} items were processed",
+                processed.len(),
+                top_stories.len()
+            );
         }
 
         let mut result = Vec::new();
+        let mut stats = Stats::default();
+        let mut detections = Detections::default();
+        let mut known = HashMap::new();
+
+        // Reuse the items of the previous fetch that haven't expired yet. Below the eager ranks,
+        // the others wait for a visitor to page to them.
+        let mut previous = std::mem::take(&mut *self.known.lock().unwrap());
+        let mut reused = Vec::new();
+        let mut to_fetch = Vec::new();
+        let mut deferred = Vec::new();
+        for (rank, id) in top_stories.iter().copied().enumerate() {
+            let reusable = previous
+                .remove(&id)
+                .filter(|entry| entry.fetched.elapsed() < self.item_ttl);
+            match reusable {
+                Some(entry) => reused.push((rank, entry)),
+                None if rank >= self.eager_ranks => deferred.push((rank, id)),
+                None => to_fetch.push((rank, id)),
+            }
+        }
+        if let Some(counter) = counter.as_ref() {
+            counter.set_total(top_stories.len() - deferred.len());
+        }
 
-        let arc = self.state.clone();
+        for (rank, entry) in reused {
+            let id = entry.item.id;
+            if let Some(counter) = counter.as_ref() {
+                counter.pending();
+                counter.done();
+            }
+            stats.record(entry.detection);
+            stats.reused += 1;
+            detections.record(
+                entry.detection,
+                entry.item.url.as_deref().unwrap_or_default(),
+            );
+            if entry.detection != Detection::Rejected {
+                on_fetched(Fetched::Video(rank, &entry.item));
+                result.push((rank, entry.item.clone()));
+            }
+            known.insert(id, entry);
+            self.mark_processed(id).await;
+        }
+        debug!(
+            "Reusing {} items, fetching {}, leaving {} for later",
+            stats.reused,
+            to_fetch.len(),
+            deferred.len()
+        );
 
-        // Fetch the items in batches to avoid hitting the rate limit.
-        for i in (0..top_stories.len()).step_by(BATCH_SIZE) {
-            let mut tasks = JoinSet::new();
+        // The front page is what most visitors look at, so it gets all of the fetches before
+        // the deeper ranks get any.
+        let deeper = to_fetch.split_off(to_fetch.partition_point(|(rank, _)| *rank < FRONT_PAGE));
 
-            for id in top_stories.iter().skip(i).take(BATCH_SIZE) {
-                tasks.spawn(arc.clone().get_item(counter.clone(), *id));
+        for (phase, to_fetch) in [to_fetch, deeper].into_iter().enumerate() {
+            if phase == 1 {
+                on_fetched(Fetched::FrontPage);
             }
 
-            while let Some(item) = tasks.join_next().await {
-                if let Ok(Some(item)) = item.unwrap() {
-                    result.push(item);
+            let to_fetch = to_fetch
+                .into_iter()
+                .map(|(rank, id)| {
+                    let ttl = (!processed.contains(&id)).then_some(self.item_ttl);
+                    (rank, id, ttl)
+                })
+                .collect();
+            let mut items = self.fetch_items(counter.clone(), to_fetch);
+            while let Some((rank, id, item)) = items.next().await {
+                match item {
+                    Ok((detection, item)) => {
+                        stats.record(detection);
+                        detections.record(detection, item.url.as_deref().unwrap_or_default());
+                        if detection != Detection::Rejected {
+                            on_fetched(Fetched::Video(rank, &item));
+                            result.push((rank, item.clone()));
+                        }
+                        // Items that came from the cache count as fetched now. They were fetched
+                        // within the TTL, so they expire at most one TTL late.
+                        let fetched = Instant::now();
+                        let entry = Known {
+                            fetched,
+                            detection,
+                            item,
+                        };
+                        known.insert(entry.item.id, entry);
+                        self.mark_processed(id).await;
+                    }
+                    Err(err) => {
+                        let url = self.state.item_url(id);
+                        warn!(item = id, url = %url, "Failed to get item: {:#}", err);
+                        stats.errors += 1;
+                    }
                 }
             }
         }
 
-        Ok(result)
+        stats.last_refresh = Some(SystemTime::now());
+        stats.last_duration = Some(started.elapsed());
+        *self.stats.write().unwrap() = stats;
+        *self.detections.write().unwrap() = detections;
+        // Items that dropped off the list are forgotten.
+        evict_oldest(&mut known, self.max_known);
+        *self.known.lock().unwrap() = known;
+        *self.top_stories.lock().unwrap() = top_stories;
+        *self.deferred.lock().unwrap() = deferred;
+        if let Err(err) = self.state.cache.finish_refresh().await {
+            warn!("Failed to clear the progress of the refresh: {:#}", err);
+        }
+
+        // The items finish in any order, but the videos are listed by their rank.
+        result.sort_unstable_by_key(|(rank, _)| *rank);
+        Ok(result.into_iter().map(|(_, item)| item).collect())
+    }
+
+    /// Fetch the next `count` of the top stories the previous fetch left for later, and return
+    /// the videos among all of the top stories fetched so far, by their rank. `None` when none
+    /// were left.
+    ///
+    /// The items are cached like those of the rest of the list, and reused by the next fetches
+    /// of the top stories until they expire.
+    pub async fn get_deeper_videos(&self, count: usize) -> Option<Vec<Item>> {
+        let to_fetch: Vec<_> = {
+            let mut deferred = self.deferred.lock().unwrap();
+            let count = count.min(deferred.len());
+            deferred.drain(..count).collect()
+        };
+        if to_fetch.is_empty() {
+            return None;
+        }
+        debug!("Fetching {} of the deeper top stories", to_fetch.len());
+
+        let to_fetch = to_fetch
+            .into_iter()
+            .map(|(rank, id)| (rank, id, Some(self.item_ttl)))
+            .collect();
+        let mut items = self.fetch_items(None, to_fetch);
+        while let Some((_, id, item)) = items.next().await {
+            match item {
+                Ok((detection, item)) => {
+                    let entry = Known {
+                        fetched: Instant::now(),
+                        detection,
+                        item,
+                    };
+                    let mut known = self.known.lock().unwrap();
+                    known.insert(id, entry);
+                    evict_oldest(&mut known, self.max_known);
+                }
+                // Like in a refresh, it is left out until the next one.
+                Err(err) => {
+                    let url = self.state.item_url(id);
+                    warn!(item = id, url = %url, "Failed to get item: {:#}", err);
+                }
+            }
+        }
+        Some(self.top_videos())
+    }
+
+    /// Whether the previous fetch of the top stories left some of them for later.
+    pub fn has_deferred(&self) -> bool {
+        !self.deferred.lock().unwrap().is_empty()
+    }
+
+    /// The videos among the top stories of the previous fetch that are known, by their rank.
+    fn top_videos(&self) -> Vec<Item> {
+        let known = self.known.lock().unwrap();
+        let top_stories = self.top_stories.lock().unwrap();
+        top_stories
+            .iter()
+            .filter_map(|id| known.get(id))
+            .filter(|entry| entry.detection != Detection::Rejected)
+            .map(|entry| entry.item.clone())
+            .collect()
+    }
+
+    /// Fetch items, with the ranks they are listed at and the TTLs they are fetched with, and
+    /// yield them as they finish.
+    ///
+    /// A bounded number of items is fetched at a time to avoid hitting the rate limit. A new
+    /// fetch starts as soon as any other finishes, so slow items don't hold up the rest. The
+    /// fetches are children of the caller's span, so they are attributed to the request (or
+    /// refresh) that triggered them.
+    fn fetch_items(
+        &self,
+        counter: Option<Arc<Counter>>,
+        to_fetch: Vec<(usize, i32, Option<Duration>)>,
+    ) -> impl Stream<Item = (usize, i32, anyhow::Result<(Detection, Item)>)> + '_ {
+        let span = Span::current();
+        stream::iter(to_fetch)
+            .map(move |(rank, id, ttl)| {
+                let item = self.state.clone().get_item(counter.clone(), id, ttl);
+                let task = tokio::spawn(item.instrument(span.clone()));
+                // An item whose task panicked fails on its own, like one that can't be fetched.
+                async move {
+                    let item = task
+                        .await
+                        .unwrap_or_else(|err| Err(anyhow!("Fetching item {} failed: {}", id, err)));
+                    (rank, id, item)
+                }
+            })
+            .buffer_unordered(self.concurrency)
+    }
+
+    /// Record that an item of the top stories was processed, so an interrupted refresh can skip it.
+    async fn mark_processed(&self, id: i32) {
+        if let Err(err) = self.state.cache.processed(id).await {
+            warn!(
+                item = id,
+                "Failed to record the progress of the refresh: {:#}", err
+            );
+        }
+    }
+
+    /// Get the videos among the latest `limit` submissions of a user, the latest first, whether
+    /// or not they made it to the top stories.
+    ///
+    /// The submissions count comments too, which are never videos. The user and the items are
+    /// fetched again once they are older than the item TTL.
+    pub async fn get_user_videos(&self, user: &str, limit: usize) -> anyhow::Result<Vec<Item>> {
+        let url = format!("{}/user/{}.json", self.state.base_url, user);
+        let json = match self.state.cache.get_fresh(&url, self.item_ttl).await? {
+            Some(json) => json,
+            None => {
+                debug!("Fetching fresh response for user {}", user);
+                let response = self.state.client.get(&url).send().await?;
+                let json = response.error_for_status()?.text().await?;
+                self.state.cache.set(&url, &json).await?;
+                json
+            }
+        };
+        let user_data: Option<User> = serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse user {}", user))?;
+        let user_data = user_data.with_context(|| format!("User {} does not exist", user))?;
+
+        let items: Vec<_> = stream::iter(user_data.submitted.into_iter().take(limit))
+            .map(|id| self.state.clone().get_item(None, id, Some(self.item_ttl)))
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+        let videos = items
+            .into_iter()
+            .filter_map(|item| match item {
+                Ok((Detection::Rejected, _)) => None,
+                Ok((_, item)) => Some(item),
+                Err(err) => {
+                    warn!(user, "Failed to get a submission: {:#}", err);
+                    None
+                }
+            })
+            .collect();
+        Ok(videos)
+    }
+
+    /// Get a single item, if it is a video.
+    pub async fn get_video(&self, id: i32) -> anyhow::Result<Option<Item>> {
+        let json = self.state.fetch_item(id, Some(self.item_ttl)).await?;
+        // Unknown items are not videos either.
+        let item = Item::parse(&json)?;
+        Ok(item.filter(|item| item.detection() != Detection::Rejected))
+    }
+
+    /// Fetch an item again, bypassing the cache, along with the oEmbed metadata of its video, e.g.
+    /// after its title was edited. Returns the item if it is still a video.
+    ///
+    /// The item replaces the one of the last fetch of the top stories, so the next refresh reuses
+    /// it rather than what was cached before.
+    pub async fn refetch_video(&self, id: i32) -> anyhow::Result<Option<Item>> {
+        let json = self
+            .state
+            .download_item(id)
+            .await
+            .with_context(|| format!("Failed to fetch item {}", id))?;
+        let Some(item) = Item::parse(&json)? else {
+            return Ok(None);
+        };
+        let detection = item.detection();
+        if let Some(known) = self.known.lock().unwrap().get_mut(&id) {
+            *known = Known {
+                fetched: Instant::now(),
+                detection,
+                item: item.clone(),
+            };
+        }
+        if detection == Detection::Rejected {
+            return Ok(None);
+        }
+
+        // Like the preview of the watch page, the metadata only enhances the video.
+        if let Some(url) = &item.url {
+            if let Err(err) = self.state.download_oembed(url).await {
+                debug!("Failed to get oEmbed data for {}: {:#}", url, err);
+            }
+        }
+        Ok(Some(item))
+    }
+
+    /// Get the first comments on a story, those shown first on Hacker News, skipping the deleted
+    /// and flagged ones.
+    pub async fn get_comments(&self, id: i32, limit: usize) -> anyhow::Result<Vec<Comment>> {
+        let json = self.state.fetch_item(id, Some(self.item_ttl)).await?;
+        let thread: Option<Thread> = serde_json::from_str(&json)?;
+        let kids = thread.map(|thread| thread.kids).unwrap_or_default();
+
+        let comments: Vec<_> = stream::iter(kids.into_iter().take(limit))
+            .map(|kid| async move {
+                let json = self.state.fetch_item(kid, Some(self.item_ttl)).await?;
+                Ok::<_, anyhow::Error>(serde_json::from_str::<Option<Comment>>(&json)?)
+            })
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+        let comments = comments.into_iter().collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(comments
+            .into_iter()
+            .flatten()
+            .filter(|comment| !comment.deleted && !comment.dead)
+            .collect())
+    }
+
+    /// Get the oEmbed metadata of a video.
+    ///
+    /// The metadata only enhances the page, so failures are logged and otherwise ignored.
+    pub async fn oembed(&self, video_url: &str) -> Option<OEmbed> {
+        match self.state.fetch_oembed(video_url).await {
+            Ok(oembed) => oembed,
+            Err(err) => {
+                debug!("Failed to get oEmbed data for {}: {:#}", video_url, err);
+                None
+            }
+        }
+    }
+
+    /// Get the oEmbed metadata of a video if it was fetched before, without fetching it, for
+    /// pages that shouldn't wait for the platform.
+    pub async fn cached_oembed(&self, video_url: &str) -> Option<OEmbed> {
+        match self.state.cached_oembed(video_url).await {
+            Ok(oembed) => oembed,
+            Err(err) => {
+                debug!("Failed to read oEmbed data for {}: {:#}", video_url, err);
+                None
+            }
+        }
+    }
+
+    /// Fetch the oEmbed metadata of the videos that isn't cached yet, up to `concurrency` at the
+    /// same time, returning for how many videos there is some.
+    pub async fn prefetch_oembeds(&self, video_urls: Vec<String>, concurrency: usize) -> usize {
+        stream::iter(video_urls)
+            .map(|url| async move { self.oembed(&url).await })
+            .buffer_unordered(concurrency.max(1))
+            .filter(|oembed| std::future::ready(oembed.is_some()))
+            .count()
+            .await
+    }
+}
+
+#[async_trait]
+impl Source for HackerNews {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn label(&self) -> &'static str {
+        "Hacker News"
+    }
+
+    async fn videos(
+        &self,
+        counter: Arc<Counter>,
+        on_fetched: &mut (dyn for<'s> FnMut(Fetched<&'s Story>) + Send),
+    ) -> anyhow::Result<Vec<Story>> {
+        // Malformed items are reported once the list is complete.
+        let items = self
+            .get_top_videos(Some(counter), |fetched| match fetched {
+                Fetched::Video(rank, item) => {
+                    if let Ok(story) = item.story() {
+                        on_fetched(Fetched::Video(rank, &story));
+                    }
+                }
+                Fetched::FrontPage => on_fetched(Fetched::FrontPage),
+            })
+            .await?;
+
+        let stories = items
+            .iter()
+            .filter_map(|item| match item.story() {
+                Ok(story) => Some(story),
+                Err(err) => {
+                    warn!("Skipping malformed item {}: {:#}", item.id, err);
+                    None
+                }
+            })
+            .collect();
+        Ok(stories)
+    }
+
+    async fn video(&self, id: &str) -> anyhow::Result<Option<Story>> {
+        let Ok(id) = id.parse() else {
+            return Ok(None);
+        };
+        match self.get_video(id).await? {
+            Some(item) => Ok(Some(item.story()?)),
+            None => Ok(None),
+        }
+    }
+
+    fn discussion_url(&self, id: &str) -> String {
+        discussion_url(id)
+    }
+
+    fn detections(&self) -> Detections {
+        self.detections.read().unwrap().clone()
+    }
+}
+
+/// Keep at most `max` of the known items, evicting those fetched longest ago.
+fn evict_oldest(known: &mut HashMap<i32, Known>, max: usize) {
+    let excess = known.len().saturating_sub(max);
+    if excess == 0 {
+        return;
+    }
+    let mut by_age: Vec<_> = known
+        .iter()
+        .map(|(id, entry)| (entry.fetched, *id))
+        .collect();
+    by_age.sort_unstable();
+    for (_, id) in by_age.into_iter().take(excess) {
+        known.remove(&id);
     }
 }
 
+/// The URL of the Hacker News discussion of an item.
+fn discussion_url(id: impl std::fmt::Display) -> String {
+    format!("https://news.ycombinator.com/item?id={}", id)
+}
+
 impl State {
     async fn get_item(
         self: Arc<Self>,
-        counter: Option<Arc<RwLock<Counter>>>,
+        counter: Option<Arc<Counter>>,
         id: i32,
-    ) -> anyhow::Result<Option<String>> {
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<(Detection, Item)> {
         if let Some(counter) = counter.as_ref() {
-            counter.write().unwrap().pending();
+            counter.pending();
         }
 
-        let url = format!("{}/item/{}.json", BASE_URL, id);
-        let cached_response = self.cache.get(&url).await?;
+        let json = self.fetch_item(id, ttl).await;
+
+        if let Some(counter) = counter.as_ref() {
+            counter.done();
+        }
+
+        let json = json.with_context(|| format!("Failed to fetch item {}", id))?;
+        let item = Item::parse(&json)
+            .with_context(|| format!("Failed to parse item {}", id))?
+            .with_context(|| format!("Item {} does not exist", id))?;
+        Ok((item.detection(), item))
+    }
 
-        if let Some(json) = cached_response {
+    /// The API URL of an item.
+    fn item_url(&self, id: i32) -> String {
+        format!("{}/item/{}.json", self.base_url, id)
+    }
+
+    /// Get the JSON of an item, from the cache if we have fetched it within the TTL, or ever
+    /// without one.
+    async fn fetch_item(&self, id: i32, ttl: Option<Duration>) -> anyhow::Result<String> {
+        let url = self.item_url(id);
+
+        let cached = match ttl {
+            Some(ttl) => self.cache.get_fresh(&url, ttl).await?,
+            None => self.cache.get(&url).await?,
+        };
+        if let Some(json) = cached {
             debug!("Using cached response for item {}", id);
-            if is_video(&json)? {
-                if let Some(counter) = counter.as_ref() {
-                    counter.write().unwrap().done();
-                }
-                return Ok(Some(json));
-            }
-        } else {
-            debug!("Fetching fresh response for item {}", id);
-            let json_text = self.client.get(&url).send().await?.text().await?;
-            debug!("Fetched response for item {}", id);
-            self.cache.set(&url, &json_text).await?;
-            if is_video(&json_text)? {
-                if let Some(counter) = counter.as_ref() {
-                    counter.write().unwrap().done();
-                }
-                return Ok(Some(json_text));
-            }
+            return Ok(json);
         }
+        self.download_item(id).await
+    }
 
-        if let Some(counter) = counter.as_ref() {
-            counter.write().unwrap().done();
+    /// Get the JSON of an item from Hacker News, and cache it. Error responses, e.g. when rate
+    /// limited, fail instead of being cached as the item.
+    async fn download_item(&self, id: i32) -> anyhow::Result<String> {
+        let url = self.item_url(id);
+        debug!("Fetching fresh response for item {}", id);
+        let response = self.client.get(&url).send().await?.error_for_status()?;
+        let json_text = response.text().await?;
+        debug!("Fetched response for item {}", id);
+        self.cache.set(&url, &json_text).await?;
+        Ok(json_text)
+    }
+
+    /// Get the oEmbed metadata of a video, from the cache if we have seen it before.
+    async fn fetch_oembed(&self, video_url: &str) -> anyhow::Result<Option<OEmbed>> {
+        let Some(url) = oembed::endpoint(video_url) else {
+            return Ok(None);
+        };
+
+        match self.cache.get(&url).await? {
+            Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+            None => self.download_oembed(video_url).await,
+        }
+    }
+
+    /// Get the oEmbed metadata of a video from its platform, and cache it.
+    async fn download_oembed(&self, video_url: &str) -> anyhow::Result<Option<OEmbed>> {
+        let Some(url) = oembed::endpoint(video_url) else {
+            return Ok(None);
+        };
+
+        debug!("Fetching oEmbed data for {}", video_url);
+        let response = self
+            .client
+            .get(&url)
+            .timeout(OEMBED_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?;
+        let json = response.text().await?;
+        self.cache.set(&url, &json).await?;
+        Ok(Some(serde_json::from_str(&json)?))
+    }
+
+    /// Get the oEmbed metadata of a video from the cache only.
+    async fn cached_oembed(&self, video_url: &str) -> anyhow::Result<Option<OEmbed>> {
+        let Some(url) = oembed::endpoint(video_url) else {
+            return Ok(None);
+        };
+        match self.cache.get(&url).await? {
+            Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+            None => Ok(None),
         }
-        Ok(None)
     }
 }
 
-fn is_video(json: &str) -> anyhow::Result<bool> {
-    let item: HashMap<String, Value> = serde_json::from_str(&json)?;
+/// Check whether an item is a video.
+pub fn is_video(json: &str) -> anyhow::Result<bool> {
+    Ok(detect(json)? != Detection::Rejected)
+}
 
-    if let Some(item) = item.get("url") {
-        if let Some(item) = item.as_str() {
-            let item = item.to_ascii_lowercase();
+/// Classify an item by whether (and why) it is a video.
+pub fn detect(json: &str) -> anyhow::Result<Detection> {
+    Ok(Item::parse(json)?.map_or(Detection::Rejected, |item| item.detection()))
+}
 
-            // if it is from youtube
-            if item.contains("http://www.youtube.com/")
-                || item.contains("https://www.youtube.com/")
-                || item.contains("http://youtu.be/")
-                || item.contains("https://youtu.be/")
-            {
-                return Ok(true);
-            }
+/// Classify a submitted URL by whether (and why) it points at a video.
+pub fn detect_url(url: &str) -> Detection {
+    let url = url.to_ascii_lowercase();
 
-            // if is has a video tag
-            if item.contains("[video]") {
-                return Ok(true);
-            }
-        }
+    // if it is from youtube
+    if url.contains("http://www.youtube.com/")
+        || url.contains("https://www.youtube.com/")
+        || url.contains("http://youtu.be/")
+        || url.contains("https://youtu.be/")
+    {
+        return Detection::YouTube;
+    }
+
+    // if is has a video tag
+    if url.contains("[video]") {
+        return Detection::VideoTag;
+    }
+
+    Detection::Rejected
+}
+
+/// Extract the video ID from a YouTube URL.
+pub fn youtube_id(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?.to_ascii_lowercase();
+    let mut segments = url.path_segments()?;
+
+    let id = match host.as_str() {
+        "youtu.be" => segments.next()?.to_string(),
+        "youtube.com" | "www.youtube.com" | "m.youtube.com" => match segments.next()? {
+            "watch" => url
+                .query_pairs()
+                .find(|(key, _)| key == "v")
+                .map(|(_, value)| value.into_owned())?,
+            "embed" | "shorts" | "live" | "v" => segments.next()?.to_string(),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then_some(id)
+}
+
+/// The platform a video is hosted on, e.g. `youtube` or `vimeo`.
+///
+/// Everything but YouTube is named after the second-level domain of the URL.
+pub fn platform(url: &str) -> String {
+    if youtube_id(url).is_some() {
+        return "youtube".to_string();
     }
 
-    Ok(false)
+    let host = Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+        .unwrap_or_default();
+    host.rsplit('.').nth(1).unwrap_or(&host).to_string()
+}
+
+/// Check whether a URL points directly at a video file that browsers can play natively.
+pub fn is_media_file(url: &str) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return false;
+    };
+
+    let path = url.path().to_ascii_lowercase();
+    [".mp4", ".webm", ".ogv", ".mov"]
+        .iter()
+        .any(|extension| path.ends_with(extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn subscribers_are_woken_on_progress() {
+        let counter = Counter::new();
+        let mut progress = counter.subscribe();
+        counter.set_total(2);
+        counter.pending();
+        counter.done();
+
+        progress.changed().await.unwrap();
+        assert_eq!(
+            *progress.borrow_and_update(),
+            Progress {
+                pending: 1,
+                done: 1,
+                total: 2,
+            }
+        );
+        // Nothing changed since.
+        assert!(!progress.has_changed().unwrap());
+    }
 }
@@ -0,0 +1,161 @@
+/// Translations of the user-facing strings in the templates.
+///
+/// Each supported language has a `locales/<code>.toml` file of `key = "value"` lines which is
+/// compiled into the binary. Every file has the same keys, from English. The language of a request is taken from the `?lang=` query parameter
+/// if present, and negotiated from the `Accept-Language` header otherwise.
+use std::{collections::HashMap, convert::Infallible, sync::OnceLock};
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::{header, request::Parts},
+};
+use serde::{Deserialize, Serialize};
+
+/// A language the interface is translated to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize)]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+    Zh,
+}
+
+type Catalog = HashMap<String, String>;
+
+impl Locale {
+    const ALL: [Locale; 3] = [Locale::En, Locale::De, Locale::Zh];
+
+    /// The language code, as used in `?lang=` and the `lang` attribute.
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::De => "de",
+            Self::Zh => "zh",
+        }
+    }
+
+    fn source(self) -> &'static str {
+        match self {
+            Self::En => include_str!("../locales/en.toml"),
+            Self::De => include_str!("../locales/de.toml"),
+            Self::Zh => include_str!("../locales/zh.toml"),
+        }
+    }
+
+    /// Find the locale for a language tag such as `de` or `zh-TW`.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let primary = tag.split(['-', '_']).next()?.trim();
+        Self::ALL
+            .into_iter()
+            .find(|locale| locale.code().eq_ignore_ascii_case(primary))
+    }
+
+    /// Pick the best supported locale from an `Accept-Language` header value.
+    pub fn negotiate(accept_language: &str) -> Option<Self> {
+        let mut ranges: Vec<(f32, &str)> = accept_language
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.parse().ok())?;
+                Some((quality, tag))
+            })
+            .filter(|(quality, _)| *quality > 0.0)
+            .collect();
+
+        // A stable sort keeps the client's order among ranges of equal quality.
+        ranges.sort_by(|a, b| b.0.total_cmp(&a.0));
+        ranges.into_iter().find_map(|(_, tag)| Self::from_tag(tag))
+    }
+
+    /// Look up the translation of a message, falling back to English and then to the key itself.
+    pub fn message(self, key: &'static str) -> &'static str {
+        let catalogs = catalogs();
+        catalogs[&self]
+            .get(key)
+            .or_else(|| catalogs[&Locale::En].get(key))
+            .map_or(key, String::as_str)
+    }
+}
+
+fn catalogs() -> &'static HashMap<Locale, Catalog> {
+    static CATALOGS: OnceLock<HashMap<Locale, Catalog>> = OnceLock::new();
+    CATALOGS.get_or_init(|| {
+        Locale::ALL
+            .into_iter()
+            .map(|locale| {
+                let catalog = toml::from_str(locale.source())
+                    .unwrap_or_else(|err| panic!("Invalid locale {}: {}", locale.code(), err));
+                (locale, catalog)
+            })
+            .collect()
+    })
+}
+
+#[derive(Deserialize)]
+struct LangQuery {
+    lang: Option<String>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Locale
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let requested = Query::<LangQuery>::from_request_parts(parts, state)
+            .await
+            .ok()
+            .and_then(|Query(query)| query.lang)
+            .and_then(|lang| Self::from_tag(&lang));
+
+        let negotiated = || {
+            parts
+                .headers
+                .get(header::ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(Self::negotiate)
+        };
+
+        Ok(requested.or_else(negotiated).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_locale_has_the_keys_of_english() {
+        let catalogs = catalogs();
+        let mut english: Vec<_> = catalogs[&Locale::En].keys().collect();
+        english.sort();
+        for locale in Locale::ALL {
+            let mut keys: Vec<_> = catalogs[&locale].keys().collect();
+            keys.sort();
+            assert_eq!(keys, english, "the keys of {}", locale.code());
+        }
+    }
+
+    #[test]
+    fn messages_fall_back_to_the_key() {
+        assert_eq!(Locale::De.message("later-title"), "Später ansehen");
+        assert_eq!(Locale::Zh.message("no-such-key"), "no-such-key");
+    }
+
+    #[test]
+    fn languages_are_negotiated_by_quality() {
+        assert_eq!(Locale::from_tag("zh-TW"), Some(Locale::Zh));
+        assert_eq!(Locale::from_tag("fr"), None);
+        assert_eq!(
+            Locale::negotiate("fr;q=0.9, de;q=0.5, en;q=0.8"),
+            Some(Locale::En)
+        );
+        assert_eq!(Locale::negotiate("fr, de;q=0"), None);
+    }
+}
@@ -0,0 +1,71 @@
+//! Find the videos among the Hacker News top stories.
+//!
+//! [`hacker_news::HackerNews`] fetches the top stories through the Hacker News API, keeps the
+//! responses in a SQLite [`cache::Cache`] and classifies every item with
+//! [`hacker_news::detect`]. [`lobsters::Lobsters`] and [`reddit::Reddit`] do the same for the
+//! hottest stories on Lobsters and hot posts of subreddits. All of them implement
+//! [`source::Source`], and the stories they report become [`video::Video`]s.
+//!
+//! The rest is the site built on them: [`state::State`] refreshes the sources and keeps what the
+//! pages, feeds and notifiers share, and [`routes::serve`] serves it. The `hnv` binary only parses
+//! its arguments and runs one of them.
+pub mod activitypub;
+pub mod assets;
+pub mod auth;
+pub mod backup;
+pub mod blocklist;
+pub mod cache;
+pub mod cli;
+pub mod config;
+pub mod digest;
+pub mod discord;
+pub mod doctor;
+pub mod downloads;
+pub mod dump;
+pub mod export;
+pub mod features;
+pub mod hacker_news;
+pub mod i18n;
+pub mod listener;
+pub mod lobsters;
+pub mod log_file;
+pub mod mastodon;
+pub mod matrix;
+pub mod mcp;
+pub mod minify;
+pub mod notify;
+pub mod ntfy;
+pub mod oembed;
+pub mod overload;
+pub mod player;
+pub mod pocket;
+pub mod preferences;
+pub mod pushgateway;
+pub mod read_later;
+pub mod reddit;
+pub mod refresh;
+pub mod routes;
+#[cfg(feature = "tokio-console")]
+pub mod runtime;
+pub mod scheduler;
+pub mod search;
+pub mod search_alerts;
+pub mod security;
+pub mod session;
+pub mod slack;
+pub mod source;
+pub mod state;
+pub mod stats;
+pub mod status;
+pub mod store;
+pub mod submissions;
+pub mod systemd;
+pub mod tag_push;
+pub mod telegram;
+pub mod telemetry;
+pub mod tui;
+pub mod version;
+pub mod video;
+pub mod wallabag;
+pub mod watch_later;
+pub mod webhooks;
@@ -0,0 +1,105 @@
+/// Binding the listening sockets of the server.
+///
+/// Sockets can also be inherited from a service manager, e.g. through systemd socket activation.
+/// The manager then holds on to the port while the server restarts, and connections made in the
+/// meantime wait in the backlog instead of being refused.
+use std::{io, net::SocketAddr};
+
+use listenfd::ListenFd;
+use socket2::{Domain, Socket, Type};
+use tokio::net::TcpListener;
+
+/// The maximum number of pending connections per socket.
+const BACKLOG: i32 = 1024;
+
+/// Bind a listening socket to an address.
+///
+/// IPv6 sockets are bound as IPv6-only, so that `[::]:3000` can be listened on next to
+/// `0.0.0.0:3000` instead of conflicting with it.
+pub fn bind(addr: SocketAddr) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(BACKLOG)?;
+
+    TcpListener::from_std(socket.into())
+}
+
+/// Take the TCP sockets passed in by the service manager, if any.
+pub fn inherited() -> io::Result<Vec<std::net::TcpListener>> {
+    let mut fds = ListenFd::from_env();
+    let mut listeners = Vec::new();
+    for index in 0..fds.len() {
+        if let Some(listener) = fds.take_tcp_listener(index)? {
+            listeners.push(listener);
+        }
+    }
+    Ok(listeners)
+}
+
+/// Use the inherited socket bound to an address, or bind a new one if there is none.
+pub fn take_or_bind(
+    inherited: &mut Vec<std::net::TcpListener>,
+    addr: SocketAddr,
+) -> io::Result<TcpListener> {
+    let position = inherited
+        .iter()
+        .position(|listener| listener.local_addr().ok() == Some(addr));
+    match position {
+        Some(index) => from_inherited(inherited.swap_remove(index)),
+        None => bind(addr),
+    }
+}
+
+/// Prepare an inherited socket for use with tokio.
+pub fn from_inherited(listener: std::net::TcpListener) -> io::Result<TcpListener> {
+    listener.set_nonblocking(true)?;
+    TcpListener::from_std(listener)
+}
+
+/// Wait until the server is asked to stop, with Ctrl-C or (on Unix) `SIGTERM`.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl-C: {}", err);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", err);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn ipv4_and_ipv6_share_a_port() {
+        let ipv4 = bind(SocketAddr::from(([0, 0, 0, 0], 0))).unwrap();
+        let port = ipv4.local_addr().unwrap().port();
+        let ipv6 = bind(SocketAddr::from(([0u16; 8], port))).unwrap();
+        assert_eq!(ipv6.local_addr().unwrap().port(), port);
+    }
+}
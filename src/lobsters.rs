@@ -0,0 +1,196 @@
+/// Get videos from the Lobsters API.
+///
+/// Lobsters serves the stories of its front page as a single JSON list, so unlike Hacker News
+/// there is one request per refresh. Stories are classified by the same URL detection, and
+/// additionally by the `video` tag Lobsters submitters apply to them.
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::debug;
+
+use crate::cache::Cache;
+use crate::hacker_news::{detect_url, Counter, Detection, Detections};
+use crate::source::{self, Fetched, Source};
+
+/// The base URL of Lobsters.
+pub const BASE_URL: &str = "https://lobste.rs";
+
+/// The name of Lobsters as a [`Source`].
+pub const NAME: &str = "lobsters";
+
+/// The fields of a Lobsters story that we make use of.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Story {
+    pub short_id: String,
+    pub title: String,
+    /// The submitted URL, empty for text posts.
+    #[serde(default)]
+    pub url: String,
+    pub comments_url: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub score: Option<i64>,
+    /// When the story was submitted, in RFC 3339 format.
+    #[serde(default)]
+    pub created_at: Option<String>,
+    pub comment_count: Option<i64>,
+}
+
+impl Story {
+    fn into_story(self) -> source::Story {
+        source::Story {
+            id: self.short_id,
+            title: self.title,
+            url: self.url,
+            discussion_url: self.comments_url,
+            score: self.score,
+            submitted_at: self
+                .created_at
+                .and_then(|time| OffsetDateTime::parse(&time, &Rfc3339).ok())
+                .map(OffsetDateTime::unix_timestamp),
+            comments: self.comment_count,
+        }
+    }
+
+    /// Classify the story by whether (and why) it is a video.
+    pub fn detection(&self) -> Detection {
+        match detect_url(&self.url) {
+            Detection::Rejected if self.tags.iter().any(|tag| tag == "video") => {
+                Detection::VideoTag
+            }
+            detection => detection,
+        }
+    }
+}
+
+/// A client of the Lobsters API that caches the stories it has seen.
+pub struct Lobsters {
+    client: Client,
+    cache: Cache,
+    base_url: String,
+    /// The outcomes of the detection in the most recent fetch of the hottest stories.
+    detections: Mutex<Detections>,
+}
+
+impl Lobsters {
+    pub fn new(cache: Cache) -> Self {
+        Self::with_base_url(cache, BASE_URL)
+    }
+
+    /// Talk to another instance than lobste.rs, or a fake for tests.
+    pub fn with_base_url(cache: Cache, base_url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            cache,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            detections: Default::default(),
+        }
+    }
+
+    /// Get the videos among the hottest stories, in the order they are listed.
+    pub async fn get_hottest_videos(&self) -> anyhow::Result<Vec<Story>> {
+        let url = format!("{}/hottest.json", self.base_url);
+
+        debug!("Fetching fresh response for the hottest stories");
+        let stories: Vec<Story> = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .context("Failed to parse the hottest stories")?;
+
+        let mut videos = Vec::new();
+        let mut detections = Detections::default();
+        for story in stories {
+            detections.record(story.detection(), &story.url);
+            if story.detection() == Detection::Rejected {
+                continue;
+            }
+            // Keep the story, so its watch page doesn't need to fetch it again.
+            let url = self.story_url(&story.short_id);
+            if self.cache.get(&url).await?.is_none() {
+                self.cache
+                    .set(&url, &serde_json::to_string(&story)?)
+                    .await?;
+            }
+            videos.push(story);
+        }
+        *self.detections.lock().unwrap() = detections;
+
+        Ok(videos)
+    }
+
+    /// Get a single story, if it is a video.
+    pub async fn get_video(&self, short_id: &str) -> anyhow::Result<Option<Story>> {
+        let url = self.story_url(short_id);
+
+        let json = match self.cache.get(&url).await? {
+            Some(json) => json,
+            None => {
+                debug!("Fetching fresh response for story {}", short_id);
+                let response = self.client.get(&url).send().await?;
+                if response.status() == StatusCode::NOT_FOUND {
+                    return Ok(None);
+                }
+                let json = response.error_for_status()?.text().await?;
+                self.cache.set(&url, &json).await?;
+                json
+            }
+        };
+
+        let story: Story = serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse story {}", short_id))?;
+        Ok(Some(story).filter(|story| story.detection() != Detection::Rejected))
+    }
+
+    fn story_url(&self, short_id: &str) -> String {
+        format!("{}/s/{}.json", self.base_url, short_id)
+    }
+}
+
+#[async_trait]
+impl Source for Lobsters {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn label(&self) -> &'static str {
+        "Lobsters"
+    }
+
+    async fn videos(
+        &self,
+        _counter: Arc<Counter>,
+        on_fetched: &mut (dyn for<'s> FnMut(Fetched<&'s source::Story>) + Send),
+    ) -> anyhow::Result<Vec<source::Story>> {
+        let stories: Vec<_> = self
+            .get_hottest_videos()
+            .await?
+            .into_iter()
+            .map(Story::into_story)
+            .collect();
+        for (rank, story) in stories.iter().enumerate() {
+            on_fetched(Fetched::Video(rank, story));
+        }
+        Ok(stories)
+    }
+
+    async fn video(&self, id: &str) -> anyhow::Result<Option<source::Story>> {
+        Ok(self.get_video(id).await?.map(Story::into_story))
+    }
+
+    fn discussion_url(&self, id: &str) -> String {
+        format!("{}/s/{}", self.base_url, id)
+    }
+
+    fn detections(&self) -> Detections {
+        self.detections.lock().unwrap().clone()
+    }
+}
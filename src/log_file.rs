@@ -0,0 +1,126 @@
+/// Writing the log to a rotated file, for deployments without journald.
+///
+/// Time-based rotation is left to `tracing-appender`, which names the files after their period,
+/// e.g. `hnv.log.2024-05-01`. Size-based rotation renames the full file to `hnv.log.1`, the one
+/// before to `hnv.log.2` and so on. Either way, the lines are written on a background thread so
+/// logging never waits for the disk.
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use tracing_appender::{
+    non_blocking::{NonBlocking, WorkerGuard},
+    rolling::{RollingFileAppender, Rotation as Period},
+};
+
+use crate::config::{LogConfig, Rotation};
+
+/// Open the log file, returning a writer and a guard that flushes the lines when dropped.
+pub fn open(config: &LogConfig) -> anyhow::Result<(NonBlocking, WorkerGuard)> {
+    let dir = match config.file.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+    let name = config
+        .file
+        .file_name()
+        .with_context(|| format!("The log file {} has no name", config.file.display()))?;
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create the log directory {}", dir.display()))?;
+
+    let period = match config.rotation {
+        Rotation::Daily => Period::DAILY,
+        Rotation::Hourly => Period::HOURLY,
+        Rotation::Never => Period::NEVER,
+        Rotation::Size => {
+            let writer = SizeRotating::open(config.file.clone(), config.max_size, config.max_files)
+                .with_context(|| format!("Failed to open {}", config.file.display()))?;
+            return Ok(tracing_appender::non_blocking(writer));
+        }
+    };
+
+    let mut appender = RollingFileAppender::builder()
+        .rotation(period)
+        .filename_prefix(name.to_string_lossy());
+    if let Some(max_files) = config.max_files {
+        appender = appender.max_log_files(max_files);
+    }
+    let appender = appender
+        .build(dir)
+        .with_context(|| format!("Failed to open {}", config.file.display()))?;
+    Ok(tracing_appender::non_blocking(appender))
+}
+
+/// A file that is moved aside once it grows beyond a size.
+struct SizeRotating {
+    path: PathBuf,
+    max_size: u64,
+    max_files: Option<usize>,
+    file: File,
+    size: u64,
+}
+
+impl SizeRotating {
+    fn open(path: PathBuf, max_size: u64, max_files: Option<usize>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_size,
+            max_files,
+            file,
+            size,
+        })
+    }
+
+    /// The path of the `n`th most recent rotated file.
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        path.into()
+    }
+
+    /// Shift the rotated files by one, dropping the oldest, and start a new file.
+    fn rotate(&mut self) -> io::Result<()> {
+        let keep = self.max_files.unwrap_or(usize::MAX);
+        if keep == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            // Find the oldest rotated file, then move every file one up from there.
+            let mut oldest = 1;
+            while oldest < keep && self.rotated(oldest).exists() {
+                oldest += 1;
+            }
+            for n in (1..oldest).rev() {
+                std::fs::rename(self.rotated(n), self.rotated(n + 1))?;
+            }
+            std::fs::rename(&self.path, self.rotated(1))?;
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for SizeRotating {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // A line is never split, so a file may end up a line larger than the maximum.
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
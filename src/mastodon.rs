@@ -0,0 +1,88 @@
+/// Tooting new videos from a Mastodon account.
+///
+/// A toot has the title, the video, the discussion and a hashtag for every tag of the title, so
+/// the videos show up in the hashtag timelines of the fediverse.
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::json;
+
+use crate::{
+    config::{MastodonConfig, NotifyRule},
+    notify::Notifier,
+    video::Video,
+};
+
+/// The longest toot most instances accept.
+const MAX_LENGTH: usize = 500;
+
+pub struct Mastodon {
+    client: Client,
+    config: MastodonConfig,
+}
+
+impl Mastodon {
+    pub fn new(config: MastodonConfig) -> Self {
+        Self {
+            client: Client::new(),
+            config,
+        }
+    }
+
+    /// The toot of a video, with the title shortened when the toot would be too long.
+    ///
+    /// Mastodon counts every link as 23 characters, whatever its length.
+    fn status(video: &Video) -> String {
+        let hashtags: Vec<_> = video.tags().iter().filter_map(|tag| hashtag(tag)).collect();
+        let hashtags = hashtags.join(" ");
+        let rest = format!(
+            "\n\n{}\n{}: {}\n\n{}",
+            video.url, video.source_label, video.discussion_url, hashtags
+        );
+        let rest_length = 2 + 23 + 1 + video.source_label.chars().count() + 2 + 23 + 2;
+        let room = MAX_LENGTH.saturating_sub(rest_length + hashtags.chars().count());
+
+        let title = match video.title.char_indices().nth(room.saturating_sub(1)) {
+            Some((end, _)) => format!("{}…", &video.title[..end]),
+            None => video.title.clone(),
+        };
+        format!("{}{}", title, rest).trim_end().to_string()
+    }
+}
+
+#[async_trait]
+impl Notifier for Mastodon {
+    fn name(&self) -> &'static str {
+        "mastodon"
+    }
+
+    fn rules(&self) -> &[NotifyRule] {
+        &self.config.rules
+    }
+
+    async fn send(&self, video: &Video) -> anyhow::Result<()> {
+        let url = format!("{}/api/v1/statuses", self.config.url.trim_end_matches('/'));
+        // The key makes the instance ignore a second toot of the same video.
+        self.client
+            .post(&url)
+            .bearer_auth(&self.config.token)
+            .header("Idempotency-Key", format!("hnv-{}", video.id))
+            .json(&json!({ "status": Self::status(video) }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// The hashtag of a tag, e.g. `#gamedev` for `game dev`, if it makes one. Mastodon doesn't
+/// link hashtags made of digits alone, like years.
+fn hashtag(tag: &str) -> Option<String> {
+    let tag: String = tag
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    if tag.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(format!("#{}", tag))
+}
@@ -0,0 +1,92 @@
+/// Posting new videos to a Matrix room through the client-server API.
+///
+/// Messages are sent with the plain text body every client shows, and the same message in HTML
+/// for the clients that render it. The transaction ID of a message is derived from the video, so
+/// the homeserver drops a message that is sent twice.
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::{Client, Url};
+use serde_json::json;
+
+use crate::{
+    config::{MatrixConfig, NotifyRule},
+    notify::Notifier,
+    video::Video,
+};
+
+pub struct Matrix {
+    client: Client,
+    config: MatrixConfig,
+}
+
+impl Matrix {
+    pub fn new(config: MatrixConfig) -> Self {
+        Self {
+            client: Client::new(),
+            config,
+        }
+    }
+
+    /// The URL a message about a video is sent to. Room IDs contain characters that have to be
+    /// escaped in paths, like `!room:example.org`.
+    fn send_url(&self, video: &Video) -> anyhow::Result<Url> {
+        let mut url = Url::parse(&self.config.homeserver)
+            .with_context(|| format!("Invalid homeserver URL {}", self.config.homeserver))?;
+        url.path_segments_mut()
+            .map_err(|()| anyhow::anyhow!("Invalid homeserver URL {}", self.config.homeserver))?
+            .pop_if_empty()
+            .extend(["_matrix", "client", "v3", "rooms"])
+            .push(&self.config.room_id)
+            .extend(["send", "m.room.message"])
+            .push(&format!("hnv-{}", video.id));
+        Ok(url)
+    }
+}
+
+#[async_trait]
+impl Notifier for Matrix {
+    fn name(&self) -> &'static str {
+        "matrix"
+    }
+
+    fn rules(&self) -> &[NotifyRule] {
+        &self.config.rules
+    }
+
+    async fn send(&self, video: &Video) -> anyhow::Result<()> {
+        let body = format!(
+            "{}\n{}\n{}: {}",
+            video.title, video.url, video.source_label, video.discussion_url
+        );
+        let formatted_body = format!(
+            "<strong>{}</strong><br>{}<br><a href=\"{}\">{}</a>",
+            escape(&video.title),
+            escape(&video.url),
+            escape(&video.discussion_url),
+            escape(video.source_label),
+        );
+        let message = json!({
+            "msgtype": "m.text",
+            "body": body,
+            "format": "org.matrix.custom.html",
+            "formatted_body": formatted_body,
+        });
+
+        self.client
+            .put(self.send_url(video)?)
+            .bearer_auth(&self.config.access_token)
+            .json(&message)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Escape text for the HTML of a message.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,279 @@
+/// Answering LLM assistants over the Model Context Protocol.
+///
+/// `hnv mcp` is started by the assistant's client and speaks JSON-RPC with it, one message per
+/// line on stdin and stdout, while the log goes to stderr as usual. It offers tools to list the
+/// front page, to search the current and recorded videos, and to read the discussion of a video.
+///
+/// The videos are refreshed on the configured schedule, as when serving the site. The tools wait
+/// for the first refresh, so that they never answer with a partial list.
+use std::sync::Arc;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tracing::{debug, info};
+
+use crate::{
+    refresh,
+    state::SharedState,
+    video::{Video, VideoId},
+};
+
+/// The version of the protocol implemented.
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// The videos listed by the tools unless asked for another number.
+const DEFAULT_LIMIT: usize = 20;
+
+/// The comments returned with a discussion.
+const COMMENTS: usize = 20;
+
+// The error codes of JSON-RPC.
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+#[derive(Deserialize)]
+struct Request {
+    /// Missing for notifications, which aren't answered.
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct ToolCall {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Deserialize)]
+struct FrontPageArguments {
+    source: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct SearchArguments {
+    query: String,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct DiscussionArguments {
+    id: String,
+}
+
+/// Answer the requests on stdin until it is closed.
+pub async fn run(state: SharedState) -> anyhow::Result<()> {
+    let first_refresh = state.start_refresh().unwrap_or_else(|running| running);
+    tokio::spawn({
+        let state = state.clone();
+        async move { state.scheduler.run(|| state.start_refresh()).await }
+    });
+    let server = Server {
+        state,
+        first_refresh,
+    };
+
+    info!("Answering MCP requests on stdin");
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => server.answer(request).await,
+            Err(err) => Some(error(Value::Null, PARSE_ERROR, &err.to_string())),
+        };
+        if let Some(response) = response {
+            stdout
+                .write_all(format!("{}\n", response).as_bytes())
+                .await?;
+            stdout.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+struct Server {
+    state: SharedState,
+    first_refresh: Arc<refresh::Job>,
+}
+
+impl Server {
+    /// The response to a request, or nothing for notifications.
+    async fn answer(&self, request: Request) -> Option<Value> {
+        debug!(method = %request.method, "MCP request");
+        let id = request.id?;
+        let result = match request.method.as_str() {
+            "initialize" => json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "hnv", "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "ping" => json!({}),
+            "tools/list" => json!({ "tools": tools() }),
+            "tools/call" => match serde_json::from_value::<ToolCall>(request.params) {
+                Ok(call) => self.call(call).await,
+                Err(err) => return Some(error(id, INVALID_PARAMS, &err.to_string())),
+            },
+            method => {
+                let message = format!("Unknown method {}", method);
+                return Some(error(id, METHOD_NOT_FOUND, &message));
+            }
+        };
+        Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    /// Call a tool. Its failures are reported to the assistant as the result.
+    async fn call(&self, call: ToolCall) -> Value {
+        let result = match call.name.as_str() {
+            "get_front_page" => match serde_json::from_value(call.arguments) {
+                Ok(arguments) => self.front_page(arguments).await,
+                Err(err) => Err(err.into()),
+            },
+            "search_videos" => match serde_json::from_value(call.arguments) {
+                Ok(arguments) => self.search(arguments).await,
+                Err(err) => Err(err.into()),
+            },
+            "get_discussion" => match serde_json::from_value(call.arguments) {
+                Ok(arguments) => self.discussion(arguments).await,
+                Err(err) => Err(err.into()),
+            },
+            name => Err(anyhow::anyhow!("Unknown tool {}", name)),
+        };
+        match result {
+            Ok(value) => json!({
+                "content": [{ "type": "text", "text": value.to_string() }],
+            }),
+            Err(err) => json!({
+                "content": [{ "type": "text", "text": format!("{:#}", err) }],
+                "isError": true,
+            }),
+        }
+    }
+
+    /// Wait for the first refresh, if it is still running.
+    async fn ready(&self) {
+        if !self.first_refresh.is_finished() {
+            refresh::report(self.first_refresh.clone(), false).await;
+        }
+    }
+
+    async fn front_page(&self, arguments: FrontPageArguments) -> anyhow::Result<Value> {
+        self.ready().await;
+        let videos: Vec<_> = self
+            .state
+            .videos()
+            .iter()
+            .filter(|video| {
+                arguments
+                    .source
+                    .as_deref()
+                    .is_none_or(|s| video.source == s)
+            })
+            .take(arguments.limit.unwrap_or(DEFAULT_LIMIT))
+            .cloned()
+            .collect();
+        Ok(json!(videos))
+    }
+
+    /// The videos whose title contains the query, ignoring case: the current ones first, then
+    /// the recorded ones, most recently listed first.
+    async fn search(&self, arguments: SearchArguments) -> anyhow::Result<Value> {
+        self.ready().await;
+        let query = arguments.query.to_lowercase();
+        let matches = |video: &Video| video.title.to_lowercase().contains(&query);
+
+        let mut videos: Vec<Video> = self
+            .state
+            .videos()
+            .iter()
+            .filter(|video| matches(video))
+            .cloned()
+            .collect();
+        let mut recorded = self.state.store.all_videos().await?;
+        recorded.sort_by_key(|video| std::cmp::Reverse(video.last_seen));
+        for video in recorded {
+            if videos.iter().any(|known| known.id == video.id) {
+                continue;
+            }
+            if let Some(video) = self.state.recorded_video(video).filter(|v| matches(v)) {
+                videos.push(video);
+            }
+        }
+        videos.truncate(arguments.limit.unwrap_or(DEFAULT_LIMIT));
+        Ok(json!(videos))
+    }
+
+    /// A video with the first comments of its discussion, which are only available for Hacker
+    /// News.
+    async fn discussion(&self, arguments: DiscussionArguments) -> anyhow::Result<Value> {
+        let id: VideoId = arguments.id.parse()?;
+        let video = self
+            .state
+            .get_video(&id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("No video with the ID {}", id))?;
+        let comments = match id.hacker_news_id() {
+            Some(item) => json!(self.state.hn.get_comments(item, COMMENTS).await?),
+            None => Value::Null,
+        };
+        Ok(json!({ "video": video, "comments": comments }))
+    }
+}
+
+/// The tools, with the JSON schemas of their arguments.
+fn tools() -> Value {
+    json!([
+        {
+            "name": "get_front_page",
+            "description": "List the videos currently on the front page, best ranked first.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "source": {
+                        "type": "string",
+                        "description": "Only list the videos of a source, e.g. hacker-news, lobsters or reddit.",
+                    },
+                    "limit": { "type": "integer", "description": "The most videos to list." },
+                },
+            },
+        },
+        {
+            "name": "search_videos",
+            "description": "Find the videos, current or seen before, whose title contains a term.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "The term, matched ignoring case." },
+                    "limit": { "type": "integer", "description": "The most videos to list." },
+                },
+                "required": ["query"],
+            },
+        },
+        {
+            "name": "get_discussion",
+            "description": "Get a video with the top comments of its discussion on Hacker News, in HTML.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": { "type": "string", "description": "The ID of the video, as listed." },
+                },
+                "required": ["id"],
+            },
+        },
+    ])
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
@@ -0,0 +1,150 @@
+/// Minification of the rendered HTML pages.
+///
+/// The templates are indented for their readers, which makes up much of the index with hundreds
+/// of videos. Every run of whitespace is collapsed into a single space, or a newline when it
+/// spans lines, which renders the same. The contents of `<pre>`, `<textarea>`, `<script>` and
+/// `<style>`, as well as quoted attribute values and comments, are left as they are.
+use axum::{
+    body::{to_bytes, Body},
+    http::header,
+    response::Response,
+};
+
+/// The elements whose whitespace is kept.
+const VERBATIM: [&str; 4] = ["pre", "textarea", "script", "style"];
+
+/// Minify an HTML response.
+pub async fn apply(response: Response) -> Response {
+    let html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    if !html {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    // The pages are rendered into a single string anyway.
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(_) => return Response::from_parts(parts, Body::empty()),
+    };
+    let Ok(html) = std::str::from_utf8(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(minify(html)))
+}
+
+/// Collapse the whitespace of an HTML document.
+fn minify(html: &str) -> String {
+    let mut minified = String::with_capacity(html.len());
+    let mut rest = html;
+    // Whether the text is within the angle brackets of a tag.
+    let mut in_tag = false;
+    while !rest.is_empty() {
+        // Copy verbatim elements up to their closing tag, and comments up to their end.
+        if let Some(end) = verbatim_end(rest).or_else(|| comment_end(rest)) {
+            minified.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+
+        let c = rest.chars().next().unwrap_or_default();
+        if in_tag && (c == '"' || c == '\'') {
+            // Copy attribute values up to the closing quote.
+            let end = rest[1..].find(c).map_or(rest.len(), |end| end + 2);
+            minified.push_str(&rest[..end]);
+            rest = &rest[end..];
+        } else if c.is_whitespace() {
+            let end = rest
+                .find(|c: char| !c.is_whitespace())
+                .unwrap_or(rest.len());
+            minified.push(if rest[..end].contains('\n') {
+                '\n'
+            } else {
+                ' '
+            });
+            rest = &rest[end..];
+        } else {
+            if c == '<' {
+                in_tag = rest[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/');
+            } else if c == '>' {
+                in_tag = false;
+            }
+            minified.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    minified
+}
+
+/// Where the comment starting the text ends, if it starts with one.
+fn comment_end(html: &str) -> Option<usize> {
+    let comment = html.strip_prefix("<!--")?;
+    let end = comment
+        .find("-->")
+        .map_or(html.len(), |end| end + "<!---->".len());
+    Some(end)
+}
+
+/// Where the element starting the text ends, if it is one whose whitespace is kept.
+fn verbatim_end(html: &str) -> Option<usize> {
+    let tag = html.strip_prefix('<')?;
+    let name = VERBATIM.into_iter().find(|name| {
+        tag.get(..name.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(name))
+            && tag[name.len()..].starts_with(|c: char| c == '>' || c.is_whitespace())
+    })?;
+    let closing = format!("</{}", name);
+    let end = html
+        .to_ascii_lowercase()
+        .find(&closing)
+        .map_or(html.len(), |start| start + closing.len());
+    Some(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whitespace_is_collapsed() {
+        assert_eq!(
+            minify("<ul>\n    <li>A  talk</li>\n</ul>\n"),
+            "<ul>\n<li>A talk</li>\n</ul>\n"
+        );
+        assert_eq!(minify("<a\n   href=\"/\">x</a>"), "<a\nhref=\"/\">x</a>");
+    }
+
+    #[test]
+    fn verbatim_elements_are_kept() {
+        let html = "<p> a </p><PRE>  b\n  c</PRE><script>\n  let x = 1;\n</script>";
+        assert_eq!(
+            minify(html),
+            "<p> a </p><PRE>  b\n  c</PRE><script>\n  let x = 1;\n</script>"
+        );
+    }
+
+    #[test]
+    fn attribute_values_are_kept() {
+        let html = "<img alt=\"Two  spaces\" data-json='{\"a\":  \"b  c\"}'  title=\"x\n  y\">";
+        assert_eq!(
+            minify(html),
+            "<img alt=\"Two  spaces\" data-json='{\"a\":  \"b  c\"}' title=\"x\n  y\">"
+        );
+    }
+
+    #[test]
+    fn quotes_in_text_and_comments_dont_start_values() {
+        assert_eq!(
+            minify("<p>It's  a   \"talk\"</p>"),
+            "<p>It's a \"talk\"</p>"
+        );
+        assert_eq!(
+            minify("<!-- don't  touch -->  <p>a  b</p>"),
+            "<!-- don't  touch --> <p>a b</p>"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+/// Posting new videos to chats and other services.
+///
+/// After every refresh, each notifier posts the listed videos that pass its rules and that it
+/// hasn't posted before. The first refresh a notifier sees only records the videos listed at the
+/// time, so enabling one doesn't flood the chat with the whole list.
+///
+/// Notifiers that post digests skip the refreshes that come too soon after their last post. The
+/// videos they skip are only recorded once posted, so the next digest picks them up.
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tracing::{info, warn};
+
+use crate::{config::NotifyRule, store::Store, video::Video};
+
+/// The pause between two posts of a notifier, to stay clear of rate limits.
+const PAUSE: Duration = Duration::from_secs(1);
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// The name the posted videos are recorded under, e.g. `telegram`.
+    fn name(&self) -> &'static str;
+
+    /// Only videos matching any of these are posted. Without rules, every new video is.
+    fn rules(&self) -> &[NotifyRule];
+
+    /// Whether the notifier posts after this refresh.
+    fn ready(&self) -> bool {
+        true
+    }
+
+    /// Post a video.
+    async fn send(&self, video: &Video) -> anyhow::Result<()>;
+
+    /// Post the new videos of a refresh, one by one unless overridden.
+    async fn send_all(&self, videos: &[&Video]) -> anyhow::Result<()> {
+        for (index, video) in videos.iter().enumerate() {
+            if index > 0 {
+                tokio::time::sleep(PAUSE).await;
+            }
+            // A video that fails to post is not retried, a duplicate post would be worse.
+            if let Err(err) = self.send(video).await {
+                warn!(
+                    notifier = self.name(),
+                    video = %video.id,
+                    "Failed to post a video: {:#}", err
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Post the new videos with every notifier.
+pub async fn run(notifiers: &[Box<dyn Notifier>], store: &Store, videos: &[Video]) {
+    for notifier in notifiers {
+        if let Err(err) = notify(notifier.as_ref(), store, videos).await {
+            warn!(
+                notifier = notifier.name(),
+                "Failed to notify about new videos: {:#}", err
+            );
+        }
+    }
+}
+
+async fn notify(notifier: &dyn Notifier, store: &Store, videos: &[Video]) -> anyhow::Result<()> {
+    if !notifier.ready() {
+        return Ok(());
+    }
+    let rules = notifier.rules();
+    let matching: Vec<_> = videos
+        .iter()
+        .filter(|video| {
+            let (platform, tags) = (video.platform(), video.tags());
+            rules.is_empty()
+                || rules
+                    .iter()
+                    .any(|rule| rule.matches(&platform, video.score, &tags))
+        })
+        .collect();
+
+    let registered = store.start_notifier(notifier.name()).await?;
+    let ids: Vec<_> = matching.iter().map(|video| video.id.clone()).collect();
+    let new = store.mark_notified(notifier.name(), &ids).await?;
+    if !registered {
+        info!(
+            "Not posting the {} videos listed when {} was enabled",
+            new.len(),
+            notifier.name()
+        );
+        return Ok(());
+    }
+
+    let new: Vec<_> = matching
+        .into_iter()
+        .filter(|video| new.contains(&video.id))
+        .collect();
+    if new.is_empty() {
+        return Ok(());
+    }
+    notifier.send_all(&new).await
+}
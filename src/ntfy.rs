@@ -0,0 +1,82 @@
+/// Pushing new videos to phones through an ntfy topic.
+///
+/// The messages are published as JSON to the server of the topic rather than with headers, which
+/// can't carry titles outside of ASCII. Tapping a notification opens the video.
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::{Client, Url};
+use serde_json::json;
+
+use crate::{
+    config::{NotifyRule, NtfyConfig},
+    notify::Notifier,
+    video::Video,
+};
+
+pub struct Ntfy {
+    client: Client,
+    config: NtfyConfig,
+    /// The URL of the server, which JSON messages are published to.
+    server: Url,
+    topic: String,
+}
+
+impl Ntfy {
+    pub fn new(config: NtfyConfig) -> anyhow::Result<Self> {
+        let mut server = Url::parse(&config.topic_url)
+            .with_context(|| format!("Invalid ntfy topic URL {}", config.topic_url))?;
+        let topic = server
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .filter(|topic| !topic.is_empty())
+            .with_context(|| format!("The ntfy URL {} has no topic", config.topic_url))?
+            .to_string();
+        server
+            .path_segments_mut()
+            .map_err(|()| anyhow::anyhow!("Invalid ntfy topic URL {}", config.topic_url))?
+            .pop();
+        Ok(Self {
+            client: Client::new(),
+            config,
+            server,
+            topic,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for Ntfy {
+    fn name(&self) -> &'static str {
+        "ntfy"
+    }
+
+    fn rules(&self) -> &[NotifyRule] {
+        &self.config.rules
+    }
+
+    async fn send(&self, video: &Video) -> anyhow::Result<()> {
+        let title = match video.score {
+            Some(score) => format!("{} · {} points", video.source_label, score),
+            None => video.source_label.to_string(),
+        };
+        let body = json!({
+            "topic": self.topic,
+            "title": title,
+            "message": video.title,
+            "click": video.url,
+            "tags": ["tv"],
+            "actions": [{
+                "action": "view",
+                "label": "Discussion",
+                "url": video.discussion_url,
+            }],
+        });
+
+        let mut request = self.client.post(self.server.clone()).json(&body);
+        if let Some(token) = &self.config.token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
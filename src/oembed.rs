@@ -0,0 +1,29 @@
+/// oEmbed metadata of video pages.
+///
+/// Video platforms describe their pages through oEmbed: title, channel, thumbnail and player size.
+/// We use it to render rich link previews on watch pages. Responses are stored in the same cache
+/// as Hacker News items, keyed by the oEmbed request URL.
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::hacker_news::youtube_id;
+
+/// The YouTube oEmbed endpoint.
+const YOUTUBE_ENDPOINT: &str = "https://www.youtube.com/oembed";
+
+/// The subset of an oEmbed response we make use of.
+#[derive(Deserialize)]
+pub struct OEmbed {
+    pub title: Option<String>,
+    pub author_name: Option<String>,
+    pub thumbnail_url: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+/// The oEmbed request URL describing a video, if its platform is supported.
+pub fn endpoint(video_url: &str) -> Option<String> {
+    youtube_id(video_url)?;
+    let url = Url::parse_with_params(YOUTUBE_ENDPOINT, [("format", "json"), ("url", video_url)]);
+    url.ok().map(String::from)
+}
@@ -0,0 +1,72 @@
+/// Answering the requests beyond the concurrency limits.
+///
+/// Shed requests are answered with a 503 and a `Retry-After`, and counted for the admin page.
+/// Visitors of the index may be shown a snapshot of it instead, rendered after every refresh
+/// without anything of their own, like the static export.
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, RwLock,
+};
+
+use axum::{
+    http::{header, HeaderValue, Method, StatusCode, Uri},
+    response::{Html, IntoResponse, Response},
+};
+use tower::BoxError;
+
+use crate::config::OverloadConfig;
+
+#[derive(Clone)]
+pub struct Overload {
+    config: OverloadConfig,
+    /// How many requests were shed since the start.
+    shed: Arc<AtomicU64>,
+    /// The index as of the last refresh, if it is served to shed visitors.
+    snapshot: Arc<RwLock<Option<Arc<str>>>>,
+}
+
+impl Overload {
+    pub fn new(config: OverloadConfig) -> Self {
+        Self {
+            config,
+            shed: Default::default(),
+            snapshot: Default::default(),
+        }
+    }
+
+    /// Whether a snapshot of the index is kept for shed visitors.
+    pub fn serves_index(&self) -> bool {
+        self.config.serve_index
+    }
+
+    /// Keep the index to show shed visitors.
+    pub fn set_snapshot(&self, html: String) {
+        *self.snapshot.write().unwrap() = Some(html.into());
+    }
+
+    /// How many requests were shed since the start.
+    pub fn shed(&self) -> u64 {
+        self.shed.load(Ordering::Relaxed)
+    }
+
+    /// Answer a request that failed in a group of routes, which may have been shed.
+    pub async fn handle(&self, method: Method, uri: Uri, error: BoxError) -> Response {
+        if !error.is::<tower::load_shed::error::Overloaded>() {
+            return crate::routes::handle_error(error).await.into_response();
+        }
+        self.shed.fetch_add(1, Ordering::Relaxed);
+
+        let snapshot = (method == Method::GET && uri.path() == "/")
+            .then(|| self.snapshot.read().unwrap().clone())
+            .flatten();
+        let mut response = match snapshot {
+            Some(html) => (StatusCode::SERVICE_UNAVAILABLE, Html(html.to_string())).into_response(),
+            None => crate::routes::handle_error(error).await.into_response(),
+        };
+        response.headers_mut().insert(
+            header::RETRY_AFTER,
+            HeaderValue::from(self.config.retry_after),
+        );
+        response
+    }
+}
@@ -0,0 +1,89 @@
+/// Playing videos in an external player, like mpv or VLC, instead of the browser.
+///
+/// The player is started with the configured command and left running on its own, its output
+/// discarded so that it doesn't draw over the terminal UI. A thread waits for it to exit, so that
+/// it doesn't linger as a zombie process for as long as hnv runs.
+use std::{
+    process::{Command, Stdio},
+    thread,
+};
+
+use anyhow::{bail, Context};
+
+use crate::{config::PlayerConfig, video::Video};
+
+/// The name of the program of a player, to tell visitors what a video was opened in.
+pub fn name(config: &PlayerConfig) -> &str {
+    config.command.first().map_or("", String::as_str)
+}
+
+/// Start playing a video.
+pub fn open(config: &PlayerConfig, video: &Video) -> anyhow::Result<()> {
+    let Some((program, args)) = config.command.split_first() else {
+        bail!("The player command is empty");
+    };
+    let mut command = Command::new(program);
+    for arg in args {
+        command.arg(substitute(arg, &video.url, &video.title));
+    }
+    if !args.iter().any(|arg| arg.contains("{url}")) {
+        command.arg(&video.url);
+    }
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to start the player {}", program))?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Fill in the `{url}` and `{title}` placeholders of an argument of the command.
+///
+/// Both are replaced in one pass, so that placeholders within the URL or the title, which come from
+/// the submitter, are left as they are.
+fn substitute(arg: &str, url: &str, title: &str) -> String {
+    let mut substituted = String::with_capacity(arg.len());
+    let mut rest = arg;
+    while let Some(start) = rest.find('{') {
+        substituted.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("{url}") {
+            substituted.push_str(url);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("{title}") {
+            substituted.push_str(title);
+            rest = after;
+        } else {
+            substituted.push('{');
+            rest = &rest[1..];
+        }
+    }
+    substituted.push_str(rest);
+    substituted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_are_substituted() {
+        assert_eq!(
+            substitute("--title={title}: {url}", "https://youtu.be/x", "A talk"),
+            "--title=A talk: https://youtu.be/x"
+        );
+        assert_eq!(substitute("{url}{url}", "a", "b"), "aa");
+        assert_eq!(substitute("{other} {", "a", "b"), "{other} {");
+    }
+
+    #[test]
+    fn placeholders_in_the_values_are_left_alone() {
+        assert_eq!(
+            substitute("{url}", "https://example.com/?q={title}", "A talk"),
+            "https://example.com/?q={title}"
+        );
+        assert_eq!(substitute("{title}", "a", "{url}"), "{url}");
+    }
+}
@@ -0,0 +1,48 @@
+/// Saving videos to a Pocket account, through the v3 API.
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::json;
+
+use crate::{config::PocketConfig, read_later::ReadLater, video::Video};
+
+pub struct Pocket {
+    client: Client,
+    config: PocketConfig,
+}
+
+impl Pocket {
+    pub fn new(config: PocketConfig) -> Self {
+        Self {
+            client: Client::new(),
+            config,
+        }
+    }
+}
+
+#[async_trait]
+impl ReadLater for Pocket {
+    fn name(&self) -> &'static str {
+        "pocket"
+    }
+
+    fn label(&self) -> &'static str {
+        "Pocket"
+    }
+
+    async fn save(&self, video: &Video) -> anyhow::Result<()> {
+        let body = json!({
+            "url": video.url,
+            "title": video.title,
+            "consumer_key": self.config.consumer_key,
+            "access_token": self.config.access_token,
+        });
+        self.client
+            .post(format!("{}/v3/add", self.config.url.trim_end_matches('/')))
+            .header("X-Accept", "application/json")
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
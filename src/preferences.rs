@@ -0,0 +1,203 @@
+/// Per-visitor display preferences.
+///
+/// Preferences are kept in plain cookies and read on every request, so pages are rendered
+/// server-side in the visitor's chosen style without any client-side script.
+use std::{convert::Infallible, fmt};
+
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::config::TimeZone;
+
+/// The cookie holding the selected colour theme.
+const THEME_COOKIE: &str = "theme";
+
+/// The cookie holding whether YouTube links point at the privacy frontend.
+const PRIVACY_COOKIE: &str = "privacy";
+
+/// The cookie holding the time zone the visitor's days start and end in.
+const TIME_ZONE_COOKIE: &str = "timezone";
+
+/// The cookie holding how the index lists the videos.
+const VIEW_COOKIE: &str = "view";
+
+/// How long preference cookies are kept by the browser.
+const COOKIE_MAX_AGE: time::Duration = time::Duration::days(365);
+
+/// The colour theme pages are rendered with.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Light,
+    Dark,
+}
+
+impl Theme {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "light" => Some(Self::Light),
+            "dark" => Some(Self::Dark),
+            _ => None,
+        }
+    }
+
+    /// The theme a toggle button switches to.
+    pub fn toggled(self) -> Self {
+        match self {
+            Self::Light => Self::Dark,
+            Self::Dark => Self::Light,
+        }
+    }
+
+    /// The message key of the theme's name.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            Self::Light => "theme-light",
+            Self::Dark => "theme-dark",
+        }
+    }
+
+    /// Build the cookie remembering this theme.
+    pub fn cookie(self) -> Cookie<'static> {
+        persistent_cookie(THEME_COOKIE, self.to_string())
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Light => "light",
+            Self::Dark => "dark",
+        })
+    }
+}
+
+/// How the index lists the videos.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum View {
+    /// A compact list of titles.
+    #[default]
+    List,
+    /// Cards with the thumbnails of the videos.
+    Grid,
+}
+
+impl View {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "list" => Some(Self::List),
+            "grid" => Some(Self::Grid),
+            _ => None,
+        }
+    }
+
+    /// The message key of the view's name.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            Self::List => "view-list",
+            Self::Grid => "view-grid",
+        }
+    }
+
+    /// Build the cookie remembering this view.
+    pub fn cookie(self) -> Cookie<'static> {
+        persistent_cookie(VIEW_COOKIE, self.to_string())
+    }
+}
+
+impl fmt::Display for View {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::List => "list",
+            Self::Grid => "grid",
+        })
+    }
+}
+
+/// The preferences of the visitor making a request.
+#[derive(Clone, Copy, Default)]
+pub struct Preferences {
+    pub theme: Theme,
+    /// Whether to use the privacy frontend, if the visitor chose either way.
+    pub privacy: Option<bool>,
+    /// The time zone of the visitor, if they chose one over that of the instance.
+    pub time_zone: Option<TimeZone>,
+    /// How the visitor last chose to list the videos of the index.
+    pub view: View,
+}
+
+impl Preferences {
+    fn from_cookies(jar: &CookieJar) -> Self {
+        let theme = jar
+            .get(THEME_COOKIE)
+            .and_then(|cookie| Theme::parse(cookie.value()))
+            .unwrap_or_default();
+        let privacy = jar
+            .get(PRIVACY_COOKIE)
+            .and_then(|cookie| match cookie.value() {
+                "on" => Some(true),
+                "off" => Some(false),
+                _ => None,
+            });
+
+        let time_zone = jar
+            .get(TIME_ZONE_COOKIE)
+            .and_then(|cookie| cookie.value().parse().ok());
+        let view = jar
+            .get(VIEW_COOKIE)
+            .and_then(|cookie| View::parse(cookie.value()))
+            .unwrap_or_default();
+
+        Self {
+            theme,
+            privacy,
+            time_zone,
+            view,
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Preferences
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let jar = CookieJar::from_request_parts(parts, state).await?;
+        Ok(Self::from_cookies(&jar))
+    }
+}
+
+/// Build the cookie remembering whether to use the privacy frontend.
+pub fn privacy_cookie(enabled: bool) -> Cookie<'static> {
+    let value = if enabled { "on" } else { "off" };
+    persistent_cookie(PRIVACY_COOKIE, value.to_string())
+}
+
+/// Build the cookie remembering the time zone of the visitor.
+pub fn time_zone_cookie(time_zone: TimeZone) -> Cookie<'static> {
+    persistent_cookie(TIME_ZONE_COOKIE, time_zone.to_string())
+}
+
+/// Forget the time zone of the visitor, going back to that of the instance.
+pub fn time_zone_removal() -> Cookie<'static> {
+    Cookie::build(TIME_ZONE_COOKIE).path("/").build()
+}
+
+/// Build a long-lived cookie for storing a preference.
+pub fn persistent_cookie(name: &'static str, value: String) -> Cookie<'static> {
+    Cookie::build((name, value))
+        .path("/")
+        .max_age(COOKIE_MAX_AGE)
+        .same_site(SameSite::Lax)
+        .http_only(true)
+        .build()
+}
@@ -0,0 +1,101 @@
+/// Pushing the metrics of one-shot refreshes to a Prometheus Pushgateway.
+///
+/// `hnv refresh` run from cron exits before anything could scrape it, so the outcome of the run
+/// is pushed in the text exposition format instead. Every push replaces the metrics of the
+/// previous run under the configured job.
+use std::time::Duration;
+
+use anyhow::Context;
+use reqwest::{header, Client, Url};
+
+use crate::{config::PushgatewayConfig, refresh};
+
+/// How long the Pushgateway has to accept the metrics, so that a hanging one doesn't keep the
+/// run from exiting.
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The metrics of a refresh run.
+pub struct RefreshMetrics {
+    pub duration: Duration,
+    /// The number of items whose fetch finished, successfully or not.
+    pub items_fetched: usize,
+    pub new_videos: usize,
+    /// The number of sources that failed.
+    pub errors: usize,
+    pub success: bool,
+}
+
+impl RefreshMetrics {
+    /// The metrics of a finished job that took `duration`, out of `sources` enabled sources.
+    pub fn new(job: &refresh::Job, duration: Duration, sources: usize) -> Self {
+        let summary = match job.outcome() {
+            Some(Ok(summary)) => Some(*summary),
+            _ => None,
+        };
+        Self {
+            duration,
+            items_fetched: job.counter.progress().done,
+            new_videos: summary.map_or(0, |summary| summary.new_videos),
+            // A refresh only fails when all of its sources did.
+            errors: summary.map_or(sources, |summary| summary.failed_sources),
+            success: summary.is_some(),
+        }
+    }
+
+    /// The metrics in the text exposition format.
+    fn render(&self) -> String {
+        let metrics = [
+            (
+                "hnv_refresh_duration_seconds",
+                "How long the refresh took.",
+                self.duration.as_secs_f64(),
+            ),
+            (
+                "hnv_refresh_items_fetched",
+                "The number of items fetched from the sources.",
+                self.items_fetched as f64,
+            ),
+            (
+                "hnv_refresh_new_videos",
+                "The number of videos listed for the first time.",
+                self.new_videos as f64,
+            ),
+            (
+                "hnv_refresh_errors",
+                "The number of sources that failed.",
+                self.errors as f64,
+            ),
+            (
+                "hnv_refresh_success",
+                "Whether the refresh succeeded.",
+                if self.success { 1.0 } else { 0.0 },
+            ),
+        ];
+        metrics
+            .iter()
+            .map(|(name, help, value)| {
+                format!("# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}\n")
+            })
+            .collect()
+    }
+}
+
+/// Push the metrics of a refresh, replacing those of the previous one.
+pub async fn push(config: &PushgatewayConfig, metrics: &RefreshMetrics) -> anyhow::Result<()> {
+    let mut url = Url::parse(&config.url)
+        .with_context(|| format!("Invalid Pushgateway URL {}", config.url))?;
+    url.path_segments_mut()
+        .map_err(|()| anyhow::anyhow!("Invalid Pushgateway URL {}", config.url))?
+        .pop_if_empty()
+        .extend(["metrics", "job", &config.job]);
+
+    Client::new()
+        .put(url)
+        .header(header::CONTENT_TYPE, "text/plain; version=0.0.4")
+        .body(metrics.render())
+        .timeout(PUSH_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
@@ -0,0 +1,20 @@
+/// Saving videos to read-it-later services, such as Pocket or Wallabag.
+///
+/// Visitors save a video with a button next to it, which adds it to the account configured for
+/// the service, so the videos end up with the rest of what the owner of the instance reads and
+/// watches later.
+use async_trait::async_trait;
+
+use crate::video::Video;
+
+#[async_trait]
+pub trait ReadLater: Send + Sync {
+    /// The name of the service in URLs, e.g. `pocket`.
+    fn name(&self) -> &'static str;
+
+    /// The name of the service shown to visitors, e.g. `Pocket`.
+    fn label(&self) -> &'static str;
+
+    /// Add a video to the account.
+    async fn save(&self, video: &Video) -> anyhow::Result<()>;
+}
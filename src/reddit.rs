@@ -0,0 +1,265 @@
+/// Get videos from subreddits through Reddit's public JSON listings.
+///
+/// Every configured subreddit is one request per refresh. Posts are classified by the same URL
+/// detection as Hacker News items, and additionally count as videos when Reddit hosts the video
+/// itself.
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::cache::Cache;
+use crate::hacker_news::{detect_url, Counter, Detection, Detections};
+use crate::source::{self, Fetched, Source, Story};
+
+/// The base URL of Reddit.
+pub const BASE_URL: &str = "https://www.reddit.com";
+
+/// The name of Reddit as a [`Source`].
+pub const NAME: &str = "reddit";
+
+/// The subreddits listed unless configured otherwise.
+pub const DEFAULT_SUBREDDITS: [&str; 2] = ["videos", "programming"];
+
+/// The number of posts fetched per subreddit.
+const LIMIT: usize = 50;
+
+/// The fields of a Reddit post that we make use of.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Post {
+    /// The base-36 ID of the post, without the `t3_` kind prefix.
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    /// The path of the comments page, e.g. `/r/videos/comments/abc123/title/`.
+    pub permalink: String,
+    pub subreddit: String,
+    /// Whether the video is hosted on Reddit itself.
+    #[serde(default)]
+    pub is_video: bool,
+    /// Announcements pinned to the top of the subreddit.
+    #[serde(default)]
+    pub stickied: bool,
+    pub score: Option<i64>,
+    /// When the post was submitted, in seconds since the Unix epoch.
+    pub created_utc: Option<f64>,
+    pub num_comments: Option<i64>,
+}
+
+impl Post {
+    fn into_story(self, base_url: &str) -> Story {
+        Story {
+            discussion_url: format!("{}{}", base_url, self.permalink),
+            id: self.id,
+            title: self.title,
+            url: self.url,
+            score: self.score,
+            submitted_at: self.created_utc.map(|time| time as i64),
+            comments: self.num_comments,
+        }
+    }
+
+    /// Classify the post by whether (and why) it is a video.
+    pub fn detection(&self) -> Detection {
+        match detect_url(&self.url) {
+            Detection::Rejected if self.is_video => Detection::VideoTag,
+            detection => detection,
+        }
+    }
+}
+
+/// A page of a listing, as returned by the API.
+#[derive(Deserialize, Serialize)]
+struct Listing {
+    data: ListingData,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ListingData {
+    children: Vec<Thing>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Thing {
+    data: Post,
+}
+
+/// A client of the Reddit API that caches the posts it has seen.
+pub struct Reddit {
+    client: Client,
+    cache: Cache,
+    base_url: String,
+    /// The subreddits listed as a source.
+    subreddits: Vec<String>,
+    /// The outcomes of the detection in the most recent fetch of the subreddits.
+    detections: Mutex<Detections>,
+}
+
+impl Reddit {
+    pub fn new(cache: Cache) -> anyhow::Result<Self> {
+        Self::with_base_url(cache, BASE_URL)
+    }
+
+    /// Talk to another URL than reddit.com, e.g. a fake for tests.
+    pub fn with_base_url(cache: Cache, base_url: &str) -> anyhow::Result<Self> {
+        // Reddit throttles requests without a descriptive user agent.
+        let client = Client::builder()
+            .user_agent(concat!("hnv/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Self {
+            client,
+            cache,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            subreddits: DEFAULT_SUBREDDITS.map(String::from).to_vec(),
+            detections: Default::default(),
+        })
+    }
+
+    /// List other subreddits than [`DEFAULT_SUBREDDITS`] as a source.
+    pub fn with_subreddits(mut self, subreddits: Vec<String>) -> Self {
+        self.subreddits = subreddits;
+        self
+    }
+
+    /// Get the videos among the hot posts of a subreddit, in the order they are listed.
+    pub async fn get_hot_videos(&self, subreddit: &str) -> anyhow::Result<Vec<Post>> {
+        let url = format!(
+            "{}/r/{}/hot.json?limit={}&raw_json=1",
+            self.base_url, subreddit, LIMIT
+        );
+
+        debug!(
+            "Fetching fresh response for the hot posts of r/{}",
+            subreddit
+        );
+        let listing: Listing = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .with_context(|| format!("Failed to parse the hot posts of r/{}", subreddit))?;
+
+        let mut videos = Vec::new();
+        for Thing { data: post } in listing.data.children {
+            if post.stickied {
+                continue;
+            }
+            self.detections
+                .lock()
+                .unwrap()
+                .record(post.detection(), &post.url);
+            if post.detection() == Detection::Rejected {
+                continue;
+            }
+            // Keep the post as a listing of its own, so its watch page doesn't need to fetch it
+            // again.
+            let url = self.post_url(&post.id);
+            if self.cache.get(&url).await?.is_none() {
+                let listing = Listing {
+                    data: ListingData {
+                        children: vec![Thing { data: post.clone() }],
+                    },
+                };
+                self.cache
+                    .set(&url, &serde_json::to_string(&listing)?)
+                    .await?;
+            }
+            videos.push(post);
+        }
+
+        Ok(videos)
+    }
+
+    /// Get a single post, if it is a video.
+    pub async fn get_video(&self, id: &str) -> anyhow::Result<Option<Post>> {
+        let url = self.post_url(id);
+
+        let json = match self.cache.get(&url).await? {
+            Some(json) => json,
+            None => {
+                debug!("Fetching fresh response for post {}", id);
+                let response = self.client.get(&url).send().await?.error_for_status()?;
+                let json = response.text().await?;
+                self.cache.set(&url, &json).await?;
+                json
+            }
+        };
+
+        // Unknown posts are returned as an empty listing.
+        let listing: Listing =
+            serde_json::from_str(&json).with_context(|| format!("Failed to parse post {}", id))?;
+        let post = listing
+            .data
+            .children
+            .into_iter()
+            .next()
+            .map(|thing| thing.data);
+        Ok(post.filter(|post| post.detection() != Detection::Rejected))
+    }
+
+    fn post_url(&self, id: &str) -> String {
+        format!("{}/by_id/t3_{}.json?raw_json=1", self.base_url, id)
+    }
+}
+
+#[async_trait]
+impl Source for Reddit {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn label(&self) -> &'static str {
+        "Reddit"
+    }
+
+    /// Every subreddit takes its turn in the list, and one that fails is skipped.
+    async fn videos(
+        &self,
+        _counter: Arc<Counter>,
+        on_fetched: &mut (dyn for<'s> FnMut(Fetched<&'s Story>) + Send),
+    ) -> anyhow::Result<Vec<Story>> {
+        let mut lists = Vec::new();
+        let mut failure = None;
+        *self.detections.lock().unwrap() = Detections::default();
+        for subreddit in &self.subreddits {
+            match self.get_hot_videos(subreddit).await {
+                Ok(posts) => lists.push(posts),
+                Err(err) => {
+                    warn!("Failed to get the posts of r/{}: {:#}", subreddit, err);
+                    failure = Some(err);
+                }
+            }
+        }
+        if let (true, Some(err)) = (lists.is_empty(), failure) {
+            return Err(err);
+        }
+
+        let stories: Vec<_> = source::interleave(lists)
+            .into_iter()
+            .map(|post| post.into_story(&self.base_url))
+            .collect();
+        for (rank, story) in stories.iter().enumerate() {
+            on_fetched(Fetched::Video(rank, story));
+        }
+        Ok(stories)
+    }
+
+    async fn video(&self, id: &str) -> anyhow::Result<Option<Story>> {
+        let post = self.get_video(id).await?;
+        Ok(post.map(|post| post.into_story(&self.base_url)))
+    }
+
+    fn discussion_url(&self, id: &str) -> String {
+        format!("{}/comments/{}", self.base_url, id)
+    }
+
+    fn detections(&self) -> Detections {
+        self.detections.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,262 @@
+/// Background refreshes of the video list.
+///
+/// Every refresh runs as a job with an ID, so that its progress can be polled while it runs and
+/// its outcome looked up afterwards. Only one refresh may run at a time.
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use anyhow::bail;
+use futures_util::{stream, Stream};
+
+use serde::Serialize;
+use tracing::{error, info, Instrument, Span};
+use utoipa::ToSchema;
+
+use crate::hacker_news::{Counter, Progress};
+
+/// How many finished jobs are remembered for polling.
+const KEEP_FINISHED: usize = 16;
+
+/// A single refresh run.
+pub struct Job {
+    pub id: u64,
+    pub counter: Arc<Counter>,
+    outcome: OnceLock<Result<Summary, String>>,
+}
+
+/// What a successful refresh did.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Summary {
+    /// The number of videos listed for the first time.
+    pub new_videos: usize,
+    /// The number of sources that failed, whose previous videos were kept.
+    pub failed_sources: usize,
+}
+
+/// The state of a job, as reported to clients polling it.
+#[derive(Serialize, ToSchema)]
+pub struct JobStatus {
+    pub id: u64,
+    /// One of `running`, `finished` or `failed`.
+    #[schema(value_type = String, example = "running")]
+    pub status: &'static str,
+    pub done: usize,
+    pub total: usize,
+    pub error: Option<String>,
+}
+
+impl Job {
+    /// Check whether the job has finished, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        self.outcome.get().is_some()
+    }
+
+    /// The outcome of the job, once it has finished.
+    pub fn outcome(&self) -> Option<&Result<Summary, String>> {
+        self.outcome.get()
+    }
+
+    /// Fail if the job did, once it has finished.
+    pub fn result(&self) -> anyhow::Result<()> {
+        match self.outcome() {
+            Some(Err(err)) => bail!("Refresh failed: {}", err),
+            _ => Ok(()),
+        }
+    }
+
+    /// Follow the status of the job, starting with the current one and ending with the final one.
+    pub fn updates(self: Arc<Self>) -> impl Stream<Item = JobStatus> {
+        let progress = self.counter.subscribe();
+        stream::unfold(Some((self, progress)), |state| async move {
+            let (job, mut progress) = state?;
+            let status = job.status();
+            if job.is_finished() {
+                return Some((status, None));
+            }
+
+            // The outcome is not published on the channel, so check for it every now and then.
+            let _ = tokio::time::timeout(Duration::from_secs(1), progress.changed()).await;
+            Some((status, Some((job, progress))))
+        })
+    }
+
+    pub fn status(&self) -> JobStatus {
+        let Progress { done, total, .. } = self.counter.progress();
+        let (status, error) = match self.outcome() {
+            None => ("running", None),
+            Some(Ok(_)) => ("finished", None),
+            Some(Err(err)) => ("failed", Some(err.clone())),
+        };
+
+        JobStatus {
+            id: self.id,
+            status,
+            done,
+            total,
+            error,
+        }
+    }
+}
+
+/// How often progress is logged when there is no progress bar.
+const LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Report the progress of a job until it finishes.
+///
+/// With `bar`, a progress bar is drawn on stderr. Otherwise, e.g. under systemd or Docker where
+/// the control characters of the bar would end up in the logs, progress lines are logged.
+pub async fn report(job: Arc<Job>, bar: bool) {
+    let mut progress = job.counter.subscribe();
+    let mut pb: Option<pbr::ProgressBar<std::io::Stderr>> = None;
+    let mut last_log = Instant::now();
+
+    while !job.is_finished() {
+        // The outcome is not published on the channel, so check for it every now and then.
+        let _ = tokio::time::timeout(Duration::from_millis(500), progress.changed()).await;
+        let Progress { done, total, .. } = job.counter.progress();
+        if total == 0 {
+            continue;
+        }
+
+        if bar {
+            pb.get_or_insert_with(|| pbr::ProgressBar::on(std::io::stderr(), total as u64))
+                .set(done as u64);
+        } else if last_log.elapsed() >= LOG_INTERVAL {
+            info!("Refresh {}: {}/{} items", job.id, done, total);
+            last_log = Instant::now();
+        }
+    }
+
+    if let Some(mut pb) = pb {
+        pb.finish();
+    }
+}
+
+/// Keeps track of the refresh jobs.
+#[derive(Default)]
+pub struct Refresher {
+    jobs: Mutex<BTreeMap<u64, Arc<Job>>>,
+}
+
+impl Refresher {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Start a refresh in the background.
+    ///
+    /// `run` is given the progress counter of the new job. If a refresh is running already, no
+    /// new one is started and the running job is returned as the error.
+    pub fn start<F, Fut>(&self, run: F) -> Result<Arc<Job>, Arc<Job>>
+    where
+        F: FnOnce(Arc<Counter>) -> Fut,
+        Fut: Future<Output = anyhow::Result<Summary>> + Send + 'static,
+    {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(running) = jobs.values().find(|job| !job.is_finished()) {
+            return Err(running.clone());
+        }
+
+        let id = jobs.keys().next_back().map_or(1, |id| id + 1);
+        let job = Arc::new(Job {
+            id,
+            counter: Counter::new(),
+            outcome: OnceLock::new(),
+        });
+        jobs.insert(id, job.clone());
+
+        // Forget the oldest jobs, they are unlikely to be polled anymore.
+        while jobs.len() > KEEP_FINISHED {
+            jobs.pop_first();
+        }
+
+        let future = run(job.counter.clone());
+        let task_job = job.clone();
+        tokio::spawn(
+            async move {
+                info!("Refresh {} started", task_job.id);
+                // The refresh runs as a task of its own, so that a panic in it still finishes the
+                // job, rather than leaving it running and blocking every later refresh.
+                let outcome = match tokio::spawn(future.in_current_span()).await {
+                    Ok(outcome) => outcome.map_err(|err| format!("{:#}", err)),
+                    Err(err) if err.is_panic() => Err("panicked".to_string()),
+                    Err(err) => Err(err.to_string()),
+                };
+                match &outcome {
+                    Ok(_) => info!("Refresh {} finished", task_job.id),
+                    Err(err) => {
+                        error!(job = task_job.id, "Refresh {} failed: {}", task_job.id, err)
+                    }
+                }
+                let _ = task_job.outcome.set(outcome);
+            }
+            .instrument(Span::current()),
+        );
+
+        Ok(job)
+    }
+
+    /// Look up a job by its ID.
+    pub fn job(&self, id: u64) -> Option<Arc<Job>> {
+        self.jobs.lock().unwrap().get(&id).cloned()
+    }
+
+    /// The most recently started job.
+    pub fn latest(&self) -> Option<Arc<Job>> {
+        self.jobs.lock().unwrap().values().next_back().cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn finished(job: &Job) -> JobStatus {
+        while !job.is_finished() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        job.status()
+    }
+
+    #[tokio::test]
+    async fn a_panicking_refresh_fails_its_job() {
+        let refresher = Refresher::new();
+        let job = refresher
+            .start(|_| async { panic!("the refresh broke") })
+            .ok()
+            .unwrap();
+
+        let status = finished(&job).await;
+        assert_eq!(status.status, "failed");
+        assert_eq!(status.error.as_deref(), Some("panicked"));
+
+        // The next refresh isn't held up by it.
+        let next = refresher
+            .start(|_| async { Ok(Summary::default()) })
+            .ok()
+            .unwrap();
+        assert_eq!(finished(&next).await.status, "finished");
+    }
+
+    #[tokio::test]
+    async fn only_one_refresh_runs_at_a_time() {
+        let refresher = Refresher::new();
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        let job = refresher
+            .start(|_| async {
+                let _ = released.await;
+                Ok(Summary::default())
+            })
+            .ok()
+            .unwrap();
+
+        let running = refresher.start(|_| async { Ok(Summary::default()) });
+        assert_eq!(running.err().map(|job| job.id), Some(job.id));
+        release.send(()).unwrap();
+        assert_eq!(finished(&job).await.status, "finished");
+    }
+}
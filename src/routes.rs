@@ -0,0 +1,943 @@
+/// The routes of the site, and what their pages have in common.
+///
+/// The handlers live in a module per feature. This one puts them together into the router, with
+/// the limits, authentication and other layers around each group of routes, and holds the
+/// extractors, templates and errors they share.
+mod activitypub;
+mod admin;
+mod api;
+mod archive;
+pub mod feeds;
+mod followed;
+pub mod index;
+mod lists;
+pub mod playlists;
+mod search;
+mod settings;
+mod status;
+pub mod watch;
+
+use std::{
+    any::Any,
+    borrow::Cow,
+    future::IntoFuture,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context;
+use askama::Template;
+use axum::{
+    async_trait,
+    body::Body,
+    error_handling::HandleErrorLayer,
+    extract::{FromRequestParts, Path},
+    http::{header, request::Parts, HeaderMap, HeaderValue, Method, Request, StatusCode, Uri},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{delete, get, post},
+    Extension, Router,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::task::JoinSet;
+use tower::{limit::GlobalConcurrencyLimitLayer, BoxError, ServiceBuilder};
+use tower_http::{
+    catch_panic::CatchPanicLayer,
+    request_id::{MakeRequestUuid, RequestId},
+    services::ServeDir,
+    trace::{DefaultOnResponse, TraceLayer},
+    validate_request::ValidateRequestHeaderLayer,
+    ServiceBuilderExt,
+};
+use tracing::{error, info, info_span, Level, Span};
+use uuid::Uuid;
+
+use crate::{
+    assets, auth, backup, config, digest,
+    i18n::Locale,
+    listener, minify, overload,
+    preferences::{Preferences, Theme},
+    refresh, security,
+    state::SharedState,
+    systemd, version,
+};
+
+/// Answer the requests to a group of routes beyond a number handled at the same time with a 503.
+///
+/// The routes share the limit, rather than each getting one of its own.
+fn limit_concurrency(router: Router, max: usize, overload: &overload::Overload) -> Router {
+    let overload = overload.clone();
+    router.layer(
+        ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(
+                move |method: Method, uri: Uri, err: BoxError| {
+                    let overload = overload.clone();
+                    async move { overload.handle(method, uri, err).await }
+                },
+            ))
+            .load_shed()
+            .layer(GlobalConcurrencyLimitLayer::new(max)),
+    )
+}
+
+/// Time requests of a group of routes out, answering them with a 408.
+fn limit_time(router: Router, timeout: Duration) -> Router {
+    router.layer(
+        ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(handle_error))
+            .timeout(timeout),
+    )
+}
+
+/// Serve the site until any of the listeners fails.
+pub async fn serve(state: SharedState) -> anyhow::Result<()> {
+    // Refresh the video list in the background, the pages show what is cached meanwhile.
+    if let Ok(job) = state.start_refresh() {
+        tokio::spawn(refresh::report(job, false));
+    }
+    tokio::spawn({
+        let state = state.clone();
+        async move { state.scheduler.run(|| state.start_refresh()).await }
+    });
+    if let Some(downloads) = state.downloads.clone() {
+        tokio::spawn(async move { downloads.run().await });
+    }
+    tokio::spawn({
+        let state = state.clone();
+        async move { state.update_stats().await }
+    });
+    tokio::spawn({
+        let state = state.clone();
+        async move { state.sessions.run().await }
+    });
+    if state.status.is_some() {
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Some(monitor) = &state.status {
+                monitor.run().await
+            }
+        });
+    }
+    if let Some(config) = &state.config.backup {
+        let backups = backup::Backups::new(config.clone(), state.cache.clone())?;
+        tokio::spawn(async move { backups.run().await });
+    }
+    if let Some(config) = &state.config.digest {
+        let digest = digest::Digest::new(config.clone(), state.config.absolute_url("/"))?;
+        let state = state.clone();
+        tokio::spawn(async move {
+            digest
+                .run(|since, limit| state.top_videos(since, limit))
+                .await
+        });
+    }
+
+    let listen = state.config.listen.clone();
+    let base_path = state.config.base_path.clone();
+    let admin_listen = state.config.admin_listen.clone();
+    let assets_dir = state.config.assets_dir.clone();
+    let minify_html = state.config.minify_html;
+    let media_dir = state
+        .config
+        .downloads
+        .as_ref()
+        .map(|config| config.dir.clone());
+    let timeouts = state.config.timeouts.clone();
+    let concurrency = state.config.concurrency.clone();
+    let overload = state.overload.clone();
+    let admin_auth = auth::RequireAuth::admin(&state.config);
+    let api_auth = auth::RequireAuth::api(&state.config);
+    let site_auth = auth::RequireAuth::site(&state.config);
+    let security_headers = security::SecurityHeaders::new(&state.config)?;
+
+    let s = ServiceBuilder::new()
+        // Tag every request with an `x-request-id` (keeping one supplied by a proxy) and open a
+        // span carrying it, so all log lines emitted while handling the request can be correlated.
+        .set_x_request_id(MakeRequestUuid)
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(make_request_span)
+                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .propagate_x_request_id()
+        .layer(Extension(state.clone()));
+
+    // build our application with a route
+    let app = Router::new()
+        .route("/", get(index::root))
+        .route("/watch/:id", get(watch::watch))
+        .route("/v/:id", get(watch::visit))
+        .route("/v/:id/discussion", get(watch::visit_discussion))
+        .route("/popular", get(archive::popular))
+        .route("/status", get(status::status))
+        .route("/archive", get(archive::archive))
+        .route("/search", get(search::search))
+        .route(
+            "/searches",
+            get(search::saved_searches).post(search::save_search),
+        )
+        .route("/searches/:id/delete", post(search::delete_saved_search))
+        .route("/searches/feed/:token", get(search::saved_search_feed))
+        .route("/stats", get(archive::archive_stats))
+        .route("/channels", get(archive::channels))
+        .route("/archive/:year/:month", get(archive::archive_month))
+        .route("/week/:year/:week", get(archive::week))
+        .route("/yesterday", get(archive::yesterday))
+        .route("/followed", get(followed::followed))
+        .route("/followed.xml", get(followed::followed_feed))
+        .route("/weeks.xml", get(archive::weeks_feed))
+        .route("/playlist", get(playlists::playlist))
+        .route("/playlist.m3u", get(playlists::playlist_m3u))
+        .route(
+            "/playlists",
+            get(playlists::playlists).post(playlists::create_playlist),
+        )
+        .route("/playlists/:id", get(playlists::own_playlist))
+        .route("/playlists/edit", post(playlists::edit_playlist))
+        .route("/playlists/:id/delete", post(playlists::delete_playlist))
+        .route("/shared/:token", get(playlists::shared_playlist))
+        .route(
+            "/shared/:token/feed.xml",
+            get(playlists::shared_playlist_feed),
+        )
+        .route("/refresh/events", get(admin::refresh_events))
+        .route("/later", get(lists::watch_later))
+        .route("/later/export.json", get(lists::export_watch_later))
+        .route("/later/import", post(lists::import_watch_later))
+        .route("/later/:id", post(lists::add_watch_later))
+        .route("/later/:id/remove", post(lists::remove_watch_later))
+        .route("/hide/:id", post(lists::hide))
+        .route("/love/:id", post(lists::love))
+        .route("/love/:id/undo", post(lists::unlove))
+        .route("/loved", get(lists::loved))
+        .route("/history", get(lists::history))
+        .route("/history/:id", post(lists::record_position))
+        .route("/hide/:id/undo", post(lists::unhide))
+        .route("/save/:service/:id", post(lists::save_to))
+        .route("/robots.txt", get(feeds::robots_txt))
+        .route("/sitemap.xml", get(feeds::sitemap_xml))
+        .route("/feed.xml", get(feeds::feed))
+        .route("/feed/:platform", get(feeds::platform_feed))
+        .route("/feed/tag/:tag", get(feeds::tag_feed))
+        .route("/feed/mine", get(feeds::my_feed).post(feeds::save_my_feed))
+        .route("/feed/u/:token", get(feeds::user_feed))
+        .route("/podcast.xml", get(feeds::podcast))
+        .route("/.well-known/webfinger", get(activitypub::webfinger))
+        .route("/ap/actor", get(activitypub::ap_actor))
+        .route("/ap/inbox", post(activitypub::ap_inbox))
+        .route("/ap/outbox", get(activitypub::ap_outbox))
+        .route("/ap/followers", get(activitypub::ap_followers))
+        .route("/ap/notes/:id", get(activitypub::ap_note))
+        .route("/theme", post(settings::set_theme))
+        .route("/privacy", post(settings::set_privacy))
+        .route("/timezone", post(settings::set_time_zone));
+    // Each group of routes gets its own timeout, so they can't be applied around the whole app.
+    let app = limit_time(
+        limit_concurrency(app, concurrency.pages, &overload),
+        timeouts.public(),
+    );
+
+    // The read API is open, unless the instance is private.
+    let api = Router::new()
+        .route("/api/v1/videos", get(api::api_videos))
+        .route("/api/v1/videos/:id/history", get(api::api_video_history));
+    let api = match api_auth {
+        Some(auth) => api.layer(ValidateRequestHeaderLayer::custom(auth)),
+        None => api,
+    };
+    // Adding videos is for the operators only.
+    let api = match admin_auth.clone() {
+        Some(auth) => api.merge(
+            Router::new()
+                .route("/api/v1/videos", post(api::api_submit_video))
+                .route("/api/v1/refresh/:id", post(api::api_refresh_video))
+                .layer(ValidateRequestHeaderLayer::custom(auth)),
+        ),
+        None => api,
+    };
+    let api = api
+        .route("/version", get(api::api_version))
+        .route("/api/v1/openapi.json", get(api::api_spec))
+        .route("/api/docs", get(api::api_docs));
+    let app = app.merge(limit_time(
+        limit_concurrency(api, concurrency.api, &overload),
+        timeouts.api(),
+    ));
+
+    let static_files = match assets_dir {
+        Some(dir) => Router::new().nest_service("/assets", ServeDir::new(dir)),
+        None => Router::new().route("/assets/*path", get(asset)),
+    };
+    let static_files = match media_dir {
+        Some(dir) => static_files.nest_service("/media", ServeDir::new(dir)),
+        None => static_files,
+    };
+    let app = app.merge(limit_time(
+        limit_concurrency(static_files, concurrency.assets, &overload),
+        timeouts.public(),
+    ));
+
+    // Private instances hide the whole site behind a login.
+    let app = match site_auth {
+        Some(auth) => app.layer(ValidateRequestHeaderLayer::custom(auth)),
+        None => app,
+    };
+
+    // The admin pages only exist when credentials or API tokens are configured.
+    let admin_app = admin_auth.map(|auth| {
+        let admin = Router::new()
+            .route("/", get(admin::admin))
+            .route("/refresh", post(admin::admin_refresh))
+            .route("/refresh/:id", get(admin::admin_refresh_status))
+            .route("/refresh/:id/events", get(admin::admin_refresh_events))
+            .route(
+                "/webhooks",
+                get(admin::admin_webhooks).post(admin::admin_add_webhook),
+            )
+            .route("/webhooks/:id", delete(admin::admin_remove_webhook))
+            .route(
+                "/webhooks/:id/deliveries",
+                get(admin::admin_webhook_deliveries),
+            )
+            .route("/detections", get(admin::admin_detections))
+            .route(
+                "/blocklist",
+                get(admin::admin_blocklist).post(admin::admin_add_block),
+            )
+            .route("/blocklist/:id", delete(admin::admin_remove_block))
+            .route("/blocklist/add", post(admin::admin_block_form))
+            .route("/blocklist/:id/remove", post(admin::admin_unblock_form))
+            .route("/cache", get(admin::admin_cache))
+            .route("/cache/body", get(admin::admin_cached_body))
+            .route("/cache/:id", get(admin::admin_cache_entry))
+            .route(
+                "/downloads",
+                get(admin::admin_downloads).post(admin::admin_queue_download),
+            )
+            .route("/downloads/pin", post(admin::admin_pin_download))
+            .route("/videos", post(admin::admin_submit_video))
+            .route("/sessions/end", post(admin::admin_end_sessions))
+            .route("/features/:name", post(admin::admin_set_feature))
+            .route("/backup", post(admin::admin_backup));
+        #[cfg(feature = "tokio-console")]
+        let admin = admin.route("/runtime", get(admin::admin_runtime));
+        let admin = limit_time(
+            admin.layer(ValidateRequestHeaderLayer::custom(auth)),
+            timeouts.admin(),
+        );
+        Router::new().nest(
+            "/admin",
+            limit_concurrency(admin, concurrency.admin, &overload),
+        )
+    });
+
+    // Sockets inherited from systemd replace the `listen` addresses, except for those bound to an
+    // admin address.
+    let mut inherited =
+        listener::inherited().context("Failed to take over the inherited sockets")?;
+    let admin_listeners = admin_listen
+        .iter()
+        .map(|addr| {
+            listener::take_or_bind(&mut inherited, *addr)
+                .with_context(|| format!("Failed to listen on {}", addr))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let listeners = if inherited.is_empty() {
+        listen
+            .iter()
+            .map(|addr| {
+                listener::bind(*addr).with_context(|| format!("Failed to listen on {}", addr))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
+    } else {
+        inherited
+            .into_iter()
+            .map(listener::from_inherited)
+            .collect::<std::io::Result<Vec<_>>>()
+            .context("Failed to take over the inherited sockets")?
+    };
+
+    // The admin pages are served next to the public pages, unless separate admin listeners (e.g.
+    // only on localhost) are configured.
+    let mut servers = Vec::new();
+    match admin_app {
+        Some(admin_app) if !admin_listeners.is_empty() => {
+            servers.push((listeners, app));
+            servers.push((admin_listeners, admin_app));
+        }
+        Some(admin_app) => servers.push((listeners, app.merge(admin_app))),
+        None => servers.push((listeners, app)),
+    }
+
+    let mut tasks = JoinSet::new();
+    for (listeners, app) in servers {
+        let app = match &base_path {
+            // Nesting serves the index at `/prefix` only, so send `/prefix/` there as well.
+            Some(base_path) => {
+                let index = base_path.clone();
+                Router::new()
+                    .route(
+                        &format!("{}/", base_path),
+                        get(|| async move { Redirect::permanent(&index) }),
+                    )
+                    .nest(base_path, app)
+            }
+            None => app,
+        };
+        // Panics are answered with the error page, rather than dropping the connection.
+        let app = app
+            .fallback(not_found)
+            .layer(CatchPanicLayer::custom(handle_panic));
+        let app = match minify_html {
+            true => app.layer(axum::middleware::map_response(minify::apply)),
+            false => app,
+        };
+        let app = app
+            .layer(axum::middleware::map_response_with_state(
+                security_headers.clone(),
+                security::apply,
+            ))
+            .layer(s.clone());
+        for listener in listeners {
+            info!("Listening on: {}", listener.local_addr()?);
+            // On shutdown, stop accepting connections but finish the requests in flight, so a
+            // restart under socket activation doesn't drop anything.
+            let server = axum::serve(listener, app.clone())
+                .with_graceful_shutdown(listener::shutdown_signal());
+            tasks.spawn(server.into_future());
+        }
+    }
+
+    // Units of `Type=notify` only count as started once there is an index to serve.
+    tokio::spawn({
+        let state = state.clone();
+        async move {
+            state.wait_for_published().await;
+            systemd::notify("READY=1");
+        }
+    });
+
+    // Run until any of the servers fails, or all of them shut down.
+    while let Some(result) = tasks.join_next().await {
+        result??;
+    }
+    systemd::notify("STOPPING=1");
+
+    // Write what the last requests cached before exiting.
+    state.cache.flush().await
+}
+
+/// Redirect to the local page named in the `Referer` header, or to the index.
+fn redirect_back(headers: &HeaderMap, base: &BasePath) -> Redirect {
+    let path = headers
+        .get(header::REFERER)
+        .and_then(|referer| referer.to_str().ok())
+        .and_then(|referer| referer.parse::<axum::http::Uri>().ok())
+        .and_then(|uri| uri.path_and_query().map(|path| path.to_string()))
+        .filter(|path| path.starts_with('/') && !path.starts_with("//"))
+        .unwrap_or_else(|| match base.0.as_str() {
+            "" => "/".to_string(),
+            base => base.to_string(),
+        });
+
+    Redirect::to(&path)
+}
+
+/// Format a point in time for display, in UTC.
+pub fn format_time(time: SystemTime) -> String {
+    OffsetDateTime::from(time)
+        .format(&Rfc3339)
+        .unwrap_or_else(|_| "-".to_string())
+}
+
+/// Serve a static asset embedded into the binary.
+async fn asset(Path(path): Path<String>, layout: Layout, headers: HeaderMap) -> Response {
+    let Some((asset, immutable)) = assets::resolve(&path) else {
+        return not_found(layout).await;
+    };
+    if immutable {
+        let headers = [
+            (header::CONTENT_TYPE, asset.mime_type),
+            (header::CACHE_CONTROL, IMMUTABLE.to_string()),
+        ];
+        return (headers, asset.data).into_response();
+    }
+
+    let etag = ETag::of(&asset.hash);
+    if etag.matches(&headers) {
+        return etag.not_modified();
+    }
+
+    etag.attach(([(header::CONTENT_TYPE, asset.mime_type)], asset.data))
+}
+
+async fn not_found(layout: Layout) -> Response {
+    not_found_page(layout)
+}
+
+fn not_found_page(layout: Layout) -> Response {
+    let message = layout.t("error-not-found").to_string();
+    ErrorTemplate::response(layout, StatusCode::NOT_FOUND, message)
+}
+
+/// The ways a request can fail, each with its own status code so that monitoring can tell
+/// Hacker News outages apart from bugs.
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    /// Hacker News (or another upstream) could not be reached or answered with an error.
+    #[error("upstream unavailable: {0:#}")]
+    UpstreamUnavailable(anyhow::Error),
+    /// The requested item doesn't exist or isn't a video.
+    #[error("not found")]
+    NotFound,
+    /// Hacker News (or another upstream) didn't answer in time.
+    #[error("upstream timed out: {0:#}")]
+    Timeout(anyhow::Error),
+    #[error("{0:#}")]
+    Internal(anyhow::Error),
+}
+
+impl AppError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::UpstreamUnavailable(_) => StatusCode::BAD_GATEWAY,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+// Tell axum how to convert `AppError` into a response.
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let layout = Layout::default();
+        let status = self.status();
+        let key = match self {
+            Self::NotFound => return not_found_page(layout),
+            Self::UpstreamUnavailable(_) => "error-upstream",
+            Self::Timeout(_) => "error-timeout",
+            Self::Internal(_) => "error-internal",
+        };
+
+        // The details may reveal internals, so they only go to the log. The visitor gets an ID to
+        // quote when reporting the problem, which finds the log line.
+        let error_id = Uuid::new_v4().simple().to_string();
+        error!(error_id = %error_id, "Request failed: {}", self);
+        let message = layout.t(key).to_string();
+        ErrorTemplate::response_with_id(layout, status, message, Some(error_id))
+    }
+}
+
+// This enables using `?` on functions that return `Result<_, anyhow::Error>` to turn them into
+// `Result<_, AppError>`. Errors from talking to upstream services are told apart by the
+// `reqwest::Error` in their chain.
+impl From<anyhow::Error> for AppError {
+    fn from(err: anyhow::Error) -> Self {
+        let upstream = err
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<reqwest::Error>());
+        match upstream {
+            Some(upstream) if upstream.is_timeout() => Self::Timeout(err),
+            Some(_) => Self::UpstreamUnavailable(err),
+            None => Self::Internal(err),
+        }
+    }
+}
+
+/// The path prefix the site is mounted at behind a reverse proxy, without a trailing slash.
+///
+/// It is either configured as `base_path`, in which case the routes are served under it, or
+/// passed by a proxy that strips it in the `X-Forwarded-Prefix` header.
+#[derive(Clone, Default, Hash)]
+pub struct BasePath(pub String);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for BasePath
+where
+    S: Send + Sync,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Ok(Extension(app)) = Extension::<SharedState>::from_request_parts(parts, state).await
+        {
+            if let Some(base_path) = &app.config.base_path {
+                return Ok(Self(base_path.clone()));
+            }
+        }
+
+        let forwarded = parts
+            .headers
+            .get("x-forwarded-prefix")
+            .and_then(|value| value.to_str().ok())
+            .map(|prefix| prefix.trim_end_matches('/'))
+            .filter(|prefix| prefix.starts_with('/') && !prefix.starts_with("//"))
+            .unwrap_or_default();
+
+        Ok(Self(forwarded.to_string()))
+    }
+}
+
+/// The per-request data every page rendered through `base.html` needs.
+#[derive(Clone, Default, Serialize)]
+pub struct Layout {
+    /// The prefix of all links to pages of the site.
+    pub base: String,
+    pub theme: Theme,
+    pub locale: Locale,
+    /// Whether YouTube links point at the privacy frontend, or `None` if none is configured.
+    pub privacy: Option<bool>,
+    /// The names and labels of the services videos can be saved to.
+    pub read_later: Vec<(&'static str, &'static str)>,
+    /// The time zone days start and end in for the visitor.
+    pub time_zone: config::TimeZone,
+    /// Whether the assets are linked to under their fingerprinted names, which the embedded
+    /// ones are served under.
+    pub fingerprinted_assets: bool,
+}
+
+impl Layout {
+    /// The URL of the index page.
+    fn home(&self) -> &str {
+        if self.base.is_empty() {
+            "/"
+        } else {
+            &self.base
+        }
+    }
+
+    /// The URL of an asset, by its path in `assets/`.
+    fn asset(&self, path: &str) -> String {
+        if self.fingerprinted_assets {
+            format!("{}/assets/{}", self.base, assets::fingerprinted(path))
+        } else {
+            format!("{}/assets/{}", self.base, path)
+        }
+    }
+
+    /// Translate a message into the visitor's language.
+    fn t(&self, key: &'static str) -> &'static str {
+        self.locale.message(key)
+    }
+
+    /// The running build, for the footer.
+    fn version(&self) -> &'static str {
+        version::LABEL
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Layout
+where
+    S: Send + Sync,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let BasePath(base) = BasePath::from_request_parts(parts, state).await?;
+        let prefs = Preferences::from_request_parts(parts, state).await?;
+        let locale = Locale::from_request_parts(parts, state).await?;
+        let app = Extension::<SharedState>::from_request_parts(parts, state).await;
+        let privacy = match &app {
+            Ok(Extension(app)) => app
+                .config
+                .privacy
+                .as_ref()
+                .map(|privacy| prefs.privacy.unwrap_or(privacy.enabled_by_default)),
+            Err(_) => None,
+        };
+        let read_later = match &app {
+            Ok(Extension(app)) => app
+                .read_later
+                .iter()
+                .map(|service| (service.name(), service.label()))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        let time_zone = match &app {
+            Ok(Extension(app)) => prefs.time_zone.unwrap_or(app.config.timezone),
+            Err(_) => prefs.time_zone.unwrap_or_default(),
+        };
+        // Assets served from a directory go by their own names.
+        let fingerprinted_assets = match &app {
+            Ok(Extension(app)) => app.config.assets_dir.is_none(),
+            Err(_) => true,
+        };
+
+        Ok(Self {
+            base,
+            theme: prefs.theme,
+            locale,
+            privacy,
+            read_later,
+            time_zone,
+            fingerprinted_assets,
+        })
+    }
+}
+
+#[derive(Template)]
+#[template(path = "error.html")]
+struct ErrorTemplate {
+    layout: Layout,
+    status: StatusCode,
+    reason: &'static str,
+    message: String,
+    /// The ID of the log line with the details of the error.
+    error_id: Option<String>,
+}
+
+impl ErrorTemplate {
+    /// Render an error page with the given status code and a message that is safe to show.
+    fn response(layout: Layout, status: StatusCode, message: String) -> Response {
+        Self::response_with_id(layout, status, message, None)
+    }
+
+    /// Like [`ErrorTemplate::response`], but also showing the ID the error was logged with.
+    fn response_with_id(
+        layout: Layout,
+        status: StatusCode,
+        message: String,
+        error_id: Option<String>,
+    ) -> Response {
+        let template = Self {
+            layout,
+            status,
+            reason: status.canonical_reason().unwrap_or("Error"),
+            message,
+            error_id,
+        };
+        (status, HtmlTemplate(template)).into_response()
+    }
+}
+
+/// A wrapper type that we'll use to encapsulate HTML parsed by askama into valid HTML for axum to serve.
+struct HtmlTemplate<T>(T);
+
+/// Allows us to convert Askama HTML templates into valid HTML for axum to serve in the response.
+impl<T> IntoResponse for HtmlTemplate<T>
+where
+    T: Template,
+{
+    fn into_response(self) -> Response {
+        // Attempt to render the template with askama
+        match self.0.render() {
+            // If we're able to successfully parse and aggregate the template, serve it
+            Ok(html) => Html(html).into_response(),
+            // If we're not, return an error or some bit of fallback HTML
+            Err(err) => template_error(err),
+        }
+    }
+}
+
+/// How long clients may reuse an asset under a fingerprinted name, whose content never changes.
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+/// How long clients and proxies may reuse a rendered page before revalidating.
+const CACHE_CONTROL: &str = "public, max-age=60, must-revalidate";
+
+/// How long the browser of a visitor may reuse a page that depends on their session, which shared
+/// caches must not keep.
+const PRIVATE_CACHE_CONTROL: &str = "private, max-age=60, must-revalidate";
+
+/// How many bytes of the digest of the content an entity tag has.
+const ETAG_LENGTH: usize = 8;
+
+/// A strong entity tag computed from the content a page is rendered from.
+///
+/// Feed readers and browsers polling the index send the tag back in `If-None-Match`, which lets us
+/// answer with a bodyless 304 instead of re-rendering the whole template.
+struct ETag {
+    tag: HeaderValue,
+    cache_control: &'static str,
+}
+
+impl ETag {
+    /// Compute the entity tag of the given content.
+    ///
+    /// The content is hashed as JSON with SHA-256. Unlike `Hash`, whose output may change between
+    /// Rust releases and platforms, the serialization is stable, so the tags survive an upgrade of
+    /// the server.
+    fn of<T: Serialize + ?Sized>(content: &T) -> Self {
+        let json = serde_json::to_vec(content).expect("the content serializes to JSON");
+        let digest = Sha256::digest(json);
+        let tag = format!("\"{}\"", hex::encode(&digest[..ETAG_LENGTH]));
+        Self {
+            tag: HeaderValue::from_str(&tag).expect("hex digest is a valid header value"),
+            cache_control: CACHE_CONTROL,
+        }
+    }
+
+    /// Keep the response out of shared caches, for pages rendered for the session of a visitor.
+    fn private(self) -> Self {
+        Self {
+            cache_control: PRIVATE_CACHE_CONTROL,
+            ..self
+        }
+    }
+
+    /// Check whether the request already holds the current version of the content.
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        let tag = self.tag.as_bytes();
+        headers
+            .get_all(header::IF_NONE_MATCH)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|candidate| candidate.trim())
+            .any(|candidate| {
+                candidate == "*"
+                    || candidate.as_bytes() == tag
+                    || candidate.strip_prefix("W/").map(str::as_bytes) == Some(tag)
+            })
+    }
+
+    /// Build the 304 response for a client whose copy is still fresh.
+    fn not_modified(self) -> Response {
+        (StatusCode::NOT_MODIFIED, self.headers()).into_response()
+    }
+
+    /// Attach the entity tag and caching headers to a full response.
+    fn attach(self, response: impl IntoResponse) -> Response {
+        (self.headers(), response).into_response()
+    }
+
+    fn headers(self) -> [(header::HeaderName, HeaderValue); 2] {
+        [
+            (header::ETAG, self.tag),
+            (
+                header::CACHE_CONTROL,
+                HeaderValue::from_static(self.cache_control),
+            ),
+        ]
+    }
+}
+
+/// Create the tracing span a request is handled in.
+fn make_request_span(request: &Request<Body>) -> Span {
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .unwrap_or("-");
+
+    info_span!(
+        "request",
+        id = %request_id,
+        method = %request.method(),
+        uri = %request.uri(),
+    )
+}
+
+/// Log a template that failed to render, and answer with a bare error that doesn't need one.
+fn template_error(err: askama::Error) -> Response {
+    let error_id = Uuid::new_v4().simple().to_string();
+    error!(error_id = %error_id, "Failed to render template: {}", err);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Failed to render the page, error ID {}", error_id),
+    )
+        .into_response()
+}
+
+/// Like [`HtmlTemplate`], but served as XML.
+struct XmlTemplate<T>(T);
+
+impl<T> IntoResponse for XmlTemplate<T>
+where
+    T: Template,
+{
+    fn into_response(self) -> Response {
+        match self.0.render() {
+            Ok(xml) => (
+                [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+                xml,
+            )
+                .into_response(),
+            Err(err) => template_error(err),
+        }
+    }
+}
+
+/// Answer a request whose handler panicked. The panic was logged with its stack trace already.
+fn handle_panic(payload: Box<dyn Any + Send>) -> Response {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or_default();
+    let error_id = Uuid::new_v4().simple().to_string();
+    error!(error_id = %error_id, "Request panicked: {}", message);
+    let layout = Layout::default();
+    let message = layout.t("error-internal").to_string();
+    ErrorTemplate::response_with_id(
+        layout,
+        StatusCode::INTERNAL_SERVER_ERROR,
+        message,
+        Some(error_id),
+    )
+}
+
+pub async fn handle_error(error: BoxError) -> impl IntoResponse {
+    if error.is::<tower::timeout::error::Elapsed>() {
+        return (StatusCode::REQUEST_TIMEOUT, Cow::from("request timed out"));
+    }
+
+    if error.is::<tower::load_shed::error::Overloaded>() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Cow::from("service is overloaded, try again later"),
+        );
+    }
+
+    let error_id = Uuid::new_v4().simple().to_string();
+    error!(error_id = %error_id, "Unhandled internal error: {}", error);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Cow::from(format!("internal error, error ID {}", error_id)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn panics() -> StatusCode {
+        panic!("the handler panicked")
+    }
+
+    #[tokio::test]
+    async fn panics_are_answered_with_the_error_page() {
+        let app: Router = Router::new()
+            .route("/", get(panics))
+            .layer(CatchPanicLayer::custom(handle_panic));
+        let request = axum::http::Request::get("/")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = tower::ServiceExt::oneshot(app, request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let page = String::from_utf8(body.to_vec()).unwrap();
+        assert!(page.contains("<h2>500 Internal Server Error</h2>"));
+        let error_id = page
+            .split("<p class=\"error-id\">")
+            .nth(1)
+            .and_then(|rest| rest.split("<code>").nth(1))
+            .and_then(|rest| rest.split("</code>").next())
+            .unwrap();
+        assert_eq!(error_id.len(), 32);
+        assert!(error_id.chars().all(|c| c.is_ascii_hexdigit()));
+        assert!(!page.contains("the handler panicked"));
+    }
+
+    #[test]
+    fn entity_tags_are_stable() {
+        // The SHA-256 digest of `"hnv"`, the string as JSON.
+        assert_eq!(ETag::of("hnv").tag, "\"4dfde16432192174\"");
+        assert_ne!(ETag::of("hnv").tag, ETag::of("hn").tag);
+    }
+}
@@ -0,0 +1,121 @@
+/// The actor Fediverse users follow, and the WebFinger lookup of it.
+use axum::{
+    body::Bytes,
+    extract::{OriginalUri, Path, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use serde::Deserialize;
+use time::OffsetDateTime;
+use tracing::warn;
+
+use super::{AppError, Layout};
+use crate::{
+    activitypub,
+    state::{SharedState, State},
+    video::VideoId,
+};
+
+/// Render the 404 page for any route we don't know about.
+#[derive(Deserialize)]
+pub struct WebFingerQuery {
+    resource: String,
+}
+
+/// Find the ActivityPub actor from its `@name@host` address.
+pub async fn webfinger(
+    Extension(state): Extension<SharedState>,
+    Query(query): Query<WebFingerQuery>,
+) -> Result<Response, AppError> {
+    let found = state
+        .activitypub
+        .as_ref()
+        .and_then(|activitypub| activitypub.webfinger(&query.resource));
+    let Some(found) = found else {
+        return Err(AppError::NotFound);
+    };
+    Ok((
+        [(header::CONTENT_TYPE, "application/jrd+json")],
+        found.to_string(),
+    )
+        .into_response())
+}
+
+fn activity_response(activity: serde_json::Value) -> Response {
+    (
+        [(header::CONTENT_TYPE, activitypub::CONTENT_TYPE)],
+        activity.to_string(),
+    )
+        .into_response()
+}
+
+pub async fn ap_actor(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+) -> Result<Response, AppError> {
+    let activitypub = state.activitypub.as_ref().ok_or(AppError::NotFound)?;
+    Ok(activity_response(activitypub.actor(layout.t("site-title"))))
+}
+
+/// Receive the follows of Fediverse users, which must be signed by them.
+pub async fn ap_inbox(
+    Extension(state): Extension<SharedState>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    let Some(activitypub) = &state.activitypub else {
+        return StatusCode::NOT_FOUND;
+    };
+    let sender = match activitypub.verify(uri.path(), &headers, &body).await {
+        Ok(sender) => sender,
+        Err(err) => {
+            warn!("Rejected an ActivityPub activity: {:#}", err);
+            return StatusCode::UNAUTHORIZED;
+        }
+    };
+    let Ok(activity) = serde_json::from_slice(&body) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    match activitypub.receive(sender, activity).await {
+        Ok(()) => StatusCode::ACCEPTED,
+        Err(err) => {
+            warn!("Failed to handle an ActivityPub activity: {:#}", err);
+            StatusCode::BAD_REQUEST
+        }
+    }
+}
+
+pub async fn ap_outbox(Extension(state): Extension<SharedState>) -> Result<Response, AppError> {
+    let activitypub = state.activitypub.as_ref().ok_or(AppError::NotFound)?;
+    let videos = state.videos();
+    let mut published = Vec::new();
+    for video in videos.iter() {
+        published.push((video, first_seen(&state, &video.id).await?));
+    }
+    Ok(activity_response(activitypub.outbox(&published)))
+}
+
+pub async fn ap_followers(Extension(state): Extension<SharedState>) -> Result<Response, AppError> {
+    let activitypub = state.activitypub.as_ref().ok_or(AppError::NotFound)?;
+    Ok(activity_response(activitypub.followers().await?))
+}
+
+pub async fn ap_note(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<VideoId>,
+) -> Result<Response, AppError> {
+    let activitypub = state.activitypub.as_ref().ok_or(AppError::NotFound)?;
+    let video = state.get_video(&id).await?.ok_or(AppError::NotFound)?;
+    let published = first_seen(&state, &id).await?;
+    Ok(activity_response(activitypub.note(&video, published)))
+}
+
+/// When a video was first listed, or now if it wasn't recorded yet.
+async fn first_seen(state: &State, id: &VideoId) -> anyhow::Result<i64> {
+    Ok(match state.store.listed_video(id).await? {
+        Some(video) => video.first_seen,
+        None => OffsetDateTime::now_utc().unix_timestamp(),
+    })
+}
@@ -0,0 +1,643 @@
+/// The admin pages, for the operators of the instance.
+use std::{
+    convert::Infallible,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use askama::Template;
+use axum::{
+    extract::{Form, Path, Query},
+    http::{header, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Redirect, Response,
+    },
+    Extension, Json,
+};
+use futures_util::{Stream, StreamExt};
+use serde::Deserialize;
+use tracing::info;
+
+use super::{format_time, not_found, AppError, BasePath, ErrorTemplate, HtmlTemplate, Layout};
+use crate::{
+    backup, blocklist, cache, features, hacker_news, refresh, scheduler, state::SharedState, store,
+    submissions, video::VideoId, webhooks,
+};
+
+/// Add a video from the admin page, then show it.
+pub async fn admin_submit_video(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+    Form(submission): Form<submissions::Submission>,
+) -> Result<Response, AppError> {
+    if let Err(message) = submission.validate() {
+        return Ok(ErrorTemplate::response(
+            layout,
+            StatusCode::BAD_REQUEST,
+            message,
+        ));
+    }
+    let video = state.submit(&submission).await?;
+    Ok(Redirect::to(&format!("{}{}", layout.base, video.watch_link)).into_response())
+}
+
+/// Log every visitor out.
+pub async fn admin_end_sessions(
+    Extension(state): Extension<SharedState>,
+    base: BasePath,
+) -> Result<Redirect, AppError> {
+    state.sessions.end_all().await?;
+    Ok(Redirect::to(&format!("{}/admin", base.0)))
+}
+
+#[derive(Deserialize)]
+pub struct FeatureForm {
+    enabled: bool,
+}
+
+/// Enable or disable an experimental behavior, until the server restarts.
+pub async fn admin_set_feature(
+    Extension(state): Extension<SharedState>,
+    base: BasePath,
+    Path(name): Path<String>,
+    Form(form): Form<FeatureForm>,
+) -> Result<Redirect, AppError> {
+    let feature = features::Feature::from_name(&name).ok_or(AppError::NotFound)?;
+    state.features.set(feature, form.enabled);
+    info!(
+        "{} the {} feature",
+        if form.enabled { "Enabled" } else { "Disabled" },
+        name
+    );
+    Ok(Redirect::to(&format!("{}/admin", base.0)))
+}
+
+/// Download a copy of the database, made while the server keeps running.
+pub async fn admin_backup(Extension(state): Extension<SharedState>) -> Result<Response, AppError> {
+    let bytes = backup::snapshot(&state.cache).await?;
+    let name = backup::file_name(chrono::Utc::now());
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/vnd.sqlite3".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", name),
+            ),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+/// Show operators the state of the instance.
+pub async fn admin(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+) -> Result<impl IntoResponse, AppError> {
+    let stats = state.hn.stats();
+    let schedule = state.scheduler.stats();
+    let mut detections: Vec<DetectionDay> = Vec::new();
+    for count in state.store.detections(ADMIN_DETECTION_DAYS).await? {
+        if detections.last().map(|day| &day.day) != Some(&count.day) {
+            detections.push(DetectionDay {
+                day: count.day.clone(),
+                ..Default::default()
+            });
+        }
+        let day = detections.last_mut().unwrap();
+        match count.rule.as_str() {
+            "youtube" => day.youtube += count.count,
+            "video-tag" => day.video_tag += count.count,
+            _ => day.rejected += count.count,
+        }
+    }
+    let template = AdminTemplate {
+        layout,
+        last_refresh: stats.last_refresh.map(format_time),
+        refresh: state.refresher.latest().map(|job| job.status()),
+        next_refresh: schedule.next_run.map(format_time),
+        schedule,
+        cache: state.hn.cache_stats().await?,
+        stats,
+        downloads: state.downloads.is_some(),
+        sessions: state.sessions.count().await?,
+        shed: state.overload.shed(),
+        detections,
+        features: state.features.all(),
+        blocks: state.blocklist.entries().await?,
+    };
+    Ok(HtmlTemplate(template))
+}
+
+/// Kick off a refresh of the video list in the background.
+///
+/// Responds with the new job, or with the running one and a conflict status if a refresh is
+/// already in progress.
+#[utoipa::path(
+    post,
+    path = "/admin/refresh",
+    responses(
+        (status = 202, description = "The refresh was started", body = refresh::JobStatus),
+        (status = 409, description = "A refresh is running already", body = refresh::JobStatus),
+        (status = 401, description = "Admin credentials or a token are required"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn admin_refresh(Extension(state): Extension<SharedState>) -> Response {
+    match state.start_refresh() {
+        Ok(job) => (StatusCode::ACCEPTED, Json(job.status())).into_response(),
+        Err(running) => (StatusCode::CONFLICT, Json(running.status())).into_response(),
+    }
+}
+
+/// Show the queued, running and finished downloads.
+pub async fn admin_downloads(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+) -> Result<Response, AppError> {
+    let Some(downloader) = &state.downloads else {
+        return Ok(not_found(layout).await);
+    };
+    let downloads: Vec<_> = state
+        .store
+        .downloads()
+        .await?
+        .into_iter()
+        .map(|download| DownloadRow {
+            updated: format_time(
+                SystemTime::UNIX_EPOCH + Duration::from_secs(download.updated_at as u64),
+            ),
+            size: downloader.size(&download),
+            download,
+        })
+        .collect();
+    let template = DownloadsTemplate {
+        layout,
+        used: downloads.iter().filter_map(|row| row.size).sum(),
+        max_size: downloader.max_size(),
+        downloads,
+    };
+    Ok(HtmlTemplate(template).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct QueueDownloadForm {
+    video: String,
+}
+
+/// Queue a video for download, or retry a download that failed.
+pub async fn admin_queue_download(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+    base: BasePath,
+    Form(form): Form<QueueDownloadForm>,
+) -> Result<Response, AppError> {
+    let Some(downloads) = &state.downloads else {
+        return Ok(not_found(layout).await);
+    };
+    let id: VideoId = form.video.trim().parse().map_err(|_| AppError::NotFound)?;
+    let video = state.get_video(&id).await?.ok_or(AppError::NotFound)?;
+    downloads.queue(&video).await?;
+    Ok(Redirect::to(&format!("{}/admin/downloads", base.0)).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct PinDownloadForm {
+    video: VideoId,
+    pinned: bool,
+}
+
+/// Keep a downloaded video when cleaning up, or stop keeping it.
+pub async fn admin_pin_download(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+    base: BasePath,
+    Form(form): Form<PinDownloadForm>,
+) -> Result<Response, AppError> {
+    if state.downloads.is_none() {
+        return Ok(not_found(layout).await);
+    }
+    state.store.pin_download(&form.video, form.pinned).await?;
+    Ok(Redirect::to(&format!("{}/admin/downloads", base.0)).into_response())
+}
+
+/// Report the metrics of the Tokio runtime, to debug refreshes that stall.
+#[cfg(feature = "tokio-console")]
+pub async fn admin_runtime() -> Json<crate::runtime::RuntimeStats> {
+    Json(crate::runtime::RuntimeStats::collect())
+}
+
+/// Report the progress of a refresh job.
+#[utoipa::path(
+    get,
+    path = "/admin/refresh/{id}",
+    params(("id" = u64, Path, description = "The ID of the refresh job")),
+    responses(
+        (status = 200, description = "The state of the job", body = refresh::JobStatus),
+        (status = 404, description = "There is no such job"),
+        (status = 401, description = "Admin credentials or a token are required"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn admin_refresh_status(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<u64>,
+    layout: Layout,
+) -> Response {
+    match state.refresher.job(id) {
+        Some(job) => Json(job.status()).into_response(),
+        None => not_found(layout).await,
+    }
+}
+
+/// Stream the progress of a refresh job as server-sent `progress` events.
+pub async fn admin_refresh_events(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<u64>,
+    layout: Layout,
+) -> Response {
+    match state.refresher.job(id) {
+        Some(job) => progress_events(job, true).into_response(),
+        None => not_found(layout).await,
+    }
+}
+
+/// List the subscriptions to events.
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks",
+    responses(
+        (status = 200, description = "The subscriptions, oldest first", body = [webhooks::WebhookInfo]),
+        (status = 401, description = "Admin credentials or a token are required"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn admin_webhooks(
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<Vec<webhooks::WebhookInfo>>, AppError> {
+    let webhooks = state.store.webhooks().await?;
+    Ok(Json(webhooks.into_iter().map(Into::into).collect()))
+}
+
+/// Subscribe a URL to events.
+///
+/// The response holds the secret the payloads are signed with, which isn't shown again.
+#[utoipa::path(
+    post,
+    path = "/admin/webhooks",
+    request_body = webhooks::Subscription,
+    responses(
+        (status = 201, description = "The new subscription", body = webhooks::WebhookInfo),
+        (status = 400, description = "The URL or an event is invalid"),
+        (status = 401, description = "Admin credentials or a token are required"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn admin_add_webhook(
+    Extension(state): Extension<SharedState>,
+    Json(subscription): Json<webhooks::Subscription>,
+) -> Result<Response, AppError> {
+    if let Err(message) = subscription.validate() {
+        return Ok((StatusCode::BAD_REQUEST, message).into_response());
+    }
+    let webhook = state.webhooks.subscribe(&subscription).await?;
+    Ok((StatusCode::CREATED, Json(webhook)).into_response())
+}
+
+/// Unsubscribe from events.
+#[utoipa::path(
+    delete,
+    path = "/admin/webhooks/{id}",
+    params(("id" = i64, Path, description = "The ID of the subscription")),
+    responses(
+        (status = 204, description = "The subscription was removed"),
+        (status = 404, description = "There is no such subscription"),
+        (status = 401, description = "Admin credentials or a token are required"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn admin_remove_webhook(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<i64>,
+    layout: Layout,
+) -> Result<Response, AppError> {
+    if state.store.remove_webhook(id).await? {
+        Ok(StatusCode::NO_CONTENT.into_response())
+    } else {
+        Ok(not_found(layout).await)
+    }
+}
+
+/// List the latest deliveries to a subscriber, to debug the ones that fail.
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks/{id}/deliveries",
+    params(("id" = i64, Path, description = "The ID of the subscription")),
+    responses(
+        (status = 200, description = "The deliveries of the last days, most recent first", body = [store::Delivery]),
+        (status = 404, description = "There is no such subscription"),
+        (status = 401, description = "Admin credentials or a token are required"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn admin_webhook_deliveries(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<i64>,
+    layout: Layout,
+) -> Result<Response, AppError> {
+    if state.store.webhook(id).await?.is_none() {
+        return Ok(not_found(layout).await);
+    }
+    let deliveries = state.store.deliveries(id, 100).await?;
+    Ok(Json(deliveries).into_response())
+}
+
+/// How far back the detections are listed, unless asked otherwise.
+const DETECTION_DAYS: u32 = 30;
+
+#[derive(Deserialize)]
+pub struct DetectionsQuery {
+    days: Option<u32>,
+}
+
+/// List how often every rule of the detection matched per day, source and platform, to notice
+/// when the detection drifts.
+#[utoipa::path(
+    get,
+    path = "/admin/detections",
+    params(("days" = Option<u32>, Query, description = "How many days back, 30 by default")),
+    responses(
+        (status = 200, description = "The outcomes of the detection, the latest day first", body = [store::DetectionCount]),
+        (status = 401, description = "Admin credentials or a token are required"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn admin_detections(
+    Extension(state): Extension<SharedState>,
+    Query(query): Query<DetectionsQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let days = query.days.unwrap_or(DETECTION_DAYS);
+    Ok(Json(state.store.detections(days).await?))
+}
+
+/// List the blocked stories and domains.
+#[utoipa::path(
+    get,
+    path = "/admin/blocklist",
+    responses(
+        (status = 200, description = "The blocked stories and domains, the latest first", body = [store::Block]),
+        (status = 401, description = "Admin credentials or a token are required"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn admin_blocklist(
+    Extension(state): Extension<SharedState>,
+) -> Result<Json<Vec<store::Block>>, AppError> {
+    Ok(Json(state.blocklist.entries().await?))
+}
+
+/// Block a story or every story linking to a domain, from now on and in the archive.
+#[utoipa::path(
+    post,
+    path = "/admin/blocklist",
+    request_body = blocklist::NewBlock,
+    responses(
+        (status = 201, description = "The new entry", body = store::Block),
+        (status = 400, description = "The kind or the value is invalid"),
+        (status = 409, description = "It is blocked already"),
+        (status = 401, description = "Admin credentials or a token are required"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn admin_add_block(
+    Extension(state): Extension<SharedState>,
+    Json(block): Json<blocklist::NewBlock>,
+) -> Result<Response, AppError> {
+    let value = match block.normalized() {
+        Ok(value) => value,
+        Err(message) => return Ok((StatusCode::BAD_REQUEST, message).into_response()),
+    };
+    match state.block(&block.kind, &value).await? {
+        Some(block) => Ok((StatusCode::CREATED, Json(block)).into_response()),
+        None => Ok(StatusCode::CONFLICT.into_response()),
+    }
+}
+
+/// Lift a block.
+#[utoipa::path(
+    delete,
+    path = "/admin/blocklist/{id}",
+    params(("id" = i64, Path, description = "The ID of the entry")),
+    responses(
+        (status = 204, description = "The block was lifted"),
+        (status = 404, description = "There is no such entry"),
+        (status = 401, description = "Admin credentials or a token are required"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn admin_remove_block(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<i64>,
+    layout: Layout,
+) -> Result<Response, AppError> {
+    if state.unblock(id).await? {
+        Ok(StatusCode::NO_CONTENT.into_response())
+    } else {
+        Ok(not_found(layout).await)
+    }
+}
+
+/// How many cache entries are listed, unless asked otherwise.
+const CACHE_ENTRIES: u32 = 100;
+
+#[derive(Deserialize)]
+pub struct CacheQuery {
+    url: Option<String>,
+    limit: Option<u32>,
+}
+
+/// List the responses in the cache, to debug stale or wrong data.
+#[utoipa::path(
+    get,
+    path = "/admin/cache",
+    params(
+        ("url" = Option<String>, Query, description = "Only the responses of URLs containing this"),
+        ("limit" = Option<u32>, Query, description = "How many, 100 by default"),
+    ),
+    responses(
+        (status = 200, description = "The responses, the latest fetched first", body = [store::CacheEntry]),
+        (status = 401, description = "Admin credentials or a token are required"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn admin_cache(
+    Extension(state): Extension<SharedState>,
+    Query(query): Query<CacheQuery>,
+) -> Result<Json<Vec<store::CacheEntry>>, AppError> {
+    // The responses queued for writing count as cached already.
+    state.cache.flush().await?;
+    let limit = query.limit.unwrap_or(CACHE_ENTRIES);
+    Ok(Json(state.store.cache_entries(query.url, limit).await?))
+}
+
+/// Describe a response in the cache.
+#[utoipa::path(
+    get,
+    path = "/admin/cache/{id}",
+    params(("id" = i64, Path, description = "The ID of the entry")),
+    responses(
+        (status = 200, description = "The entry", body = store::CacheEntry),
+        (status = 404, description = "There is no such entry"),
+        (status = 401, description = "Admin credentials or a token are required"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn admin_cache_entry(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<i64>,
+    layout: Layout,
+) -> Result<Response, AppError> {
+    match state.store.cache_entry(id).await? {
+        Some(entry) => Ok(Json(entry).into_response()),
+        None => Ok(not_found(layout).await),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CachedBodyQuery {
+    url: String,
+}
+
+/// Get the cached body of a URL, as it was fetched.
+#[utoipa::path(
+    get,
+    path = "/admin/cache/body",
+    params(("url" = String, Query, description = "The URL the response was fetched from")),
+    responses(
+        (status = 200, description = "The body, as JSON when it is", content_type = "application/json"),
+        (status = 404, description = "Nothing is cached for the URL"),
+        (status = 401, description = "Admin credentials or a token are required"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn admin_cached_body(
+    Extension(state): Extension<SharedState>,
+    Query(query): Query<CachedBodyQuery>,
+    layout: Layout,
+) -> Result<Response, AppError> {
+    state.cache.flush().await?;
+    let Some(body) = state.cache.get(&query.url).await? else {
+        return Ok(not_found(layout).await);
+    };
+    let content_type = match serde_json::from_str::<serde::de::IgnoredAny>(&body) {
+        Ok(_) => "application/json",
+        Err(_) => "text/plain; charset=utf-8",
+    };
+    Ok(([(header::CONTENT_TYPE, content_type)], body).into_response())
+}
+
+/// Block a story or a domain from the admin page.
+pub async fn admin_block_form(
+    Extension(state): Extension<SharedState>,
+    base: BasePath,
+    Form(block): Form<blocklist::NewBlock>,
+) -> Result<Response, AppError> {
+    let value = match block.normalized() {
+        Ok(value) => value,
+        Err(message) => return Ok((StatusCode::BAD_REQUEST, message).into_response()),
+    };
+    state.block(&block.kind, &value).await?;
+    Ok(Redirect::to(&format!("{}/admin", base.0)).into_response())
+}
+
+/// Lift a block from the admin page.
+pub async fn admin_unblock_form(
+    Extension(state): Extension<SharedState>,
+    base: BasePath,
+    Path(id): Path<i64>,
+) -> Result<Redirect, AppError> {
+    state.unblock(id).await?;
+    Ok(Redirect::to(&format!("{}/admin", base.0)))
+}
+
+/// Stream the progress of the latest refresh to visitors waiting for the video list.
+///
+/// Unlike the admin events, these leave out why a refresh failed.
+pub async fn refresh_events(Extension(state): Extension<SharedState>, layout: Layout) -> Response {
+    match state.refresher.latest() {
+        Some(job) => progress_events(job, false).into_response(),
+        None => not_found(layout).await,
+    }
+}
+
+fn progress_events(
+    job: Arc<refresh::Job>,
+    with_errors: bool,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events = job.updates().map(move |mut status| {
+        if !with_errors {
+            status.error = None;
+        }
+        Event::default().event("progress").json_data(status)
+    });
+    // Serializing the status can't fail, so there is nothing to report.
+    Sse::new(events.filter_map(|event| async move { event.ok().map(Ok) }))
+        .keep_alive(KeepAlive::default())
+}
+
+#[derive(Template)]
+#[template(path = "admin.html")]
+struct AdminTemplate {
+    layout: Layout,
+    last_refresh: Option<String>,
+    /// The most recent refresh job.
+    refresh: Option<refresh::JobStatus>,
+    /// When the next scheduled refresh is due, if any.
+    next_refresh: Option<String>,
+    schedule: scheduler::SchedulerStats,
+    cache: cache::CacheStats,
+    stats: hacker_news::Stats,
+    /// Whether videos can be downloaded, which links the downloads page.
+    downloads: bool,
+    /// The live visitor sessions.
+    sessions: u64,
+    /// The requests answered with a 503 for being beyond the concurrency limits.
+    shed: u64,
+    /// The outcomes of the detection over the last days, the latest first.
+    detections: Vec<DetectionDay>,
+    /// The experimental behaviors, with whether they are enabled.
+    features: Vec<(features::Feature, bool)>,
+    /// The blocked stories and domains, the latest first.
+    blocks: Vec<store::Block>,
+}
+
+/// The days of detections shown on the admin page.
+const ADMIN_DETECTION_DAYS: u32 = 7;
+
+/// The outcomes of the detection on a day, over every source and platform.
+#[derive(Default)]
+struct DetectionDay {
+    day: String,
+    youtube: i64,
+    video_tag: i64,
+    rejected: i64,
+}
+
+struct DownloadRow {
+    download: store::Download,
+    /// When the state of the download last changed.
+    updated: String,
+    /// The size of the downloaded file.
+    size: Option<u64>,
+}
+
+#[derive(Template)]
+#[template(path = "downloads.html")]
+struct DownloadsTemplate {
+    layout: Layout,
+    downloads: Vec<DownloadRow>,
+    /// The bytes the downloaded videos take.
+    used: u64,
+    max_size: Option<u64>,
+}
@@ -0,0 +1,204 @@
+/// The JSON API, and its OpenAPI document.
+use askama::Template;
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use utoipa::{
+    openapi::{
+        security::{Http, HttpAuthScheme, SecurityScheme},
+        Server,
+    },
+    Modify, OpenApi,
+};
+
+use super::{AppError, BasePath, HtmlTemplate};
+use crate::{
+    blocklist, refresh,
+    state::SharedState,
+    store, submissions, version,
+    video::{Repost, Video, VideoId},
+    webhooks,
+};
+
+/// The OpenAPI description of the JSON endpoints, generated from the handlers.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "hnv"),
+    paths(
+        api_videos,
+        api_video_history,
+        api_submit_video,
+        api_refresh_video,
+        api_version,
+        super::admin::admin_refresh,
+        super::admin::admin_refresh_status,
+        super::admin::admin_webhooks,
+        super::admin::admin_add_webhook,
+        super::admin::admin_remove_webhook,
+        super::admin::admin_webhook_deliveries,
+        super::admin::admin_detections,
+        super::admin::admin_blocklist,
+        super::admin::admin_add_block,
+        super::admin::admin_remove_block,
+        super::admin::admin_cache,
+        super::admin::admin_cache_entry,
+        super::admin::admin_cached_body,
+    ),
+    components(schemas(
+        Video,
+        Repost,
+        submissions::Submission,
+        refresh::JobStatus,
+        webhooks::Subscription,
+        webhooks::WebhookInfo,
+        store::Delivery,
+        store::ScorePoint,
+        store::DetectionCount,
+        store::Block,
+        store::CacheEntry,
+        blocklist::NewBlock,
+        version::Build
+    )),
+    modifiers(&BearerAuth)
+)]
+struct ApiDoc;
+
+/// Declares the bearer tokens of the `[api]` configuration.
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+    }
+}
+
+/// Tell which build is running.
+#[utoipa::path(
+    get,
+    path = "/version",
+    responses(
+        (status = 200, description = "The version, commit and time of the build", body = version::Build),
+    )
+)]
+pub async fn api_version() -> impl IntoResponse {
+    Json(version::BUILD)
+}
+
+/// Serve the OpenAPI document of the JSON API.
+pub async fn api_spec(Extension(state): Extension<SharedState>) -> impl IntoResponse {
+    let mut spec = ApiDoc::openapi();
+    spec.servers = Some(vec![Server::new(&state.config.public_url)]);
+    Json(spec)
+}
+
+/// The policy of the API documentation, which loads Swagger UI from unpkg and starts it inline.
+const API_DOCS_POLICY: &str = "default-src 'self'; script-src https://unpkg.com 'unsafe-inline'; \
+    style-src https://unpkg.com; img-src 'self' data:; frame-ancestors 'none'";
+
+/// Browse the API documentation in Swagger UI.
+pub async fn api_docs(base: BasePath) -> impl IntoResponse {
+    (
+        [(header::CONTENT_SECURITY_POLICY, API_DOCS_POLICY)],
+        HtmlTemplate(SwaggerTemplate {
+            spec_url: format!("{}/api/v1/openapi.json", base.0),
+        }),
+    )
+}
+
+/// List the current videos as JSON.
+#[utoipa::path(
+    get,
+    path = "/api/v1/videos",
+    responses(
+        (status = 200, description = "The videos currently on the front page", body = [Video]),
+        (status = 401, description = "A token is required on private instances"),
+    ),
+    security((), ("bearer" = []))
+)]
+pub async fn api_videos(Extension(state): Extension<SharedState>) -> Json<Vec<Video>> {
+    Json(state.videos().to_vec())
+}
+
+/// List the scores sampled for a video by the refreshes, to chart how it climbed.
+#[utoipa::path(
+    get,
+    path = "/api/v1/videos/{id}/history",
+    params(("id" = String, Path, description = "The ID of the video, e.g. `1` or `lobsters-abc123`")),
+    responses(
+        (status = 200, description = "The sampled scores, the earliest first", body = [store::ScorePoint]),
+        (status = 404, description = "The video was never listed"),
+        (status = 401, description = "A token is required on private instances"),
+    ),
+    security((), ("bearer" = []))
+)]
+pub async fn api_video_history(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<VideoId>,
+) -> Result<Json<Vec<store::ScorePoint>>, AppError> {
+    if state.store.listed_video(&id).await?.is_none() {
+        return Err(AppError::NotFound);
+    }
+    Ok(Json(state.store.score_history(&id).await?))
+}
+
+/// Add a video that no source lists, e.g. one that never made it to Hacker News.
+#[utoipa::path(
+    post,
+    path = "/api/v1/videos",
+    request_body = submissions::Submission,
+    responses(
+        (status = 201, description = "The video was added", body = Video),
+        (status = 400, description = "The URL is not a video"),
+        (status = 401, description = "Admin credentials or a token are required"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn api_submit_video(
+    Extension(state): Extension<SharedState>,
+    Json(submission): Json<submissions::Submission>,
+) -> Result<Response, AppError> {
+    if let Err(message) = submission.validate() {
+        return Ok((StatusCode::BAD_REQUEST, message).into_response());
+    }
+    let video = state.submit(&submission).await?;
+    Ok((StatusCode::CREATED, Json(video)).into_response())
+}
+
+/// Fetch a story again and update the published videos with it, e.g. when its title was edited.
+#[utoipa::path(
+    post,
+    path = "/api/v1/refresh/{id}",
+    params(("id" = i32, Path, description = "The ID of the item on Hacker News")),
+    responses(
+        (status = 200, description = "The video as fetched again", body = Video),
+        (status = 404, description = "The item doesn't exist on Hacker News or isn't a video"),
+        (status = 401, description = "Admin credentials or a token are required"),
+        (status = 502, description = "Hacker News could not be reached"),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn api_refresh_video(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<VideoId>,
+) -> Result<Json<Video>, AppError> {
+    // Only Hacker News items are fetched one by one, the other sources list theirs at once.
+    let id = id.hacker_news_id().ok_or(AppError::NotFound)?;
+    let video = state
+        .refetch_video(id)
+        .await
+        .map_err(AppError::UpstreamUnavailable)?;
+    video.map(Json).ok_or(AppError::NotFound)
+}
+
+#[derive(Template)]
+#[template(path = "swagger.html")]
+struct SwaggerTemplate {
+    spec_url: String,
+}
@@ -0,0 +1,376 @@
+/// Browsing the archive: by month, week and day, the most popular videos, its statistics and the
+/// leaderboard of channels.
+use std::sync::Arc;
+
+use askama::Template;
+use axum::{
+    extract::{Path, Query},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use serde::Deserialize;
+use time::{format_description::well_known::Rfc2822, OffsetDateTime};
+
+use super::{AppError, HtmlTemplate, Layout, XmlTemplate};
+use crate::{config, i18n::Locale, state::SharedState, stats, store, video::Video};
+
+/// How far back the popular page counts clicks.
+pub const POPULAR_DAYS: u32 = 7;
+
+/// List the videos clicked most often this week.
+pub async fn popular(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+) -> Result<impl IntoResponse, AppError> {
+    let mut videos = Vec::new();
+    for (id, clicks) in state.store.most_clicked(POPULAR_DAYS, 30).await? {
+        if let Some(video) = state.get_video(&id).await? {
+            let video = video.with_frontend(state.frontend(&layout));
+            videos.push((video, clicks));
+        }
+    }
+
+    Ok(HtmlTemplate(PopularTemplate { layout, videos }))
+}
+
+/// List the months of the archive, with the number of videos first listed in each.
+pub async fn archive(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+) -> Result<impl IntoResponse, AppError> {
+    let months = state.store.archive_months(layout.time_zone).await?;
+    let (year, week, _) = layout.time_zone.today().to_iso_week_date();
+    Ok(HtmlTemplate(ArchiveTemplate {
+        layout,
+        months,
+        this_week: (year, week),
+    }))
+}
+
+/// List the videos first listed in a month, e.g. `/archive/2024/05`.
+pub async fn archive_month(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+    Path((year, month)): Path<(i32, u8)>,
+) -> Result<impl IntoResponse, AppError> {
+    let (start, end) = month_bounds(year, month, layout.time_zone).ok_or(AppError::NotFound)?;
+    let mut videos = Vec::new();
+    for listed in state.store.videos_between(start, end).await? {
+        let day = layout
+            .time_zone
+            .date(listed.first_seen)
+            .map(|date| date.to_string())
+            .unwrap_or_default();
+        if let Some(video) = state.recorded_video(listed) {
+            videos.push((video.with_frontend(state.frontend(&layout)), day));
+        }
+    }
+
+    // The neighbouring months with videos, as the archive lists them from the most recent.
+    let months = state.store.archive_months(layout.time_zone).await?;
+    let at = months.partition_point(|m| (m.year, m.month) > (year, month));
+    let newer = months[..at].last().map(|m| (m.year, m.month));
+    let older = months[at..]
+        .iter()
+        .find(|m| (m.year, m.month) < (year, month))
+        .map(|m| (m.year, m.month));
+
+    Ok(HtmlTemplate(ArchiveMonthTemplate {
+        layout,
+        year,
+        month,
+        videos,
+        newer,
+        older,
+    }))
+}
+
+/// Show the statistics of the archive, as last aggregated.
+pub async fn archive_stats(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+) -> impl IntoResponse {
+    let stats = state.stats.read().unwrap().clone();
+    HtmlTemplate(StatsTemplate {
+        layout,
+        stats,
+        chart_height: stats::CHART_HEIGHT,
+    })
+}
+
+/// Rank the YouTube channels by the videos of theirs that surfaced over a period, as of the
+/// last aggregation of the statistics.
+pub async fn channels(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+    Query(query): Query<ChannelsQuery>,
+) -> impl IntoResponse {
+    let stats = state.stats.read().unwrap().clone();
+    let since = match query.period.days() {
+        Some(days) => OffsetDateTime::now_utc().unix_timestamp() - days * 24 * 60 * 60,
+        None => i64::MIN,
+    };
+    let channels = stats.map(|stats| {
+        let mut channels = stats.leaderboard(since);
+        channels.truncate(LEADERBOARD_CHANNELS);
+        channels
+    });
+    HtmlTemplate(ChannelsTemplate {
+        layout,
+        period: query.period,
+        channels,
+    })
+}
+
+/// The most channels the leaderboard ranks.
+const LEADERBOARD_CHANNELS: usize = 50;
+
+#[derive(Deserialize)]
+pub struct ChannelsQuery {
+    #[serde(default)]
+    period: Period,
+}
+
+/// The period the channel leaderboard covers, up to now.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Period {
+    Week,
+    Month,
+    Year,
+    #[default]
+    All,
+}
+
+impl Period {
+    /// The length of the period in days, or none for all time.
+    fn days(self) -> Option<i64> {
+        match self {
+            Self::Week => Some(7),
+            Self::Month => Some(30),
+            Self::Year => Some(365),
+            Self::All => None,
+        }
+    }
+
+    /// The value of the `period` parameter.
+    fn param(self) -> &'static str {
+        match self {
+            Self::Week => "week",
+            Self::Month => "month",
+            Self::Year => "year",
+            Self::All => "all",
+        }
+    }
+}
+
+/// The start and the end of a month in a time zone, in seconds since the Unix epoch.
+fn month_bounds(year: i32, month: u8, time_zone: config::TimeZone) -> Option<(i64, i64)> {
+    let month = time::Month::try_from(month).ok()?;
+    let start = time::Date::from_calendar_date(year, month, 1).ok()?;
+    let end = match month {
+        time::Month::December => time::Date::from_calendar_date(year + 1, time::Month::January, 1),
+        month => time::Date::from_calendar_date(year, month.next(), 1),
+    }
+    .ok()?;
+    Some((time_zone.midnight(start), time_zone.midnight(end)))
+}
+
+/// List the videos submitted yesterday in the visitor's time zone, the highest scoring first, for
+/// catching up on what the live list already moved past.
+pub async fn yesterday(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+) -> Result<impl IntoResponse, AppError> {
+    let day = layout.time_zone.today() - time::Duration::days(1);
+    let start = layout.time_zone.midnight(day);
+    let end = layout.time_zone.midnight(day + time::Duration::days(1));
+    let videos = state
+        .store
+        .videos_submitted_between(start, end)
+        .await?
+        .into_iter()
+        .filter_map(|listed| state.recorded_video(listed))
+        .map(|video| video.with_frontend(state.frontend(&layout)))
+        .collect();
+
+    Ok(HtmlTemplate(YesterdayTemplate {
+        layout,
+        day,
+        videos,
+    }))
+}
+
+/// How many of the past weeks the feed of the weeks has.
+const FEED_WEEKS: i64 = 12;
+
+/// List the videos that scored highest in an ISO week, e.g. `/week/2024/5`.
+pub async fn week(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+    Path((year, week)): Path<(i32, u8)>,
+) -> Result<impl IntoResponse, AppError> {
+    let monday = week_start(year, week).ok_or(AppError::NotFound)?;
+    let videos = state
+        .week_videos(monday, layout.time_zone)
+        .await?
+        .into_iter()
+        .map(|(video, score)| (video.with_frontend(state.frontend(&layout)), score))
+        .collect();
+
+    let this_week = week_start_of(layout.time_zone.today());
+    let iso_week = |monday: time::Date| {
+        let (year, week, _) = monday.to_iso_week_date();
+        (year, week)
+    };
+    Ok(HtmlTemplate(WeekTemplate {
+        layout,
+        year,
+        week,
+        days: (monday, monday + time::Duration::days(6)),
+        videos,
+        newer: Some(monday + time::Duration::weeks(1))
+            .filter(|newer| *newer <= this_week)
+            .map(iso_week),
+        older: Some(iso_week(monday - time::Duration::weeks(1))),
+    }))
+}
+
+/// Subscribe to the top videos of every past week, one entry per week.
+pub async fn weeks_feed(Extension(state): Extension<SharedState>) -> Result<Response, AppError> {
+    let locale = Locale::default();
+    let time_zone = state.config.timezone;
+    let this_week = week_start_of(time_zone.today());
+    let mut weeks = Vec::new();
+    for ago in 1..=FEED_WEEKS {
+        let monday = this_week - time::Duration::weeks(ago);
+        let videos = state.week_videos(monday, time_zone).await?;
+        if videos.is_empty() {
+            continue;
+        }
+        let (year, week, _) = monday.to_iso_week_date();
+        weeks.push(WeekItem {
+            title: format!("{} {:04}-W{:02}", locale.message("week-title"), year, week),
+            link: state
+                .config
+                .absolute_url(&format!("/week/{}/{}", year, week)),
+            published: OffsetDateTime::from_unix_timestamp(
+                time_zone.midnight(monday + time::Duration::weeks(1)),
+            )
+            .ok()
+            .and_then(|time| time.format(&Rfc2822).ok())
+            .unwrap_or_default(),
+            videos: videos.into_iter().map(|(video, _)| video).collect(),
+        });
+    }
+
+    Ok(XmlTemplate(WeeksFeedTemplate {
+        title: format!(
+            "{} - {}",
+            locale.message("site-title"),
+            locale.message("week-title")
+        ),
+        link: state.config.absolute_url("/archive"),
+        weeks,
+    })
+    .into_response())
+}
+
+/// The Monday starting an ISO week, if there is such a week.
+fn week_start(year: i32, week: u8) -> Option<time::Date> {
+    time::Date::from_iso_week_date(year, week, time::Weekday::Monday).ok()
+}
+
+/// The Monday starting the ISO week of a day.
+pub fn week_start_of(day: time::Date) -> time::Date {
+    day - time::Duration::days(day.weekday().number_days_from_monday().into())
+}
+
+#[derive(Template)]
+#[template(path = "yesterday.html")]
+struct YesterdayTemplate {
+    layout: Layout,
+    day: time::Date,
+    videos: Vec<Video>,
+}
+
+#[derive(Template)]
+#[template(path = "week.html")]
+struct WeekTemplate {
+    layout: Layout,
+    year: i32,
+    week: u8,
+    /// The Monday and the Sunday of the week.
+    days: (time::Date, time::Date),
+    /// The videos that scored highest in the week, with the highest score each reached.
+    videos: Vec<(Video, i64)>,
+    /// The ISO year and week of the next week, unless it is still to come.
+    newer: Option<(i32, u8)>,
+    older: Option<(i32, u8)>,
+}
+
+struct WeekItem {
+    title: String,
+    /// The absolute URL of the page of the week.
+    link: String,
+    /// When the week ended, in RFC 2822 format.
+    published: String,
+    /// The videos that scored highest in the week.
+    videos: Vec<Video>,
+}
+
+#[derive(Template)]
+#[template(path = "weeks.xml")]
+struct WeeksFeedTemplate {
+    title: String,
+    link: String,
+    weeks: Vec<WeekItem>,
+}
+
+#[derive(Template)]
+#[template(path = "archive.html")]
+struct ArchiveTemplate {
+    layout: Layout,
+    months: Vec<store::ArchiveMonth>,
+    /// The ISO year and week of today.
+    this_week: (i32, u8),
+}
+
+#[derive(Template)]
+#[template(path = "archive_month.html")]
+struct ArchiveMonthTemplate {
+    layout: Layout,
+    year: i32,
+    month: u8,
+    /// The videos with the day they were first listed, the most recent first.
+    videos: Vec<(Video, String)>,
+    /// The closest months with videos, as years and months.
+    newer: Option<(i32, u8)>,
+    older: Option<(i32, u8)>,
+}
+
+#[derive(Template)]
+#[template(path = "stats.html")]
+struct StatsTemplate {
+    layout: Layout,
+    /// Missing until the first aggregation is done.
+    stats: Option<Arc<stats::ArchiveStats>>,
+    chart_height: u32,
+}
+
+#[derive(Template)]
+#[template(path = "channels.html")]
+struct ChannelsTemplate {
+    layout: Layout,
+    period: Period,
+    /// Missing until the first aggregation is done.
+    channels: Option<Vec<stats::Channel>>,
+}
+
+#[derive(Template)]
+#[template(path = "popular.html")]
+struct PopularTemplate {
+    layout: Layout,
+    /// The videos with their number of clicks, most clicked first.
+    videos: Vec<(Video, u64)>,
+}
@@ -0,0 +1,418 @@
+/// The feeds of the videos, and the files crawlers are pointed at.
+use std::collections::{BTreeSet, HashSet};
+
+use askama::Template;
+use axum::{
+    extract::{Form, Path},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Extension,
+};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+use time::{format_description::well_known::Rfc2822, OffsetDateTime};
+
+use super::{
+    archive::week_start_of, not_found, AppError, BasePath, HtmlTemplate, Layout, XmlTemplate,
+};
+use crate::{
+    i18n::Locale,
+    session::{self, Session},
+    state::{SharedState, SourceLink, State},
+    store, tag_push,
+    video::Video,
+};
+
+/// Serve the configured robots.txt, defaulting to allowing everything and pointing at the sitemap.
+pub async fn robots_txt(Extension(state): Extension<SharedState>) -> impl IntoResponse {
+    let body = match &state.config.robots_txt {
+        Some(body) => body.clone(),
+        None => format!(
+            "User-agent: *\nAllow: /\n\nSitemap: {}\n",
+            state.config.absolute_url("/sitemap.xml")
+        ),
+    };
+
+    ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body)
+}
+
+/// List the pages of this instance for search engines.
+pub async fn sitemap_xml(
+    Extension(state): Extension<SharedState>,
+) -> Result<impl IntoResponse, AppError> {
+    Ok(XmlTemplate(archive_sitemap(&state).await?))
+}
+
+/// The sitemap of the index, the archive and its months and weeks, and the watch and tag pages of
+/// every recorded video, so videos stay indexed once they left the front page.
+async fn archive_sitemap(state: &State) -> anyhow::Result<SitemapTemplate> {
+    let url = |loc: &str, changefreq| SitemapUrl {
+        loc: state.config.absolute_url(loc),
+        changefreq,
+    };
+    let mut urls = vec![url("/", "hourly"), url("/archive", "daily")];
+
+    let time_zone = state.config.timezone;
+    for month in state.store.archive_months(time_zone).await? {
+        let path = format!("/archive/{:04}/{:02}", month.year, month.month);
+        urls.push(url(&path, "weekly"));
+    }
+
+    let archived = state.store.all_videos().await?;
+    let weeks: BTreeSet<_> = archived
+        .iter()
+        .filter_map(|listed| time_zone.date(listed.first_seen))
+        .map(|day| week_start_of(day).to_iso_week_date())
+        .map(|(year, week, _)| (year, week))
+        .collect();
+    for (year, week) in weeks.into_iter().rev() {
+        urls.push(url(&format!("/week/{}/{}", year, week), "weekly"));
+    }
+
+    // The current videos come first, some may not be recorded yet.
+    let mut seen = HashSet::new();
+    let mut tags = BTreeSet::new();
+    let archived = archived
+        .into_iter()
+        .rev()
+        .filter_map(|listed| state.recorded_video(listed));
+    for video in state.videos().iter().cloned().chain(archived) {
+        if seen.insert(video.id.clone()) {
+            tags.extend(video.tags());
+            urls.push(url(&video.watch_link, "daily"));
+        }
+    }
+
+    // Tags have no page of their own, their search is it.
+    for tag in tags {
+        let mut loc = reqwest::Url::parse(&state.config.absolute_url("/search"))?;
+        loc.query_pairs_mut()
+            .append_pair("q", &format!("tag:{}", tag));
+        urls.push(SitemapUrl {
+            loc: loc.into(),
+            changefreq: "daily",
+        });
+    }
+
+    Ok(SitemapTemplate { urls })
+}
+
+/// The sitemap of the index and the watch pages of the current videos, the pages of a static
+/// export.
+pub fn sitemap(state: &State) -> SitemapTemplate {
+    let mut urls = vec![SitemapUrl {
+        loc: state.config.absolute_url("/"),
+        changefreq: "hourly",
+    }];
+
+    // Watch pages of the videos currently on the front page.
+    for video in state.videos().iter() {
+        urls.push(SitemapUrl {
+            loc: state.config.absolute_url(&video.watch_link),
+            changefreq: "daily",
+        });
+    }
+
+    SitemapTemplate { urls }
+}
+
+/// Subscribe to all current videos.
+pub async fn feed(
+    Extension(state): Extension<SharedState>,
+    method: Method,
+    headers: HeaderMap,
+) -> Response {
+    published_feed(&state, &method, &headers, None, |_| true)
+}
+
+/// Subscribe to the videos of one platform, e.g. `/feed/youtube.xml`.
+pub async fn platform_feed(
+    Extension(state): Extension<SharedState>,
+    Path(file): Path<String>,
+    layout: Layout,
+    method: Method,
+    headers: HeaderMap,
+) -> Response {
+    let Some(platform) = file.strip_suffix(".xml") else {
+        return not_found(layout).await;
+    };
+    published_feed(&state, &method, &headers, Some(platform), |video| {
+        video.platform() == platform
+    })
+}
+
+/// Subscribe to the videos with a tag in their title, e.g. `/feed/tag/gamedev.xml` for titles
+/// containing `[gamedev]`.
+pub async fn tag_feed(
+    Extension(state): Extension<SharedState>,
+    Path(file): Path<String>,
+    layout: Layout,
+    method: Method,
+    headers: HeaderMap,
+) -> Response {
+    let Some(tag) = file.strip_suffix(".xml") else {
+        return not_found(layout).await;
+    };
+    let tag = tag.to_lowercase();
+    published_feed(&state, &method, &headers, Some(&tag), |video| {
+        video.tags().contains(&tag)
+    })
+}
+
+#[derive(Deserialize)]
+pub struct FeedForm {
+    /// The name of the source to narrow the feed to, or nothing for all of them.
+    source: String,
+    /// The tags to narrow the feed to, separated by spaces or commas.
+    tags: String,
+    /// The ntfy topic to push the new videos with the tags to, if the instance offers it.
+    #[serde(default)]
+    push_topic: String,
+}
+
+/// Show the private feed of the visitor and set up what it lists.
+pub async fn my_feed(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+    session: Session,
+) -> Result<impl IntoResponse, AppError> {
+    let feed = state.store.feed(session.id()).await?;
+    let url = feed.as_ref().map(|feed| {
+        state
+            .config
+            .absolute_url(&format!("/feed/u/{}.xml", feed.token))
+    });
+
+    Ok(HtmlTemplate(MyFeedTemplate {
+        layout,
+        sources: state.source_links(),
+        feed,
+        url,
+        push_server: state
+            .config
+            .tag_push
+            .as_ref()
+            .map(|tag_push| tag_push.server_url.clone()),
+    }))
+}
+
+/// Set what the private feed of the visitor lists, making it the first time.
+pub async fn save_my_feed(
+    Extension(state): Extension<SharedState>,
+    session: Session,
+    jar: CookieJar,
+    base: BasePath,
+    Form(form): Form<FeedForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let source = Some(form.source.as_str()).filter(|name| state.source(name).is_some());
+    let tags: Vec<String> = form
+        .tags
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .map(|tag| tag.trim_matches(['[', ']']).to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    // Topics are only pushed to on instances that offer it, and anything else is left out.
+    let push_topic = Some(form.push_topic.trim())
+        .filter(|topic| state.config.tag_push.is_some() && tag_push::valid_topic(topic));
+
+    let jar = session.persist(&state.sessions, jar).await?;
+    state
+        .store
+        .save_feed(session.id(), source, &tags, push_topic)
+        .await?;
+    Ok((jar, Redirect::to(&format!("{}/feed/mine", base.0))))
+}
+
+/// Subscribe to the current videos as a visitor set up their feed, e.g. `/feed/u/0123abcd.xml`.
+/// The videos they hid are left out.
+pub async fn user_feed(
+    Extension(state): Extension<SharedState>,
+    Path(file): Path<String>,
+    layout: Layout,
+) -> Result<Response, AppError> {
+    let Some(token) = file.strip_suffix(".xml") else {
+        return Ok(not_found(layout).await);
+    };
+    let Some((owner, settings)) = state.store.feed_by_token(token).await? else {
+        return Ok(not_found(layout).await);
+    };
+    // Feed readers keep the session alive, just like visits. The token stands in for the
+    // signature.
+    if !state
+        .store
+        .see_session(&owner, session::SESSION_DAYS, true)
+        .await?
+    {
+        return Ok(not_found(layout).await);
+    }
+
+    let hidden = state.store.hidden(&owner).await?;
+    let slice = Locale::default().message("feed-mine-title");
+    Ok(render_feed(&state, Some(slice), |video| {
+        !hidden.contains(&video.id) && settings.matches(video.source, &video.tags())
+    }))
+}
+
+/// Render a feed of the published videos, which only changes when they are published again.
+///
+/// Feed readers poll with `HEAD` and `If-Modified-Since`, which are answered from the time they
+/// were published without rendering the feed.
+fn published_feed(
+    state: &State,
+    method: &Method,
+    headers: &HeaderMap,
+    slice: Option<&str>,
+    filter: impl Fn(&Video) -> bool,
+) -> Response {
+    let Some(published_at) = state.published_at() else {
+        return render_feed(state, slice, filter);
+    };
+    let last_modified = [(header::LAST_MODIFIED, httpdate::fmt_http_date(published_at))];
+    let fresh = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok())
+        .is_some_and(|since| since >= published_at);
+    if fresh {
+        return (StatusCode::NOT_MODIFIED, last_modified).into_response();
+    }
+    if method == Method::HEAD {
+        let content_type = [(header::CONTENT_TYPE, "application/xml; charset=utf-8")];
+        return (last_modified, content_type).into_response();
+    }
+    (last_modified, render_feed(state, slice, filter)).into_response()
+}
+
+/// Render an RSS feed of the current videos that pass `filter`.
+fn render_feed(state: &State, slice: Option<&str>, filter: impl Fn(&Video) -> bool) -> Response {
+    XmlTemplate(feed_template(state, slice, filter)).into_response()
+}
+
+/// The RSS feed of the current videos that pass `filter`, titled after the `slice` they are.
+pub fn feed_template(
+    state: &State,
+    slice: Option<&str>,
+    filter: impl Fn(&Video) -> bool,
+) -> FeedTemplate {
+    let items = state
+        .videos()
+        .iter()
+        .filter(|video| filter(video))
+        .map(|video| FeedItem {
+            link: state.config.absolute_url(&video.watch_link),
+            video: video.clone(),
+        })
+        .collect();
+
+    let title = Locale::default().message("site-title");
+    FeedTemplate {
+        title: match slice {
+            Some(slice) => format!("{} - {}", title, slice),
+            None => title.to_string(),
+        },
+        link: state.config.absolute_url("/"),
+        items,
+    }
+}
+
+/// Subscribe to the downloaded videos in a podcast app, which downloads them in turn.
+pub async fn podcast(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+) -> Result<Response, AppError> {
+    let Some(downloads) = &state.downloads else {
+        return Ok(not_found(layout).await);
+    };
+
+    let mut items = Vec::new();
+    for download in state.store.finished_downloads().await?.into_iter().rev() {
+        // Skip the files that went missing.
+        let (Some(file), Some(length)) = (&download.file, downloads.size(&download)) else {
+            continue;
+        };
+        let published = OffsetDateTime::from_unix_timestamp(download.updated_at)
+            .ok()
+            .and_then(|time| time.format(&Rfc2822).ok())
+            .unwrap_or_default();
+        items.push(PodcastItem {
+            link: state
+                .config
+                .absolute_url(&format!("/watch/{}", download.id)),
+            enclosure: state.config.absolute_url(&format!("/media/{}", file)),
+            length,
+            mime_type: mime_guess::from_path(file)
+                .first_or_octet_stream()
+                .to_string(),
+            published,
+            download,
+        });
+    }
+
+    let template = PodcastTemplate {
+        title: Locale::default().message("site-title").to_string(),
+        link: state.config.absolute_url("/"),
+        items,
+    };
+    Ok(XmlTemplate(template).into_response())
+}
+
+#[derive(Template)]
+#[template(path = "my_feed.html")]
+struct MyFeedTemplate {
+    layout: Layout,
+    /// The enabled sources, which the feed can be narrowed to.
+    sources: Vec<SourceLink>,
+    /// The settings of the feed, once the visitor made one.
+    feed: Option<store::FeedSettings>,
+    /// The address of the feed to subscribe to.
+    url: Option<String>,
+    /// The ntfy server the new videos with the tags can be pushed through, if the instance
+    /// offers it.
+    push_server: Option<String>,
+}
+
+pub struct FeedItem {
+    /// The absolute URL of the watch page.
+    pub link: String,
+    pub video: Video,
+}
+
+#[derive(Template)]
+#[template(path = "feed.xml")]
+pub struct FeedTemplate {
+    pub title: String,
+    pub link: String,
+    pub items: Vec<FeedItem>,
+}
+
+struct PodcastItem {
+    download: store::Download,
+    /// The absolute URL of the watch page.
+    link: String,
+    /// The absolute URL of the downloaded file.
+    enclosure: String,
+    /// The size of the file in bytes.
+    length: u64,
+    mime_type: String,
+    /// When the video was downloaded, in RFC 2822 format.
+    published: String,
+}
+
+#[derive(Template)]
+#[template(path = "podcast.xml")]
+struct PodcastTemplate {
+    title: String,
+    link: String,
+    items: Vec<PodcastItem>,
+}
+
+struct SitemapUrl {
+    loc: String,
+    changefreq: &'static str,
+}
+
+#[derive(Template)]
+#[template(path = "sitemap.xml")]
+pub struct SitemapTemplate {
+    urls: Vec<SitemapUrl>,
+}
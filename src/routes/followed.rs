@@ -0,0 +1,72 @@
+/// The videos submitted by the followed Hacker News users.
+use askama::Template;
+use axum::{
+    response::{IntoResponse, Response},
+    Extension,
+};
+
+use super::{
+    feeds::{FeedItem, FeedTemplate},
+    AppError, HtmlTemplate, Layout, XmlTemplate,
+};
+use crate::{
+    i18n::Locale,
+    state::{FollowedVideo, SharedState},
+};
+
+/// List the latest video submissions of the followed users, the latest first.
+pub async fn followed(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+) -> Result<impl IntoResponse, AppError> {
+    if state.config.hacker_news.followed.is_empty() {
+        return Err(AppError::NotFound);
+    }
+    let videos = state
+        .followed()
+        .iter()
+        .map(|followed| FollowedVideo {
+            submitter: followed.submitter.clone(),
+            video: followed
+                .video
+                .clone()
+                .with_frontend(state.frontend(&layout)),
+        })
+        .collect();
+
+    Ok(HtmlTemplate(FollowedTemplate { layout, videos }))
+}
+
+/// Subscribe to the video submissions of the followed users.
+pub async fn followed_feed(Extension(state): Extension<SharedState>) -> Result<Response, AppError> {
+    if state.config.hacker_news.followed.is_empty() {
+        return Err(AppError::NotFound);
+    }
+    let items = state
+        .followed()
+        .iter()
+        .map(|followed| FeedItem {
+            link: state.config.absolute_url(&followed.video.watch_link),
+            video: followed.video.clone(),
+        })
+        .collect();
+
+    let locale = Locale::default();
+    Ok(XmlTemplate(FeedTemplate {
+        title: format!(
+            "{} - {}",
+            locale.message("site-title"),
+            locale.message("followed-title")
+        ),
+        link: state.config.absolute_url("/followed"),
+        items,
+    })
+    .into_response())
+}
+
+#[derive(Template)]
+#[template(path = "followed.html")]
+struct FollowedTemplate {
+    layout: Layout,
+    videos: Vec<FollowedVideo>,
+}
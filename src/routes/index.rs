@@ -0,0 +1,337 @@
+/// The index, the list of the videos as of the last refresh.
+use std::collections::HashMap;
+
+use askama::Template;
+use axum::{
+    extract::Query,
+    http::{header, HeaderMap},
+    response::IntoResponse,
+    Extension, Json,
+};
+use axum_extra::extract::CookieJar;
+use axum_macros::debug_handler;
+use serde::{Deserialize, Serialize};
+
+use super::{archive::POPULAR_DAYS, AppError, ETag, HtmlTemplate, Layout};
+use crate::{
+    hacker_news,
+    preferences::{Preferences, View},
+    refresh,
+    session::Session,
+    state::{HistoryEntry, SharedState, SourceLink},
+    video::{Video, VideoId},
+};
+
+#[debug_handler]
+pub async fn root(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+    preferences: Preferences,
+    jar: CookieJar,
+    session: Session,
+    Query(query): Query<IndexQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    // Scripts asking for JSON get the videos of the API, without needing to know its path.
+    if prefers_json(&headers) {
+        let vary = [(header::VARY, "Accept")];
+        return Ok((vary, Json(state.videos().to_vec())).into_response());
+    }
+
+    // Choosing a view remembers it.
+    let view = query.view.unwrap_or(preferences.view);
+    let jar = match query.view {
+        Some(view) => jar.add(view.cookie()),
+        None => jar,
+    };
+
+    // The deeper pages may list top stories the refreshes left for later.
+    let page = query.page.max(1);
+    if page > 1 {
+        state.list_deeper(page * PAGE_SIZE).await;
+    }
+
+    let mut videos: Vec<Video> = state
+        .videos()
+        .iter()
+        .map(|video| video.clone().with_frontend(state.frontend(&layout)))
+        .collect();
+
+    // Only sources that are enabled are filtered by, the parameter ends up in links.
+    let source = query.source.filter(|name| state.source(name).is_some());
+    if let Some(source) = &source {
+        videos.retain(|video| video.source == source);
+    }
+
+    // The clicks are only counted and shown when sorting by them.
+    let clicks: HashMap<VideoId, u64> = match query.sort {
+        Sort::Rank | Sort::Comments => HashMap::new(),
+        Sort::Clicks => {
+            let clicked = state.store.most_clicked(POPULAR_DAYS, u32::MAX).await?;
+            clicked.into_iter().collect()
+        }
+    };
+    match query.sort {
+        Sort::Rank => {}
+        Sort::Clicks => {
+            videos.sort_by_key(|video| std::cmp::Reverse(clicks.get(&video.id).copied()))
+        }
+        Sort::Comments => videos.sort_by_key(|video| std::cmp::Reverse(video.comments)),
+    }
+
+    let saved = state.store.watch_later(session.id()).await?;
+    let hidden = state.store.hidden(session.id()).await?;
+    let loved = state.store.favorites(session.id()).await?;
+    let mut continue_watching = state.history(&session, &layout).await?;
+    continue_watching.retain(|entry| entry.position > 0);
+    continue_watching.truncate(CONTINUE_WATCHING);
+    let loves: HashMap<VideoId, u64> = state
+        .store
+        .most_favorited(u32::MAX)
+        .await?
+        .into_iter()
+        .collect();
+    let hidden_count = videos.iter().filter(|v| hidden.contains(&v.id)).count();
+    if !query.show_hidden {
+        videos.retain(|video| !hidden.contains(&video.id));
+    }
+    let hot = hot_discussions(&videos);
+
+    let deferred = state.hn.has_deferred()
+        && source
+            .as_deref()
+            .is_none_or(|source| source == hacker_news::NAME);
+    // A page past the end, e.g. when the deeper stories held fewer videos than a page, shows
+    // the last one.
+    let page = page.min(videos.len().div_ceil(PAGE_SIZE).max(1));
+    let more = videos.len() > page * PAGE_SIZE || deferred;
+    let videos: Vec<Video> = videos
+        .into_iter()
+        .skip((page - 1) * PAGE_SIZE)
+        .take(PAGE_SIZE)
+        .collect();
+
+    // The page looks different depending on the visitor's preferences, language and saved or
+    // hidden videos, so they are part of the tag and shared caches must not serve one visitor's
+    // page to another.
+    let refresh = state.running_refresh();
+    let progress = refresh.as_ref().map(|job| (job.done, job.total));
+    let etag = ETag::of(&(
+        &videos,
+        &layout,
+        &saved,
+        &hidden,
+        &loved,
+        continue_watching
+            .iter()
+            .map(|entry| (&entry.video.id, entry.position))
+            .collect::<Vec<_>>(),
+        videos.iter().map(|v| loves.get(&v.id)).collect::<Vec<_>>(),
+        query.show_hidden,
+        &source,
+        query.sort,
+        videos.iter().map(|v| clicks.get(&v.id)).collect::<Vec<_>>(),
+        (progress, view, page, more),
+    ))
+    .private();
+    let vary = [(header::VARY, "Cookie, Accept-Language, Accept")];
+    if etag.matches(&headers) {
+        return Ok((jar, vary, etag.not_modified()).into_response());
+    }
+
+    let template = IndexTemplate {
+        layout,
+        refresh,
+        videos,
+        hot,
+        saved,
+        hidden,
+        hidden_count,
+        show_hidden: query.show_hidden,
+        sources: state.source_links(),
+        source,
+        sort: query.sort,
+        clicks,
+        loved,
+        loves,
+        continue_watching,
+        view,
+        page,
+        more,
+    };
+    Ok((jar, vary, etag.attach(HtmlTemplate(template))).into_response())
+}
+
+/// Whether the `Accept` header ranks JSON above HTML. Wildcards alone, as sent by most clients
+/// that don't care, get HTML.
+fn prefers_json(headers: &HeaderMap) -> bool {
+    let mut json = 0.0;
+    let mut html = 0.0;
+    let ranges = headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+    for range in ranges {
+        let mut params = range.split(';').map(str::trim);
+        let media_type = params.next().unwrap_or_default().to_ascii_lowercase();
+        let quality = params
+            .find_map(|param| param.strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        match media_type.as_str() {
+            "application/json" => json = f32::max(json, quality),
+            "text/html" | "application/xhtml+xml" => html = f32::max(html, quality),
+            _ => {}
+        }
+    }
+    json > html
+}
+
+/// The videos with the most comments for their points, among those that drew more comments
+/// than points.
+pub fn hot_discussions(videos: &[Video]) -> Vec<Video> {
+    let mut hot: Vec<Video> = videos
+        .iter()
+        .filter(|video| video.is_hot_discussion())
+        .cloned()
+        .collect();
+    hot.sort_by_key(|video| std::cmp::Reverse(video.comments_per_point()));
+    hot.truncate(HOT_DISCUSSIONS);
+    hot
+}
+
+#[derive(Deserialize)]
+pub struct IndexQuery {
+    /// List the videos the visitor dismissed as well.
+    #[serde(default)]
+    show_hidden: bool,
+    /// Only list the videos found on the source with this name.
+    source: Option<String>,
+    #[serde(default)]
+    sort: Sort,
+    /// List the videos this way from now on.
+    view: Option<View>,
+    /// The page of the list, counting from 1.
+    #[serde(default)]
+    page: usize,
+}
+
+/// The orders the index can be listed in.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    /// As ranked by the sources.
+    #[default]
+    Rank,
+    /// The most clicked by the visitors of this instance this week first.
+    Clicks,
+    /// The most commented on their sources first.
+    Comments,
+}
+
+impl Sort {
+    /// The value of the `sort` parameter, which is left out for the default.
+    fn param(self) -> Option<&'static str> {
+        match self {
+            Self::Rank => None,
+            Self::Clicks => Some("clicks"),
+            Self::Comments => Some("comments"),
+        }
+    }
+}
+
+/// How many videos a page of the index lists, as many as the front page of Hacker News.
+pub const PAGE_SIZE: usize = hacker_news::FRONT_PAGE;
+
+/// How many videos the index offers to continue watching.
+const CONTINUE_WATCHING: usize = 5;
+
+/// How many hot discussions the index highlights.
+const HOT_DISCUSSIONS: usize = 3;
+
+#[derive(Template)]
+#[template(path = "index.html")]
+pub struct IndexTemplate {
+    pub layout: Layout,
+    /// The refresh in progress, if any.
+    pub refresh: Option<refresh::JobStatus>,
+    pub videos: Vec<Video>,
+    /// The videos with more comments than points, the most discussed for their score first.
+    pub hot: Vec<Video>,
+    /// The IDs of the videos the visitor saved for later.
+    pub saved: Vec<VideoId>,
+    /// The IDs of the videos the visitor dismissed.
+    pub hidden: Vec<VideoId>,
+    /// How many of the current videos are dismissed.
+    pub hidden_count: usize,
+    pub show_hidden: bool,
+    /// The enabled sources, which the index can be filtered by when there is more than one.
+    pub sources: Vec<SourceLink>,
+    /// The name of the source the index is filtered by, if any.
+    pub source: Option<String>,
+    pub sort: Sort,
+    /// The clicks of the videos this week, when sorted by them.
+    pub clicks: HashMap<VideoId, u64>,
+    /// The IDs of the videos the visitor loves.
+    pub loved: Vec<VideoId>,
+    /// How many visitors love each video.
+    pub loves: HashMap<VideoId, u64>,
+    /// The videos the visitor stopped watching halfway, the last first.
+    pub continue_watching: Vec<HistoryEntry>,
+    pub view: View,
+    /// The page of the list shown, counting from 1.
+    pub page: usize,
+    /// Whether there are more videos after this page.
+    pub more: bool,
+}
+
+impl IndexTemplate {
+    /// A link to the index filtered by another source, or by none.
+    fn source_link(&self, source: Option<&str>) -> String {
+        self.link(source, self.sort, self.show_hidden)
+    }
+
+    /// A link to the index in another order.
+    fn sort_link(&self, sort: Sort) -> String {
+        self.link(self.source.as_deref(), sort, self.show_hidden)
+    }
+
+    /// A link to the index showing the dismissed videos, or not.
+    fn hidden_link(&self, show_hidden: bool) -> String {
+        self.link(self.source.as_deref(), self.sort, show_hidden)
+    }
+
+    /// A link to another page of the list, as filtered and sorted.
+    fn page_link(&self, page: usize) -> String {
+        let link = self.link(self.source.as_deref(), self.sort, self.show_hidden);
+        match page {
+            1 => link,
+            _ => {
+                let separator = if link == "?" { "" } else { "&" };
+                format!("{}{}page={}", link, separator, page)
+            }
+        }
+    }
+
+    /// A link to the index listing the videos another way, which is remembered.
+    fn view_link(&self, view: View) -> String {
+        let link = self.link(self.source.as_deref(), self.sort, self.show_hidden);
+        let separator = if link == "?" { "" } else { "&" };
+        format!("{}{}view={}", link, separator, view)
+    }
+
+    fn link(&self, source: Option<&str>, sort: Sort, show_hidden: bool) -> String {
+        let mut params = Vec::new();
+        if let Some(source) = source {
+            params.push(format!("source={}", source));
+        }
+        if let Some(sort) = sort.param() {
+            params.push(format!("sort={}", sort));
+        }
+        if show_hidden {
+            params.push("show_hidden=true".to_string());
+        }
+        format!("?{}", params.join("&"))
+    }
+}
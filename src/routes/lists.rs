@@ -0,0 +1,283 @@
+/// The lists visitors keep of videos: to watch later, loved, hidden and watched.
+use std::collections::HashMap;
+
+use askama::Template;
+use axum::{
+    extract::{Form, Path, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Extension, Json,
+};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+use tracing::info;
+
+use super::{not_found, redirect_back, AppError, BasePath, HtmlTemplate, Layout};
+use crate::{
+    session::Session,
+    state::{HistoryEntry, SharedState},
+    video::{Video, VideoId},
+    watch_later,
+};
+
+/// List the videos the visitor saved for later.
+pub async fn watch_later(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+    session: Session,
+    Query(import): Query<ImportResult>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut videos = Vec::new();
+    for id in state.store.watch_later(session.id()).await? {
+        if let Some(video) = state.get_video(&id).await? {
+            videos.push(video.with_frontend(state.frontend(&layout)));
+        }
+    }
+
+    Ok(HtmlTemplate(WatchLaterTemplate {
+        layout,
+        videos,
+        import: import.imported.zip(import.unmatched),
+    }))
+}
+
+/// Download the watch-later list, to import it elsewhere.
+pub async fn export_watch_later(
+    Extension(state): Extension<SharedState>,
+    session: Session,
+) -> Result<impl IntoResponse, AppError> {
+    let mut entries = Vec::new();
+    for id in state.store.watch_later(session.id()).await? {
+        if let Some(video) = state.get_video(&id).await? {
+            entries.push(watch_later::Entry {
+                id: Some(id.to_string()),
+                title: Some(video.title),
+                url: video.url,
+            });
+        }
+    }
+    Ok((
+        [(
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"watch-later.json\"",
+        )],
+        Json(entries),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct ImportForm {
+    /// An exported list, a YouTube Takeout CSV or URLs, one per line.
+    list: String,
+}
+
+/// How an import went, shown on the watch-later page after it.
+#[derive(Deserialize)]
+pub struct ImportResult {
+    imported: Option<usize>,
+    unmatched: Option<usize>,
+}
+
+/// Save the videos of an imported list for later, if they were ever listed here.
+pub async fn import_watch_later(
+    Extension(state): Extension<SharedState>,
+    session: Session,
+    jar: CookieJar,
+    base: BasePath,
+    Form(form): Form<ImportForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let jar = session.persist(&state.sessions, jar).await?;
+    let mut known = HashMap::new();
+    for listed in state.store.all_videos().await? {
+        known
+            .entry(crate::video::canonical_id(&listed.url))
+            .or_insert(listed.id);
+    }
+
+    let (mut imported, mut unmatched) = (0, 0);
+    // Saved from the last to the first, so the first ends up on top of the list.
+    for key in watch_later::parse(&form.list).iter().rev() {
+        match known.get(key) {
+            Some(id) => {
+                state.store.add_watch_later(session.id(), id).await?;
+                imported += 1;
+            }
+            None => unmatched += 1,
+        }
+    }
+    let location = format!(
+        "{}/later?imported={}&unmatched={}",
+        base.0, imported, unmatched
+    );
+    Ok((jar, Redirect::to(&location)))
+}
+
+/// Save a video for later.
+pub async fn add_watch_later(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<VideoId>,
+    session: Session,
+    jar: CookieJar,
+    base: BasePath,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let jar = session.persist(&state.sessions, jar).await?;
+    state.store.add_watch_later(session.id(), &id).await?;
+    Ok((jar, redirect_back(&headers, &base)))
+}
+
+/// Remove a video from the watch-later list.
+pub async fn remove_watch_later(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<VideoId>,
+    session: Session,
+    base: BasePath,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    state.store.remove_watch_later(session.id(), &id).await?;
+    Ok(redirect_back(&headers, &base))
+}
+
+/// Save a video to a read-it-later service, e.g. `/save/pocket/123`.
+pub async fn save_to(
+    Extension(state): Extension<SharedState>,
+    Path((service, id)): Path<(String, VideoId)>,
+    layout: Layout,
+    base: BasePath,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let Some(service) = state.read_later.iter().find(|s| s.name() == service) else {
+        return Ok(not_found(layout).await);
+    };
+    let video = state.get_video(&id).await?.ok_or(AppError::NotFound)?;
+    service
+        .save(&video)
+        .await
+        .map_err(AppError::UpstreamUnavailable)?;
+    info!(video = %id, "Saved a video to {}", service.label());
+    Ok(redirect_back(&headers, &base).into_response())
+}
+
+/// Love a video, which counts towards the loved page.
+pub async fn love(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<VideoId>,
+    session: Session,
+    jar: CookieJar,
+    base: BasePath,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let jar = session.persist(&state.sessions, jar).await?;
+    state.store.add_favorite(session.id(), &id).await?;
+    Ok((jar, redirect_back(&headers, &base)))
+}
+
+/// Stop loving a video.
+pub async fn unlove(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<VideoId>,
+    session: Session,
+    base: BasePath,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    state.store.remove_favorite(session.id(), &id).await?;
+    Ok(redirect_back(&headers, &base))
+}
+
+#[derive(Deserialize)]
+pub struct PositionForm {
+    /// Where the visitor is in the video, in seconds, or zero once it was watched to the end.
+    position: u32,
+}
+
+/// Note where the player of the watch page is in a video, so that it can be resumed.
+pub async fn record_position(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<VideoId>,
+    session: Session,
+    jar: CookieJar,
+    Form(form): Form<PositionForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let jar = session.persist(&state.sessions, jar).await?;
+    state
+        .store
+        .record_position(session.id(), &id, form.position)
+        .await?;
+    Ok((jar, StatusCode::NO_CONTENT))
+}
+
+/// List the videos the visitor opened, the last first.
+pub async fn history(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+    session: Session,
+) -> Result<impl IntoResponse, AppError> {
+    let videos = state.history(&session, &layout).await?;
+    Ok(HtmlTemplate(HistoryTemplate { layout, videos }))
+}
+
+/// List the videos loved by the most visitors of this instance.
+pub async fn loved(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+) -> Result<impl IntoResponse, AppError> {
+    let mut videos = Vec::new();
+    for (id, loves) in state.store.most_favorited(30).await? {
+        if let Some(video) = state.get_video(&id).await? {
+            let video = video.with_frontend(state.frontend(&layout));
+            videos.push((video, loves));
+        }
+    }
+
+    Ok(HtmlTemplate(LovedTemplate { layout, videos }))
+}
+
+/// Dismiss a video so it is no longer listed on the index.
+pub async fn hide(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<VideoId>,
+    session: Session,
+    jar: CookieJar,
+    base: BasePath,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let jar = session.persist(&state.sessions, jar).await?;
+    state.store.hide(session.id(), &id).await?;
+    Ok((jar, redirect_back(&headers, &base)))
+}
+
+/// Bring back a dismissed video.
+pub async fn unhide(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<VideoId>,
+    session: Session,
+    base: BasePath,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    state.store.unhide(session.id(), &id).await?;
+    Ok(redirect_back(&headers, &base))
+}
+
+#[derive(Template)]
+#[template(path = "later.html")]
+struct WatchLaterTemplate {
+    layout: Layout,
+    videos: Vec<Video>,
+    /// The videos imported and those not found, after an import.
+    import: Option<(usize, usize)>,
+}
+
+#[derive(Template)]
+#[template(path = "history.html")]
+struct HistoryTemplate {
+    layout: Layout,
+    videos: Vec<HistoryEntry>,
+}
+
+#[derive(Template)]
+#[template(path = "loved.html")]
+struct LovedTemplate {
+    layout: Layout,
+    /// The videos with the number of visitors loving them, most loved first.
+    videos: Vec<(Video, u64)>,
+}
@@ -0,0 +1,247 @@
+/// The playlist of the current videos, and the playlists visitors put together.
+use askama::Template;
+use axum::{
+    extract::{Form, Path},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Redirect, Response},
+    Extension,
+};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+
+use super::{
+    feeds::{FeedItem, FeedTemplate},
+    not_found, redirect_back, AppError, BasePath, HtmlTemplate, Layout, XmlTemplate,
+};
+use crate::{
+    i18n::Locale,
+    session::Session,
+    state::SharedState,
+    store,
+    video::{Video, VideoId},
+};
+
+/// Play the current videos one after another.
+pub async fn playlist(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+) -> impl IntoResponse {
+    let videos = state
+        .videos()
+        .iter()
+        .filter(|video| video.youtube_id().is_some() || video.is_media_file())
+        .cloned()
+        .collect();
+
+    HtmlTemplate(PlaylistTemplate {
+        layout,
+        videos,
+        list: PlaylistPage::Current,
+    })
+}
+
+/// Export the current videos as an M3U playlist for desktop players like mpv or VLC.
+pub async fn playlist_m3u(Extension(state): Extension<SharedState>) -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, "audio/x-mpegurl; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "inline; filename=\"playlist.m3u\"",
+            ),
+        ],
+        m3u(&state.videos()),
+    )
+}
+
+/// Build an M3U playlist of videos.
+pub fn m3u(videos: &[Video]) -> String {
+    let mut m3u = String::from("#EXTM3U\n");
+    for video in videos {
+        // Line breaks would end the entry early.
+        let title = video.title.replace(['\r', '\n'], " ");
+        m3u.push_str(&format!("#EXTINF:-1,{}\n{}\n", title, video.url));
+    }
+    m3u
+}
+
+/// How long the names of playlists can be, in characters.
+const PLAYLIST_NAME_LENGTH: usize = 100;
+
+#[derive(Deserialize)]
+pub struct PlaylistForm {
+    name: String,
+}
+
+#[derive(Deserialize)]
+pub struct PlaylistEditForm {
+    playlist: i64,
+    video: VideoId,
+    edit: store::PlaylistEdit,
+}
+
+/// List the playlists of the visitor.
+pub async fn playlists(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+    session: Session,
+) -> Result<impl IntoResponse, AppError> {
+    let playlists = state.store.playlists(session.id()).await?;
+    Ok(HtmlTemplate(PlaylistsTemplate { layout, playlists }))
+}
+
+/// Create a playlist and open it.
+pub async fn create_playlist(
+    Extension(state): Extension<SharedState>,
+    session: Session,
+    jar: CookieJar,
+    base: BasePath,
+    Form(form): Form<PlaylistForm>,
+) -> Result<Response, AppError> {
+    let name: String = form
+        .name
+        .trim()
+        .chars()
+        .take(PLAYLIST_NAME_LENGTH)
+        .collect();
+    if name.is_empty() {
+        return Ok(Redirect::to(&format!("{}/playlists", base.0)).into_response());
+    }
+    let jar = session.persist(&state.sessions, jar).await?;
+    let id = state.store.create_playlist(session.id(), &name).await?;
+    Ok((jar, Redirect::to(&format!("{}/playlists/{}", base.0, id))).into_response())
+}
+
+/// Play a playlist of the visitor one video after another.
+pub async fn own_playlist(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<i64>,
+    layout: Layout,
+    session: Session,
+) -> Result<Response, AppError> {
+    let Some((playlist, ids)) = state.store.playlist(session.id(), id).await? else {
+        return Ok(not_found(layout).await);
+    };
+    let videos = state.playlist_videos(&ids, &layout).await?;
+    let share_url = state
+        .config
+        .absolute_url(&format!("/shared/{}", playlist.token));
+
+    Ok(HtmlTemplate(PlaylistTemplate {
+        layout,
+        videos,
+        list: PlaylistPage::Own(playlist, share_url),
+    })
+    .into_response())
+}
+
+/// Play a playlist someone shared, without changing it.
+pub async fn shared_playlist(
+    Extension(state): Extension<SharedState>,
+    Path(token): Path<String>,
+    layout: Layout,
+) -> Result<Response, AppError> {
+    let Some((playlist, ids)) = state.store.shared_playlist(&token).await? else {
+        return Ok(not_found(layout).await);
+    };
+    let videos = state.playlist_videos(&ids, &layout).await?;
+
+    Ok(HtmlTemplate(PlaylistTemplate {
+        layout,
+        videos,
+        list: PlaylistPage::Shared(playlist),
+    })
+    .into_response())
+}
+
+/// Subscribe to a playlist someone shared.
+pub async fn shared_playlist_feed(
+    Extension(state): Extension<SharedState>,
+    Path(token): Path<String>,
+) -> Result<Response, AppError> {
+    let (playlist, ids) = state
+        .store
+        .shared_playlist(&token)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let mut items = Vec::new();
+    for id in ids {
+        if let Some(video) = state.get_video(&id).await? {
+            items.push(FeedItem {
+                link: state.config.absolute_url(&video.watch_link),
+                video,
+            });
+        }
+    }
+
+    let title = Locale::default().message("site-title");
+    Ok(XmlTemplate(FeedTemplate {
+        title: format!("{} - {}", title, playlist.name),
+        link: state
+            .config
+            .absolute_url(&format!("/shared/{}", playlist.token)),
+        items,
+    })
+    .into_response())
+}
+
+/// Add, remove or move a video in a playlist of the visitor.
+pub async fn edit_playlist(
+    Extension(state): Extension<SharedState>,
+    session: Session,
+    base: BasePath,
+    headers: HeaderMap,
+    Form(form): Form<PlaylistEditForm>,
+) -> Result<impl IntoResponse, AppError> {
+    if form.edit == store::PlaylistEdit::Add && state.get_video(&form.video).await?.is_none() {
+        return Err(AppError::NotFound);
+    }
+    if !state
+        .store
+        .edit_playlist(session.id(), form.playlist, &form.video, form.edit)
+        .await?
+    {
+        return Err(AppError::NotFound);
+    }
+    Ok(redirect_back(&headers, &base))
+}
+
+/// Delete a playlist of the visitor.
+pub async fn delete_playlist(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<i64>,
+    session: Session,
+    base: BasePath,
+) -> Result<impl IntoResponse, AppError> {
+    if !state.store.delete_playlist(session.id(), id).await? {
+        return Err(AppError::NotFound);
+    }
+    Ok(Redirect::to(&format!("{}/playlists", base.0)))
+}
+
+#[derive(Template)]
+#[template(path = "playlist.html")]
+struct PlaylistTemplate {
+    layout: Layout,
+    /// The videos that can be played in the page.
+    videos: Vec<Video>,
+    /// Which videos are played.
+    list: PlaylistPage,
+}
+
+/// The videos a [`PlaylistTemplate`] plays.
+enum PlaylistPage {
+    /// The current videos.
+    Current,
+    /// A playlist of the visitor, which they can change and share under the URL.
+    Own(store::Playlist, String),
+    /// A playlist someone shared, which can only be played.
+    Shared(store::Playlist),
+}
+
+#[derive(Template)]
+#[template(path = "playlists.html")]
+struct PlaylistsTemplate {
+    layout: Layout,
+    playlists: Vec<store::Playlist>,
+}
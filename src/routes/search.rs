@@ -0,0 +1,207 @@
+/// Searching the archive, and the searches visitors save.
+use askama::Template;
+use axum::{
+    extract::{Form, Path, Query},
+    response::{IntoResponse, Redirect, Response},
+    Extension,
+};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+
+use super::{
+    feeds::{FeedItem, FeedTemplate},
+    AppError, BasePath, HtmlTemplate, Layout, XmlTemplate,
+};
+use crate::{
+    i18n::Locale,
+    search::{self, parse_date},
+    session::Session,
+    state::SharedState,
+    store,
+    video::Video,
+};
+
+/// The most videos a search lists.
+const SEARCH_RESULTS: u32 = 100;
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    q: String,
+    /// The first day to search, as `YYYY-MM-DD`.
+    #[serde(default)]
+    from: String,
+    /// The last day to search, included.
+    #[serde(default)]
+    to: String,
+}
+
+/// Search the archive, optionally between two days, e.g.
+/// `/search?q=demo+platform:youtube+score:>100&from=2023-03-01&to=2023-03-31`. See [`search`]
+/// for the query language.
+pub async fn search(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+    Query(query): Query<SearchQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let from = parse_date(&query.from);
+    let to = parse_date(&query.to);
+    let mut filter = search::parse(&query.q);
+    search::between(&mut filter, from, to);
+    let searched = !filter.is_empty();
+
+    let mut videos = Vec::new();
+    if searched {
+        let found = state
+            .store
+            .search_videos(&filter, layout.time_zone, SEARCH_RESULTS)
+            .await?;
+        for listed in found {
+            let day = layout
+                .time_zone
+                .date(listed.first_seen)
+                .map(|date| date.to_string())
+                .unwrap_or_default();
+            // The platforms are only told apart by the host in the query, which is close enough
+            // to narrow the results down but not to be sure.
+            let Some(video) = state.recorded_video(listed).filter(|video| {
+                filter.platforms.is_empty() || filter.platforms.contains(&video.platform())
+            }) else {
+                continue;
+            };
+            videos.push((video.with_frontend(state.frontend(&layout)), day));
+        }
+    }
+
+    Ok(HtmlTemplate(SearchTemplate {
+        layout,
+        query: query.q,
+        from: from.map(|date| date.to_string()).unwrap_or_default(),
+        to: to.map(|date| date.to_string()).unwrap_or_default(),
+        searched,
+        videos,
+    }))
+}
+
+/// How long saved searches can be, in characters.
+const SAVED_SEARCH_LENGTH: usize = 200;
+
+/// The most matches the feed of a saved search lists.
+const SAVED_SEARCH_MATCHES: u32 = 50;
+
+#[derive(Deserialize)]
+pub struct SavedSearchForm {
+    q: String,
+}
+
+/// List the searches the visitor saved, with the feeds of their matches.
+pub async fn saved_searches(
+    Extension(state): Extension<SharedState>,
+    layout: Layout,
+    session: Session,
+) -> Result<impl IntoResponse, AppError> {
+    let searches = state
+        .store
+        .saved_searches(session.id())
+        .await?
+        .into_iter()
+        .map(|search| {
+            let feed = state
+                .config
+                .absolute_url(&format!("/searches/feed/{}.xml", search.token));
+            (search, feed)
+        })
+        .collect();
+    Ok(HtmlTemplate(SavedSearchesTemplate {
+        layout,
+        searches,
+        push: state.config.tag_push.is_some(),
+    }))
+}
+
+/// Save a search of the visitor, to be alerted to the new videos matching it.
+pub async fn save_search(
+    Extension(state): Extension<SharedState>,
+    session: Session,
+    jar: CookieJar,
+    base: BasePath,
+    Form(form): Form<SavedSearchForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let query: String = form.q.trim().chars().take(SAVED_SEARCH_LENGTH).collect();
+    if search::parse(&query).is_empty() {
+        return Ok((jar, Redirect::to(&format!("{}/searches", base.0))));
+    }
+    let jar = session.persist(&state.sessions, jar).await?;
+    state.store.save_search(session.id(), &query).await?;
+    Ok((jar, Redirect::to(&format!("{}/searches", base.0))))
+}
+
+/// Delete a saved search of the visitor.
+pub async fn delete_saved_search(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<i64>,
+    session: Session,
+    base: BasePath,
+) -> Result<impl IntoResponse, AppError> {
+    if !state.store.delete_saved_search(session.id(), id).await? {
+        return Err(AppError::NotFound);
+    }
+    Ok(Redirect::to(&format!("{}/searches", base.0)))
+}
+
+/// Follow the new videos matching a saved search, e.g. `/searches/feed/0123abcd.xml`.
+pub async fn saved_search_feed(
+    Extension(state): Extension<SharedState>,
+    Path(file): Path<String>,
+) -> Result<Response, AppError> {
+    let token = file.strip_suffix(".xml").ok_or(AppError::NotFound)?;
+    let (search, ids) = state
+        .store
+        .saved_search_matches(token, SAVED_SEARCH_MATCHES)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let mut items = Vec::new();
+    for id in ids {
+        if let Some(video) = state.get_video(&id).await? {
+            items.push(FeedItem {
+                link: state.config.absolute_url(&video.watch_link),
+                video,
+            });
+        }
+    }
+
+    let mut link =
+        reqwest::Url::parse(&state.config.absolute_url("/search")).map_err(anyhow::Error::from)?;
+    link.query_pairs_mut().append_pair("q", &search.query);
+    let title = Locale::default().message("site-title");
+    Ok(XmlTemplate(FeedTemplate {
+        title: format!("{} - {}", title, search.query),
+        link: link.into(),
+        items,
+    })
+    .into_response())
+}
+
+#[derive(Template)]
+#[template(path = "saved_searches.html")]
+struct SavedSearchesTemplate {
+    layout: Layout,
+    /// The saved searches with the addresses of the feeds of their matches.
+    searches: Vec<(store::SavedSearch, String)>,
+    /// Whether matches are pushed to the ntfy topic of the private feed.
+    push: bool,
+}
+
+#[derive(Template)]
+#[template(path = "search.html")]
+struct SearchTemplate {
+    layout: Layout,
+    query: String,
+    /// The days searched between, as given to the date inputs.
+    from: String,
+    to: String,
+    /// Whether anything was searched for, rather than opening the form.
+    searched: bool,
+    /// The videos found with the day they were first listed, the most recent first.
+    videos: Vec<(Video, String)>,
+}
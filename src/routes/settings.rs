@@ -0,0 +1,68 @@
+/// Setting the preferences of visitors, which are kept in cookies.
+use axum::{
+    extract::Form,
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+
+use super::{redirect_back, BasePath};
+use crate::preferences::{self, Theme};
+
+#[derive(Deserialize)]
+pub struct ThemeForm {
+    theme: Theme,
+}
+
+/// Remember the visitor's theme choice and send them back to the page they came from.
+pub async fn set_theme(
+    jar: CookieJar,
+    base: BasePath,
+    headers: HeaderMap,
+    Form(form): Form<ThemeForm>,
+) -> Response {
+    (jar.add(form.theme.cookie()), redirect_back(&headers, &base)).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct PrivacyForm {
+    enabled: bool,
+}
+
+/// Remember whether the visitor wants YouTube links to point at the privacy frontend.
+pub async fn set_privacy(
+    jar: CookieJar,
+    base: BasePath,
+    headers: HeaderMap,
+    Form(form): Form<PrivacyForm>,
+) -> Response {
+    (
+        jar.add(preferences::privacy_cookie(form.enabled)),
+        redirect_back(&headers, &base),
+    )
+        .into_response()
+}
+
+#[derive(Deserialize)]
+pub struct TimeZoneForm {
+    /// An offset from UTC like `+02:00`, or nothing for the time zone of the instance.
+    timezone: String,
+}
+
+/// Remember the time zone the visitor's days start and end in.
+pub async fn set_time_zone(
+    jar: CookieJar,
+    base: BasePath,
+    headers: HeaderMap,
+    Form(form): Form<TimeZoneForm>,
+) -> Response {
+    let jar = match form.timezone.trim() {
+        "" => jar.remove(preferences::time_zone_removal()),
+        time_zone => match time_zone.parse() {
+            Ok(time_zone) => jar.add(preferences::time_zone_cookie(time_zone)),
+            Err(_) => jar,
+        },
+    };
+    (jar, redirect_back(&headers, &base)).into_response()
+}
@@ -0,0 +1,30 @@
+/// The status of the sources.
+use askama::Template;
+use axum::{response::IntoResponse, Extension};
+
+use super::{format_time, HtmlTemplate, Layout};
+use crate::{state::SharedState, status};
+
+/// Show whether Hacker News is answering, and when the videos were last refreshed.
+pub async fn status(Extension(state): Extension<SharedState>, layout: Layout) -> impl IntoResponse {
+    let stats = state.hn.stats();
+    HtmlTemplate(StatusTemplate {
+        layout,
+        hacker_news: state.status.as_ref().map(|monitor| monitor.summary()),
+        last_refresh: stats.last_refresh.map(format_time),
+        refresh_errors: stats.errors,
+        chart_height: status::CHART_HEIGHT,
+    })
+}
+
+#[derive(Template)]
+#[template(path = "status.html")]
+struct StatusTemplate {
+    layout: Layout,
+    /// Missing when the API isn't watched.
+    hacker_news: Option<status::Summary>,
+    last_refresh: Option<String>,
+    /// The items that failed to be fetched in the last refresh.
+    refresh_errors: usize,
+    chart_height: u32,
+}
@@ -0,0 +1,145 @@
+/// The page of a video, and the redirects to the video and its discussion.
+use askama::Template;
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+
+use super::{not_found, AppError, HtmlTemplate, Layout};
+use crate::{
+    oembed,
+    session::Session,
+    state::SharedState,
+    store,
+    video::{Video, VideoId},
+};
+
+/// Show a single video with an embedded player and rich link previews for chat apps.
+pub async fn watch(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<VideoId>,
+    layout: Layout,
+    session: Session,
+) -> Result<Response, AppError> {
+    let Some(video) = state.get_video(&id).await? else {
+        return Ok(not_found(layout).await);
+    };
+
+    // Only visitors who stored something have a history, opening a page doesn't start a session.
+    let position = match session.is_new() {
+        true => 0,
+        false => state.store.record_opened(session.id(), &id).await?,
+    };
+
+    let video = video.with_frontend(state.frontend(&layout));
+    // Looked up after the refreshes, the page doesn't wait for the platform.
+    let oembed = state.hn.cached_oembed(&video.url).await;
+    let (media_file, subtitles) = match &state.downloads {
+        Some(downloads) => match state.store.download(&id).await? {
+            Some(download) => (download.file.clone(), downloads.subtitles(&download)),
+            None => (None, Vec::new()),
+        },
+        None => (None, Vec::new()),
+    };
+    // Playlists only play what the player page can.
+    let playlists = match video.youtube_id().is_some() || video.is_media_file() {
+        true => state.store.playlists(session.id()).await?,
+        false => Vec::new(),
+    };
+    let sparkline = sparkline(&state.store.score_history(&id).await?);
+    let template = WatchTemplate {
+        layout,
+        page_url: state.config.absolute_url(&video.watch_link),
+        video,
+        oembed,
+        media_file,
+        subtitles,
+        position: Some(position),
+        playlists,
+        sparkline,
+    };
+    Ok(HtmlTemplate(template).into_response())
+}
+
+/// The width and height of the score sparkline on the watch page.
+const SPARKLINE_SIZE: (i64, i64) = (100, 20);
+
+/// Lay out the sampled scores of a video as the points of an SVG polyline, once there are
+/// at least two of them to draw a line between.
+fn sparkline(points: &[store::ScorePoint]) -> Option<String> {
+    let (first, last) = (points.first()?, points.last()?);
+    if points.len() < 2 {
+        return None;
+    }
+    let (width, height) = SPARKLINE_SIZE;
+    let span = (last.time - first.time).max(1);
+    let top = points.iter().map(|point| point.score).max()?.max(1);
+    let points: Vec<_> = points
+        .iter()
+        .map(|point| {
+            let x = (point.time - first.time) * width / span;
+            let y = height - point.score.max(0) * height / top;
+            format!("{x},{y}")
+        })
+        .collect();
+    Some(points.join(" "))
+}
+
+/// Send the visitor on to a video, counting the click.
+pub async fn visit(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<VideoId>,
+    layout: Layout,
+    session: Session,
+) -> Result<Response, AppError> {
+    let video = state.get_video(&id).await?.ok_or(AppError::NotFound)?;
+    let video = video.with_frontend(state.frontend(&layout));
+    state.store.record_click(&id).await?;
+    if !session.is_new() {
+        state.store.record_opened(session.id(), &id).await?;
+    }
+    Ok(outbound(&video.link))
+}
+
+/// Send the visitor on to the discussion of a video on its source.
+pub async fn visit_discussion(
+    Extension(state): Extension<SharedState>,
+    Path(id): Path<VideoId>,
+) -> Result<Response, AppError> {
+    let source = state.video_source(&id.source).ok_or(AppError::NotFound)?;
+    Ok(outbound(&source.discussion_url(&id.id)))
+}
+
+/// Redirect to an external site without telling it which page (or instance) linked to it.
+fn outbound(url: &str) -> Response {
+    (
+        StatusCode::FOUND,
+        [
+            (header::LOCATION, url),
+            (header::REFERRER_POLICY, "no-referrer"),
+        ],
+    )
+        .into_response()
+}
+
+#[derive(Template)]
+#[template(path = "watch.html")]
+pub struct WatchTemplate {
+    pub layout: Layout,
+    pub page_url: String,
+    pub video: Video,
+    pub oembed: Option<oembed::OEmbed>,
+    /// The downloaded copy of the video in the media directory, if any.
+    pub media_file: Option<String>,
+    /// The languages and files of the subtitles downloaded with it.
+    pub subtitles: Vec<(String, String)>,
+    /// Where the visitor stopped watching the video, in seconds. Without one, as in static
+    /// exports, the position isn't reported back.
+    pub position: Option<u32>,
+    /// The playlists of the visitor the video can be added to.
+    pub playlists: Vec<store::Playlist>,
+    /// The points of a line charting the sampled scores of the video, if there are enough.
+    pub sparkline: Option<String>,
+}
@@ -0,0 +1,106 @@
+/// Metrics of the Tokio runtime, for debugging stalls.
+///
+/// Only built with the `tokio-console` feature, which also serves the runtime to `tokio-console`.
+/// The per-poll metrics and the blocking pool are only known to Tokio when built with
+/// `RUSTFLAGS="--cfg tokio_unstable"`, and are left out otherwise.
+use serde::Serialize;
+use tokio::runtime::Handle;
+
+/// A snapshot of the metrics of the runtime.
+#[derive(Serialize)]
+pub struct RuntimeStats {
+    /// The number of tasks that have been spawned and not finished yet.
+    pub alive_tasks: usize,
+    /// The number of tasks waiting in the queue shared by the workers.
+    pub global_queue_depth: usize,
+    /// The number of tasks spawned since the start.
+    pub spawned_tasks: Option<u64>,
+    /// The number of threads of the blocking pool, e.g. running SQLite queries.
+    pub blocking_threads: Option<usize>,
+    /// The number of threads of the blocking pool that are waiting for work.
+    pub idle_blocking_threads: Option<usize>,
+    /// The number of blocking tasks waiting for a thread.
+    pub blocking_queue_depth: Option<usize>,
+    pub workers: Vec<WorkerStats>,
+}
+
+/// The metrics of a worker thread.
+///
+/// A worker whose park count doesn't change while its busy time grows is stuck in a single poll.
+#[derive(Serialize)]
+pub struct WorkerStats {
+    /// Seconds the worker spent running tasks since the start.
+    pub busy_seconds: f64,
+    /// How often the worker went to sleep for lack of work.
+    pub park_count: u64,
+    /// The number of tasks polled since the start.
+    pub polls: Option<u64>,
+    /// The moving average of the time a poll took, in microseconds.
+    pub mean_poll_micros: Option<f64>,
+}
+
+impl RuntimeStats {
+    /// Take a snapshot of the runtime the caller runs on.
+    pub fn collect() -> Self {
+        let metrics = Handle::current().metrics();
+        let workers = (0..metrics.num_workers())
+            .map(|worker| {
+                #[cfg(tokio_unstable)]
+                let (polls, mean_poll_micros) = (
+                    Some(metrics.worker_poll_count(worker)),
+                    Some(metrics.worker_mean_poll_time(worker).as_secs_f64() * 1e6),
+                );
+                #[cfg(not(tokio_unstable))]
+                let (polls, mean_poll_micros) = (None, None);
+
+                WorkerStats {
+                    busy_seconds: metrics.worker_total_busy_duration(worker).as_secs_f64(),
+                    park_count: metrics.worker_park_count(worker),
+                    polls,
+                    mean_poll_micros,
+                }
+            })
+            .collect();
+
+        #[cfg(tokio_unstable)]
+        let (spawned_tasks, blocking_threads, idle_blocking_threads, blocking_queue_depth) = (
+            Some(metrics.spawned_tasks_count()),
+            Some(metrics.num_blocking_threads()),
+            Some(metrics.num_idle_blocking_threads()),
+            Some(metrics.blocking_queue_depth()),
+        );
+        #[cfg(not(tokio_unstable))]
+        let (spawned_tasks, blocking_threads, idle_blocking_threads, blocking_queue_depth) =
+            (None, None, None, None);
+
+        Self {
+            alive_tasks: metrics.num_alive_tasks(),
+            global_queue_depth: metrics.global_queue_depth(),
+            spawned_tasks,
+            blocking_threads,
+            idle_blocking_threads,
+            blocking_queue_depth,
+            workers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn every_worker_is_reported() {
+        let (started, ready) = tokio::sync::oneshot::channel();
+        let task = tokio::spawn(async move {
+            let _ = started.send(());
+            std::future::pending::<()>().await;
+        });
+        ready.await.unwrap();
+
+        let stats = RuntimeStats::collect();
+        assert_eq!(stats.workers.len(), 2);
+        assert!(stats.alive_tasks >= 1);
+        task.abort();
+    }
+}
@@ -0,0 +1,160 @@
+/// Periodic refreshes of the video list.
+///
+/// Refreshes are scheduled either on a fixed interval, counted from the end of the previous
+/// refresh, or on a cron expression. A random jitter is added to every delay so that instances
+/// started at the same time don't hit the Hacker News API in lockstep. Refreshes never overlap:
+/// when one is still running (e.g. started from the admin page), the scheduled one is skipped.
+///
+/// Under a systemd watchdog, the loop feeds it while waiting for the next refresh, and while a
+/// refresh runs only as it makes progress, so a loop or a refresh that stalls gets the server
+/// restarted.
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::Context;
+use rand::Rng;
+use tracing::{debug, info};
+
+use crate::{config::RefreshConfig, refresh::Job, systemd};
+
+/// How often the loop checks whether the refresh it started has finished.
+const JOB_POLL: Duration = Duration::from_secs(1);
+
+/// When refreshes are due.
+enum Schedule {
+    Interval(Duration),
+    Cron(Box<cron::Schedule>),
+}
+
+/// Counters of the scheduled refreshes, shown on the admin page.
+#[derive(Clone, Default)]
+pub struct SchedulerStats {
+    /// When the next refresh is due.
+    pub next_run: Option<SystemTime>,
+    /// The number of refreshes the scheduler started.
+    pub started: usize,
+    /// The number of refreshes skipped because another one was running.
+    pub skipped: usize,
+    /// The number of scheduled refreshes that failed.
+    pub failed: usize,
+}
+
+pub struct Scheduler {
+    schedule: Option<Schedule>,
+    jitter: Duration,
+    /// How often the systemd watchdog is to be fed, if it is enabled.
+    watchdog: Option<Duration>,
+    stats: Mutex<SchedulerStats>,
+}
+
+impl Scheduler {
+    pub fn new(config: &RefreshConfig) -> anyhow::Result<Self> {
+        let schedule = match &config.cron {
+            Some(expression) => {
+                let schedule = cron::Schedule::from_str(expression)
+                    .with_context(|| format!("Invalid refresh schedule {:?}", expression))?;
+                Some(Schedule::Cron(Box::new(schedule)))
+            }
+            None if config.interval == 0 => None,
+            None => Some(Schedule::Interval(Duration::from_secs(config.interval))),
+        };
+
+        Ok(Self {
+            schedule,
+            jitter: Duration::from_secs(config.jitter),
+            watchdog: systemd::watchdog_interval(),
+            stats: Default::default(),
+        })
+    }
+
+    /// Get the counters of the scheduled refreshes.
+    pub fn stats(&self) -> SchedulerStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Start refreshes with `start` whenever they are due, forever.
+    ///
+    /// Returns right away if scheduled refreshes are disabled, unless the watchdog is to be fed.
+    pub async fn run<F>(&self, start: F)
+    where
+        F: Fn() -> Result<Arc<Job>, Arc<Job>>,
+    {
+        let Some(schedule) = &self.schedule else {
+            info!("Scheduled refreshes are disabled");
+            while let Some(interval) = self.watchdog {
+                self.sleep(interval).await;
+            }
+            return;
+        };
+
+        while let Some(delay) = self.next_delay(schedule) {
+            self.stats.lock().unwrap().next_run = Some(SystemTime::now() + delay);
+            debug!("Next refresh in {:?}", delay);
+            self.sleep(delay).await;
+
+            let job = match start() {
+                Ok(job) => job,
+                Err(running) => {
+                    info!(
+                        "Skipping scheduled refresh, refresh {} is running",
+                        running.id
+                    );
+                    self.stats.lock().unwrap().skipped += 1;
+                    continue;
+                }
+            };
+            self.stats.lock().unwrap().started += 1;
+
+            // Intervals count from the end of a refresh, so wait for it. The watchdog is fed as
+            // its items come in, so a refresh that stops making progress starves it.
+            let mut progress = job.counter.subscribe();
+            while !job.is_finished() {
+                let changed = tokio::time::timeout(JOB_POLL, progress.changed()).await;
+                if let (Ok(Ok(())), Some(_)) = (changed, self.watchdog) {
+                    systemd::notify("WATCHDOG=1");
+                }
+            }
+            if let Some(Err(_)) = job.outcome() {
+                self.stats.lock().unwrap().failed += 1;
+            }
+        }
+
+        info!("The refresh schedule has no upcoming runs");
+    }
+
+    /// Sleep for a while, feeding the watchdog meanwhile if it is enabled.
+    async fn sleep(&self, duration: Duration) {
+        let Some(interval) = self.watchdog else {
+            return tokio::time::sleep(duration).await;
+        };
+        let deadline = Instant::now() + duration;
+        loop {
+            systemd::notify("WATCHDOG=1");
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return;
+            }
+            tokio::time::sleep(left.min(interval)).await;
+        }
+    }
+
+    /// How long to wait until the next refresh, or `None` if there is none.
+    fn next_delay(&self, schedule: &Schedule) -> Option<Duration> {
+        let delay = match schedule {
+            Schedule::Interval(interval) => *interval,
+            Schedule::Cron(schedule) => {
+                let next = schedule.upcoming(chrono::Utc).next()?;
+                (next - chrono::Utc::now()).to_std().unwrap_or_default()
+            }
+        };
+
+        let jitter = match self.jitter.as_millis() {
+            0 => Duration::ZERO,
+            max => Duration::from_millis(rand::thread_rng().gen_range(0..=max as u64)),
+        };
+        Some(delay + jitter)
+    }
+}
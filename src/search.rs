@@ -0,0 +1,204 @@
+/// The query language of the search page.
+///
+/// A query is a list of words, which the title must all contain, and filters of the form
+/// `name:value`:
+///
+/// - `platform:youtube`, `source:lobsters` and `tag:gamedev` narrow the videos to a platform, a
+///   source or a tag in the title. Repeating `platform:` or `source:` allows any of them.
+/// - `score:>100`, `score:>=100`, `score:<10`, `score:<=10` and `score:42` compare the points.
+/// - `after:2024-01-01` and `before:2024-02-01` narrow the videos to those first listed after or
+///   before a day, both excluded.
+///
+/// Double quotes keep a phrase together, e.g. `"rust conf"`. Anything that isn't a valid filter
+/// is searched for as a word, so a title containing `c++:` can still be found.
+use crate::{store::VideoSearch, video::Video};
+
+/// Parse a query into the search it stands for.
+pub fn parse(query: &str) -> VideoSearch {
+    let mut search = VideoSearch::default();
+    for token in tokens(query) {
+        if !apply_filter(&mut search, &token) {
+            search.terms.push(token);
+        }
+    }
+    search
+}
+
+/// Whether a video first listed on a day passes a search, as it would in the store.
+pub fn matches(search: &VideoSearch, video: &Video, day: time::Date) -> bool {
+    let title = video.title.to_lowercase();
+    let tags = video.tags();
+    let score_passes = |bound: Option<i64>, passes: fn(i64, i64) -> bool| {
+        bound.is_none_or(|bound| video.score.is_some_and(|score| passes(score, bound)))
+    };
+
+    search
+        .terms
+        .iter()
+        .all(|term| title.contains(&term.to_lowercase()))
+        && search.tags.iter().all(|tag| tags.contains(tag))
+        && (search.platforms.is_empty() || search.platforms.contains(&video.platform()))
+        && (search.sources.is_empty() || search.sources.iter().any(|s| s == video.source))
+        && score_passes(search.min_score, |score, min| score >= min)
+        && score_passes(search.max_score, |score, max| score <= max)
+        && search.start.is_none_or(|start| day >= start)
+        && search.end.is_none_or(|end| day < end)
+}
+
+/// Add a `name:value` filter to a search, returning whether the token was one.
+fn apply_filter(search: &mut VideoSearch, token: &str) -> bool {
+    let Some((name, value)) = token.split_once(':') else {
+        return false;
+    };
+    if value.is_empty() {
+        return false;
+    }
+    let value = value.to_lowercase();
+    match name.to_lowercase().as_str() {
+        "platform" => search.platforms.push(value),
+        "source" => search.sources.push(value),
+        "tag" => search.tags.push(value.trim_matches(['[', ']']).to_string()),
+        "score" => {
+            let (min, max) = match score_bounds(&value) {
+                Some(bounds) => bounds,
+                None => return false,
+            };
+            if let Some(min) = min {
+                search.min_score = Some(search.min_score.map_or(min, |other| other.max(min)));
+            }
+            if let Some(max) = max {
+                search.max_score = Some(search.max_score.map_or(max, |other| other.min(max)));
+            }
+        }
+        "after" => match parse_date(&value).and_then(|date| date.next_day()) {
+            Some(start) => {
+                search.start = Some(search.start.map_or(start, |other| other.max(start)))
+            }
+            None => return false,
+        },
+        "before" => match parse_date(&value) {
+            Some(end) => search.end = Some(search.end.map_or(end, |other| other.min(end))),
+            None => return false,
+        },
+        _ => return false,
+    }
+    true
+}
+
+/// The least and the most points a comparison like `>100` allows.
+fn score_bounds(value: &str) -> Option<(Option<i64>, Option<i64>)> {
+    if let Some(score) = value.strip_prefix(">=") {
+        return Some((Some(score.parse().ok()?), None));
+    }
+    if let Some(score) = value.strip_prefix("<=") {
+        return Some((None, Some(score.parse().ok()?)));
+    }
+    if let Some(score) = value.strip_prefix('>') {
+        return Some((Some(score.parse::<i64>().ok()?.checked_add(1)?), None));
+    }
+    if let Some(score) = value.strip_prefix('<') {
+        return Some((None, Some(score.parse::<i64>().ok()?.checked_sub(1)?)));
+    }
+    let score = value.parse().ok()?;
+    Some((Some(score), Some(score)))
+}
+
+/// Split a query at whitespace, except inside double quotes, which are dropped.
+fn tokens(query: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    for c in query.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => token.push(c),
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// Narrow a search to the days between the date inputs of the search form, both included.
+pub fn between(search: &mut VideoSearch, from: Option<time::Date>, to: Option<time::Date>) {
+    if let Some(from) = from {
+        search.start = Some(search.start.map_or(from, |other| other.max(from)));
+    }
+    if let Some(end) = to.and_then(|to| to.next_day()) {
+        search.end = Some(search.end.map_or(end, |other| other.min(end)));
+    }
+}
+
+/// A day as sent by date inputs, `YYYY-MM-DD`.
+pub fn parse_date(date: &str) -> Option<time::Date> {
+    let mut parts = date.trim().splitn(3, '-');
+    let year = parts.next()?.parse().ok()?;
+    let month = time::Month::try_from(parts.next()?.parse::<u8>().ok()?).ok()?;
+    let day = parts.next()?.parse().ok()?;
+    time::Date::from_calendar_date(year, month, day).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: time::Month, day: u8) -> time::Date {
+        time::Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn filters_and_words_are_told_apart() {
+        let search = parse("platform:youtube score:>100 before:2024-01-01 rust tag:[gamedev]");
+        assert_eq!(search.terms, ["rust"]);
+        assert_eq!(search.platforms, ["youtube"]);
+        assert_eq!(search.tags, ["gamedev"]);
+        assert_eq!((search.min_score, search.max_score), (Some(101), None));
+        assert_eq!(search.end, Some(date(2024, time::Month::January, 1)));
+    }
+
+    #[test]
+    fn quotes_keep_phrases_together() {
+        assert_eq!(parse(r#""rust conf" 2024"#).terms, ["rust conf", "2024"]);
+        // An unterminated quote runs to the end of the query.
+        assert_eq!(parse(r#"talk "rust conf"#).terms, ["talk", "rust conf"]);
+        assert_eq!(parse(r#""""#), VideoSearch::default());
+        assert_eq!(parse(r#"tag:"game dev""#).tags, ["game dev"]);
+    }
+
+    #[test]
+    fn invalid_filters_are_searched_for_as_words() {
+        let search = parse("platform: score:lots after:yesterday c++: :x");
+        assert_eq!(
+            search.terms,
+            ["platform:", "score:lots", "after:yesterday", "c++:", ":x"]
+        );
+        assert!(search.platforms.is_empty());
+        assert_eq!(
+            (search.min_score, search.max_score, search.start),
+            (None, None, None)
+        );
+    }
+
+    #[test]
+    fn repeated_bounds_narrow_the_search() {
+        let search =
+            parse("score:>=10 score:>20 score:<=100 score:<50 after:2024-01-01 after:2024-02-01");
+        assert_eq!((search.min_score, search.max_score), (Some(21), Some(49)));
+        assert_eq!(search.start, Some(date(2024, time::Month::February, 2)));
+        assert_eq!(parse("score:42").min_score, Some(42));
+        assert_eq!(parse("score:42").max_score, Some(42));
+        // Bounds beyond the points there can be are no filters.
+        assert_eq!(parse("score:>9223372036854775807").min_score, None);
+    }
+
+    #[test]
+    fn like_wildcards_in_words_are_kept_literally() {
+        assert_eq!(parse("100% a_b \\").terms, ["100%", "a_b", "\\"]);
+    }
+}
@@ -0,0 +1,76 @@
+/// Alerting visitors to the new videos matching the searches they saved.
+///
+/// After every refresh, the new videos are matched against every saved search. The matches are
+/// recorded for the feed of the search, and pushed to the ntfy topic of the visitor's private feed
+/// when the instance offers it.
+use async_trait::async_trait;
+use tracing::warn;
+
+use crate::{
+    config::{NotifyRule, TimeZone},
+    notify::Notifier,
+    search,
+    store::Store,
+    tag_push::TagPush,
+    video::Video,
+};
+
+pub struct SearchAlerts {
+    store: Store,
+    /// The days of the `before:` and `after:` filters start in this time zone.
+    time_zone: TimeZone,
+    push: Option<TagPush>,
+}
+
+impl SearchAlerts {
+    pub fn new(store: Store, time_zone: TimeZone, push: Option<TagPush>) -> Self {
+        Self {
+            store,
+            time_zone,
+            push,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for SearchAlerts {
+    fn name(&self) -> &'static str {
+        "saved-searches"
+    }
+
+    /// Every new video is matched against the saved searches instead.
+    fn rules(&self) -> &[NotifyRule] {
+        &[]
+    }
+
+    async fn send(&self, video: &Video) -> anyhow::Result<()> {
+        self.send_all(&[video]).await
+    }
+
+    async fn send_all(&self, videos: &[&Video]) -> anyhow::Result<()> {
+        // The videos are new, so they were first listed today.
+        let today = self.time_zone.today();
+        for (saved, topic) in self.store.search_alerts().await? {
+            let filter = search::parse(&saved.query);
+            let matching: Vec<_> = videos
+                .iter()
+                .filter(|video| search::matches(&filter, video, today))
+                .collect();
+            if matching.is_empty() {
+                continue;
+            }
+            let ids: Vec<_> = matching.iter().map(|video| video.id.clone()).collect();
+            self.store.record_search_matches(saved.id, &ids).await?;
+
+            let (Some(push), Some(topic)) = (&self.push, topic) else {
+                continue;
+            };
+            for video in matching {
+                if let Err(err) = push.push(&topic, &saved.query, video).await {
+                    warn!(video = %video.id, "Failed to push a video to {}: {:#}", topic, err);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+/// Security headers on the HTML pages.
+///
+/// The `Content-Security-Policy` only lets the pages load their own assets and embed players from
+/// YouTube, the privacy frontend and the configured hosts, and keeps other sites from framing
+/// them. Pages that need more, like the API docs, set their own policy, which is left alone.
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::{header, HeaderValue},
+    response::Response,
+};
+
+use crate::config::Config;
+
+/// Where YouTube players, and the iframe API controlling them, are loaded from.
+const YOUTUBE: &str = "https://www.youtube.com";
+
+#[derive(Clone)]
+pub struct SecurityHeaders {
+    policy: Arc<HeaderValue>,
+}
+
+impl SecurityHeaders {
+    pub fn new(config: &Config) -> anyhow::Result<Self> {
+        let policy = match &config.security.content_security_policy {
+            Some(policy) => policy.clone(),
+            None => policy(config),
+        };
+        Ok(Self {
+            policy: Arc::new(HeaderValue::from_str(&policy)?),
+        })
+    }
+}
+
+/// The policy built from the settings.
+fn policy(config: &Config) -> String {
+    let mut frame_hosts = vec![YOUTUBE.to_string()];
+    let frontend = config
+        .privacy
+        .as_ref()
+        .and_then(|privacy| reqwest::Url::parse(&privacy.frontend).ok());
+    if let Some(frontend) = frontend {
+        frame_hosts.push(frontend.origin().ascii_serialization());
+    }
+    frame_hosts.extend(config.security.frame_hosts.iter().cloned());
+    let frame_ancestors = match config.security.frame_ancestors.as_slice() {
+        [] => "'none'".to_string(),
+        hosts => hosts.join(" "),
+    };
+
+    format!(
+        "default-src 'self'; script-src 'self' {YOUTUBE}; frame-src {}; img-src 'self' data: https:; \
+         media-src 'self' https:; object-src 'none'; base-uri 'self'; frame-ancestors {}",
+        frame_hosts.join(" "),
+        frame_ancestors,
+    )
+}
+
+/// Add the security headers to an HTML response.
+pub async fn apply(State(headers): State<SecurityHeaders>, mut response: Response) -> Response {
+    let html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    if !html {
+        return response;
+    }
+
+    let response_headers = response.headers_mut();
+    if !response_headers.contains_key(header::CONTENT_SECURITY_POLICY) {
+        response_headers.insert(header::CONTENT_SECURITY_POLICY, (*headers.policy).clone());
+    }
+    response_headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    // YouTube refuses to play embedded videos without knowing the site they are embedded on.
+    response_headers.insert(
+        header::REFERRER_POLICY,
+        HeaderValue::from_static("strict-origin-when-cross-origin"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+
+    use super::*;
+    use crate::config::PrivacyConfig;
+
+    fn response(content_type: &str) -> Response {
+        Response::builder()
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn players_are_framed_from_youtube_the_frontend_and_the_configured_hosts() {
+        let mut config = Config {
+            privacy: Some(PrivacyConfig {
+                frontend: "https://yewtu.be/".to_string(),
+                enabled_by_default: false,
+            }),
+            ..Config::default()
+        };
+        config.security.frame_hosts = vec!["https://player.vimeo.com".to_string()];
+
+        let built = policy(&config);
+        assert!(built.contains(
+            "frame-src https://www.youtube.com https://yewtu.be https://player.vimeo.com;"
+        ));
+        assert!(built.ends_with("frame-ancestors 'none'"));
+
+        config.security.frame_ancestors = vec!["https://example.com".to_string()];
+        assert!(policy(&config).ends_with("frame-ancestors https://example.com"));
+    }
+
+    #[test]
+    fn a_configured_policy_replaces_the_built_one() {
+        let mut config = Config::default();
+        config.security.content_security_policy = Some("default-src *".to_string());
+        let headers = SecurityHeaders::new(&config).unwrap();
+        assert_eq!(*headers.policy, "default-src *");
+
+        config.security.content_security_policy = Some("default-src\n*".to_string());
+        assert!(SecurityHeaders::new(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn only_pages_without_a_policy_of_their_own_get_the_policy() {
+        let headers = SecurityHeaders::new(&Config::default()).unwrap();
+
+        let page = apply(State(headers.clone()), response("text/html; charset=utf-8")).await;
+        assert!(page.headers()[header::CONTENT_SECURITY_POLICY]
+            .to_str()
+            .unwrap()
+            .starts_with("default-src 'self'"));
+        assert_eq!(page.headers()[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert!(page.headers().contains_key(header::REFERRER_POLICY));
+
+        let mut docs = response("text/html");
+        docs.headers_mut().insert(
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static("default-src 'none'"),
+        );
+        let docs = apply(State(headers.clone()), docs).await;
+        assert_eq!(
+            docs.headers()[header::CONTENT_SECURITY_POLICY],
+            "default-src 'none'"
+        );
+
+        let json = apply(State(headers), response("application/json")).await;
+        assert!(!json.headers().contains_key(header::CONTENT_SECURITY_POLICY));
+        assert!(!json.headers().contains_key(header::X_CONTENT_TYPE_OPTIONS));
+    }
+}
@@ -0,0 +1,229 @@
+/// Anonymous visitor sessions.
+///
+/// A visitor is given a random session ID the first time they store something (e.g. save a video
+/// for later). The ID keys the visitor's rows in the store, and is kept in a cookie signed with a
+/// key of the instance, so that visitors can't pick the session of someone else.
+///
+/// Sessions are also recorded server-side: a session that isn't in the store isn't accepted, even
+/// with a valid signature. This lets sessions expire after a year without visits, and the operator
+/// end all of them at once. Cookies from before sessions were signed are accepted without a
+/// signature as long as their session was taken over from that time and never signed since, and
+/// are signed the next time the visitor stores something.
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::request::Parts,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{preferences::persistent_cookie, routes::AppError, state::SharedState, store::Store};
+
+/// The cookie holding the session ID.
+const SESSION_COOKIE: &str = "session";
+
+/// The name of the signing key in the store.
+const KEY_NAME: &str = "session";
+
+/// How long a session lasts without visits.
+pub const SESSION_DAYS: u32 = 365;
+
+/// How often expired sessions are purged.
+const PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+/// Signs and checks session cookies.
+pub struct Sessions {
+    store: Store,
+    key: Vec<u8>,
+}
+
+impl Sessions {
+    /// Load the signing key, making one on first start.
+    pub async fn new(store: Store) -> anyhow::Result<Self> {
+        let key = match store.key(KEY_NAME).await? {
+            Some(key) => key,
+            None => {
+                let mut key = [0; 32];
+                rand::thread_rng().fill_bytes(&mut key);
+                let key = hex::encode(key);
+                store.add_key(KEY_NAME, key).await?
+            }
+        };
+
+        Ok(Self {
+            store,
+            key: key.into_bytes(),
+        })
+    }
+
+    /// Count the live sessions.
+    pub async fn count(&self) -> anyhow::Result<u64> {
+        self.store.session_count().await
+    }
+
+    /// End every session, logging all visitors out. Their lists are deleted with the next purge.
+    pub async fn end_all(&self) -> anyhow::Result<usize> {
+        let ended = self.store.end_sessions().await?;
+        info!("Ended {} sessions", ended);
+        Ok(ended)
+    }
+
+    /// Purge the expired sessions and the lists of ended ones, every day.
+    pub async fn run(&self) {
+        loop {
+            match self.store.purge_sessions(SESSION_DAYS).await {
+                Ok(0) => {}
+                Ok(expired) => info!("Purged {} expired sessions", expired),
+                Err(err) => warn!("Failed to purge the expired sessions: {:#}", err),
+            }
+            tokio::time::sleep(PURGE_INTERVAL).await;
+        }
+    }
+
+    /// The cookie value of a session: its ID and signature.
+    fn sign(&self, id: &str) -> String {
+        format!(
+            "{}.{}",
+            id,
+            URL_SAFE_NO_PAD.encode(self.mac(id).finalize().into_bytes())
+        )
+    }
+
+    /// The session ID of a cookie value, if its signature is valid.
+    fn verify<'v>(&self, value: &'v str) -> Option<&'v str> {
+        let (id, signature) = value.rsplit_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        self.mac(id).verify_slice(&signature).ok()?;
+        Some(id)
+    }
+
+    fn mac(&self, id: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC takes keys of any size");
+        mac.update(id.as_bytes());
+        mac
+    }
+}
+
+/// The session of the visitor making a request.
+pub struct Session {
+    id: String,
+    is_new: bool,
+    /// Whether the cookie has to be set again, for sessions from unsigned cookies.
+    resign: bool,
+}
+
+impl Session {
+    /// The session ID.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Whether the session was started by this request, so nothing was stored for it yet.
+    pub fn is_new(&self) -> bool {
+        self.is_new
+    }
+
+    /// Record the session and add its cookie to the response if it was started by this request.
+    pub async fn persist(&self, sessions: &Sessions, jar: CookieJar) -> anyhow::Result<CookieJar> {
+        if self.is_new {
+            sessions.store.start_session(&self.id).await?;
+        }
+        if self.resign {
+            sessions.store.sign_session(&self.id).await?;
+        }
+        if self.is_new || self.resign {
+            Ok(jar.add(persistent_cookie(SESSION_COOKIE, sessions.sign(&self.id))))
+        } else {
+            Ok(jar)
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Session
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let jar = CookieJar::from_headers(&parts.headers);
+        let Extension(app) = Extension::<SharedState>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let sessions = &app.sessions;
+
+        let value = jar.get(SESSION_COOKIE).map(|cookie| cookie.value());
+        let (id, resign) = match value {
+            Some(value) if !value.contains('.') => (Some(value), true),
+            Some(value) => (sessions.verify(value), false),
+            None => (None, false),
+        };
+        if let Some(id) = id {
+            let seen = sessions.store.see_session(id, SESSION_DAYS, !resign).await;
+            if seen.map_err(|err| AppError::from(err).into_response())? {
+                return Ok(Self {
+                    id: id.to_string(),
+                    is_new: false,
+                    resign,
+                });
+            }
+        }
+
+        Ok(Self {
+            id: Uuid::new_v4().to_string(),
+            is_new: true,
+            resign: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_rusqlite::Connection;
+
+    use super::*;
+
+    /// Sessions with a key of their own, kept in a store in memory.
+    async fn new_sessions() -> Sessions {
+        let conn = Connection::open_in_memory().await.unwrap();
+        Sessions::new(Store::new(conn).await.unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn signed_ids_are_verified() {
+        let sessions = new_sessions().await;
+        let value = sessions.sign("abc");
+        assert!(value.starts_with("abc."));
+        assert_eq!(sessions.verify(&value), Some("abc"));
+
+        // The key is kept in the store, so cookies stay valid across restarts.
+        let restarted = Sessions::new(sessions.store.clone()).await.unwrap();
+        assert_eq!(restarted.verify(&value), Some("abc"));
+    }
+
+    #[tokio::test]
+    async fn forged_cookies_are_rejected() {
+        let sessions = new_sessions().await;
+        let value = sessions.sign("abc");
+        let (_, signature) = value.split_once('.').unwrap();
+        assert_eq!(sessions.verify(&format!("abd.{}", signature)), None);
+        assert_eq!(sessions.verify("abc"), None);
+        assert_eq!(sessions.verify("abc."), None);
+        assert_eq!(sessions.verify("abc.not base64"), None);
+
+        // Another instance signs with a key of its own.
+        let other = new_sessions().await;
+        assert_eq!(sessions.verify(&other.sign("abc")), None);
+    }
+}
@@ -0,0 +1,110 @@
+/// Posting digests of the new videos to a Slack channel through an incoming webhook.
+///
+/// The new videos of a refresh are posted together in a single message, and at most one message
+/// is posted per `digest_minutes`, so busy periods don't flood the channel.
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::json;
+
+use crate::{
+    config::{NotifyRule, SlackConfig},
+    notify::Notifier,
+    video::Video,
+};
+
+/// The most videos listed in a message, as Slack allows up to 50 blocks.
+const MAX_VIDEOS: usize = 40;
+
+pub struct Slack {
+    client: Client,
+    config: SlackConfig,
+    /// When the last digest was posted.
+    last_sent: Mutex<Option<Instant>>,
+}
+
+impl Slack {
+    pub fn new(config: SlackConfig) -> Self {
+        Self {
+            client: Client::new(),
+            config,
+            last_sent: Mutex::new(None),
+        }
+    }
+
+    /// The line of a video in a digest: its title linking to the video, its score and its
+    /// discussion.
+    fn line(video: &Video) -> String {
+        let mut line = format!("*<{}|{}>*\n", escape(&video.url), escape(&video.title));
+        if let Some(score) = video.score {
+            line.push_str(&format!("{} points · ", score));
+        }
+        line.push_str(&format!(
+            "<{}|{}>",
+            escape(&video.discussion_url),
+            escape(video.source_label)
+        ));
+        line
+    }
+}
+
+#[async_trait]
+impl Notifier for Slack {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    fn rules(&self) -> &[NotifyRule] {
+        &self.config.rules
+    }
+
+    fn ready(&self) -> bool {
+        let interval = Duration::from_secs(self.config.digest_minutes * 60);
+        let last_sent = self.last_sent.lock().unwrap();
+        last_sent.is_none_or(|last_sent| last_sent.elapsed() >= interval)
+    }
+
+    async fn send(&self, video: &Video) -> anyhow::Result<()> {
+        self.send_all(&[video]).await
+    }
+
+    async fn send_all(&self, videos: &[&Video]) -> anyhow::Result<()> {
+        let summary = match videos.len() {
+            1 => "1 new video".to_string(),
+            count => format!("{} new videos", count),
+        };
+        let mut blocks: Vec<_> = videos
+            .iter()
+            .take(MAX_VIDEOS)
+            .map(|video| json!({ "type": "section", "text": { "type": "mrkdwn", "text": Self::line(video) } }))
+            .collect();
+        if videos.len() > MAX_VIDEOS {
+            let more = format!("and {} more", videos.len() - MAX_VIDEOS);
+            blocks.push(
+                json!({ "type": "context", "elements": [{ "type": "mrkdwn", "text": more }] }),
+            );
+        }
+
+        // The digest counts as posted even if it fails, so a broken webhook isn't retried on
+        // every refresh either.
+        *self.last_sent.lock().unwrap() = Some(Instant::now());
+        self.client
+            .post(&self.config.webhook_url)
+            .json(&json!({ "text": summary, "blocks": blocks }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Escape text for Slack's mrkdwn.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,93 @@
+/// The aggregators videos are found on.
+///
+/// Every aggregator (Hacker News, Lobsters, subreddits, ...) implements [`Source`]. The refresh
+/// loop only talks to the trait, so adding an aggregator means implementing it and registering
+/// it, without touching the loop.
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::hacker_news::{Counter, Detections};
+
+/// What a source reports while it fetches its videos.
+#[derive(Clone, Copy, Debug)]
+pub enum Fetched<T> {
+    /// A video, with its rank among the stories of the source.
+    Video(usize, T),
+    /// Every video on the front page of the source has been reported. Sources that get their
+    /// whole list at once don't report this.
+    FrontPage,
+}
+
+/// A video found on a source.
+#[derive(Clone, Debug)]
+pub struct Story {
+    /// The ID of the story on its source.
+    pub id: String,
+    pub title: String,
+    /// The URL the story was submitted with.
+    pub url: String,
+    /// The discussion of the story on its source.
+    pub discussion_url: String,
+    /// The points of the story on its source, if it has any.
+    pub score: Option<i64>,
+    /// When the story was submitted, in seconds since the Unix epoch, if the source tells.
+    pub submitted_at: Option<i64>,
+    /// The number of comments in the discussion of the story, if the source tells.
+    pub comments: Option<i64>,
+}
+
+#[async_trait]
+pub trait Source: Send + Sync {
+    /// The name of the source in video IDs, URLs and the configuration, e.g. `lobsters`.
+    ///
+    /// Names consist of lower case letters and dashes.
+    fn name(&self) -> &'static str;
+
+    /// The name of the source shown to visitors.
+    fn label(&self) -> &'static str;
+
+    /// Get the videos the source currently lists, most prominent first.
+    ///
+    /// `on_fetched` is called with every video as soon as it is known, so callers can show the
+    /// videos before the whole list is done. Sources that fetch their stories one by one report
+    /// their progress on `counter`, and fetch the stories of their front page first.
+    async fn videos(
+        &self,
+        counter: Arc<Counter>,
+        on_fetched: &mut (dyn for<'s> FnMut(Fetched<&'s Story>) + Send),
+    ) -> anyhow::Result<Vec<Story>>;
+
+    /// Get a single story, if it is a video.
+    async fn video(&self, id: &str) -> anyhow::Result<Option<Story>>;
+
+    /// The URL of the discussion of a story, without fetching it.
+    fn discussion_url(&self, id: &str) -> String;
+
+    /// The outcomes of the detection over the stories of the last fetch.
+    fn detections(&self) -> Detections;
+}
+
+/// Merge ranked lists, taking turns between them so none is buried.
+pub fn interleave<T>(lists: Vec<Vec<T>>) -> Vec<T> {
+    interleave_weighted(lists.into_iter().map(|list| (1, list)).collect())
+}
+
+/// Merge ranked lists like [`interleave`], taking as many items of each list per turn as its
+/// weight.
+pub fn interleave_weighted<T>(lists: Vec<(usize, Vec<T>)>) -> Vec<T> {
+    let mut merged = Vec::with_capacity(lists.iter().map(|(_, list)| list.len()).sum());
+    let mut lists: Vec<_> = lists
+        .into_iter()
+        .map(|(weight, list)| (weight.max(1), list.into_iter()))
+        .collect();
+    loop {
+        let before = merged.len();
+        for (weight, list) in &mut lists {
+            merged.extend(list.by_ref().take(*weight));
+        }
+        if merged.len() == before {
+            return merged;
+        }
+    }
+}
@@ -0,0 +1,921 @@
+/// The state the handlers, the commands and the background tasks share.
+use std::{
+    collections::HashMap,
+    io::Write as _,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::Context;
+use serde::Serialize;
+use time::OffsetDateTime;
+use tracing::{debug, info, warn, Instrument, Span};
+
+use crate::{
+    activitypub, blocklist, cache, config, discord, downloads, export, features, hacker_news,
+    lobsters, mastodon, matrix, notify, ntfy, overload, pocket, read_later, reddit, refresh,
+    routes::Layout,
+    scheduler, search_alerts,
+    session::{self, Session},
+    slack,
+    source::{self, Fetched, Source},
+    stats, status, store, submissions, tag_push, telegram,
+    video::{collapse_reposts, keep_newest, Video, VideoId},
+    wallabag, webhooks,
+};
+
+/// How many videos the page of a week lists.
+const WEEK_VIDEOS: u32 = 30;
+
+/// A video submitted by a followed user.
+#[derive(Clone)]
+pub struct FollowedVideo {
+    /// The username of the submitter on Hacker News.
+    pub submitter: String,
+    pub video: Video,
+}
+
+/// How many of the last opened videos the history lists.
+const HISTORY_LENGTH: u32 = 50;
+
+pub type SharedState = Arc<State>;
+
+/// A source of videos with its settings, and the videos of its last successful fetch.
+pub struct SourceEntry {
+    source: Arc<dyn Source>,
+    settings: config::SourceSettings,
+    last: Mutex<Option<(Instant, Vec<Video>)>>,
+}
+
+impl SourceEntry {
+    fn new(source: Arc<dyn Source>, settings: config::SourceSettings) -> Self {
+        Self {
+            source,
+            settings,
+            last: Mutex::new(None),
+        }
+    }
+
+    /// The videos of the last fetch, if they are recent enough to be reused.
+    fn reusable(&self) -> Option<Vec<Video>> {
+        let interval = Duration::from_secs(self.settings.interval?);
+        let last = self.last.lock().unwrap();
+        let (fetched, videos) = last.as_ref()?;
+        (fetched.elapsed() < interval).then(|| videos.clone())
+    }
+
+    /// The videos of the last successful fetch, however old they are.
+    fn previous(&self) -> Option<Vec<Video>> {
+        let last = self.last.lock().unwrap();
+        last.as_ref().map(|(_, videos)| videos.clone())
+    }
+}
+
+/// A source as offered in the filter of the index.
+#[derive(Clone, Copy)]
+pub struct SourceLink {
+    pub name: &'static str,
+    pub label: &'static str,
+}
+
+pub struct State {
+    pub config: config::Config,
+    /// The Hacker News client, which is also used for its statistics and oEmbed lookups.
+    pub hn: Arc<hacker_news::HackerNews>,
+    /// The enabled sources, in the order their videos take turns in the list.
+    pub sources: Vec<SourceEntry>,
+    /// The videos added by hand, which aren't listed with those of the sources.
+    submissions: submissions::Submissions,
+    pub cache: cache::Cache,
+    pub store: store::Store,
+    pub sessions: session::Sessions,
+    /// Answers the requests beyond the concurrency limits.
+    pub overload: overload::Overload,
+    /// Where new videos are posted after every refresh.
+    notifiers: Vec<Box<dyn notify::Notifier>>,
+    /// The actor Fediverse users follow, if enabled.
+    pub activitypub: Option<activitypub::ActivityPub>,
+    /// The services visitors can save videos to.
+    pub read_later: Vec<Box<dyn read_later::ReadLater>>,
+    /// Delivers events to the subscribers of the webhooks.
+    pub webhooks: webhooks::Webhooks,
+    /// The experimental behaviors, toggled on the admin page.
+    pub features: Arc<features::Features>,
+    /// The stories and domains kept off the instance.
+    pub blocklist: blocklist::Blocklist,
+    /// Downloads videos to watch offline, if enabled.
+    pub downloads: Option<downloads::Downloads>,
+    /// Watches the Hacker News API for the status page, if enabled.
+    pub status: Option<status::Monitor>,
+    pub refresher: refresh::Refresher,
+    pub scheduler: scheduler::Scheduler,
+    /// The videos as of the last refresh, which the pages are rendered from.
+    videos: RwLock<Arc<Vec<Video>>>,
+    /// When the videos were last published, to the second as HTTP dates have it.
+    published_at: tokio::sync::watch::Sender<Option<SystemTime>>,
+    /// The latest video submissions of the followed Hacker News users, the latest first.
+    followed: RwLock<Arc<Vec<FollowedVideo>>>,
+    /// The statistics of the archive, once aggregated.
+    pub stats: RwLock<Option<Arc<stats::ArchiveStats>>>,
+    /// Held while fetching the deeper top stories, so visitors paging at the same time fetch
+    /// them once.
+    deeper: tokio::sync::Mutex<()>,
+}
+
+impl State {
+    pub async fn new(config: config::Config) -> anyhow::Result<Self> {
+        let cache = if config.database == cache::default_path() {
+            cache::Cache::new().await
+        } else {
+            cache::Cache::open(&config.database).await
+        };
+        let cache =
+            cache.with_context(|| format!("Failed to open {}", config.database.display()))?;
+        let store = store::Store::new(cache.connection())
+            .await
+            .context("Failed to open store")?;
+
+        let hn = Arc::new(
+            hacker_news::HackerNews::with_base_url(cache.clone(), &config.hacker_news_url)
+                .with_concurrency(config.refresh.concurrency)
+                .with_item_ttl(Duration::from_secs(config.refresh.item_ttl))
+                .with_eager_ranks(config.refresh.eager_ranks.unwrap_or(usize::MAX))
+                .with_max_known(config.memory.max_items.unwrap_or(usize::MAX))
+                .with_resume_within(Duration::from_secs(config.refresh.resume_within)),
+        );
+        let mut sources = Vec::new();
+        if config.hacker_news.enabled {
+            sources.push(SourceEntry::new(hn.clone(), config.hacker_news.settings()));
+        }
+        let status = (config.hacker_news.enabled && config.status.interval > 0)
+            .then(|| status::Monitor::new(&config.status, &config.hacker_news_url));
+        if config.lobsters.enabled {
+            let lobsters = lobsters::Lobsters::with_base_url(cache.clone(), &config.lobsters.url);
+            sources.push(SourceEntry::new(
+                Arc::new(lobsters),
+                config.lobsters.settings(),
+            ));
+        }
+        if config.reddit.enabled {
+            let reddit = reddit::Reddit::with_base_url(cache.clone(), &config.reddit.url)?
+                .with_subreddits(config.reddit.subreddits.clone());
+            sources.push(SourceEntry::new(Arc::new(reddit), config.reddit.settings()));
+        }
+        // The configured order first, the rest as they are.
+        sources.sort_by_key(|entry| {
+            let name = entry.source.name();
+            let position = config.source_order.iter().position(|other| other == name);
+            position.unwrap_or(usize::MAX)
+        });
+
+        let submissions = submissions::Submissions::new(store.clone(), &config.public_url);
+        let sessions = session::Sessions::new(store.clone())
+            .await
+            .context("Failed to set up the sessions")?;
+
+        let webhooks = webhooks::Webhooks::new(store.clone())?;
+        let mut notifiers: Vec<Box<dyn notify::Notifier>> = vec![Box::new(webhooks.clone())];
+        let features = Arc::new(features::Features::new(&config.features));
+        let blocklist = blocklist::Blocklist::new(store.clone())
+            .await
+            .context("Failed to load the blocklist")?;
+        let downloads = config
+            .downloads
+            .clone()
+            .map(|config| downloads::Downloads::new(config, store.clone(), features.clone()))
+            .transpose()?;
+        if let Some(downloads) = downloads.as_ref().filter(|downloads| downloads.has_rules()) {
+            notifiers.push(Box::new(downloads.clone()));
+        }
+        if let Some(telegram) = &config.telegram {
+            notifiers.push(Box::new(telegram::Telegram::new(telegram.clone())));
+        }
+        if let Some(discord) = &config.discord {
+            notifiers.push(Box::new(discord::Discord::new(discord.clone(), hn.clone())));
+        }
+        if let Some(slack) = &config.slack {
+            notifiers.push(Box::new(slack::Slack::new(slack.clone())));
+        }
+        if let Some(mastodon) = &config.mastodon {
+            notifiers.push(Box::new(mastodon::Mastodon::new(mastodon.clone())));
+        }
+        if let Some(matrix) = &config.matrix {
+            notifiers.push(Box::new(matrix::Matrix::new(matrix.clone())));
+        }
+        if let Some(ntfy) = &config.ntfy {
+            notifiers.push(Box::new(ntfy::Ntfy::new(ntfy.clone())?));
+        }
+        if let Some(tag_push) = &config.tag_push {
+            notifiers.push(Box::new(tag_push::TagPush::new(
+                tag_push.clone(),
+                store.clone(),
+            )?));
+        }
+        let search_push = config
+            .tag_push
+            .clone()
+            .map(|tag_push| tag_push::TagPush::new(tag_push, store.clone()))
+            .transpose()?;
+        notifiers.push(Box::new(search_alerts::SearchAlerts::new(
+            store.clone(),
+            config.timezone,
+            search_push,
+        )));
+        let activitypub = match &config.activitypub {
+            Some(activitypub) => Some(
+                activitypub::ActivityPub::new(
+                    activitypub.clone(),
+                    &config.public_url,
+                    store.clone(),
+                )
+                .await
+                .context("Failed to set up the ActivityPub actor")?,
+            ),
+            None => None,
+        };
+        if let Some(activitypub) = &activitypub {
+            notifiers.push(Box::new(activitypub.clone()));
+        }
+
+        let mut read_later: Vec<Box<dyn read_later::ReadLater>> = Vec::new();
+        if let Some(pocket) = &config.pocket {
+            read_later.push(Box::new(pocket::Pocket::new(pocket.clone())));
+        }
+        if let Some(wallabag) = &config.wallabag {
+            read_later.push(Box::new(wallabag::Wallabag::new(wallabag.clone())));
+        }
+
+        Ok(Self {
+            scheduler: scheduler::Scheduler::new(&config.refresh)?,
+            overload: overload::Overload::new(config.overload.clone()),
+            features,
+            blocklist,
+            config,
+            hn,
+            sources,
+            submissions,
+            cache,
+            store,
+            sessions,
+            notifiers,
+            activitypub,
+            read_later,
+            webhooks,
+            downloads,
+            status,
+            refresher: refresh::Refresher::new(),
+            videos: Default::default(),
+            published_at: Default::default(),
+            followed: Default::default(),
+            stats: Default::default(),
+            deeper: Default::default(),
+        })
+    }
+
+    /// The privacy frontend YouTube links should point at for a visitor, if any.
+    pub fn frontend(&self, layout: &Layout) -> Option<&str> {
+        let privacy = self.config.privacy.as_ref()?;
+        (layout.privacy == Some(true)).then_some(privacy.frontend.as_str())
+    }
+
+    /// The videos of the most recent successful refresh.
+    pub fn videos(&self) -> Arc<Vec<Video>> {
+        self.videos.read().unwrap().clone()
+    }
+
+    /// The latest video submissions of the followed users.
+    pub fn followed(&self) -> Arc<Vec<FollowedVideo>> {
+        self.followed.read().unwrap().clone()
+    }
+
+    /// Look up the latest video submissions of the followed users again, keeping the previous
+    /// ones of the users that couldn't be looked up.
+    async fn refresh_followed(&self) {
+        let config = &self.config.hacker_news;
+        let previous = self.followed();
+        let mut followed = Vec::new();
+        for user in &config.followed {
+            match self.hn.get_user_videos(user, config.followed_depth).await {
+                Ok(items) => {
+                    let stories = items.iter().filter_map(|item| item.story().ok());
+                    followed.extend(stories.map(|story| FollowedVideo {
+                        submitter: user.clone(),
+                        video: Video::new(self.hn.as_ref(), story),
+                    }));
+                }
+                Err(err) => {
+                    warn!(user, "Failed to get the submissions of {}: {:#}", user, err);
+                    let kept = previous
+                        .iter()
+                        .filter(|followed| &followed.submitter == user);
+                    followed.extend(kept.cloned());
+                }
+            }
+        }
+        followed.retain(|followed| {
+            !self
+                .blocklist
+                .blocks(&followed.video.id, &followed.video.url)
+        });
+        followed.sort_by_key(|followed| std::cmp::Reverse(followed.video.submitted_at));
+        *self.followed.write().unwrap() = Arc::new(followed);
+    }
+
+    /// When the videos were last published, if they were yet.
+    pub fn published_at(&self) -> Option<SystemTime> {
+        *self.published_at.borrow()
+    }
+
+    /// Wait until the videos are first published.
+    pub async fn wait_for_published(&self) {
+        let mut published_at = self.published_at.subscribe();
+        // The sender lives as long as the state, so this can't fail.
+        let _ = published_at.wait_for(Option::is_some).await;
+    }
+
+    /// The videos of a playlist that are still known.
+    ///
+    /// Like the history, the videos are read as recorded when they were listed.
+    pub async fn playlist_videos(
+        &self,
+        ids: &[VideoId],
+        layout: &Layout,
+    ) -> anyhow::Result<Vec<Video>> {
+        Ok(self
+            .store
+            .listed_videos(ids)
+            .await?
+            .into_iter()
+            .filter_map(|listed| self.recorded_video(listed))
+            .map(|video| video.with_frontend(self.frontend(layout)))
+            .collect())
+    }
+
+    /// The videos that scored highest in the week starting on a Monday in a time zone, with the
+    /// highest score each reached in it.
+    pub async fn week_videos(
+        &self,
+        monday: time::Date,
+        time_zone: config::TimeZone,
+    ) -> anyhow::Result<Vec<(Video, i64)>> {
+        let start = time_zone.midnight(monday);
+        let end = time_zone.midnight(monday + time::Duration::weeks(1));
+        let mut videos = Vec::new();
+        for (id, score) in self.store.peak_scores(start, end, WEEK_VIDEOS).await? {
+            let listed = self.store.listed_video(&id).await?;
+            if let Some(video) = listed.and_then(|listed| self.recorded_video(listed)) {
+                videos.push((video, score));
+            }
+        }
+        Ok(videos)
+    }
+
+    /// The videos a visitor opened, the last first.
+    ///
+    /// The videos are read as recorded when they were listed, rather than fetched from their
+    /// sources, so that rendering a page never waits for them.
+    pub async fn history(
+        &self,
+        session: &Session,
+        layout: &Layout,
+    ) -> anyhow::Result<Vec<HistoryEntry>> {
+        if session.is_new() {
+            return Ok(Vec::new());
+        }
+        let opened = self.store.history(session.id(), HISTORY_LENGTH).await?;
+        let ids: Vec<_> = opened.iter().map(|(id, _)| id.clone()).collect();
+        let videos: HashMap<_, _> = self
+            .store
+            .listed_videos(&ids)
+            .await?
+            .into_iter()
+            .filter_map(|listed| self.recorded_video(listed))
+            .map(|video| (video.id.clone(), video))
+            .collect();
+        Ok(opened
+            .into_iter()
+            .filter_map(|(id, position)| {
+                let video = videos.get(&id)?.clone();
+                let video = video.with_frontend(self.frontend(layout));
+                Some(HistoryEntry { video, position })
+            })
+            .collect())
+    }
+
+    /// Aggregate the statistics of the archive again.
+    pub async fn update_stats(&self) {
+        match stats::aggregate(self).await {
+            Ok(stats) => *self.stats.write().unwrap() = Some(Arc::new(stats)),
+            Err(err) => warn!("Failed to aggregate the statistics: {:#}", err),
+        }
+    }
+
+    /// The status of the refresh in progress, if any.
+    pub fn running_refresh(&self) -> Option<refresh::JobStatus> {
+        let job = self.refresher.latest()?;
+        (!job.is_finished()).then(|| job.status())
+    }
+
+    /// Refresh the video list and wait for the refresh to finish.
+    ///
+    /// With `progress_bar`, progress is drawn as a bar instead of being logged.
+    pub async fn refresh_now(self: &Arc<Self>, progress_bar: bool) -> anyhow::Result<()> {
+        self.wait_for_refresh(progress_bar).await.result()
+    }
+
+    /// Run the detection on every source, printing the videos as they are found.
+    ///
+    /// Unlike a refresh, nothing is published or recorded, and the videos come in the order they
+    /// are found in rather than by rank. Fails only when every source did.
+    pub async fn fetch(&self, ndjson: bool) -> anyhow::Result<()> {
+        let mut succeeded = 0;
+        let mut failure = None;
+        for entry in &self.sources {
+            let limit = entry.settings.limit.unwrap_or(usize::MAX);
+            let mut printed = Ok(());
+            let mut on_fetched = |fetched: Fetched<&source::Story>| {
+                let Fetched::Video(rank, story) = fetched else {
+                    return;
+                };
+                if rank >= limit || printed.is_err() {
+                    return;
+                }
+                let video = Video::new(entry.source.as_ref(), story.clone());
+                if !self.blocklist.blocks(&video.id, &video.url) {
+                    printed = print_fetched(rank, &video, ndjson);
+                }
+            };
+            let result = entry
+                .source
+                .videos(hacker_news::Counter::new(), &mut on_fetched)
+                .await;
+            // E.g. the reader of a pipe went away.
+            printed.context("Failed to print the videos")?;
+            match result {
+                Ok(_) => succeeded += 1,
+                Err(err) => {
+                    warn!(
+                        source = entry.source.name(),
+                        "Failed to get the {} videos: {:#}",
+                        entry.source.label(),
+                        err
+                    );
+                    failure = Some(err);
+                }
+            }
+        }
+
+        if let (Some(err), 0) = (failure, succeeded) {
+            return Err(err);
+        }
+        self.cache.flush().await
+    }
+
+    /// Refresh the video list, or wait for the refresh in progress, returning the finished job.
+    pub async fn wait_for_refresh(self: &Arc<Self>, progress_bar: bool) -> Arc<refresh::Job> {
+        let job = self.start_refresh().unwrap_or_else(|running| running);
+        refresh::report(job.clone(), progress_bar).await;
+        job
+    }
+
+    /// Refresh the video list in the background.
+    ///
+    /// A source that fails keeps its previous videos, and the refresh only fails when every
+    /// source failed. Afterwards, what is held in memory is cut down to the configured caps.
+    pub fn start_refresh(self: &Arc<Self>) -> Result<Arc<refresh::Job>, Arc<refresh::Job>> {
+        let state = self.clone();
+        self.refresher.start(move |counter| async move {
+            // On a cold start there is nothing to show yet, so fill the list in as the videos
+            // arrive. Otherwise keep showing the previous list until the new one is complete,
+            // except for a fresh front page, which is shown on top of the rest as soon as it is.
+            let cold = state.videos().is_empty();
+            let max_videos = state.config.memory.max_videos.unwrap_or(usize::MAX);
+            let mut lists: Vec<Vec<Video>> = Vec::new();
+            let mut succeeded = 0;
+            let mut failure = None;
+            let mut summary = refresh::Summary::default();
+
+            for (index, entry) in state.sources.iter().enumerate() {
+                if let Some(videos) = entry.reusable() {
+                    lists.push(videos);
+                    succeeded += 1;
+                    continue;
+                }
+
+                let limit = entry.settings.limit.unwrap_or(usize::MAX);
+                let mut partial: Vec<(usize, Video)> = Vec::new();
+                let mut on_fetched = |fetched: Fetched<&source::Story>| {
+                    let fresh: Vec<Video> = match fetched {
+                        Fetched::Video(rank, story) if rank < limit => {
+                            let video = Video::new(entry.source.as_ref(), story.clone());
+                            let at = partial.partition_point(|(other, _)| *other < rank);
+                            partial.insert(at, (rank, video));
+                            if !cold {
+                                return;
+                            }
+                            partial.iter().map(|(_, video)| video.clone()).collect()
+                        }
+                        Fetched::FrontPage if !cold => {
+                            let mut fresh: Vec<_> =
+                                partial.iter().map(|(_, video)| video.clone()).collect();
+                            for video in entry.previous().unwrap_or_default() {
+                                if fresh.len() < limit && fresh.iter().all(|f| f.id != video.id) {
+                                    fresh.push(video);
+                                }
+                            }
+                            fresh
+                        }
+                        _ => return,
+                    };
+                    let mut shown = lists.clone();
+                    shown.push(fresh);
+                    // The sources after this one keep showing their previous videos meanwhile.
+                    let later = &state.sources[index + 1..];
+                    shown.extend(later.iter().filter_map(SourceEntry::previous));
+                    state.publish(shown);
+                };
+
+                match entry.source.videos(counter.clone(), &mut on_fetched).await {
+                    Ok(stories) => {
+                        succeeded += 1;
+                        let videos: Vec<_> = stories
+                            .into_iter()
+                            .take(limit)
+                            .map(|story| Video::new(entry.source.as_ref(), story))
+                            .collect();
+                        let videos = keep_newest(videos, max_videos);
+                        *entry.last.lock().unwrap() = Some((Instant::now(), videos.clone()));
+                        lists.push(videos);
+
+                        let detections = entry.source.detections();
+                        let name = entry.source.name();
+                        if let Err(err) = state.store.record_detections(name, &detections).await {
+                            warn!(source = name, "Failed to record the detections: {:#}", err);
+                        }
+                    }
+                    Err(err) => {
+                        warn!(
+                            source = entry.source.name(),
+                            "Failed to get the {} videos: {:#}",
+                            entry.source.label(),
+                            err
+                        );
+                        lists.extend(entry.previous());
+                        failure = Some(err);
+                        summary.failed_sources += 1;
+                    }
+                }
+            }
+
+            if let (Some(err), 0) = (failure, succeeded) {
+                return Err(err);
+            }
+            state.publish(lists);
+            state.cache.flush().await?;
+            if state.overload.serves_index() {
+                match export::render_index(&state) {
+                    Ok(html) => state.overload.set_snapshot(html),
+                    Err(err) => warn!("Failed to render the index for overloads: {:#}", err),
+                }
+            }
+
+            // Recorded as part of the refresh, so that `hnv refresh` records them too.
+            let videos = state.videos();
+            let now = OffsetDateTime::now_utc().unix_timestamp();
+            let listed = videos
+                .iter()
+                .map(|video| store::ListedVideo::new(video, now))
+                .collect();
+            match state.store.record_videos(listed).await {
+                Ok(new) => summary.new_videos = new,
+                Err(err) => warn!("Failed to record the listed videos: {:#}", err),
+            }
+
+            // Submissions of the followed users are listed apart, top stories or not.
+            if !state.config.hacker_news.followed.is_empty() {
+                state.refresh_followed().await;
+            }
+
+            // The previews of the watch pages are looked up in the background as well, apart from
+            // the posts so neither waits for the other.
+            let concurrency = state.config.refresh.oembed_concurrency;
+            if concurrency > 0 {
+                let state = state.clone();
+                let urls = videos.iter().map(|video| video.url.clone()).collect();
+                tokio::spawn(
+                    async move {
+                        let found = state.hn.prefetch_oembeds(urls, concurrency).await;
+                        debug!("Looked up the oEmbed data of {} videos", found);
+                        state.trim_cache().await;
+                    }
+                    .instrument(Span::current()),
+                );
+            }
+
+            // Posting may take a while, the refresh is done without it.
+            tokio::spawn({
+                let state = state.clone();
+                async move {
+                    let event = webhooks::Event::refresh_completed(videos.len());
+                    if let Err(err) = state.webhooks.emit(event).await {
+                        warn!("Failed to deliver the end of the refresh: {:#}", err);
+                    }
+                    notify::run(&state.notifiers, &state.store, &videos).await;
+                    state.update_stats().await;
+                }
+                .instrument(Span::current())
+            });
+
+            state.trim_cache().await;
+            Ok(summary)
+        })
+    }
+
+    /// Evict the responses fetched longest ago from the cache while it is held in memory, down
+    /// to the configured cap. A database on disk doesn't count against the memory.
+    async fn trim_cache(&self) {
+        let Some(max_cached) = self.config.memory.max_cached else {
+            return;
+        };
+        if !self.cache.in_memory() {
+            return;
+        }
+        match self.cache.trim(max_cached).await {
+            Ok(0) => {}
+            Ok(evicted) => info!("Evicted {} responses from the cache", evicted),
+            Err(err) => warn!("Failed to trim the cache: {:#}", err),
+        }
+    }
+
+    /// Get the highest scoring videos first listed since a time, in seconds since the Unix epoch,
+    /// leaving out those of sources that are no longer enabled.
+    pub async fn top_videos(&self, since: i64, limit: u32) -> anyhow::Result<Vec<Video>> {
+        let listed = self.store.top_videos(since, limit).await?;
+        Ok(listed
+            .into_iter()
+            .filter_map(|listed| self.recorded_video(listed))
+            .collect())
+    }
+
+    /// A video as recorded in the store, if its source is still enabled.
+    pub fn recorded_video(&self, listed: store::ListedVideo) -> Option<Video> {
+        let source = self.video_source(&listed.id.source)?;
+        let story = source::Story {
+            id: listed.id.id,
+            title: listed.title,
+            url: listed.url,
+            discussion_url: listed.discussion_url,
+            score: listed.score,
+            submitted_at: listed.submitted_at,
+            comments: listed.comments,
+        };
+        let video = Video::new(source, story);
+        (!self.blocklist.blocks(&video.id, &video.url)).then_some(video)
+    }
+
+    /// Show the videos of the sources, taking turns between them as weighted, up to the
+    /// configured cap.
+    fn publish(&self, lists: Vec<Vec<Video>>) {
+        let max_videos = self.config.memory.max_videos.unwrap_or(usize::MAX);
+        let lists = lists
+            .into_iter()
+            .map(|list| {
+                let source = list.first().and_then(|video| self.source(video.source));
+                (source.map_or(1, |entry| entry.settings.weight), list)
+            })
+            .collect();
+        let videos = source::interleave_weighted(lists)
+            .into_iter()
+            .filter(|video| !self.blocklist.blocks(&video.id, &video.url));
+        let videos = keep_newest(collapse_reposts(videos), max_videos);
+        *self.videos.write().unwrap() = Arc::new(videos);
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        self.published_at.send_replace(Some(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(now.as_secs()),
+        ));
+    }
+
+    /// The enabled source with a name, if any.
+    pub fn source(&self, name: &str) -> Option<&SourceEntry> {
+        self.sources
+            .iter()
+            .find(|entry| entry.source.name() == name)
+    }
+
+    /// The enabled sources, for the filter of the index.
+    pub fn source_links(&self) -> Vec<SourceLink> {
+        self.sources
+            .iter()
+            .map(|entry| SourceLink {
+                name: entry.source.name(),
+                label: entry.source.label(),
+            })
+            .collect()
+    }
+
+    /// The source videos are found on with a name, which may also be the submitted videos.
+    pub fn video_source(&self, name: &str) -> Option<&dyn Source> {
+        match self.source(name) {
+            Some(entry) => Some(entry.source.as_ref()),
+            None => (name == submissions::NAME).then_some(&self.submissions as &dyn Source),
+        }
+    }
+
+    /// Get a single video from its source, if it is a video.
+    pub async fn get_video(&self, id: &VideoId) -> anyhow::Result<Option<Video>> {
+        let Some(source) = self.video_source(&id.source) else {
+            return Ok(None);
+        };
+        let story = source.video(&id.id).await?;
+        let video = story.map(|story| Video::new(source, story));
+        Ok(video.filter(|video| !self.blocklist.blocks(&video.id, &video.url)))
+    }
+
+    /// Block a story or a domain, and take it off the published videos right away.
+    pub async fn block(&self, kind: &str, value: &str) -> anyhow::Result<Option<store::Block>> {
+        let block = self.blocklist.add(kind, value).await?;
+        if let Some(block) = &block {
+            info!("Blocked the {} {}", block.kind, block.value);
+            self.republish();
+        }
+        Ok(block)
+    }
+
+    /// Lift a block, and bring the videos it kept off back right away.
+    pub async fn unblock(&self, id: i64) -> anyhow::Result<bool> {
+        let removed = self.blocklist.remove(id).await?;
+        if removed {
+            info!("Lifted block {}", id);
+            self.republish();
+        }
+        Ok(removed)
+    }
+
+    /// Publish the videos of the last fetches again, e.g. after the blocklist changed.
+    fn republish(&self) {
+        let lists: Vec<_> = self
+            .sources
+            .iter()
+            .filter_map(SourceEntry::previous)
+            .collect();
+        match lists.is_empty() {
+            // Nothing was fetched completely yet, so only what is shown can be filtered.
+            true => self.publish(vec![self.videos().to_vec()]),
+            false => self.publish(lists),
+        }
+    }
+
+    /// List at least `wanted` videos if there are as many, fetching the deeper Hacker News top
+    /// stories a page at a time as long as there are fewer. The refreshes leave them for when a
+    /// visitor pages to them (see [`config::RefreshConfig::eager_ranks`]).
+    pub async fn list_deeper(&self, wanted: usize) {
+        let Some(entry) = self.source(hacker_news::NAME) else {
+            return;
+        };
+        let max_videos = self.config.memory.max_videos.unwrap_or(usize::MAX);
+        let limit = entry.settings.limit.unwrap_or(usize::MAX);
+        let _deeper = self.deeper.lock().await;
+        while self.videos().len() < wanted.min(max_videos) {
+            let Some(items) = self.hn.get_deeper_videos(hacker_news::FRONT_PAGE).await else {
+                break;
+            };
+            let videos: Vec<_> = items
+                .iter()
+                .filter_map(|item| item.story().ok())
+                .take(limit)
+                .map(|story| Video::new(self.hn.as_ref(), story))
+                .collect();
+            let videos = keep_newest(videos, max_videos);
+            match entry.last.lock().unwrap().as_mut() {
+                // The time of the refresh stays, the videos are only completed.
+                Some((_, last)) => *last = videos,
+                // Until the first refresh completes, there is nothing to complete.
+                None => break,
+            }
+            self.republish();
+        }
+    }
+
+    /// Fetch a Hacker News story again and publish it in place of what the last refresh got, or
+    /// take it off the published videos if it no longer is one. Returns the video, if it is one.
+    ///
+    /// A story that wasn't published before doesn't show up until the next refresh, which ranks
+    /// it.
+    pub async fn refetch_video(&self, id: i32) -> anyhow::Result<Option<Video>> {
+        let item = self.hn.refetch_video(id).await?;
+        let video = match item {
+            Some(item) => Some(Video::new(self.hn.as_ref(), item.story()?)),
+            None => None,
+        };
+        let video_id = VideoId::new(hacker_news::NAME, id.to_string());
+        let update = |videos: &mut Vec<Video>| {
+            let listed = videos.iter().any(|listed| listed.id == video_id);
+            match &video {
+                Some(video) => videos
+                    .iter_mut()
+                    .filter(|listed| listed.id == video_id)
+                    .for_each(|listed| *listed = video.clone()),
+                None => videos.retain(|listed| listed.id != video_id),
+            }
+            listed
+        };
+
+        let mut listed = false;
+        for entry in &self.sources {
+            if let Some((_, videos)) = entry.last.lock().unwrap().as_mut() {
+                listed |= update(videos);
+            }
+        }
+        if self.sources.iter().all(|entry| entry.previous().is_none()) {
+            // Nothing was fetched completely yet, so only what is shown can be updated.
+            let mut videos = self.videos().to_vec();
+            listed |= update(&mut videos);
+            self.publish(vec![videos]);
+        } else {
+            self.republish();
+        }
+        info!(item = id, "Fetched item {} again", id);
+
+        // The archive keeps the title of the last refresh otherwise.
+        if let Some(video) = video.as_ref().filter(|_| listed) {
+            let now = OffsetDateTime::now_utc().unix_timestamp();
+            self.store
+                .record_videos(vec![store::ListedVideo::new(video, now)])
+                .await?;
+        }
+        Ok(video)
+    }
+
+    /// Add a video by hand, with the title of its platform unless one is given.
+    pub async fn submit(&self, submission: &submissions::Submission) -> anyhow::Result<Video> {
+        let title = match submission.title() {
+            Some(title) => title.to_string(),
+            None => self
+                .hn
+                .oembed(&submission.url)
+                .await
+                .and_then(|oembed| oembed.title)
+                .unwrap_or_else(|| submission.url.clone()),
+        };
+        let story = self.submissions.add(&submission.url, &title).await?;
+        let video = Video::new(&self.submissions, story);
+        info!(video = %video.id, "Added {}", video.url);
+
+        let event = webhooks::Event::video_detected(&video);
+        let webhooks = self.webhooks.clone();
+        tokio::spawn(async move {
+            if let Err(err) = webhooks.emit(event).await {
+                warn!("Failed to deliver a submitted video: {:#}", err);
+            }
+        });
+        Ok(video)
+    }
+}
+
+/// A video in the watch history of a visitor.
+pub struct HistoryEntry {
+    pub video: Video,
+    /// Where the visitor stopped watching, in seconds.
+    pub position: u32,
+}
+
+impl HistoryEntry {
+    /// The position as shown to visitors, like `1:02:03` or `2:03`.
+    pub fn position_label(&self) -> String {
+        let (hours, minutes, seconds) = (
+            self.position / 3600,
+            self.position / 60 % 60,
+            self.position % 60,
+        );
+        match hours {
+            0 => format!("{}:{:02}", minutes, seconds),
+            _ => format!("{}:{:02}:{:02}", hours, minutes, seconds),
+        }
+    }
+}
+
+/// A video as printed by `hnv fetch --ndjson`.
+#[derive(Serialize)]
+struct FetchedVideo<'a> {
+    /// The rank of the story on its source, counting from 1.
+    rank: usize,
+    #[serde(flatten)]
+    video: &'a Video,
+}
+
+/// Print a video found by `hnv fetch` on a line of its own.
+fn print_fetched(rank: usize, video: &Video, ndjson: bool) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    if ndjson {
+        let line = FetchedVideo {
+            rank: rank + 1,
+            video,
+        };
+        serde_json::to_writer(&mut stdout, &line)?;
+        writeln!(stdout)
+    } else {
+        writeln!(stdout, "{}\t{}\t{}", video.id, video.title, video.url)
+    }
+}
@@ -0,0 +1,194 @@
+/// Statistics of the archive, for the `/stats` page.
+///
+/// Aggregating the whole archive takes a scan of the recorded videos and an oEmbed lookup per
+/// YouTube video for its channel, which is too slow for a page view. The statistics are computed
+/// in the background when the server starts and after every refresh, and the page shows the last
+/// result.
+use std::collections::HashMap;
+
+use anyhow::Context;
+use time::{Duration, OffsetDateTime};
+use tracing::info;
+
+use crate::state::State;
+
+/// The days charted, up to today.
+const DAYS: i64 = 30;
+
+/// The channels listed.
+const TOP_CHANNELS: usize = 10;
+
+/// The height of the bars of the busiest day, in pixels.
+pub const CHART_HEIGHT: u32 = 100;
+
+pub struct ArchiveStats {
+    /// When the statistics were computed, in RFC 3339.
+    pub computed_at: String,
+    /// All the recorded videos.
+    pub videos: usize,
+    /// The videos first listed on each of the last days, the oldest first.
+    pub days: Vec<Day>,
+    /// The platforms by number of videos, the most common first.
+    pub platforms: Vec<Group>,
+    /// The YouTube channels by number of videos, the most common first.
+    pub channels: Vec<Group>,
+    /// The YouTube videos whose channel is known, for the leaderboard of channels.
+    pub channel_videos: Vec<ChannelVideo>,
+    /// The average score of the videos that have one.
+    pub average_score: Option<f64>,
+}
+
+pub struct Day {
+    /// The day, as `YYYY-MM-DD`.
+    pub date: String,
+    pub videos: usize,
+    /// The height of its bar in the chart.
+    pub height: u32,
+}
+
+/// A YouTube video of a known channel.
+pub struct ChannelVideo {
+    pub channel: String,
+    /// When the video was first listed, in seconds since the Unix epoch.
+    pub first_seen: i64,
+    pub score: Option<i64>,
+}
+
+/// A channel on the leaderboard.
+pub struct Channel {
+    pub name: String,
+    pub videos: usize,
+    /// The sum of the scores of the videos.
+    pub score: i64,
+}
+
+impl ArchiveStats {
+    /// Rank the channels by the number of their videos first listed since a time, in seconds
+    /// since the Unix epoch, and then by the sum of their scores.
+    pub fn leaderboard(&self, since: i64) -> Vec<Channel> {
+        let mut channels: HashMap<&str, (usize, i64)> = HashMap::new();
+        for video in &self.channel_videos {
+            if video.first_seen >= since {
+                let (videos, score) = channels.entry(&video.channel).or_default();
+                *videos += 1;
+                *score += video.score.unwrap_or(0);
+            }
+        }
+        let mut channels: Vec<_> = channels
+            .into_iter()
+            .map(|(name, (videos, score))| Channel {
+                name: name.to_string(),
+                videos,
+                score,
+            })
+            .collect();
+        channels.sort_by(|a, b| {
+            (b.videos, b.score)
+                .cmp(&(a.videos, a.score))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        channels
+    }
+}
+
+/// Videos grouped by a property.
+pub struct Group {
+    pub name: String,
+    pub videos: usize,
+    pub average_score: Option<f64>,
+}
+
+/// Aggregate the recorded videos.
+pub async fn aggregate(state: &State) -> anyhow::Result<ArchiveStats> {
+    let now = OffsetDateTime::now_utc();
+    let time_zone = state.config.timezone;
+    let today = time_zone.today();
+    let first_day = today - Duration::days(DAYS - 1);
+    let mut per_day = vec![0; DAYS as usize];
+    let mut platforms = Scores::default();
+    let mut channels = Scores::default();
+    let mut channel_videos = Vec::new();
+    let mut all = Scores::default();
+
+    let recorded = state.store.all_videos().await?;
+    let count = recorded.len();
+    for listed in recorded {
+        let first_seen = listed.first_seen;
+        let day = time_zone
+            .date(listed.first_seen)
+            .context("Invalid first listing time")?;
+        if day >= first_day && day <= today {
+            per_day[(day - first_day).whole_days() as usize] += 1;
+        }
+        let Some(video) = state.recorded_video(listed) else {
+            continue;
+        };
+        all.add(String::new(), video.score);
+        platforms.add(video.platform(), video.score);
+        if video.youtube_id().is_some() {
+            let channel = state.hn.oembed(&video.url).await;
+            if let Some(channel) = channel.and_then(|oembed| oembed.author_name) {
+                channels.add(channel.clone(), video.score);
+                channel_videos.push(ChannelVideo {
+                    channel,
+                    first_seen,
+                    score: video.score,
+                });
+            }
+        }
+    }
+
+    let busiest = per_day.iter().copied().max().unwrap_or(0).max(1);
+    let days = per_day
+        .into_iter()
+        .enumerate()
+        .map(|(index, videos)| Day {
+            date: (first_day + Duration::days(index as i64)).to_string(),
+            videos,
+            height: (videos as u32 * CHART_HEIGHT) / busiest as u32,
+        })
+        .collect();
+    let mut channels = channels.groups();
+    channels.truncate(TOP_CHANNELS);
+    let stats = ArchiveStats {
+        computed_at: now.format(&time::format_description::well_known::Rfc3339)?,
+        videos: count,
+        days,
+        platforms: platforms.groups(),
+        channels,
+        channel_videos,
+        average_score: all.groups().first().and_then(|all| all.average_score),
+    };
+    info!("Aggregated the statistics of {} videos", count);
+    Ok(stats)
+}
+
+/// The number of videos and the sum of their scores by group.
+#[derive(Default)]
+struct Scores(HashMap<String, (usize, i64, usize)>);
+
+impl Scores {
+    fn add(&mut self, name: String, score: Option<i64>) {
+        let (videos, total, scored) = self.0.entry(name).or_default();
+        *videos += 1;
+        if let Some(score) = score {
+            *total += score;
+            *scored += 1;
+        }
+    }
+
+    /// The groups, the largest first.
+    fn groups(self) -> Vec<Group> {
+        let mut groups: Vec<_> = self
+            .0
+            .into_iter()
+            .map(|(name, (videos, total, scored))| Group {
+                name,
+                videos,
+                average_score: (scored > 0).then(|| total as f64 / scored as f64),
+            })
+            .collect();
+        groups.sort_by(|a, b| b.videos.cmp(&a.videos).then_with(|| a.name.cmp(&b.name)));
+        groups
+    }
+}
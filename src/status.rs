@@ -0,0 +1,234 @@
+/// Watching the Hacker News API, for the `/status` page.
+///
+/// Visitors missing videos can't tell whether hnv or Hacker News is at fault. A background task
+/// requests the newest item id every `status.interval` seconds, which is cheap for the API and
+/// doesn't touch the cache, and keeps the latency and outcome of the last day of requests in
+/// memory. The page shows the latest outcome and charts the hours before it. Nothing is
+/// persisted, the history starts over with the server.
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime},
+};
+
+use reqwest::Client;
+use tracing::debug;
+
+use crate::config::StatusConfig;
+
+/// The hours charted, up to the current one.
+const HOURS: u64 = 24;
+
+/// How long the API may take to answer before the request counts as failed.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The height of the bar of the slowest hour, in pixels.
+pub const CHART_HEIGHT: u32 = 100;
+
+/// A request to the API.
+struct Probe {
+    time: SystemTime,
+    latency: Duration,
+    /// Why the request failed, if it did.
+    error: Option<String>,
+}
+
+/// How the API is doing, as of the latest request.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Health {
+    /// Nothing was requested yet.
+    Unknown,
+    Up,
+    /// Answering, but slower than `status.slow`.
+    Slow,
+    Down,
+}
+
+impl Health {
+    pub fn label_key(self) -> &'static str {
+        match self {
+            Health::Unknown => "status-unknown",
+            Health::Up => "status-up",
+            Health::Slow => "status-slow",
+            Health::Down => "status-down",
+        }
+    }
+
+    /// The class of the label, which colors it.
+    pub fn class(self) -> &'static str {
+        match self {
+            Health::Unknown => "unknown",
+            Health::Up => "up",
+            Health::Slow => "slow",
+            Health::Down => "down",
+        }
+    }
+}
+
+/// The requests of an hour.
+pub struct Hour {
+    /// When the hour started, in RFC 3339.
+    pub start: String,
+    pub probes: usize,
+    pub failed: usize,
+    /// The median latency of the answered requests, in milliseconds.
+    pub latency: Option<u64>,
+    /// The height of its bar in the chart.
+    pub height: u32,
+}
+
+/// What the page shows.
+pub struct Summary {
+    pub health: Health,
+    /// When the latest request was made, in RFC 3339.
+    pub checked_at: Option<String>,
+    /// How long the latest request took, in milliseconds.
+    pub latency: Option<u64>,
+    /// Why the latest request failed, if it did.
+    pub error: Option<String>,
+    /// The median latency of the answered requests of the day, in milliseconds.
+    pub median: Option<u64>,
+    /// The share of failed requests of the day, in percent.
+    pub error_rate: Option<f64>,
+    /// The last hours, the oldest first.
+    pub hours: Vec<Hour>,
+}
+
+pub struct Monitor {
+    client: Client,
+    url: String,
+    interval: Duration,
+    slow: Duration,
+    /// The requests of the last day, the oldest first.
+    probes: Mutex<VecDeque<Probe>>,
+}
+
+impl Monitor {
+    pub fn new(config: &StatusConfig, base_url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            url: format!("{}/maxitem.json", base_url.trim_end_matches('/')),
+            interval: Duration::from_secs(config.interval),
+            slow: Duration::from_millis(config.slow),
+            probes: Default::default(),
+        }
+    }
+
+    /// Request the API every interval, forever.
+    pub async fn run(&self) {
+        let mut interval = tokio::time::interval(self.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let probe = self.probe().await;
+            if let Some(error) = &probe.error {
+                debug!("Hacker News is unavailable: {}", error);
+            }
+
+            let mut probes = self.probes.lock().unwrap();
+            probes.push_back(probe);
+            let since = SystemTime::now() - Duration::from_secs(HOURS * 60 * 60);
+            while probes.front().is_some_and(|probe| probe.time < since) {
+                probes.pop_front();
+            }
+        }
+    }
+
+    async fn probe(&self) -> Probe {
+        let time = SystemTime::now();
+        let started = Instant::now();
+        let response = self.client.get(&self.url).timeout(TIMEOUT).send().await;
+        let error = match response.and_then(|response| response.error_for_status()) {
+            Ok(response) => match response.json::<u64>().await {
+                Ok(_) => None,
+                Err(err) => Some(format!("Unexpected answer: {}", err)),
+            },
+            Err(err) => Some(format!("{:#}", anyhow::Error::from(err))),
+        };
+        Probe {
+            time,
+            latency: started.elapsed(),
+            error,
+        }
+    }
+
+    /// Sum up the requests of the last day.
+    pub fn summary(&self) -> Summary {
+        let probes = self.probes.lock().unwrap();
+        let latest = probes.back();
+        let health = match latest {
+            None => Health::Unknown,
+            Some(probe) if probe.error.is_some() => Health::Down,
+            Some(probe) if probe.latency > self.slow => Health::Slow,
+            Some(_) => Health::Up,
+        };
+
+        let failed = probes.iter().filter(|probe| probe.error.is_some()).count();
+        let error_rate = (!probes.is_empty()).then(|| failed as f64 * 100.0 / probes.len() as f64);
+
+        Summary {
+            health,
+            checked_at: latest.map(|probe| crate::routes::format_time(probe.time)),
+            latency: latest.map(|probe| millis(probe.latency)),
+            error: latest.and_then(|probe| probe.error.clone()),
+            median: median(probes.iter()),
+            error_rate,
+            hours: hours(&probes),
+        }
+    }
+}
+
+/// Group the requests by the hour, charting their median latencies.
+fn hours(probes: &VecDeque<Probe>) -> Vec<Hour> {
+    let hour = Duration::from_secs(60 * 60);
+    let now = SystemTime::now();
+    let elapsed = now
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        % hour.as_secs();
+    let current = now - Duration::from_secs(elapsed);
+
+    let mut hours: Vec<_> = (0..HOURS as u32)
+        .rev()
+        .map(|ago| {
+            let start = current - hour * ago;
+            let of_hour: Vec<_> = probes
+                .iter()
+                .filter(|probe| probe.time >= start && probe.time < start + hour)
+                .collect();
+            Hour {
+                start: crate::routes::format_time(start),
+                probes: of_hour.len(),
+                failed: of_hour.iter().filter(|probe| probe.error.is_some()).count(),
+                latency: median(of_hour.into_iter()),
+                height: 0,
+            }
+        })
+        .collect();
+
+    let slowest = hours
+        .iter()
+        .filter_map(|hour| hour.latency)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    for hour in &mut hours {
+        hour.height = (hour.latency.unwrap_or(0) * CHART_HEIGHT as u64 / slowest) as u32;
+    }
+    hours
+}
+
+/// The median latency of the answered requests, in milliseconds.
+fn median<'a>(probes: impl Iterator<Item = &'a Probe>) -> Option<u64> {
+    let mut latencies: Vec<_> = probes
+        .filter(|probe| probe.error.is_none())
+        .map(|probe| millis(probe.latency))
+        .collect();
+    latencies.sort_unstable();
+    latencies.get(latencies.len() / 2).copied()
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
@@ -0,0 +1,2716 @@
+/// Persistent per-visitor data, such as the watch-later list.
+///
+/// Items are stored by their [`VideoId`], in its textual form. SQLite stores Hacker News IDs as
+/// integers either way, so lists saved before there were other sources stay valid.
+///
+/// The store lives in the same SQLite database as the cache, but unlike the cache its contents
+/// can't be re-fetched from Hacker News, so it is kept in tables of its own.
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use tokio_rusqlite::{params, params_from_iter, types::Value, Connection, OptionalExtension};
+use utoipa::ToSchema;
+
+use crate::{
+    config::TimeZone,
+    hacker_news::Detections,
+    video::{Video, VideoId},
+};
+
+/// Tables holding a per-session set of item IDs.
+const ITEM_LISTS: [&str; 3] = ["watch_later", "hidden", "favorites"];
+
+/// How long the items posted by notifiers are remembered.
+const NOTIFIED_DAYS: u32 = 30;
+
+/// How long the deliveries of webhooks are logged.
+const DELIVERIES_DAYS: u32 = 7;
+
+/// A `LIKE` pattern matching `text` literally between a prefix and a suffix, to be used with
+/// `ESCAPE '\'`.
+fn like_pattern(prefix: &str, text: &str, suffix: &str) -> String {
+    let text = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("{}{}{}", prefix, text, suffix)
+}
+
+/// The version of the tables this store creates, recorded in the database for `hnv doctor`.
+/// Raise it with every migration.
+pub const SCHEMA_VERSION: i64 = 4;
+
+/// The store struct that stores the connection to the SQLite database.
+#[derive(Clone)]
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    /// Create a new store instance on top of an open database connection.
+    ///
+    /// This function creates the store tables if they don't exist yet.
+    pub async fn new(conn: Connection) -> anyhow::Result<Self> {
+        conn.call(|conn| {
+            for table in ITEM_LISTS {
+                conn.execute(
+                    &format!(
+                        "CREATE TABLE IF NOT EXISTS {table} (
+                        session TEXT NOT NULL,
+                        item_id INTEGER NOT NULL,
+                        added_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+                        PRIMARY KEY (session, item_id)
+                    )"
+                    ),
+                    [],
+                )?;
+            }
+
+            // The sessions of visitors, which the rows of the lists above belong to. Sessions
+            // started before there was a table for them are taken over from the lists, and are
+            // the only ones whose cookies may come without a signature.
+            let has_sessions = conn
+                .prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'sessions'")?
+                .exists([])?;
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS sessions (
+                    id TEXT PRIMARY KEY,
+                    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+                    seen_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+                    signed INTEGER NOT NULL DEFAULT 1
+                )",
+                [],
+            )?;
+            if !has_sessions {
+                for table in ITEM_LISTS {
+                    conn.execute(
+                        &format!(
+                            "INSERT OR IGNORE INTO sessions (id, signed) SELECT session, 0 FROM {table}"
+                        ),
+                        [],
+                    )?;
+                }
+            }
+            // Tables from before the column can't tell the sessions apart, so they are all taken to
+            // be signed.
+            let has_signed = conn
+                .prepare("SELECT 1 FROM pragma_table_info('sessions') WHERE name = 'signed'")?
+                .exists([])?;
+            if !has_signed {
+                conn.execute(
+                    "ALTER TABLE sessions ADD COLUMN signed INTEGER NOT NULL DEFAULT 1",
+                    [],
+                )?;
+            }
+
+            // The videos visitors opened, with where they stopped watching, in seconds.
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS history (
+                    session TEXT NOT NULL,
+                    item_id INTEGER NOT NULL,
+                    position INTEGER NOT NULL DEFAULT 0,
+                    opened_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+                    PRIMARY KEY (session, item_id)
+                )",
+                [],
+            )?;
+
+            // The playlists of visitors, with their videos in the order they are played.
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS playlists (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    session TEXT NOT NULL,
+                    name TEXT NOT NULL,
+                    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+                    token TEXT
+                )",
+                [],
+            )?;
+            // Anyone with the unguessable token of a playlist can see it.
+            let has_token = conn
+                .prepare("SELECT 1 FROM pragma_table_info('playlists') WHERE name = 'token'")?
+                .exists([])?;
+            if !has_token {
+                conn.execute("ALTER TABLE playlists ADD COLUMN token TEXT", [])?;
+            }
+            conn.execute(
+                "UPDATE playlists SET token = lower(hex(randomblob(16))) WHERE token IS NULL",
+                [],
+            )?;
+            conn.execute(
+                "CREATE UNIQUE INDEX IF NOT EXISTS playlists_token ON playlists (token)",
+                [],
+            )?;
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS playlist_items (
+                    playlist_id INTEGER NOT NULL,
+                    item_id INTEGER NOT NULL,
+                    position INTEGER NOT NULL,
+                    PRIMARY KEY (playlist_id, item_id)
+                )",
+                [],
+            )?;
+
+            // The private feeds of visitors, with the source and the space-separated tags they
+            // are narrowed to.
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS feeds (
+                    session TEXT PRIMARY KEY,
+                    token TEXT NOT NULL UNIQUE,
+                    source TEXT,
+                    tags TEXT NOT NULL DEFAULT '',
+                    push_topic TEXT
+                )",
+                [],
+            )?;
+            let has_push_topic = conn
+                .prepare("SELECT 1 FROM pragma_table_info('feeds') WHERE name = 'push_topic'")?
+                .exists([])?;
+            if !has_push_topic {
+                conn.execute("ALTER TABLE feeds ADD COLUMN push_topic TEXT", [])?;
+            }
+
+            // The searches visitors saved, and the new videos that matched them since. Anyone with
+            // the unguessable token of a search can follow its matches.
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS saved_searches (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    session TEXT NOT NULL,
+                    query TEXT NOT NULL,
+                    token TEXT NOT NULL UNIQUE,
+                    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+                    UNIQUE (session, query)
+                )",
+                [],
+            )?;
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS saved_search_matches (
+                    search_id INTEGER NOT NULL,
+                    item_id INTEGER NOT NULL,
+                    matched_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+                    PRIMARY KEY (search_id, item_id)
+                )",
+                [],
+            )?;
+
+            // Clicks on short links are anonymous, they are only counted per item.
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS clicks (
+                    item_id INTEGER NOT NULL,
+                    clicked_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+                )",
+                [],
+            )?;
+            conn.execute(
+                "CREATE INDEX IF NOT EXISTS clicks_clicked_at ON clicks (clicked_at)",
+                [],
+            )?;
+
+            // The notifiers that have seen a refresh, and the videos each of them posted, so
+            // every video is posted once.
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS notifiers (
+                    notifier TEXT PRIMARY KEY,
+                    started_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+                )",
+                [],
+            )?;
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS notified (
+                    notifier TEXT NOT NULL,
+                    item_id INTEGER NOT NULL,
+                    notified_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+                    PRIMARY KEY (notifier, item_id)
+                )",
+                [],
+            )?;
+
+            // The subscribers of the webhooks, with the events each subscribed to separated by
+            // commas, and a log of what was delivered to them.
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS webhooks (
+                    id INTEGER PRIMARY KEY,
+                    url TEXT NOT NULL,
+                    secret TEXT NOT NULL,
+                    events TEXT NOT NULL,
+                    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+                )",
+                [],
+            )?;
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS webhook_deliveries (
+                    id TEXT PRIMARY KEY,
+                    webhook_id INTEGER NOT NULL,
+                    event TEXT NOT NULL,
+                    attempts INTEGER NOT NULL,
+                    status INTEGER,
+                    error TEXT,
+                    delivered_at INTEGER NOT NULL
+                )",
+                [],
+            )?;
+            conn.execute(
+                "CREATE INDEX IF NOT EXISTS webhook_deliveries_webhook
+                ON webhook_deliveries (webhook_id, delivered_at)",
+                [],
+            )?;
+
+            // The videos listed by the refreshes, with when they were first and last listed.
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS videos (
+                    item_id TEXT PRIMARY KEY,
+                    title TEXT NOT NULL,
+                    url TEXT NOT NULL,
+                    discussion_url TEXT NOT NULL,
+                    score INTEGER,
+                    first_seen INTEGER NOT NULL,
+                    last_seen INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+                    submitted_at INTEGER,
+                    comments INTEGER
+                )",
+                [],
+            )?;
+            let has_submitted_at = conn
+                .prepare("SELECT 1 FROM pragma_table_info('videos') WHERE name = 'submitted_at'")?
+                .exists([])?;
+            if !has_submitted_at {
+                conn.execute("ALTER TABLE videos ADD COLUMN submitted_at INTEGER", [])?;
+            }
+            let has_comments = conn
+                .prepare("SELECT 1 FROM pragma_table_info('videos') WHERE name = 'comments'")?
+                .exists([])?;
+            if !has_comments {
+                conn.execute("ALTER TABLE videos ADD COLUMN comments INTEGER", [])?;
+            }
+            conn.execute(
+                "CREATE INDEX IF NOT EXISTS videos_first_seen ON videos (first_seen)",
+                [],
+            )?;
+
+            // The scores of the videos as sampled by every refresh, to tell how they climbed.
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS scores (
+                    item_id TEXT NOT NULL,
+                    sampled_at INTEGER NOT NULL,
+                    score INTEGER NOT NULL,
+                    PRIMARY KEY (item_id, sampled_at)
+                )",
+                [],
+            )?;
+            conn.execute(
+                "CREATE INDEX IF NOT EXISTS scores_sampled_at ON scores (sampled_at)",
+                [],
+            )?;
+
+            // The videos queued for download, and where they were downloaded to. Pinned videos
+            // are kept when the downloads are cleaned up.
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS downloads (
+                    item_id TEXT PRIMARY KEY,
+                    url TEXT NOT NULL,
+                    title TEXT NOT NULL,
+                    state TEXT NOT NULL,
+                    file TEXT,
+                    error TEXT,
+                    queued_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+                    updated_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+                    pinned INTEGER NOT NULL DEFAULT 0
+                )",
+                [],
+            )?;
+            let has_pinned = conn
+                .prepare("SELECT 1 FROM pragma_table_info('downloads') WHERE name = 'pinned'")?
+                .exists([])?;
+            if !has_pinned {
+                conn.execute(
+                    "ALTER TABLE downloads ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0",
+                    [],
+                )?;
+            }
+
+            // Keys made by the instance, such as the signing key of the ActivityPub actor in PEM
+            // format.
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS keys (
+                    name TEXT PRIMARY KEY,
+                    pem TEXT NOT NULL,
+                    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+                )",
+                [],
+            )?;
+
+            // The Fediverse accounts following the ActivityPub actor, with the inbox activities
+            // are delivered to.
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS followers (
+                    actor TEXT PRIMARY KEY,
+                    inbox TEXT NOT NULL,
+                    followed_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+                )",
+                [],
+            )?;
+
+            // The outcomes of the video detection, summed over the refreshes of a day in UTC.
+            // Rejected stories have an empty platform.
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS detections (
+                    day TEXT NOT NULL,
+                    source TEXT NOT NULL,
+                    rule TEXT NOT NULL,
+                    platform TEXT NOT NULL,
+                    count INTEGER NOT NULL,
+                    PRIMARY KEY (day, source, rule, platform)
+                )",
+                [],
+            )?;
+
+            // The stories and domains kept off the instance, see `blocklist`.
+            conn.execute(
+                "CREATE TABLE IF NOT EXISTS blocklist (
+                    id INTEGER PRIMARY KEY,
+                    kind TEXT NOT NULL,
+                    value TEXT NOT NULL,
+                    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+                    UNIQUE (kind, value)
+                )",
+                [],
+            )?;
+
+            // Don't lower the version of a database a newer version migrated.
+            let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+            if version < SCHEMA_VERSION {
+                conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+            }
+
+            tokio_rusqlite::Result::Ok(())
+        })
+        .await?;
+        Ok(Self { conn })
+    }
+
+    /// Get the items a session saved for later, most recently saved first.
+    pub async fn watch_later(&self, session: &str) -> anyhow::Result<Vec<VideoId>> {
+        self.items("watch_later", session).await
+    }
+
+    /// Save an item for later.
+    pub async fn add_watch_later(&self, session: &str, item_id: &VideoId) -> anyhow::Result<()> {
+        self.add_item("watch_later", session, item_id).await
+    }
+
+    /// Remove an item from the watch-later list.
+    pub async fn remove_watch_later(&self, session: &str, item_id: &VideoId) -> anyhow::Result<()> {
+        self.remove_item("watch_later", session, item_id).await
+    }
+
+    /// Get the items a session dismissed.
+    pub async fn hidden(&self, session: &str) -> anyhow::Result<Vec<VideoId>> {
+        self.items("hidden", session).await
+    }
+
+    /// Dismiss an item so it is no longer listed.
+    pub async fn hide(&self, session: &str, item_id: &VideoId) -> anyhow::Result<()> {
+        self.add_item("hidden", session, item_id).await
+    }
+
+    /// Bring back a dismissed item.
+    pub async fn unhide(&self, session: &str, item_id: &VideoId) -> anyhow::Result<()> {
+        self.remove_item("hidden", session, item_id).await
+    }
+
+    /// Get the items a session loves, most recently loved first.
+    pub async fn favorites(&self, session: &str) -> anyhow::Result<Vec<VideoId>> {
+        self.items("favorites", session).await
+    }
+
+    /// Love an item.
+    pub async fn add_favorite(&self, session: &str, item_id: &VideoId) -> anyhow::Result<()> {
+        self.add_item("favorites", session, item_id).await
+    }
+
+    /// Stop loving an item.
+    pub async fn remove_favorite(&self, session: &str, item_id: &VideoId) -> anyhow::Result<()> {
+        self.remove_item("favorites", session, item_id).await
+    }
+
+    /// Note that a session opened an item, moving it to the top of its history. Returns where the
+    /// session stopped watching it before.
+    pub async fn record_opened(&self, session: &str, item_id: &VideoId) -> anyhow::Result<u32> {
+        let session = session.to_string();
+        let item_id = item_id.to_string();
+
+        let position = self
+            .conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO history (session, item_id) VALUES (?1, ?2)
+                    ON CONFLICT (session, item_id)
+                    DO UPDATE SET opened_at = CAST(strftime('%s', 'now') AS INTEGER)",
+                    params![session, item_id],
+                )?;
+                let position = conn.query_row(
+                    "SELECT position FROM history WHERE session = ?1 AND item_id = ?2",
+                    params![session, item_id],
+                    |row| row.get(0),
+                )?;
+                Ok(position)
+            })
+            .await?;
+
+        Ok(position)
+    }
+
+    /// Note where a session is in watching an item, in seconds. Zero means it wasn't started or
+    /// was watched to the end.
+    pub async fn record_position(
+        &self,
+        session: &str,
+        item_id: &VideoId,
+        position: u32,
+    ) -> anyhow::Result<()> {
+        let session = session.to_string();
+        let item_id = item_id.to_string();
+
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO history (session, item_id, position) VALUES (?1, ?2, ?3)
+                    ON CONFLICT (session, item_id) DO UPDATE SET position = excluded.position,
+                        opened_at = CAST(strftime('%s', 'now') AS INTEGER)",
+                    params![session, item_id, position],
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get the items a session opened with their positions, the last opened first.
+    pub async fn history(&self, session: &str, limit: u32) -> anyhow::Result<Vec<(VideoId, u32)>> {
+        let session = session.to_string();
+
+        let result = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT CAST(item_id AS TEXT), position FROM history WHERE session = ?1
+                    ORDER BY opened_at DESC, rowid DESC LIMIT ?2",
+                )?;
+                let items = stmt
+                    .query_map(params![session, limit], |row| {
+                        Ok((row.get(0)?, row.get(1)?))
+                    })?
+                    .collect::<Result<Vec<(String, u32)>, _>>()?;
+                Ok(items)
+            })
+            .await?;
+
+        Ok(result
+            .into_iter()
+            .filter_map(|(id, position)| Some((id.parse().ok()?, position)))
+            .collect())
+    }
+
+    /// Get the playlists of a session, the last created first.
+    pub async fn playlists(&self, session: &str) -> anyhow::Result<Vec<Playlist>> {
+        let session = session.to_string();
+
+        let playlists = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT {PLAYLIST_COLUMNS} FROM playlists WHERE session = ?1 ORDER BY id DESC"
+                ))?;
+                let playlists = stmt
+                    .query_map(params![session], |row| {
+                        Ok(Playlist {
+                            id: row.get(0)?,
+                            name: row.get(1)?,
+                            token: row.get(2)?,
+                            videos: row.get(3)?,
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(playlists)
+            })
+            .await?;
+
+        Ok(playlists)
+    }
+
+    /// Create an empty playlist, returning its ID.
+    pub async fn create_playlist(&self, session: &str, name: &str) -> anyhow::Result<i64> {
+        let session = session.to_string();
+        let name = name.to_string();
+
+        let id = self
+            .conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO playlists (session, name, token)
+                    VALUES (?1, ?2, lower(hex(randomblob(16))))",
+                    params![session, name],
+                )?;
+                Ok(conn.last_insert_rowid())
+            })
+            .await?;
+
+        Ok(id)
+    }
+
+    /// Get a playlist of a session and its items, in the order they are played.
+    pub async fn playlist(
+        &self,
+        session: &str,
+        id: i64,
+    ) -> anyhow::Result<Option<(Playlist, Vec<VideoId>)>> {
+        let session = session.to_string();
+        self.find_playlist("id = ?1 AND session = ?2", vec![id.into(), session.into()])
+            .await
+    }
+
+    /// Get the playlist shared under a token and its items, in the order they are played.
+    pub async fn shared_playlist(
+        &self,
+        token: &str,
+    ) -> anyhow::Result<Option<(Playlist, Vec<VideoId>)>> {
+        let token = token.to_string();
+        self.find_playlist("token = ?1", vec![token.into()]).await
+    }
+
+    /// Get the playlist matching an SQL condition on `params` and its items.
+    async fn find_playlist(
+        &self,
+        condition: &'static str,
+        params: Vec<Value>,
+    ) -> anyhow::Result<Option<(Playlist, Vec<VideoId>)>> {
+        let result = self
+            .conn
+            .call(move |conn| {
+                let playlist = conn
+                    .query_row(
+                        &format!("SELECT {PLAYLIST_COLUMNS} FROM playlists WHERE {condition}"),
+                        params_from_iter(params),
+                        |row| {
+                            Ok(Playlist {
+                                id: row.get(0)?,
+                                name: row.get(1)?,
+                                token: row.get(2)?,
+                                videos: row.get(3)?,
+                            })
+                        },
+                    )
+                    .optional()?;
+                let Some(playlist) = playlist else {
+                    return Ok(None);
+                };
+                let mut stmt = conn.prepare(
+                    "SELECT CAST(item_id AS TEXT) FROM playlist_items
+                    WHERE playlist_id = ?1 ORDER BY position",
+                )?;
+                let ids = stmt
+                    .query_map(params![playlist.id], |row| row.get(0))?
+                    .collect::<Result<Vec<String>, _>>()?;
+                Ok(Some((playlist, ids)))
+            })
+            .await?;
+
+        Ok(result.map(|(playlist, ids)| {
+            (
+                playlist,
+                ids.iter().filter_map(|id| id.parse().ok()).collect(),
+            )
+        }))
+    }
+
+    /// Delete a playlist of a session. Returns whether there was one.
+    pub async fn delete_playlist(&self, session: &str, id: i64) -> anyhow::Result<bool> {
+        let session = session.to_string();
+
+        let deleted = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let deleted = tx.execute(
+                    "DELETE FROM playlists WHERE id = ?1 AND session = ?2",
+                    params![id, session],
+                )?;
+                tx.execute(
+                    "DELETE FROM playlist_items WHERE playlist_id = ?1",
+                    params![id],
+                )?;
+                tx.commit()?;
+                Ok(deleted > 0)
+            })
+            .await?;
+
+        Ok(deleted)
+    }
+
+    /// Change a playlist of a session. Returns whether there was one.
+    pub async fn edit_playlist(
+        &self,
+        session: &str,
+        id: i64,
+        item_id: &VideoId,
+        edit: PlaylistEdit,
+    ) -> anyhow::Result<bool> {
+        let session = session.to_string();
+        let item_id = item_id.to_string();
+
+        let found = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let owned = tx
+                    .prepare("SELECT 1 FROM playlists WHERE id = ?1 AND session = ?2")?
+                    .exists(params![id, session])?;
+                if !owned {
+                    return Ok(false);
+                }
+                match edit {
+                    PlaylistEdit::Add => {
+                        tx.execute(
+                            "INSERT OR IGNORE INTO playlist_items (playlist_id, item_id, position)
+                            SELECT ?1, ?2, COALESCE(MAX(position), 0) + 1
+                            FROM playlist_items WHERE playlist_id = ?1",
+                            params![id, item_id],
+                        )?;
+                    }
+                    PlaylistEdit::Remove => {
+                        tx.execute(
+                            "DELETE FROM playlist_items WHERE playlist_id = ?1 AND item_id = ?2",
+                            params![id, item_id],
+                        )?;
+                    }
+                    PlaylistEdit::Up | PlaylistEdit::Down => {
+                        // Swap the positions of the item and its neighbour.
+                        let neighbour = match edit {
+                            PlaylistEdit::Up => {
+                                "SELECT item_id, position FROM playlist_items
+                                WHERE playlist_id = ?1 AND position < ?2
+                                ORDER BY position DESC LIMIT 1"
+                            }
+                            _ => {
+                                "SELECT item_id, position FROM playlist_items
+                                WHERE playlist_id = ?1 AND position > ?2
+                                ORDER BY position LIMIT 1"
+                            }
+                        };
+                        let position: Option<i64> = tx
+                            .query_row(
+                                "SELECT position FROM playlist_items
+                                WHERE playlist_id = ?1 AND item_id = ?2",
+                                params![id, item_id],
+                                |row| row.get(0),
+                            )
+                            .optional()?;
+                        let neighbour: Option<(Value, i64)> = match position {
+                            Some(position) => tx
+                                .query_row(neighbour, params![id, position], |row| {
+                                    Ok((row.get(0)?, row.get(1)?))
+                                })
+                                .optional()?,
+                            None => None,
+                        };
+                        if let (Some(position), Some((other, other_position))) =
+                            (position, neighbour)
+                        {
+                            tx.execute(
+                                "UPDATE playlist_items SET position = ?3
+                                WHERE playlist_id = ?1 AND item_id = ?2",
+                                params![id, other, position],
+                            )?;
+                            tx.execute(
+                                "UPDATE playlist_items SET position = ?3
+                                WHERE playlist_id = ?1 AND item_id = ?2",
+                                params![id, item_id, other_position],
+                            )?;
+                        }
+                    }
+                }
+                tx.commit()?;
+                Ok(true)
+            })
+            .await?;
+
+        Ok(found)
+    }
+
+    /// Get the settings of the private feed of a session, if it has one.
+    pub async fn feed(&self, session: &str) -> anyhow::Result<Option<FeedSettings>> {
+        let feed = self.find_feed("session = ?1", session.to_string()).await?;
+        Ok(feed.map(|(_, settings)| settings))
+    }
+
+    /// Get the session of the private feed with a token and its settings.
+    pub async fn feed_by_token(
+        &self,
+        token: &str,
+    ) -> anyhow::Result<Option<(String, FeedSettings)>> {
+        self.find_feed("token = ?1", token.to_string()).await
+    }
+
+    /// Set what the private feed of a session lists and the ntfy topic the new videos with its
+    /// tags are pushed to, making one if it has none yet.
+    pub async fn save_feed(
+        &self,
+        session: &str,
+        source: Option<&str>,
+        tags: &[String],
+        push_topic: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let session = session.to_string();
+        let source = source.map(str::to_string);
+        let tags = tags.join(" ");
+        let push_topic = push_topic.map(str::to_string);
+
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO feeds (session, token, source, tags, push_topic)
+                    VALUES (?1, lower(hex(randomblob(16))), ?2, ?3, ?4)
+                    ON CONFLICT (session) DO UPDATE SET
+                        source = excluded.source,
+                        tags = excluded.tags,
+                        push_topic = excluded.push_topic",
+                    params![session, source, tags, push_topic],
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get the private feeds whose new videos are pushed to an ntfy topic, which only those
+    /// subscribing to tags are.
+    pub async fn tag_subscriptions(&self) -> anyhow::Result<Vec<FeedSettings>> {
+        let feeds = self
+            .conn
+            .call(|conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT token, source, tags, push_topic FROM feeds
+                    WHERE push_topic IS NOT NULL AND tags != ''",
+                )?;
+                let feeds = stmt
+                    .query_map([], |row| {
+                        let tags: String = row.get(2)?;
+                        Ok(FeedSettings {
+                            token: row.get(0)?,
+                            source: row.get(1)?,
+                            tags: tags.split_whitespace().map(str::to_string).collect(),
+                            push_topic: row.get(3)?,
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(feeds)
+            })
+            .await?;
+
+        Ok(feeds)
+    }
+
+    /// Get the saved searches of a session, the most recently saved first.
+    pub async fn saved_searches(&self, session: &str) -> anyhow::Result<Vec<SavedSearch>> {
+        let session = session.to_string();
+
+        let searches = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT {SAVED_SEARCH_COLUMNS} FROM saved_searches WHERE session = ?1
+                    ORDER BY id DESC"
+                ))?;
+                let searches = stmt
+                    .query_map(params![session], |row| {
+                        Ok(SavedSearch {
+                            id: row.get(0)?,
+                            query: row.get(1)?,
+                            token: row.get(2)?,
+                            matches: row.get(3)?,
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(searches)
+            })
+            .await?;
+
+        Ok(searches)
+    }
+
+    /// Save a search of a session, unless it is saved already.
+    pub async fn save_search(&self, session: &str, query: &str) -> anyhow::Result<()> {
+        let session = session.to_string();
+        let query = query.to_string();
+
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT OR IGNORE INTO saved_searches (session, query, token)
+                    VALUES (?1, ?2, lower(hex(randomblob(16))))",
+                    params![session, query],
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete a saved search of a session with its matches. Returns whether there was one.
+    pub async fn delete_saved_search(&self, session: &str, id: i64) -> anyhow::Result<bool> {
+        let session = session.to_string();
+
+        let deleted = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let deleted = tx.execute(
+                    "DELETE FROM saved_searches WHERE id = ?1 AND session = ?2",
+                    params![id, session],
+                )?;
+                tx.execute(
+                    "DELETE FROM saved_search_matches WHERE search_id = ?1",
+                    params![id],
+                )?;
+                tx.commit()?;
+                Ok(deleted > 0)
+            })
+            .await?;
+
+        Ok(deleted)
+    }
+
+    /// Get every saved search, with the ntfy topic of the private feed of its session, if any.
+    pub async fn search_alerts(&self) -> anyhow::Result<Vec<(SavedSearch, Option<String>)>> {
+        let searches = self
+            .conn
+            .call(|conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT saved_searches.id, query, saved_searches.token, 0, feeds.push_topic
+                    FROM saved_searches LEFT JOIN feeds USING (session)",
+                )?;
+                let searches = stmt
+                    .query_map([], |row| {
+                        let search = SavedSearch {
+                            id: row.get(0)?,
+                            query: row.get(1)?,
+                            token: row.get(2)?,
+                            matches: row.get(3)?,
+                        };
+                        Ok((search, row.get(4)?))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(searches)
+            })
+            .await?;
+
+        Ok(searches)
+    }
+
+    /// Record the new videos matching a saved search.
+    pub async fn record_search_matches(
+        &self,
+        search_id: i64,
+        item_ids: &[VideoId],
+    ) -> anyhow::Result<()> {
+        let item_ids: Vec<String> = item_ids.iter().map(VideoId::to_string).collect();
+
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                {
+                    let mut stmt = tx.prepare(
+                        "INSERT OR IGNORE INTO saved_search_matches (search_id, item_id)
+                        VALUES (?1, ?2)",
+                    )?;
+                    for item_id in item_ids {
+                        stmt.execute(params![search_id, item_id])?;
+                    }
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get the saved search with a token and up to `limit` of its matches, the latest first.
+    pub async fn saved_search_matches(
+        &self,
+        token: &str,
+        limit: u32,
+    ) -> anyhow::Result<Option<(SavedSearch, Vec<VideoId>)>> {
+        let token = token.to_string();
+
+        let found = self
+            .conn
+            .call(move |conn| {
+                let search = conn
+                    .query_row(
+                        &format!(
+                            "SELECT {SAVED_SEARCH_COLUMNS} FROM saved_searches WHERE token = ?1"
+                        ),
+                        params![token],
+                        |row| {
+                            Ok(SavedSearch {
+                                id: row.get(0)?,
+                                query: row.get(1)?,
+                                token: row.get(2)?,
+                                matches: row.get(3)?,
+                            })
+                        },
+                    )
+                    .optional()?;
+                let Some(search) = search else {
+                    return Ok(None);
+                };
+                let mut stmt = conn.prepare(
+                    "SELECT CAST(item_id AS TEXT) FROM saved_search_matches WHERE search_id = ?1
+                    ORDER BY matched_at DESC, rowid DESC LIMIT ?2",
+                )?;
+                let ids = stmt
+                    .query_map(params![search.id, limit], |row| row.get(0))?
+                    .collect::<Result<Vec<String>, _>>()?;
+                Ok(Some((search, ids)))
+            })
+            .await?;
+
+        Ok(found.map(|(search, ids)| {
+            (
+                search,
+                ids.iter().filter_map(|id| id.parse().ok()).collect(),
+            )
+        }))
+    }
+
+    /// Get the private feed matching an SQL condition on `param`, with its session.
+    async fn find_feed(
+        &self,
+        condition: &'static str,
+        param: String,
+    ) -> anyhow::Result<Option<(String, FeedSettings)>> {
+        let feed = self
+            .conn
+            .call(move |conn| {
+                let feed = conn
+                    .query_row(
+                        &format!(
+                            "SELECT session, token, source, tags, push_topic
+                            FROM feeds WHERE {condition}"
+                        ),
+                        params![param],
+                        |row| {
+                            let tags: String = row.get(3)?;
+                            let settings = FeedSettings {
+                                token: row.get(1)?,
+                                source: row.get(2)?,
+                                tags: tags.split_whitespace().map(str::to_string).collect(),
+                                push_topic: row.get(4)?,
+                            };
+                            Ok((row.get(0)?, settings))
+                        },
+                    )
+                    .optional()?;
+                Ok(feed)
+            })
+            .await?;
+
+        Ok(feed)
+    }
+
+    /// Get the most loved items with the number of sessions loving them, most loved first.
+    pub async fn most_favorited(&self, limit: u32) -> anyhow::Result<Vec<(VideoId, u64)>> {
+        let result = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT CAST(item_id AS TEXT), COUNT(*) AS count FROM favorites
+                    GROUP BY item_id ORDER BY count DESC, MAX(added_at) DESC LIMIT ?1",
+                )?;
+                let items = stmt
+                    .query_map(params![limit], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<Result<Vec<(String, u64)>, _>>()?;
+                Ok(items)
+            })
+            .await?;
+
+        Ok(result
+            .into_iter()
+            .filter_map(|(id, count)| Some((id.parse().ok()?, count)))
+            .collect())
+    }
+
+    /// Start a session, if it wasn't started yet.
+    pub async fn start_session(&self, id: &str) -> anyhow::Result<()> {
+        let id = id.to_string();
+
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT OR IGNORE INTO sessions (id, signed) VALUES (?1, 1)",
+                    params![id],
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Note that the cookie of a session taken over from the lists is signed now, so that it is no
+    /// longer accepted without a signature.
+    pub async fn sign_session(&self, id: &str) -> anyhow::Result<()> {
+        let id = id.to_string();
+
+        self.conn
+            .call(move |conn| {
+                conn.execute("UPDATE sessions SET signed = 1 WHERE id = ?1", params![id])?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Check that a session exists and was seen in the last `days` days, noting that it was seen
+    /// now. The time it was seen at is only updated once a day, to spare a write per request.
+    ///
+    /// Sessions presented without a signature are only accepted if their cookies were never
+    /// signed.
+    pub async fn see_session(&self, id: &str, days: u32, signed: bool) -> anyhow::Result<bool> {
+        let id = id.to_string();
+        let since = format!("-{} days", days);
+
+        let valid = self
+            .conn
+            .call(move |conn| {
+                let exists = conn
+                    .prepare(
+                        "SELECT 1 FROM sessions
+                        WHERE id = ?1 AND seen_at >= CAST(strftime('%s', 'now', ?2) AS INTEGER)
+                            AND (?3 OR signed = 0)",
+                    )?
+                    .exists(params![id, since, signed])?;
+                if exists {
+                    conn.execute(
+                        "UPDATE sessions SET seen_at = CAST(strftime('%s', 'now') AS INTEGER)
+                        WHERE id = ?1 AND seen_at < CAST(strftime('%s', 'now', '-1 day') AS INTEGER)",
+                        params![id],
+                    )?;
+                }
+                Ok(exists)
+            })
+            .await?;
+
+        Ok(valid)
+    }
+
+    /// Count the sessions.
+    pub async fn session_count(&self) -> anyhow::Result<u64> {
+        let count = self
+            .conn
+            .call(|conn| Ok(conn.query_row("SELECT COUNT(*) FROM sessions", [], |row| row.get(0))?))
+            .await?;
+
+        Ok(count)
+    }
+
+    /// End every session, returning how many there were. Their rows are purged with
+    /// [`Store::purge_sessions`].
+    pub async fn end_sessions(&self) -> anyhow::Result<usize> {
+        let ended = self
+            .conn
+            .call(|conn| Ok(conn.execute("DELETE FROM sessions", [])?))
+            .await?;
+
+        Ok(ended)
+    }
+
+    /// End the sessions not seen in the last `days` days, and delete the rows of the sessions
+    /// that ended. Returns the number of sessions that expired.
+    pub async fn purge_sessions(&self, days: u32) -> anyhow::Result<usize> {
+        let since = format!("-{} days", days);
+
+        let expired = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let expired = tx.execute(
+                    "DELETE FROM sessions WHERE seen_at < CAST(strftime('%s', 'now', ?1) AS INTEGER)",
+                    params![since],
+                )?;
+                let tables = ["history", "playlists", "feeds", "saved_searches"];
+                for table in ITEM_LISTS.into_iter().chain(tables) {
+                    tx.execute(
+                        &format!(
+                            "DELETE FROM {table} WHERE session NOT IN (SELECT id FROM sessions)"
+                        ),
+                        [],
+                    )?;
+                }
+                tx.execute(
+                    "DELETE FROM playlist_items WHERE playlist_id NOT IN (SELECT id FROM playlists)",
+                    [],
+                )?;
+                tx.execute(
+                    "DELETE FROM saved_search_matches
+                    WHERE search_id NOT IN (SELECT id FROM saved_searches)",
+                    [],
+                )?;
+                tx.commit()?;
+                Ok(expired)
+            })
+            .await?;
+
+        Ok(expired)
+    }
+
+    /// Record a click on the short link of an item.
+    pub async fn record_click(&self, item_id: &VideoId) -> anyhow::Result<()> {
+        let item_id = item_id.to_string();
+
+        self.conn
+            .call(move |conn| {
+                conn.execute("INSERT INTO clicks (item_id) VALUES (?)", params![item_id])?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get the most clicked items of the last `days` days with their click counts, most clicked
+    /// first.
+    pub async fn most_clicked(&self, days: u32, limit: u32) -> anyhow::Result<Vec<(VideoId, u64)>> {
+        let since = format!("-{} days", days);
+
+        let result = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT CAST(item_id AS TEXT), COUNT(*) AS count FROM clicks
+                    WHERE clicked_at >= CAST(strftime('%s', 'now', ?1) AS INTEGER)
+                    GROUP BY item_id ORDER BY count DESC, MAX(clicked_at) DESC LIMIT ?2",
+                )?;
+                let items = stmt
+                    .query_map(params![since, limit], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<Result<Vec<(String, u64)>, _>>()?;
+                Ok(items)
+            })
+            .await?;
+
+        Ok(result
+            .into_iter()
+            .filter_map(|(id, count)| Some((id.parse().ok()?, count)))
+            .collect())
+    }
+
+    /// Register a notifier, returning whether it was registered before.
+    pub async fn start_notifier(&self, notifier: &str) -> anyhow::Result<bool> {
+        let notifier = notifier.to_string();
+
+        let inserted = self
+            .conn
+            .call(move |conn| {
+                let inserted = conn.execute(
+                    "INSERT OR IGNORE INTO notifiers (notifier) VALUES (?)",
+                    params![notifier],
+                )?;
+                Ok(inserted)
+            })
+            .await?;
+
+        Ok(inserted == 0)
+    }
+
+    /// Add the outcomes of the detection in a fetch of a source to those of today.
+    pub async fn record_detections(
+        &self,
+        source: &str,
+        detections: &Detections,
+    ) -> anyhow::Result<()> {
+        let source = source.to_string();
+        let counts: Vec<_> = detections
+            .counts
+            .iter()
+            .map(|((rule, platform), count)| (*rule, platform.clone(), *count as i64))
+            .collect();
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                {
+                    let mut stmt = tx.prepare(
+                        "INSERT INTO detections (day, source, rule, platform, count)
+                        VALUES (date('now'), ?1, ?2, ?3, ?4)
+                        ON CONFLICT (day, source, rule, platform) DO UPDATE SET
+                            count = count + excluded.count",
+                    )?;
+                    for (rule, platform, count) in counts {
+                        stmt.execute(params![source, rule, platform, count])?;
+                    }
+                }
+                tx.commit()?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// The outcomes of the detection over the last days, the latest day first.
+    pub async fn detections(&self, days: u32) -> anyhow::Result<Vec<DetectionCount>> {
+        let result = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT day, source, rule, platform, count FROM detections
+                    WHERE day > date('now', ?1)
+                    ORDER BY day DESC, source, rule, count DESC, platform",
+                )?;
+                let rows = stmt.query_map(params![format!("-{} days", days)], |row| {
+                    Ok(DetectionCount {
+                        day: row.get(0)?,
+                        source: row.get(1)?,
+                        rule: row.get(2)?,
+                        platform: row.get(3)?,
+                        count: row.get(4)?,
+                    })
+                })?;
+                Ok(rows.collect::<Result<Vec<_>, _>>()?)
+            })
+            .await?;
+        Ok(result)
+    }
+
+    /// Block a story or a domain, returning the entry, or `None` if it was blocked already.
+    pub async fn add_block(&self, kind: &str, value: &str) -> anyhow::Result<Option<Block>> {
+        let (kind, value) = (kind.to_string(), value.to_string());
+        let block = self
+            .conn
+            .call(move |conn| {
+                let block = conn
+                    .query_row(
+                        "INSERT INTO blocklist (kind, value) VALUES (?1, ?2)
+                        ON CONFLICT (kind, value) DO NOTHING
+                        RETURNING id, kind, value, created_at",
+                        params![kind, value],
+                        |row| {
+                            Ok(Block {
+                                id: row.get(0)?,
+                                kind: row.get(1)?,
+                                value: row.get(2)?,
+                                created_at: row.get(3)?,
+                            })
+                        },
+                    )
+                    .optional()?;
+                Ok(block)
+            })
+            .await?;
+        Ok(block)
+    }
+
+    /// The blocked stories and domains, the latest first.
+    pub async fn blocks(&self) -> anyhow::Result<Vec<Block>> {
+        let blocks = self
+            .conn
+            .call(|conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, kind, value, created_at FROM blocklist ORDER BY id DESC",
+                )?;
+                let blocks = stmt
+                    .query_map([], |row| {
+                        Ok(Block {
+                            id: row.get(0)?,
+                            kind: row.get(1)?,
+                            value: row.get(2)?,
+                            created_at: row.get(3)?,
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(blocks)
+            })
+            .await?;
+        Ok(blocks)
+    }
+
+    /// Lift a block, returning whether there was one.
+    pub async fn remove_block(&self, id: i64) -> anyhow::Result<bool> {
+        let removed = self
+            .conn
+            .call(move |conn| Ok(conn.execute("DELETE FROM blocklist WHERE id = ?", params![id])?))
+            .await?;
+        Ok(removed > 0)
+    }
+
+    /// The responses in the cache, the latest fetched first, optionally only those whose URL
+    /// contains some text.
+    pub async fn cache_entries(
+        &self,
+        url: Option<String>,
+        limit: u32,
+    ) -> anyhow::Result<Vec<CacheEntry>> {
+        self.query_cache_entries(
+            "WHERE instr(url, ?1) > 0 ORDER BY fetched_at DESC, id DESC LIMIT ?2",
+            vec![url.unwrap_or_default().into(), i64::from(limit).into()],
+        )
+        .await
+    }
+
+    /// A response in the cache, by its ID.
+    pub async fn cache_entry(&self, id: i64) -> anyhow::Result<Option<CacheEntry>> {
+        let entries = self
+            .query_cache_entries("WHERE id = ?1", vec![id.into()])
+            .await?;
+        Ok(entries.into_iter().next())
+    }
+
+    async fn query_cache_entries(
+        &self,
+        clause: &'static str,
+        params: Vec<Value>,
+    ) -> anyhow::Result<Vec<CacheEntry>> {
+        let entries = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT id, url, NULLIF(fetched_at, 0), length(CAST(response AS BLOB)),
+                        CASE
+                            WHEN response = 'null' THEN 'null'
+                            WHEN json_valid(response) THEN 'ok'
+                            ELSE 'invalid'
+                        END
+                    FROM cache {}",
+                    clause
+                ))?;
+                let entries = stmt
+                    .query_map(params_from_iter(params), |row| {
+                        Ok(CacheEntry {
+                            id: row.get(0)?,
+                            url: row.get(1)?,
+                            fetched_at: row.get(2)?,
+                            size: row.get(3)?,
+                            status: row.get(4)?,
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(entries)
+            })
+            .await?;
+        Ok(entries)
+    }
+
+    /// Record that a notifier posts items, returning those it hadn't posted before.
+    ///
+    /// Records older than [`NOTIFIED_DAYS`] are forgotten, long after the items have dropped off
+    /// the sources.
+    pub async fn mark_notified(
+        &self,
+        notifier: &str,
+        item_ids: &[VideoId],
+    ) -> anyhow::Result<Vec<VideoId>> {
+        let notifier = notifier.to_string();
+        let item_ids: Vec<_> = item_ids.to_vec();
+
+        let result = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                tx.execute(
+                    "DELETE FROM notified
+                    WHERE notified_at < CAST(strftime('%s', 'now', ?1) AS INTEGER)",
+                    params![format!("-{} days", NOTIFIED_DAYS)],
+                )?;
+                let mut new = Vec::new();
+                {
+                    let mut stmt = tx.prepare(
+                        "INSERT OR IGNORE INTO notified (notifier, item_id) VALUES (?1, ?2)",
+                    )?;
+                    for item_id in item_ids {
+                        if stmt.execute(params![notifier, item_id.to_string()])? > 0 {
+                            new.push(item_id);
+                        }
+                    }
+                }
+                tx.commit()?;
+                Ok(new)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Subscribe a URL to events, returning the ID of the subscription.
+    pub async fn add_webhook(
+        &self,
+        url: &str,
+        secret: &str,
+        events: &[String],
+    ) -> anyhow::Result<i64> {
+        let (url, secret, events) = (url.to_string(), secret.to_string(), events.join(","));
+
+        let id = self
+            .conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO webhooks (url, secret, events) VALUES (?1, ?2, ?3)",
+                    params![url, secret, events],
+                )?;
+                Ok(conn.last_insert_rowid())
+            })
+            .await?;
+
+        Ok(id)
+    }
+
+    /// Get the subscriptions to events, oldest first.
+    pub async fn webhooks(&self) -> anyhow::Result<Vec<Webhook>> {
+        let result = self
+            .conn
+            .call(|conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, url, secret, events, created_at FROM webhooks ORDER BY id",
+                )?;
+                let webhooks = stmt
+                    .query_map([], |row| {
+                        let events: String = row.get(3)?;
+                        Ok(Webhook {
+                            id: row.get(0)?,
+                            url: row.get(1)?,
+                            secret: row.get(2)?,
+                            events: events.split(',').map(str::to_string).collect(),
+                            created_at: row.get(4)?,
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(webhooks)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Get a subscription to events.
+    pub async fn webhook(&self, id: i64) -> anyhow::Result<Option<Webhook>> {
+        Ok(self
+            .webhooks()
+            .await?
+            .into_iter()
+            .find(|webhook| webhook.id == id))
+    }
+
+    /// Unsubscribe from events, returning whether there was such a subscription.
+    pub async fn remove_webhook(&self, id: i64) -> anyhow::Result<bool> {
+        let removed = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let removed = tx.execute("DELETE FROM webhooks WHERE id = ?", params![id])?;
+                tx.execute(
+                    "DELETE FROM webhook_deliveries WHERE webhook_id = ?",
+                    params![id],
+                )?;
+                tx.commit()?;
+                Ok(removed)
+            })
+            .await?;
+
+        Ok(removed > 0)
+    }
+
+    /// Get a key made by the instance.
+    pub async fn key(&self, name: &'static str) -> anyhow::Result<Option<String>> {
+        let pem = self
+            .conn
+            .call(move |conn| {
+                let pem = conn
+                    .query_row(
+                        "SELECT pem FROM keys WHERE name = ?1",
+                        params![name],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                Ok(pem)
+            })
+            .await?;
+
+        Ok(pem)
+    }
+
+    /// Keep a newly made key, returning the key to use: this one, unless another process kept
+    /// one first.
+    pub async fn add_key(&self, name: &'static str, pem: String) -> anyhow::Result<String> {
+        let pem = self
+            .conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT OR IGNORE INTO keys (name, pem) VALUES (?1, ?2)",
+                    params![name, pem],
+                )?;
+                let pem = conn.query_row(
+                    "SELECT pem FROM keys WHERE name = ?1",
+                    params![name],
+                    |row| row.get(0),
+                )?;
+                Ok(pem)
+            })
+            .await?;
+
+        Ok(pem)
+    }
+
+    /// Record a follower of the ActivityPub actor, or update its inbox.
+    pub async fn add_follower(&self, actor: &str, inbox: &str) -> anyhow::Result<()> {
+        let actor = actor.to_string();
+        let inbox = inbox.to_string();
+
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO followers (actor, inbox) VALUES (?1, ?2)
+                    ON CONFLICT (actor) DO UPDATE SET inbox = excluded.inbox",
+                    params![actor, inbox],
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Forget a follower of the ActivityPub actor.
+    pub async fn remove_follower(&self, actor: &str) -> anyhow::Result<()> {
+        let actor = actor.to_string();
+
+        self.conn
+            .call(move |conn| {
+                conn.execute("DELETE FROM followers WHERE actor = ?1", params![actor])?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get the inboxes of the followers of the ActivityPub actor, once each, since followers on
+    /// the same server may share one.
+    pub async fn follower_inboxes(&self) -> anyhow::Result<Vec<String>> {
+        let inboxes = self
+            .conn
+            .call(|conn| {
+                let mut stmt =
+                    conn.prepare("SELECT DISTINCT inbox FROM followers ORDER BY inbox")?;
+                let inboxes = stmt
+                    .query_map([], |row| row.get(0))?
+                    .collect::<Result<Vec<String>, _>>()?;
+                Ok(inboxes)
+            })
+            .await?;
+
+        Ok(inboxes)
+    }
+
+    /// Count the followers of the ActivityPub actor.
+    pub async fn follower_count(&self) -> anyhow::Result<u64> {
+        let count = self
+            .conn
+            .call(|conn| {
+                let count =
+                    conn.query_row("SELECT COUNT(*) FROM followers", [], |row| row.get(0))?;
+                Ok(count)
+            })
+            .await?;
+
+        Ok(count)
+    }
+
+    /// Log the outcome of a delivery.
+    ///
+    /// Deliveries older than [`DELIVERIES_DAYS`] are forgotten.
+    pub async fn record_delivery(&self, webhook_id: i64, delivery: Delivery) -> anyhow::Result<()> {
+        self.conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                tx.execute(
+                    "DELETE FROM webhook_deliveries
+                    WHERE delivered_at < CAST(strftime('%s', 'now', ?1) AS INTEGER)",
+                    params![format!("-{} days", DELIVERIES_DAYS)],
+                )?;
+                tx.execute(
+                    "INSERT INTO webhook_deliveries
+                    (id, webhook_id, event, attempts, status, error, delivered_at)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        delivery.id,
+                        webhook_id,
+                        delivery.event,
+                        delivery.attempts,
+                        delivery.status,
+                        delivery.error,
+                        delivery.delivered_at
+                    ],
+                )?;
+                tx.commit()?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get the latest deliveries to a subscriber, most recent first.
+    pub async fn deliveries(&self, webhook_id: i64, limit: u32) -> anyhow::Result<Vec<Delivery>> {
+        let result = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, event, attempts, status, error, delivered_at FROM webhook_deliveries
+                    WHERE webhook_id = ?1 ORDER BY delivered_at DESC, rowid DESC LIMIT ?2",
+                )?;
+                let deliveries = stmt
+                    .query_map(params![webhook_id, limit], |row| {
+                        Ok(Delivery {
+                            id: row.get(0)?,
+                            event: row.get(1)?,
+                            attempts: row.get(2)?,
+                            status: row.get(3)?,
+                            error: row.get(4)?,
+                            delivered_at: row.get(5)?,
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(deliveries)
+            })
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Record the videos of a refresh, updating their titles and scores if they were listed
+    /// before, when they keep their `first_seen`. Their scores are sampled at `last_seen`.
+    ///
+    /// Videos are never forgotten, they make up the archive. Returns how many were listed for the
+    /// first time.
+    pub async fn record_videos(&self, videos: Vec<ListedVideo>) -> anyhow::Result<usize> {
+        let new = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                let mut new = 0;
+                {
+                    let mut known =
+                        tx.prepare("SELECT EXISTS (SELECT 1 FROM videos WHERE item_id = ?1)")?;
+                    let mut stmt = tx.prepare(
+                        "INSERT INTO videos (item_id, title, url, discussion_url, score,
+                            first_seen, last_seen, submitted_at, comments)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                        ON CONFLICT (item_id) DO UPDATE SET
+                            title = excluded.title,
+                            url = excluded.url,
+                            discussion_url = excluded.discussion_url,
+                            score = excluded.score,
+                            last_seen = excluded.last_seen,
+                            submitted_at = COALESCE(excluded.submitted_at, submitted_at),
+                            comments = excluded.comments",
+                    )?;
+                    let mut sample = tx.prepare(
+                        "INSERT OR REPLACE INTO scores (item_id, sampled_at, score)
+                        VALUES (?1, ?2, ?3)",
+                    )?;
+                    for video in videos {
+                        let id = video.id.to_string();
+                        if let Some(score) = video.score {
+                            sample.execute(params![id, video.last_seen, score])?;
+                        }
+                        if !known.query_row(params![id], |row| row.get::<_, bool>(0))? {
+                            new += 1;
+                        }
+                        stmt.execute(params![
+                            id,
+                            video.title,
+                            video.url,
+                            video.discussion_url,
+                            video.score,
+                            video.first_seen,
+                            video.last_seen,
+                            video.submitted_at,
+                            video.comments
+                        ])?;
+                    }
+                }
+                tx.commit()?;
+                Ok(new)
+            })
+            .await?;
+
+        Ok(new)
+    }
+
+    /// Get the highest scoring videos first listed since a time, in seconds since the Unix
+    /// epoch.
+    pub async fn top_videos(&self, since: i64, limit: u32) -> anyhow::Result<Vec<ListedVideo>> {
+        self.query_videos(
+            "WHERE first_seen >= ?1
+            ORDER BY score IS NULL, score DESC, first_seen DESC LIMIT ?2",
+            vec![since.into(), i64::from(limit).into()],
+        )
+        .await
+    }
+
+    /// Get the videos with the highest scores sampled between two times, in seconds since the
+    /// Unix epoch, with the highest score each reached in that time. The highest come first.
+    pub async fn peak_scores(
+        &self,
+        start: i64,
+        end: i64,
+        limit: u32,
+    ) -> anyhow::Result<Vec<(VideoId, i64)>> {
+        let result = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT item_id, MAX(score) AS peak FROM scores
+                    WHERE sampled_at >= ?1 AND sampled_at < ?2
+                    GROUP BY item_id ORDER BY peak DESC, item_id LIMIT ?3",
+                )?;
+                let peaks = stmt
+                    .query_map(params![start, end, limit], |row| {
+                        Ok((row.get(0)?, row.get(1)?))
+                    })?
+                    .collect::<Result<Vec<(String, i64)>, _>>()?;
+                Ok(peaks)
+            })
+            .await?;
+
+        Ok(result
+            .into_iter()
+            .filter_map(|(id, peak)| Some((id.parse().ok()?, peak)))
+            .collect())
+    }
+
+    /// Get the scores sampled for a video, the earliest first.
+    pub async fn score_history(&self, item_id: &VideoId) -> anyhow::Result<Vec<ScorePoint>> {
+        let item_id = item_id.to_string();
+
+        let points = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT sampled_at, score FROM scores WHERE item_id = ?1 ORDER BY sampled_at",
+                )?;
+                let points = stmt
+                    .query_map(params![item_id], |row| {
+                        Ok(ScorePoint {
+                            time: row.get(0)?,
+                            score: row.get(1)?,
+                        })
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(points)
+            })
+            .await?;
+
+        Ok(points)
+    }
+
+    /// Get a recorded video.
+    pub async fn listed_video(&self, item_id: &VideoId) -> anyhow::Result<Option<ListedVideo>> {
+        let videos = self
+            .query_videos("WHERE item_id = ?1", vec![item_id.to_string().into()])
+            .await?;
+        Ok(videos.into_iter().next())
+    }
+
+    /// Get the videos with the given IDs, in the order of the IDs, leaving out those that were
+    /// never listed.
+    pub async fn listed_videos(&self, item_ids: &[VideoId]) -> anyhow::Result<Vec<ListedVideo>> {
+        if item_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let placeholders: Vec<_> = (1..=item_ids.len()).map(|n| format!("?{}", n)).collect();
+        let params = item_ids.iter().map(|id| id.to_string().into()).collect();
+        let videos = self
+            .query_videos(
+                format!("WHERE item_id IN ({})", placeholders.join(", ")),
+                params,
+            )
+            .await?;
+        let videos: HashMap<_, _> = videos
+            .into_iter()
+            .map(|video| (video.id.clone(), video))
+            .collect();
+        Ok(item_ids
+            .iter()
+            .filter_map(|id| videos.get(id).cloned())
+            .collect())
+    }
+
+    /// Get the videos first listed between two times, in seconds since the Unix epoch, the most
+    /// recent first.
+    pub async fn videos_between(&self, start: i64, end: i64) -> anyhow::Result<Vec<ListedVideo>> {
+        self.query_videos(
+            "WHERE first_seen >= ?1 AND first_seen < ?2 ORDER BY first_seen DESC",
+            vec![start.into(), end.into()],
+        )
+        .await
+    }
+
+    /// Get the videos submitted between two times, in seconds since the Unix epoch, the highest
+    /// scoring first. Videos of sources that don't tell when they were submitted count as
+    /// submitted when they were first listed.
+    pub async fn videos_submitted_between(
+        &self,
+        start: i64,
+        end: i64,
+    ) -> anyhow::Result<Vec<ListedVideo>> {
+        self.query_videos(
+            "WHERE COALESCE(submitted_at, first_seen) >= ?1
+                AND COALESCE(submitted_at, first_seen) < ?2
+            ORDER BY score IS NULL, score DESC, first_seen DESC",
+            vec![start.into(), end.into()],
+        )
+        .await
+    }
+
+    /// Get the videos passing a search, with its days starting in a time zone. The most recent
+    /// come first.
+    pub async fn search_videos(
+        &self,
+        search: &VideoSearch,
+        time_zone: TimeZone,
+        limit: u32,
+    ) -> anyhow::Result<Vec<ListedVideo>> {
+        let mut conditions = Vec::new();
+        let mut params: Vec<Value> = Vec::new();
+        let mut param = |value: Value| {
+            params.push(value);
+            format!("?{}", params.len())
+        };
+
+        let phrases = search.terms.iter().cloned();
+        let tags = search.tags.iter().map(|tag| format!("[{}]", tag));
+        for phrase in phrases.chain(tags) {
+            conditions.push(format!(
+                "title LIKE {} ESCAPE '\\'",
+                param(like_pattern("%", &phrase, "%").into())
+            ));
+        }
+        let platforms: Vec<_> = search
+            .platforms
+            .iter()
+            .map(|platform| match platform.as_str() {
+                "youtube" => "(url LIKE '%://youtu.be/%' OR url LIKE '%youtube.com/%')".to_string(),
+                // The platform is the second-level domain of the host.
+                platform => format!(
+                    "(url LIKE {} ESCAPE '\\' OR url LIKE {} ESCAPE '\\')",
+                    param(like_pattern("%://", platform, ".%").into()),
+                    param(like_pattern("%://%.", platform, ".%").into()),
+                ),
+            })
+            .collect();
+        if !platforms.is_empty() {
+            conditions.push(format!("({})", platforms.join(" OR ")));
+        }
+        let sources: Vec<_> = search
+            .sources
+            .iter()
+            .map(|source| match source.as_str() {
+                // Hacker News items are stored by their bare numeric ID.
+                crate::hacker_news::NAME => "item_id NOT LIKE '%-%'".to_string(),
+                source => format!(
+                    "item_id LIKE {} ESCAPE '\\'",
+                    param(like_pattern("", source, "-%").into())
+                ),
+            })
+            .collect();
+        if !sources.is_empty() {
+            conditions.push(format!("({})", sources.join(" OR ")));
+        }
+        if let Some(min_score) = search.min_score {
+            conditions.push(format!("score >= {}", param(min_score.into())));
+        }
+        if let Some(max_score) = search.max_score {
+            conditions.push(format!("score <= {}", param(max_score.into())));
+        }
+        if let Some(start) = search.start {
+            let start = time_zone.midnight(start);
+            conditions.push(format!("first_seen >= {}", param(start.into())));
+        }
+        if let Some(end) = search.end {
+            let end = time_zone.midnight(end);
+            conditions.push(format!("first_seen < {}", param(end.into())));
+        }
+
+        let filter = match conditions.is_empty() {
+            true => String::new(),
+            false => format!("WHERE {}", conditions.join(" AND ")),
+        };
+        let limit = param(i64::from(limit).into());
+        self.query_videos(
+            format!("{} ORDER BY first_seen DESC LIMIT {}", filter, limit),
+            params,
+        )
+        .await
+    }
+
+    /// Count the recorded videos by the month they were first listed in, in a time zone, the
+    /// most recent first.
+    pub async fn archive_months(&self, time_zone: TimeZone) -> anyhow::Result<Vec<ArchiveMonth>> {
+        // The offsets of time zones are whole quarters of an hour, so the days, and the months,
+        // start at the start of a quarter. The videos are counted by quarter here, and those by
+        // month in the time zone below, whose offset may change in between.
+        let quarters = self
+            .conn
+            .call(|conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT first_seen / 900 * 900, COUNT(*) FROM videos GROUP BY 1 ORDER BY 1",
+                )?;
+                let quarters = stmt
+                    .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, u64>(1)?)))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(quarters)
+            })
+            .await?;
+
+        let mut months: Vec<ArchiveMonth> = Vec::new();
+        for (quarter, videos) in quarters {
+            let Some(date) = time_zone.date(quarter) else {
+                continue;
+            };
+            let (year, month) = (date.year(), u8::from(date.month()));
+            match months.last_mut() {
+                Some(last) if (last.year, last.month) == (year, month) => last.videos += videos,
+                _ => months.push(ArchiveMonth {
+                    year,
+                    month,
+                    videos,
+                }),
+            }
+        }
+        months.reverse();
+        Ok(months)
+    }
+
+    /// Get all the recorded videos, those first listed first.
+    pub async fn all_videos(&self) -> anyhow::Result<Vec<ListedVideo>> {
+        self.query_videos("ORDER BY first_seen, item_id", Vec::new())
+            .await
+    }
+
+    async fn query_videos(
+        &self,
+        clause: impl Into<String>,
+        params: Vec<Value>,
+    ) -> anyhow::Result<Vec<ListedVideo>> {
+        let clause = clause.into();
+        let result = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT item_id, title, url, discussion_url, score, first_seen, last_seen,
+                        submitted_at, comments
+                    FROM videos {}",
+                    clause
+                ))?;
+                let videos = stmt
+                    .query_map(params_from_iter(params), |row| {
+                        Ok((
+                            row.get::<_, String>(0)?,
+                            row.get(1)?,
+                            row.get(2)?,
+                            row.get(3)?,
+                            row.get(4)?,
+                            row.get(5)?,
+                            row.get(6)?,
+                            row.get(7)?,
+                            row.get(8)?,
+                        ))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(videos)
+            })
+            .await?;
+
+        Ok(result
+            .into_iter()
+            .filter_map(
+                |(
+                    id,
+                    title,
+                    url,
+                    discussion_url,
+                    score,
+                    first_seen,
+                    last_seen,
+                    submitted_at,
+                    comments,
+                )| {
+                    Some(ListedVideo {
+                        id: id.parse().ok()?,
+                        title,
+                        url,
+                        discussion_url,
+                        score,
+                        submitted_at,
+                        comments,
+                        first_seen,
+                        last_seen,
+                    })
+                },
+            )
+            .collect())
+    }
+
+    /// Queue a video for download, returning whether it was queued. Videos that were queued
+    /// before are only queued again if their download failed or was removed.
+    pub async fn queue_download(
+        &self,
+        item_id: &VideoId,
+        url: &str,
+        title: &str,
+    ) -> anyhow::Result<bool> {
+        let (item_id, url, title) = (item_id.to_string(), url.to_string(), title.to_string());
+
+        let queued = self
+            .conn
+            .call(move |conn| {
+                let queued = conn.execute(
+                    "INSERT INTO downloads (item_id, url, title, state) VALUES (?1, ?2, ?3, 'queued')
+                    ON CONFLICT (item_id) DO UPDATE SET
+                        state = 'queued',
+                        error = NULL,
+                        queued_at = excluded.queued_at,
+                        updated_at = excluded.updated_at
+                    WHERE state IN ('failed', 'removed')",
+                    params![item_id, url, title],
+                )?;
+                Ok(queued)
+            })
+            .await?;
+
+        Ok(queued > 0)
+    }
+
+    /// Get the download that was queued first.
+    pub async fn next_download(&self) -> anyhow::Result<Option<Download>> {
+        Ok(self
+            .query_downloads(
+                "WHERE state = 'queued' ORDER BY queued_at, rowid LIMIT 1",
+                None,
+            )
+            .await?
+            .pop())
+    }
+
+    /// Get a download.
+    pub async fn download(&self, item_id: &VideoId) -> anyhow::Result<Option<Download>> {
+        Ok(self
+            .query_downloads("WHERE item_id = ?", Some(item_id.to_string()))
+            .await?
+            .pop())
+    }
+
+    /// Get all downloads, most recently queued first.
+    pub async fn downloads(&self) -> anyhow::Result<Vec<Download>> {
+        self.query_downloads("ORDER BY queued_at DESC, rowid DESC", None)
+            .await
+    }
+
+    /// Record the progress of a download.
+    pub async fn update_download(
+        &self,
+        item_id: &VideoId,
+        state: DownloadState,
+        file: Option<String>,
+        error: Option<String>,
+    ) -> anyhow::Result<()> {
+        let item_id = item_id.to_string();
+
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "UPDATE downloads
+                    SET state = ?2, file = ?3, error = ?4, updated_at = strftime('%s', 'now')
+                    WHERE item_id = ?1",
+                    params![item_id, state.to_string(), file, error],
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get the downloaded videos, those downloaded first first.
+    pub async fn finished_downloads(&self) -> anyhow::Result<Vec<Download>> {
+        self.query_downloads("WHERE state = 'done' ORDER BY updated_at, rowid", None)
+            .await
+    }
+
+    /// Pin a downloaded video so it is never removed, or unpin it.
+    pub async fn pin_download(&self, item_id: &VideoId, pinned: bool) -> anyhow::Result<()> {
+        let item_id = item_id.to_string();
+
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "UPDATE downloads SET pinned = ?2 WHERE item_id = ?1",
+                    params![item_id, pinned],
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Queue the downloads that were interrupted, e.g. by a restart, again.
+    pub async fn requeue_running_downloads(&self) -> anyhow::Result<()> {
+        self.conn
+            .call(|conn| {
+                conn.execute(
+                    "UPDATE downloads SET state = 'queued' WHERE state = 'running'",
+                    [],
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn query_downloads(
+        &self,
+        clause: &'static str,
+        param: Option<String>,
+    ) -> anyhow::Result<Vec<Download>> {
+        let result = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT item_id, url, title, state, file, error, updated_at, pinned
+                    FROM downloads {clause}"
+                ))?;
+                let rows = stmt
+                    .query_map(params_from_iter(param), |row| {
+                        Ok((
+                            row.get::<_, String>(0)?,
+                            row.get(1)?,
+                            row.get(2)?,
+                            row.get::<_, String>(3)?,
+                            row.get(4)?,
+                            row.get(5)?,
+                            row.get(6)?,
+                            row.get(7)?,
+                        ))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(rows)
+            })
+            .await?;
+
+        Ok(result
+            .into_iter()
+            .filter_map(|(id, url, title, state, file, error, updated_at, pinned)| {
+                Some(Download {
+                    id: id.parse().ok()?,
+                    url,
+                    title,
+                    state: state.parse().ok()?,
+                    file,
+                    error,
+                    updated_at,
+                    pinned,
+                })
+            })
+            .collect())
+    }
+
+    async fn items(&self, table: &'static str, session: &str) -> anyhow::Result<Vec<VideoId>> {
+        let session = session.to_string();
+
+        let result = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT CAST(item_id AS TEXT) FROM {table} WHERE session = ? ORDER BY added_at DESC"
+                ))?;
+                let ids = stmt
+                    .query_map(params![session], |row| row.get(0))?
+                    .collect::<Result<Vec<String>, _>>()?;
+                Ok(ids)
+            })
+            .await?;
+
+        Ok(result.iter().filter_map(|id| id.parse().ok()).collect())
+    }
+
+    async fn add_item(
+        &self,
+        table: &'static str,
+        session: &str,
+        item_id: &VideoId,
+    ) -> anyhow::Result<()> {
+        let session = session.to_string();
+        let item_id = item_id.to_string();
+
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    &format!("INSERT OR IGNORE INTO {table} (session, item_id) VALUES (?1, ?2)"),
+                    params![session, item_id],
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn remove_item(
+        &self,
+        table: &'static str,
+        session: &str,
+        item_id: &VideoId,
+    ) -> anyhow::Result<()> {
+        let session = session.to_string();
+        let item_id = item_id.to_string();
+
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    &format!("DELETE FROM {table} WHERE session = ?1 AND item_id = ?2"),
+                    params![session, item_id],
+                )?;
+                Ok(())
+            })
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// A subscription of a URL to events.
+pub struct Webhook {
+    pub id: i64,
+    pub url: String,
+    /// The key the payloads are signed with.
+    pub secret: String,
+    /// The names of the events, e.g. `video.detected`.
+    pub events: Vec<String>,
+    /// When the subscription was made, in seconds since the Unix epoch.
+    pub created_at: i64,
+}
+
+/// The outcome of delivering an event to a subscriber.
+#[derive(Serialize, ToSchema)]
+pub struct Delivery {
+    /// The ID of the delivery, also sent in the `X-Hnv-Delivery` header.
+    pub id: String,
+    pub event: String,
+    /// How many times the event was sent, including the retries.
+    pub attempts: u32,
+    /// The status of the last response, if there was any.
+    pub status: Option<u16>,
+    /// Why the delivery failed, if it did.
+    pub error: Option<String>,
+    /// When the delivery finished, in seconds since the Unix epoch.
+    pub delivered_at: i64,
+}
+
+/// A story or domain kept off the instance.
+#[derive(Clone, Serialize, ToSchema)]
+pub struct Block {
+    pub id: i64,
+    /// `story` or `domain`.
+    pub kind: String,
+    /// The ID of the story, e.g. `1` or `lobsters-abc123`, or the domain, e.g. `example.com`.
+    pub value: String,
+    /// When it was blocked, in seconds since the Unix epoch.
+    pub created_at: i64,
+}
+
+/// A response in the cache, without its body.
+#[derive(Clone, Serialize, ToSchema)]
+pub struct CacheEntry {
+    pub id: i64,
+    /// The URL the response was fetched from.
+    pub url: String,
+    /// When the response was fetched, in seconds since the Unix epoch, unless it was cached
+    /// before fetch times were recorded.
+    pub fetched_at: Option<i64>,
+    /// The size of the body in bytes.
+    pub size: u64,
+    /// `ok` for JSON, `null` for the `null` of items that don't exist, or `invalid` for
+    /// anything else, like an error page.
+    pub status: String,
+}
+
+/// How often a rule of the detection matched on a day.
+#[derive(Clone, Serialize, ToSchema)]
+pub struct DetectionCount {
+    /// The day in UTC, as `YYYY-MM-DD`.
+    pub day: String,
+    /// The source the stories were listed on, e.g. `hacker-news`.
+    pub source: String,
+    /// The rule that classified the stories, e.g. `youtube` or `rejected`.
+    pub rule: String,
+    /// The platform of the videos, e.g. `vimeo`, or empty for rejected stories.
+    pub platform: String,
+    /// The stories classified, summed over the refreshes of the day.
+    pub count: i64,
+}
+
+/// The score of a video as sampled by a refresh.
+#[derive(Clone, Copy, Serialize, ToSchema)]
+pub struct ScorePoint {
+    /// When the score was sampled, in seconds since the Unix epoch.
+    pub time: i64,
+    pub score: i64,
+}
+
+/// A video listed by a refresh.
+#[derive(Clone)]
+pub struct ListedVideo {
+    pub id: VideoId,
+    pub title: String,
+    pub url: String,
+    pub discussion_url: String,
+    pub score: Option<i64>,
+    /// When the video was submitted to its source, in seconds since the Unix epoch, if known.
+    pub submitted_at: Option<i64>,
+    /// The number of comments in the discussion on its source, if known.
+    pub comments: Option<i64>,
+    /// When the video was first listed, in seconds since the Unix epoch.
+    pub first_seen: i64,
+    /// When the video was last listed, in seconds since the Unix epoch.
+    pub last_seen: i64,
+}
+
+impl ListedVideo {
+    /// A video as recorded by a refresh, as if first listed at `now`.
+    pub fn new(video: &Video, now: i64) -> Self {
+        Self {
+            id: video.id.clone(),
+            title: video.title.clone(),
+            url: video.url.clone(),
+            discussion_url: video.discussion_url.clone(),
+            score: video.score,
+            submitted_at: video.submitted_at,
+            comments: video.comments,
+            first_seen: now,
+            last_seen: now,
+        }
+    }
+}
+
+/// The columns of `playlists` read into a [`Playlist`].
+const PLAYLIST_COLUMNS: &str =
+    "id, name, token, (SELECT COUNT(*) FROM playlist_items WHERE playlist_id = id)";
+
+/// A playlist of a visitor.
+pub struct Playlist {
+    pub id: i64,
+    pub name: String,
+    /// The unlisted token the playlist is shared under.
+    pub token: String,
+    pub videos: u64,
+}
+
+/// A change to a playlist.
+#[derive(Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistEdit {
+    Add,
+    Remove,
+    /// Play the video earlier.
+    Up,
+    /// Play the video later.
+    Down,
+}
+
+/// What the private feed of a visitor lists, besides leaving out the videos they hid.
+pub struct FeedSettings {
+    /// The unlisted token the feed is found under.
+    pub token: String,
+    /// Only list the videos of the source with this name.
+    pub source: Option<String>,
+    /// Only list the videos with one of these tags, unless there are none.
+    pub tags: Vec<String>,
+    /// The ntfy topic the new videos with the tags are pushed to, if any.
+    pub push_topic: Option<String>,
+}
+
+impl FeedSettings {
+    /// Whether the feed lists a video of a source with some tags.
+    pub fn matches(&self, source: &str, tags: &[String]) -> bool {
+        self.source.as_ref().is_none_or(|name| name == source)
+            && (self.tags.is_empty() || tags.iter().any(|tag| self.tags.contains(tag)))
+    }
+}
+
+/// What a search of the archive narrows the videos to. Every condition must hold, while lists of
+/// platforms or sources pass videos on any of them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VideoSearch {
+    /// Words or phrases the title must contain, ignoring the case of ASCII letters.
+    pub terms: Vec<String>,
+    /// The platforms the videos may be on, e.g. `youtube` or `vimeo`.
+    pub platforms: Vec<String>,
+    /// The names of the sources the videos may be found on, e.g. `lobsters`.
+    pub sources: Vec<String>,
+    /// Tags the title must have, in lower case and without brackets.
+    pub tags: Vec<String>,
+    pub min_score: Option<i64>,
+    pub max_score: Option<i64>,
+    /// The first day the videos may be first listed on.
+    pub start: Option<time::Date>,
+    /// The day from which on the videos may no longer be first listed.
+    pub end: Option<time::Date>,
+}
+
+impl VideoSearch {
+    /// Whether the search narrows down the videos at all.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A search a visitor saved, to be alerted to the new videos matching it.
+pub struct SavedSearch {
+    pub id: i64,
+    /// The query, in the language of the search page.
+    pub query: String,
+    /// The unlisted token the feed of its matches is found under.
+    pub token: String,
+    /// How many videos matched it so far.
+    pub matches: i64,
+}
+
+/// The columns a [`SavedSearch`] is read from, in order.
+const SAVED_SEARCH_COLUMNS: &str = "id, query, token,
+    (SELECT COUNT(*) FROM saved_search_matches WHERE search_id = saved_searches.id)";
+
+/// A month of the archive.
+pub struct ArchiveMonth {
+    pub year: i32,
+    /// From 1 for January.
+    pub month: u8,
+    /// The videos first listed in the month.
+    pub videos: u64,
+}
+
+/// A video queued for download.
+pub struct Download {
+    pub id: VideoId,
+    pub url: String,
+    pub title: String,
+    pub state: DownloadState,
+    /// The name of the downloaded file in the media directory, once done.
+    pub file: Option<String>,
+    /// Why the download failed, if it did.
+    pub error: Option<String>,
+    /// When the state last changed, in seconds since the Unix epoch.
+    pub updated_at: i64,
+    /// Whether the downloaded file is kept when cleaning up.
+    pub pinned: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DownloadState {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    /// The file was deleted to make room, or because it was too old.
+    Removed,
+}
+
+impl fmt::Display for DownloadState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Done => "done",
+            Self::Failed => "failed",
+            Self::Removed => "removed",
+        })
+    }
+}
+
+impl FromStr for DownloadState {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(match s {
+            "queued" => Self::Queued,
+            "running" => Self::Running,
+            "done" => Self::Done,
+            "failed" => Self::Failed,
+            "removed" => Self::Removed,
+            _ => bail!("invalid download state {:?}", s),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn like_patterns_match_wildcards_literally() {
+        assert_eq!(like_pattern("%", "100%", "%"), "%100\\%%");
+        assert_eq!(like_pattern("", "a_b\\c", "-%"), "a\\_b\\\\c-%");
+    }
+
+    #[tokio::test]
+    async fn searches_escape_like_wildcards() {
+        let conn = Connection::open_in_memory().await.unwrap();
+        let store = Store::new(conn).await.unwrap();
+        let video = |id: &str, title: &str| ListedVideo {
+            id: id.parse().unwrap(),
+            title: title.to_string(),
+            url: format!("https://youtu.be/{}", id),
+            discussion_url: String::new(),
+            score: Some(1),
+            submitted_at: None,
+            comments: None,
+            first_seen: 0,
+            last_seen: 0,
+        };
+        store
+            .record_videos(vec![
+                video("1", "100% Rust"),
+                video("2", "1000 Rust"),
+                video("3", "a_b"),
+                video("4", "axb"),
+            ])
+            .await
+            .unwrap();
+        let titles = |query: &str| {
+            let search = crate::search::parse(query);
+            let store = store.clone();
+            async move {
+                let found = store
+                    .search_videos(&search, TimeZone::default(), 10)
+                    .await
+                    .unwrap();
+                found
+                    .into_iter()
+                    .map(|video| video.title)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(titles("100%").await, ["100% Rust"]);
+        assert_eq!(titles("a_b").await, ["a_b"]);
+        assert_eq!(titles("rust").await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn listed_videos_are_read_in_the_order_asked_for() {
+        let conn = Connection::open_in_memory().await.unwrap();
+        let store = Store::new(conn).await.unwrap();
+        let video = |id: &str| ListedVideo {
+            id: id.parse().unwrap(),
+            title: format!("Video {}", id),
+            url: format!("https://youtu.be/{}", id),
+            discussion_url: String::new(),
+            score: None,
+            submitted_at: None,
+            comments: None,
+            first_seen: 0,
+            last_seen: 0,
+        };
+        store
+            .record_videos(vec![video("1"), video("2"), video("3")])
+            .await
+            .unwrap();
+
+        let ids: Vec<VideoId> = ["3", "4", "1", "3"]
+            .iter()
+            .map(|id| id.parse().unwrap())
+            .collect();
+        let titles: Vec<_> = store
+            .listed_videos(&ids)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|video| video.title)
+            .collect();
+        assert_eq!(titles, ["Video 3", "Video 1", "Video 3"]);
+    }
+
+    #[tokio::test]
+    async fn months_of_the_archive_start_in_the_time_zone() {
+        let conn = Connection::open_in_memory().await.unwrap();
+        let store = Store::new(conn).await.unwrap();
+        let video = |id: &str, first_seen: i64| ListedVideo {
+            id: id.parse().unwrap(),
+            title: format!("Video {}", id),
+            url: format!("https://youtu.be/{}", id),
+            discussion_url: String::new(),
+            score: None,
+            submitted_at: None,
+            comments: None,
+            first_seen,
+            last_seen: first_seen,
+        };
+        // 22:30 UTC on the last day of June is already July in Berlin, on summer time.
+        let late = 1719786600;
+        store
+            .record_videos(vec![video("1", late), video("2", late - 60 * 60)])
+            .await
+            .unwrap();
+
+        let counts = |months: Vec<ArchiveMonth>| {
+            months
+                .into_iter()
+                .map(|month| (month.year, month.month, month.videos))
+                .collect::<Vec<_>>()
+        };
+        let utc = store.archive_months(TimeZone::default()).await.unwrap();
+        assert_eq!(counts(utc), [(2024, 6, 2)]);
+        let berlin = "Europe/Berlin".parse().unwrap();
+        let berlin = store.archive_months(berlin).await.unwrap();
+        assert_eq!(counts(berlin), [(2024, 7, 1), (2024, 6, 1)]);
+    }
+
+    #[tokio::test]
+    async fn only_sessions_taken_over_from_the_lists_go_unsigned() {
+        let conn = Connection::open_in_memory().await.unwrap();
+        conn.call(|conn| {
+            conn.execute(
+                "CREATE TABLE favorites (
+                    session TEXT NOT NULL,
+                    item_id INTEGER NOT NULL,
+                    added_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+                    PRIMARY KEY (session, item_id)
+                )",
+                [],
+            )?;
+            conn.execute(
+                "INSERT INTO favorites (session, item_id) VALUES ('legacy', 1)",
+                [],
+            )?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+        let store = Store::new(conn).await.unwrap();
+        store.start_session("signed").await.unwrap();
+
+        assert!(store.see_session("legacy", 365, false).await.unwrap());
+        assert!(store.see_session("legacy", 365, true).await.unwrap());
+        assert!(!store.see_session("signed", 365, false).await.unwrap());
+        assert!(store.see_session("signed", 365, true).await.unwrap());
+
+        store.sign_session("legacy").await.unwrap();
+        assert!(!store.see_session("legacy", 365, false).await.unwrap());
+        assert!(store.see_session("legacy", 365, true).await.unwrap());
+    }
+}
@@ -0,0 +1,147 @@
+/// Videos added by hand, for those that never make it to the front page of a source.
+///
+/// Operators submit a URL through the API or the admin page. It goes through the same detection
+/// as the stories of the sources, and the video is recorded with the others under the `submitted`
+/// source. Submitted videos aren't listed on the front page, which only shows what the sources
+/// list, but they get a watch page and show up wherever the recorded videos do.
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+use crate::{
+    hacker_news::{detect_url, is_media_file, Counter, Detection, Detections},
+    source::{Fetched, Source, Story},
+    store::{ListedVideo, Store},
+    video::VideoId,
+};
+
+/// The name of the source of submitted videos.
+pub const NAME: &str = "submitted";
+
+/// The characters of the hash of the URL in the IDs of submitted videos.
+const ID_LENGTH: usize = 16;
+
+/// A video to add.
+#[derive(Deserialize, ToSchema)]
+pub struct Submission {
+    #[schema(example = "https://www.youtube.com/watch?v=dQw4w9WgXcQ")]
+    pub url: String,
+    /// The title to show, taken from the platform of the video if missing.
+    pub title: Option<String>,
+}
+
+impl Submission {
+    /// Check that the URL points at a video, explaining what is wrong otherwise.
+    pub fn validate(&self) -> Result<(), String> {
+        match reqwest::Url::parse(&self.url) {
+            Ok(url) if ["http", "https"].contains(&url.scheme()) => {}
+            _ => return Err(format!("{} is not an HTTP URL", self.url)),
+        }
+        if detect_url(&self.url) == Detection::Rejected && !is_media_file(&self.url) {
+            return Err(format!("{} is not a video", self.url));
+        }
+        Ok(())
+    }
+
+    /// The given title, unless it is blank as sent by an empty form field.
+    pub fn title(&self) -> Option<&str> {
+        self.title
+            .as_deref()
+            .map(str::trim)
+            .filter(|title| !title.is_empty())
+    }
+}
+
+pub struct Submissions {
+    store: Store,
+    public_url: String,
+}
+
+impl Submissions {
+    pub fn new(store: Store, public_url: &str) -> Self {
+        Self {
+            store,
+            public_url: public_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Record a video, returning its story. Submitting a URL again updates its title.
+    pub async fn add(&self, url: &str, title: &str) -> anyhow::Result<Story> {
+        let mut id = hex::encode(Sha256::digest(url.as_bytes()));
+        id.truncate(ID_LENGTH);
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let story = Story {
+            discussion_url: self.discussion_url(&id),
+            id,
+            title: title.to_string(),
+            url: url.to_string(),
+            score: None,
+            submitted_at: Some(now),
+            comments: None,
+        };
+
+        let listed = ListedVideo {
+            id: VideoId::new(NAME, story.id.clone()),
+            title: story.title.clone(),
+            url: story.url.clone(),
+            discussion_url: story.discussion_url.clone(),
+            score: None,
+            submitted_at: story.submitted_at,
+            comments: None,
+            first_seen: now,
+            last_seen: now,
+        };
+        self.store.record_videos(vec![listed]).await?;
+        Ok(story)
+    }
+}
+
+#[async_trait]
+impl Source for Submissions {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn label(&self) -> &'static str {
+        "Submitted"
+    }
+
+    /// Submitted videos are never on the front page.
+    async fn videos(
+        &self,
+        _counter: Arc<Counter>,
+        _on_fetched: &mut (dyn for<'s> FnMut(Fetched<&'s Story>) + Send),
+    ) -> anyhow::Result<Vec<Story>> {
+        Ok(Vec::new())
+    }
+
+    async fn video(&self, id: &str) -> anyhow::Result<Option<Story>> {
+        let listed = self
+            .store
+            .listed_video(&VideoId::new(NAME, id.to_string()))
+            .await?;
+        Ok(listed.map(|listed| Story {
+            id: listed.id.id,
+            title: listed.title,
+            url: listed.url,
+            discussion_url: listed.discussion_url,
+            score: listed.score,
+            submitted_at: listed.submitted_at,
+            comments: listed.comments,
+        }))
+    }
+
+    /// There is no discussion of submitted videos, so they link to their watch page instead.
+    fn discussion_url(&self, id: &str) -> String {
+        format!("{}/watch/{}-{}", self.public_url, NAME, id)
+    }
+
+    /// Nothing is fetched on refreshes, so nothing is detected either.
+    fn detections(&self) -> Detections {
+        Detections::default()
+    }
+}
@@ -0,0 +1,57 @@
+/// Telling systemd how the server is doing, for units of `Type=notify`.
+///
+/// `READY=1` is only sent once the cache is open, the sockets are listened on and the first videos
+/// are published, so that units ordered after hnv don't start against an empty index. With
+/// `WatchdogSec=` set, the refresh loop keeps sending `WATCHDOG=1` while it waits and while its
+/// refreshes make progress, and systemd restarts a server whose loop or refresh stalled. Outside
+/// of systemd, i.e. without `NOTIFY_SOCKET`, nothing is sent.
+use std::time::Duration;
+
+use tracing::warn;
+
+/// Send a state like `READY=1` to the service manager, if there is one.
+pub fn notify(state: &str) {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    if let Err(err) = send(&path, state) {
+        warn!("Failed to notify systemd of {:?}: {}", state, err);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn send(path: &std::ffi::OsStr, state: &str) -> std::io::Result<()> {
+    use std::os::{
+        linux::net::SocketAddrExt,
+        unix::{
+            ffi::OsStrExt,
+            net::{SocketAddr, UnixDatagram},
+        },
+    };
+
+    let socket = UnixDatagram::unbound()?;
+    // A leading `@` stands for a socket in the abstract namespace.
+    let addr = match path.as_bytes().strip_prefix(b"@") {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(path)?,
+    };
+    socket.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn send(_path: &std::ffi::OsStr, _state: &str) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// How often the watchdog is to be fed: half the timeout systemd set for this process, if any.
+pub fn watchdog_interval() -> Option<Duration> {
+    // The timeout is meant for the main process only, not for the ones it spawns.
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse() != Ok(std::process::id()) {
+            return None;
+        }
+    }
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec) / 2)
+}
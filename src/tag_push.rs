@@ -0,0 +1,105 @@
+/// Pushing the new videos with the tags visitors subscribe to through ntfy.
+///
+/// Visitors pick the tags in the settings of their private feed, along with a topic of their
+/// own on the configured server, which they subscribe to in the ntfy app. Each new video with
+/// any of the tags is pushed to the topic, unless the feed is narrowed to another source.
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::{Client, Url};
+use serde_json::json;
+use tracing::warn;
+
+use crate::{
+    config::{NotifyRule, TagPushConfig},
+    notify::Notifier,
+    store::Store,
+    video::Video,
+};
+
+/// The longest topic name ntfy accepts.
+const MAX_TOPIC_LENGTH: usize = 64;
+
+/// Whether a topic name is one ntfy accepts: letters, digits, dashes and underscores.
+pub fn valid_topic(topic: &str) -> bool {
+    !topic.is_empty()
+        && topic.len() <= MAX_TOPIC_LENGTH
+        && topic
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+pub struct TagPush {
+    client: Client,
+    config: TagPushConfig,
+    /// The URL of the server, which JSON messages are published to.
+    server: Url,
+    store: Store,
+}
+
+impl TagPush {
+    pub fn new(config: TagPushConfig, store: Store) -> anyhow::Result<Self> {
+        let server = Url::parse(&config.server_url)
+            .with_context(|| format!("Invalid ntfy server URL {}", config.server_url))?;
+        Ok(Self {
+            client: Client::new(),
+            config,
+            server,
+            store,
+        })
+    }
+
+    /// Push a video to a topic, titled after why it is pushed.
+    pub async fn push(&self, topic: &str, title: &str, video: &Video) -> anyhow::Result<()> {
+        let body = json!({
+            "topic": topic,
+            "title": title,
+            "message": video.title,
+            "click": video.url,
+            "tags": ["tv"],
+        });
+
+        let mut request = self.client.post(self.server.clone()).json(&body);
+        if let Some(token) = &self.config.token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for TagPush {
+    fn name(&self) -> &'static str {
+        "tag-push"
+    }
+
+    /// Every new video is matched against the subscriptions of the visitors instead.
+    fn rules(&self) -> &[NotifyRule] {
+        &[]
+    }
+
+    async fn send(&self, video: &Video) -> anyhow::Result<()> {
+        self.send_all(&[video]).await
+    }
+
+    async fn send_all(&self, videos: &[&Video]) -> anyhow::Result<()> {
+        let subscriptions = self.store.tag_subscriptions().await?;
+        for video in videos {
+            let tags = video.tags();
+            if tags.is_empty() {
+                continue;
+            }
+            let topics = subscriptions
+                .iter()
+                .filter(|feed| feed.matches(video.source, &tags))
+                .filter_map(|feed| feed.push_topic.as_deref());
+            for topic in topics {
+                // One topic failing doesn't keep the video from the others.
+                if let Err(err) = self.push(topic, &tags.join(" "), video).await {
+                    warn!(video = %video.id, "Failed to push a video to {}: {:#}", topic, err);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+/// Posting new videos to a Telegram chat through the Bot API.
+use anyhow::bail;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    config::{NotifyRule, TelegramConfig},
+    notify::Notifier,
+    video::Video,
+};
+
+/// A bot posting to a single chat.
+pub struct Telegram {
+    client: Client,
+    config: TelegramConfig,
+}
+
+/// The envelope of every Bot API response.
+#[derive(Deserialize)]
+struct Response {
+    ok: bool,
+    description: Option<String>,
+}
+
+impl Telegram {
+    pub fn new(config: TelegramConfig) -> Self {
+        Self {
+            client: Client::new(),
+            config,
+        }
+    }
+
+    /// The message of a video: its title, the video and the discussion.
+    fn message(video: &Video) -> String {
+        format!(
+            "<b>{}</b>\n{}\n<a href=\"{}\">{}</a>",
+            escape(&video.title),
+            escape(&video.url),
+            escape(&video.discussion_url),
+            escape(video.source_label),
+        )
+    }
+}
+
+#[async_trait]
+impl Notifier for Telegram {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    fn rules(&self) -> &[NotifyRule] {
+        &self.config.rules
+    }
+
+    async fn send(&self, video: &Video) -> anyhow::Result<()> {
+        let url = format!(
+            "{}/bot{}/sendMessage",
+            self.config.url.trim_end_matches('/'),
+            self.config.token
+        );
+        let body = json!({
+            "chat_id": self.config.chat_id,
+            "text": Self::message(video),
+            "parse_mode": "HTML",
+        });
+
+        // Errors are explained in the body, the status alone doesn't tell much.
+        let response: Response = self
+            .client
+            .post(&url)
+            .json(&body)
+            .send()
+            .await?
+            .json()
+            .await?;
+        if !response.ok {
+            bail!(
+                "Telegram refused the message: {}",
+                response.description.unwrap_or_default()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Escape text for Telegram's HTML formatting.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
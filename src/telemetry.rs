@@ -0,0 +1,184 @@
+/// Logging, and optionally exporting traces over OTLP and reporting errors to Sentry.
+///
+/// Log lines go to stderr, as text or with `--log-format json` as JSON objects carrying the spans
+/// they were logged in, e.g. the request with its ID. With a `[log]` file configured, they are
+/// written to it in the same format as well.
+///
+/// Traces are exported when `OTEL_EXPORTER_OTLP_ENDPOINT` (or the traces specific
+/// `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`) is set. The exporter is configured through the other
+/// standard `OTEL_*` environment variables, e.g. `OTEL_SERVICE_NAME` or
+/// `OTEL_EXPORTER_OTLP_HEADERS`.
+///
+/// Errors are reported to Sentry when `SENTRY_DSN` is set, along with `SENTRY_ENVIRONMENT` and
+/// `SENTRY_RELEASE` if given. Every error logged becomes an event, carrying the fields of the log
+/// line and its spans (e.g. the request or the refresh job), with the warnings and info lines
+/// before it as breadcrumbs. Panics are reported as well.
+///
+/// Panics are logged as errors with their stack trace, in the span they happened in, instead of
+/// printed to stderr.
+///
+/// With the `tokio-console` feature, the runtime is also served to `tokio-console`, configured
+/// through the `TOKIO_CONSOLE_*` environment variables. Tokio only reports its tasks when built
+/// with `RUSTFLAGS="--cfg tokio_unstable"`, so the console is left out otherwise.
+use anyhow::Context;
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_sdk::{runtime, trace::TracerProvider, Resource};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{
+    field::RecordFields,
+    filter::LevelFilter,
+    fmt::{
+        format::{DefaultFields, Writer},
+        FormatFields, MakeWriter,
+    },
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+    Layer, Registry,
+};
+
+use crate::{cli::LogFormat, config::LogConfig, log_file};
+
+/// Flushes the exported spans and the reported errors when dropped.
+pub struct Guard {
+    provider: Option<TracerProvider>,
+    _sentry: Option<sentry::ClientInitGuard>,
+    /// Declared last, so the errors of flushing the others still end up in the file.
+    _log_file: Option<WorkerGuard>,
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take() {
+            for result in provider.force_flush() {
+                if let Err(err) = result {
+                    eprintln!("Failed to flush traces: {}", err);
+                }
+            }
+        }
+    }
+}
+
+/// A layer writing the log lines in a format.
+///
+/// The levels are filtered per layer, as the console needs Tokio's trace level events.
+fn fmt_layer<W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<Registry> + Send + Sync>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let fmt = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match format {
+        LogFormat::Text if ansi => fmt.with_filter(LevelFilter::INFO).boxed(),
+        LogFormat::Text => fmt
+            .fmt_fields(PlainFields(DefaultFields::new()))
+            .with_filter(LevelFilter::INFO)
+            .boxed(),
+        LogFormat::Json => fmt
+            .json()
+            .with_span_list(true)
+            .with_filter(LevelFilter::INFO)
+            .boxed(),
+    }
+}
+
+/// Log panics with their stack trace, which the request they happened in is logged along with.
+fn log_panics() {
+    std::panic::set_hook(Box::new(|info| {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("Box<dyn Any>");
+        let location = info
+            .location()
+            .map(|location| location.to_string())
+            .unwrap_or_default();
+        let backtrace = std::backtrace::Backtrace::force_capture();
+        tracing::error!("Panicked at {}: {}\n{}", location, message, backtrace);
+    }));
+}
+
+/// The default fields, as a type of their own.
+///
+/// Layers keep the formatted fields of spans by the type of their formatter, so without it a
+/// layer without colors would reuse the fields colored by another.
+struct PlainFields(DefaultFields);
+
+impl<'w> FormatFields<'w> for PlainFields {
+    fn format_fields<R: RecordFields>(&self, writer: Writer<'w>, fields: R) -> std::fmt::Result {
+        self.0.format_fields(writer, fields)
+    }
+}
+
+/// Install the global subscriber, logging to stderr so it doesn't mix with command output.
+pub fn init(format: LogFormat, log: Option<&LogConfig>) -> anyhow::Result<Guard> {
+    let mut fmt = vec![fmt_layer(format, std::io::stderr, true)];
+    let log_file = match log {
+        Some(log) => {
+            let (writer, guard) = log_file::open(log)?;
+            fmt.push(fmt_layer(format, writer, false));
+            Some(guard)
+        }
+        None => None,
+    };
+    // Before Sentry, whose hook reports the panic and then calls this one.
+    log_panics();
+    let sentry = std::env::var_os("SENTRY_DSN").map(|_| {
+        sentry::init(sentry::ClientOptions {
+            release: sentry::release_name!(),
+            ..Default::default()
+        })
+    });
+    let registry = tracing_subscriber::registry()
+        .with(fmt)
+        .with(sentry.as_ref().map(|_| sentry_tracing::layer()));
+    #[cfg(all(feature = "tokio-console", tokio_unstable))]
+    let registry = registry.with(console_subscriber::spawn());
+    #[cfg(all(feature = "tokio-console", not(tokio_unstable)))]
+    eprintln!("Built without `--cfg tokio_unstable`, so tokio-console is not served");
+
+    let enabled = [
+        "OTEL_EXPORTER_OTLP_ENDPOINT",
+        "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+    ]
+    .iter()
+    .any(|name| std::env::var_os(name).is_some());
+    if !enabled {
+        registry.init();
+        return Ok(Guard {
+            provider: None,
+            _sentry: sentry,
+            _log_file: log_file,
+        });
+    }
+
+    let mut resource = Resource::default();
+    if std::env::var_os("OTEL_SERVICE_NAME").is_none() {
+        resource = resource.merge(&Resource::new([KeyValue::new("service.name", "hnv")]));
+    }
+
+    let exporter = opentelemetry_otlp::new_exporter()
+        .tonic()
+        .build_span_exporter()
+        .context("Failed to set up the OTLP exporter")?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_config(opentelemetry_sdk::trace::config().with_resource(resource))
+        .build();
+    let tracer = provider.tracer("hnv");
+
+    registry
+        .with(
+            tracing_opentelemetry::layer()
+                .with_tracer(tracer)
+                .with_filter(LevelFilter::INFO),
+        )
+        .init();
+    Ok(Guard {
+        provider: Some(provider),
+        _sentry: sentry,
+        _log_file: log_file,
+    })
+}
@@ -0,0 +1,256 @@
+/// Browsing the current videos in the terminal.
+///
+/// The list is drawn on stderr, so that the URL of the video picked with Enter is the only thing
+/// printed to stdout and can be handed to a player, e.g. `mpv "$(hnv tui)"`. Typing after `/`
+/// narrows the list to the titles matching the typed letters in order, best matches first, and
+/// `o` plays the selected video in the configured player without leaving the list.
+use std::io::{stderr, IsTerminal, Stderr};
+
+use anyhow::bail;
+use ratatui::{
+    backend::CrosstermBackend,
+    crossterm::{
+        event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+        execute,
+        terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    layout::Constraint,
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Row, Table, TableState},
+    Frame, Terminal,
+};
+
+use crate::{config::PlayerConfig, player, state::SharedState, video::Video};
+
+/// How many rows Page Up and Page Down move.
+const PAGE: usize = 10;
+
+/// Browse the current videos, printing the URL of the one picked.
+pub async fn run(state: SharedState) -> anyhow::Result<()> {
+    if !stderr().is_terminal() {
+        bail!("The terminal UI is drawn on stderr, which isn't a terminal");
+    }
+    let videos = state.videos().to_vec();
+    let player = state.config.player.clone();
+    let picked = tokio::task::spawn_blocking(move || browse(videos, player)).await??;
+    if let Some(video) = picked {
+        println!("{}", video.url);
+    }
+    Ok(())
+}
+
+/// Run the terminal UI until the visitor picks a video or quits.
+fn browse(videos: Vec<Video>, player: PlayerConfig) -> anyhow::Result<Option<Video>> {
+    enable_raw_mode()?;
+    execute!(stderr(), EnterAlternateScreen)?;
+    let result = Terminal::new(CrosstermBackend::new(stderr()))
+        .map_err(anyhow::Error::from)
+        .and_then(|mut terminal| Browser::new(videos, player).run(&mut terminal));
+
+    // Leave the terminal as it was, whatever happened.
+    disable_raw_mode()?;
+    execute!(stderr(), LeaveAlternateScreen)?;
+    result
+}
+
+struct Browser {
+    videos: Vec<Video>,
+    /// The letters the titles are filtered by.
+    filter: String,
+    /// Whether keys are typed into the filter.
+    filtering: bool,
+    /// The indices of the videos passing the filter, in the order shown.
+    shown: Vec<usize>,
+    table: TableState,
+    player: PlayerConfig,
+    /// What happened to the last video played, shown until the next key.
+    played: Option<String>,
+}
+
+impl Browser {
+    fn new(videos: Vec<Video>, player: PlayerConfig) -> Self {
+        let mut browser = Self {
+            videos,
+            filter: String::new(),
+            filtering: false,
+            shown: Vec::new(),
+            table: TableState::default(),
+            player,
+            played: None,
+        };
+        browser.apply_filter();
+        browser
+    }
+
+    fn run(
+        mut self,
+        terminal: &mut Terminal<CrosstermBackend<Stderr>>,
+    ) -> anyhow::Result<Option<Video>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match self.handle(key) {
+                Action::Continue => {}
+                Action::Quit => return Ok(None),
+                Action::Pick => {
+                    let picked = self.table.selected().and_then(|row| self.shown.get(row));
+                    return Ok(picked.map(|&index| self.videos[index].clone()));
+                }
+            }
+        }
+    }
+
+    fn handle(&mut self, key: KeyEvent) -> Action {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Action::Quit;
+        }
+        self.played = None;
+        match key.code {
+            KeyCode::Enter if self.filtering => self.filtering = false,
+            KeyCode::Enter => return Action::Pick,
+            KeyCode::Esc if self.filtering => {
+                self.filtering = false;
+                self.filter.clear();
+                self.apply_filter();
+            }
+            KeyCode::Backspace if self.filtering => {
+                self.filter.pop();
+                self.apply_filter();
+            }
+            KeyCode::Char(c) if self.filtering => {
+                self.filter.push(c);
+                self.apply_filter();
+            }
+            KeyCode::Esc | KeyCode::Char('q') => return Action::Quit,
+            KeyCode::Char('/') => self.filtering = true,
+            KeyCode::Char('o') => self.play(),
+            KeyCode::Down | KeyCode::Char('j') => self.move_by(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_by(-1),
+            KeyCode::PageDown => self.move_by(PAGE as isize),
+            KeyCode::PageUp => self.move_by(-(PAGE as isize)),
+            KeyCode::Home | KeyCode::Char('g') => self.move_by(isize::MIN),
+            KeyCode::End | KeyCode::Char('G') => self.move_by(isize::MAX),
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    /// Play the selected video in the player.
+    fn play(&mut self) {
+        let Some(&index) = self.table.selected().and_then(|row| self.shown.get(row)) else {
+            return;
+        };
+        let video = &self.videos[index];
+        self.played = Some(match player::open(&self.player, video) {
+            Ok(()) => format!("Playing in {}: {}", player::name(&self.player), video.title),
+            Err(err) => format!("{:#}", err),
+        });
+    }
+
+    /// Move the selection by a number of rows, stopping at the first and the last.
+    fn move_by(&mut self, rows: isize) {
+        let Some(last) = self.shown.len().checked_sub(1) else {
+            return;
+        };
+        let current = self.table.selected().unwrap_or(0);
+        let row = current.saturating_add_signed(rows).min(last);
+        self.table.select(Some(row));
+    }
+
+    /// Show the videos matching the filter, the best matches first, or all of them in their
+    /// order without one.
+    fn apply_filter(&mut self) {
+        let mut matches: Vec<(usize, i64)> = self
+            .videos
+            .iter()
+            .enumerate()
+            .filter_map(|(index, video)| Some((index, fuzzy_score(&self.filter, &video.title)?)))
+            .collect();
+        matches.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+        self.shown = matches.into_iter().map(|(index, _)| index).collect();
+        self.table.select((!self.shown.is_empty()).then_some(0));
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [list, status] =
+            ratatui::layout::Layout::vertical([Constraint::Fill(1), Constraint::Length(1)])
+                .areas(frame.area());
+
+        let rows = self.shown.iter().map(|&index| {
+            let video = &self.videos[index];
+            Row::new([
+                video
+                    .score
+                    .map(|score| score.to_string())
+                    .unwrap_or_default(),
+                video.title.clone(),
+                video.source_label.to_string(),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(6),
+                Constraint::Fill(1),
+                Constraint::Length(12),
+            ],
+        )
+        .header(
+            Row::new(["Score", "Title", "Source"]).style(Style::new().add_modifier(Modifier::BOLD)),
+        )
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, list, &mut self.table);
+
+        let line = match (self.filtering, self.filter.is_empty(), &self.played) {
+            (true, _, _) => format!("/{}", self.filter),
+            (false, _, Some(played)) => played.clone(),
+            (false, true, None) => {
+                "j/k move · / filter · o play · Enter print the URL · q quit".to_string()
+            }
+            (false, false, None) => format!("/{} · Esc clears", self.filter),
+        };
+        let count = format!("{}/{}", self.shown.len(), self.videos.len());
+        frame.render_widget(Line::raw(line), status);
+        frame.render_widget(Line::raw(count).right_aligned(), status);
+    }
+}
+
+enum Action {
+    Continue,
+    Quit,
+    /// Print the URL of the selected video and exit.
+    Pick,
+}
+
+/// How well a title matches the letters of a filter, which must all appear in it in order,
+/// ignoring case. Runs of letters and letters starting words score higher.
+fn fuzzy_score(filter: &str, title: &str) -> Option<i64> {
+    let mut score = 0;
+    let mut previous: Option<char> = None;
+    let mut matched_previous = false;
+    let mut letters = filter.chars().flat_map(char::to_lowercase).peekable();
+    for c in title.chars().flat_map(char::to_lowercase) {
+        let Some(&letter) = letters.peek() else {
+            break;
+        };
+        if c == letter {
+            letters.next();
+            score += 1;
+            if matched_previous {
+                score += 5;
+            }
+            if previous.is_none_or(|previous| !previous.is_alphanumeric()) {
+                score += 3;
+            }
+        }
+        matched_previous = c == letter;
+        previous = Some(c);
+    }
+    letters.peek().is_none().then_some(score)
+}
@@ -0,0 +1,36 @@
+/// Which build of hnv is running, so that bug reports can name it.
+///
+/// The commit and the time of the build are embedded by `build.rs`.
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// The version, the commit and the date of the build, e.g.
+/// `0.1.0 (1a2b3c4d5e6f, 2024-05-01T12:00:00Z)`.
+pub const LABEL: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("HNV_GIT_HASH"),
+    ", ",
+    env!("HNV_BUILT_AT"),
+    ")"
+);
+
+/// The build, as served on `/version`.
+#[derive(Serialize, ToSchema)]
+pub struct Build {
+    /// The version of the package.
+    #[schema(example = "0.1.0")]
+    pub version: &'static str,
+    /// The abbreviated hash of the commit, or `unknown` when built outside of git.
+    #[schema(example = "1a2b3c4d5e6f")]
+    pub git_hash: &'static str,
+    /// When it was built, in RFC 3339.
+    #[schema(example = "2024-05-01T12:00:00Z")]
+    pub built_at: &'static str,
+}
+
+pub const BUILD: Build = Build {
+    version: env!("CARGO_PKG_VERSION"),
+    git_hash: env!("HNV_GIT_HASH"),
+    built_at: env!("HNV_BUILT_AT"),
+};
@@ -0,0 +1,403 @@
+/// The videos found on the sources, as listed on the site.
+///
+/// A [`Video`] is built from the [`Story`](crate::source::Story) of a source, with the links the
+/// site serves it under and the details of its platform. Submissions of the same video to Hacker
+/// News are told apart by their canonical ID, see [`collapse_reposts`].
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    hacker_news,
+    source::{self, Source},
+};
+
+/// How many comments a discussion needs before it can be hot, so that a handful of comments on
+/// a story without points doesn't count.
+pub const HOT_MIN_COMMENTS: i64 = 10;
+
+/// Fold the submissions of a video on Hacker News into the first of them, keeping the order.
+///
+/// Videos are matched by their canonical ID, so a YouTube video posted under different URLs is
+/// still collapsed. Videos from other sources are left alone.
+pub fn collapse_reposts(videos: impl IntoIterator<Item = Video>) -> Vec<Video> {
+    let mut collapsed: Vec<Video> = Vec::new();
+    let mut first: HashMap<String, usize> = HashMap::new();
+    for video in videos {
+        if !video.id.is_hacker_news() {
+            collapsed.push(video);
+            continue;
+        }
+        match first.entry(canonical_id(&video.url)) {
+            Entry::Occupied(entry) => collapsed[*entry.get()].reposts.push(video.repost()),
+            Entry::Vacant(entry) => {
+                entry.insert(collapsed.len());
+                collapsed.push(video);
+            }
+        }
+    }
+    collapsed
+}
+
+/// Keep at most `max` of the videos, dropping those submitted longest ago and keeping the order
+/// of the rest. Videos without a submission time count as submitted when they are listed, now.
+pub fn keep_newest(videos: Vec<Video>, max: usize) -> Vec<Video> {
+    let excess = videos.len().saturating_sub(max);
+    if excess == 0 {
+        return videos;
+    }
+    // Of videos as old, the lower ranked go first.
+    let mut by_age: Vec<usize> = (0..videos.len()).collect();
+    by_age.sort_by_key(|&index| {
+        let submitted_at = videos[index].submitted_at.unwrap_or(i64::MAX);
+        (submitted_at, std::cmp::Reverse(index))
+    });
+    let dropped: HashSet<usize> = by_age.into_iter().take(excess).collect();
+    videos
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| !dropped.contains(index))
+        .map(|(_, video)| video)
+        .collect()
+}
+
+#[derive(Clone, Hash, Serialize, ToSchema)]
+pub struct Video {
+    /// The item ID on Hacker News, or the source and story ID for other sources.
+    #[schema(value_type = serde_json::Value, example = 1)]
+    pub id: VideoId,
+    /// The name of the source the video was found on, e.g. `hacker-news`.
+    #[schema(value_type = String)]
+    pub source: &'static str,
+    /// The name of the source shown to visitors.
+    #[serde(skip)]
+    pub source_label: &'static str,
+    pub title: String,
+    /// The discussion on Hacker News, for videos found there.
+    pub hn_link: Option<String>,
+    /// The discussion on the source of the video.
+    pub discussion_url: String,
+    pub watch_link: String,
+    /// A compact link to the video that counts clicks.
+    pub short_link: String,
+    /// A link to the discussion that doesn't leak the referrer.
+    pub discussion_link: String,
+    /// The URL the item was submitted with.
+    pub url: String,
+    /// The URL visitors are sent to, which may point at a privacy frontend instead of `url`.
+    pub link: String,
+    /// The URL of an embeddable player for the video, if the platform provides one.
+    pub embed_url: Option<String>,
+    /// The points of the story on its source, as of the last refresh.
+    pub score: Option<i64>,
+    /// When the story was submitted to its source, in seconds since the Unix epoch, if known.
+    pub submitted_at: Option<i64>,
+    /// The number of comments in the discussion on its source, as of the last refresh.
+    pub comments: Option<i64>,
+    /// The other submissions of the same video on Hacker News, the highest ranked first.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub reposts: Vec<Repost>,
+}
+
+/// Another submission of a listed video, shown on its card instead of as a video of its own.
+#[derive(Clone, Hash, Serialize, ToSchema)]
+pub struct Repost {
+    #[schema(value_type = serde_json::Value, example = 1)]
+    pub id: VideoId,
+    /// A link to the discussion that doesn't leak the referrer.
+    pub discussion_link: String,
+    /// The points of the story, as of the last refresh.
+    pub score: Option<i64>,
+}
+
+impl Video {
+    /// Build a video from a story found on a source.
+    pub fn new(source: &dyn Source, story: source::Story) -> Self {
+        let id = VideoId::new(source.name(), story.id);
+        let watch_link = format!("/watch/{}", id);
+        let short_link = format!("/v/{}", id);
+        let discussion_link = format!("/v/{}/discussion", id);
+        let embed_url = hacker_news::youtube_id(&story.url)
+            .map(|id| format!("https://www.youtube.com/embed/{}", id));
+        let hn_link = id.is_hacker_news().then(|| story.discussion_url.clone());
+
+        Video {
+            id,
+            source: source.name(),
+            source_label: source.label(),
+            title: story.title,
+            hn_link,
+            discussion_url: story.discussion_url,
+            watch_link,
+            short_link,
+            discussion_link,
+            link: story.url.clone(),
+            url: story.url,
+            embed_url,
+            score: story.score,
+            submitted_at: story.submitted_at,
+            comments: story.comments,
+            reposts: Vec::new(),
+        }
+    }
+
+    /// Point the links of YouTube videos at an Invidious or Piped instance, if one is given.
+    ///
+    /// Both frontends mirror YouTube's `/watch?v=` and `/embed/` paths.
+    pub fn with_frontend(mut self, frontend: Option<&str>) -> Self {
+        if let (Some(frontend), Some(id)) = (frontend, self.youtube_id()) {
+            let frontend = frontend.trim_end_matches('/');
+            self.link = format!("{}/watch?v={}", frontend, id);
+            self.embed_url = Some(format!("{}/embed/{}", frontend, id));
+        }
+        self
+    }
+
+    /// The comments on the video for each of its points, in thousandths.
+    pub fn comments_per_point(&self) -> i64 {
+        self.comments.unwrap_or(0) * 1000 / self.score.unwrap_or(0).max(1)
+    }
+
+    /// Whether the video drew more comments than points, which often makes for an interesting
+    /// thread.
+    pub fn is_hot_discussion(&self) -> bool {
+        self.comments
+            .is_some_and(|comments| comments >= HOT_MIN_COMMENTS)
+            && self.comments_per_point() > 1000
+    }
+
+    /// The video as a repost of another submission of it.
+    pub fn repost(&self) -> Repost {
+        Repost {
+            id: self.id.clone(),
+            discussion_link: self.discussion_link.clone(),
+            score: self.score,
+        }
+    }
+
+    /// The YouTube video ID, if the video is on YouTube.
+    pub fn youtube_id(&self) -> Option<String> {
+        hacker_news::youtube_id(&self.url)
+    }
+
+    /// The URL of a thumbnail of the video, if the video is on YouTube.
+    pub fn thumbnail_url(&self) -> Option<String> {
+        self.youtube_id()
+            .map(|id| format!("https://i.ytimg.com/vi/{}/mqdefault.jpg", id))
+    }
+
+    /// The platform the video is hosted on, e.g. `youtube` or `vimeo`.
+    pub fn platform(&self) -> String {
+        hacker_news::platform(&self.url)
+    }
+
+    /// The tags in the title, i.e. the bracketed annotations like `[video]`, in lower case.
+    pub fn tags(&self) -> Vec<String> {
+        self.title
+            .split('[')
+            .skip(1)
+            .filter_map(|rest| rest.split_once(']'))
+            .map(|(tag, _)| tag.trim().to_lowercase())
+            .filter(|tag| !tag.is_empty())
+            .collect()
+    }
+
+    /// Check whether the URL points directly at a media file a browser can play.
+    pub fn is_media_file(&self) -> bool {
+        hacker_news::is_media_file(&self.url)
+    }
+}
+
+/// The ID of a video, which tells the source it was found on.
+///
+/// Hacker News items keep their plain numeric IDs, so existing links and lists stay valid. Stories
+/// from other sources are prefixed with the name of the source, e.g. `lobsters-abc123`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VideoId {
+    /// The name of the source, e.g. `hacker-news`.
+    pub source: String,
+    /// The ID of the story on its source.
+    pub id: String,
+}
+
+impl VideoId {
+    pub fn new(source: &str, id: String) -> Self {
+        Self {
+            source: source.to_string(),
+            id,
+        }
+    }
+
+    pub fn is_hacker_news(&self) -> bool {
+        self.source == hacker_news::NAME
+    }
+
+    /// The item ID on Hacker News, for videos found there.
+    pub fn hacker_news_id(&self) -> Option<i32> {
+        self.is_hacker_news()
+            .then(|| self.id.parse().ok())
+            .flatten()
+    }
+}
+
+impl fmt::Display for VideoId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.is_hacker_news() {
+            true => write!(f, "{}", self.id),
+            false => write!(f, "{}-{}", self.source, self.id),
+        }
+    }
+}
+
+impl FromStr for VideoId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+            return Ok(Self::new(hacker_news::NAME, s.to_string()));
+        }
+
+        // The IDs end up in API URLs, so nothing but letters and digits gets through.
+        let (source, id) = s
+            .rsplit_once('-')
+            .with_context(|| format!("invalid video ID {:?}", s))?;
+        let valid_source =
+            !source.is_empty() && source.bytes().all(|b| b.is_ascii_lowercase() || b == b'-');
+        let valid_id = !id.is_empty() && id.bytes().all(|b| b.is_ascii_alphanumeric());
+        if !valid_source || !valid_id {
+            bail!("invalid video ID {:?}", s);
+        }
+        Ok(Self::new(source, id.to_string()))
+    }
+}
+
+/// Hacker News IDs are serialized as numbers, as they were before there were other sources.
+impl Serialize for VideoId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.id.parse() {
+            Ok(id) if self.is_hacker_news() => serializer.serialize_i32(id),
+            _ => serializer.collect_str(self),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for VideoId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        id.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// The ID a video is matched by: its YouTube ID, or else its URL.
+pub fn canonical_id(url: &str) -> String {
+    hacker_news::youtube_id(url).unwrap_or_else(|| url.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A story of a source with the given ID and URL.
+    fn video(source: &'static str, id: &str, url: &str) -> Video {
+        Video {
+            id: VideoId::new(source, id.to_string()),
+            source,
+            source_label: source,
+            title: format!("Video {}", id),
+            hn_link: None,
+            discussion_url: String::new(),
+            watch_link: String::new(),
+            short_link: String::new(),
+            discussion_link: String::new(),
+            link: url.to_string(),
+            url: url.to_string(),
+            embed_url: None,
+            score: None,
+            submitted_at: None,
+            comments: None,
+            reposts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn reposts_are_collapsed_into_the_first_submission() {
+        let videos = collapse_reposts([
+            video(
+                hacker_news::NAME,
+                "1",
+                "https://www.youtube.com/watch?v=abc",
+            ),
+            video(hacker_news::NAME, "2", "https://example.com/talk.mp4"),
+            video(hacker_news::NAME, "3", "https://youtu.be/abc"),
+            video("lobsters", "x1", "https://youtu.be/abc"),
+        ]);
+
+        let ids: Vec<_> = videos.iter().map(|video| video.id.to_string()).collect();
+        assert_eq!(ids, ["1", "2", "lobsters-x1"]);
+        assert_eq!(videos[0].reposts.len(), 1);
+        assert_eq!(videos[0].reposts[0].id.to_string(), "3");
+        assert!(videos[1].reposts.is_empty());
+    }
+
+    #[test]
+    fn the_videos_submitted_longest_ago_are_dropped() {
+        let videos = ["1", "2", "3", "4"].map(|id| {
+            let mut video = video(hacker_news::NAME, id, &format!("https://youtu.be/{}", id));
+            video.submitted_at = match id {
+                "1" => Some(300),
+                "2" => Some(100),
+                "3" => None,
+                _ => Some(200),
+            };
+            video
+        });
+
+        let kept = keep_newest(videos.to_vec(), 2);
+        let ids: Vec<_> = kept.iter().map(|video| video.id.to_string()).collect();
+        assert_eq!(ids, ["1", "3"]);
+        assert_eq!(keep_newest(videos.to_vec(), 5).len(), 4);
+    }
+
+    #[test]
+    fn video_ids_are_parsed_back_from_their_display() {
+        for id in ["42", "lobsters-abc123", "reddit-programming-1x2y"] {
+            assert_eq!(id.parse::<VideoId>().unwrap().to_string(), id);
+        }
+        let id: VideoId = "lobsters-abc123".parse().unwrap();
+        assert_eq!((id.source.as_str(), id.id.as_str()), ("lobsters", "abc123"));
+        assert_eq!("42".parse::<VideoId>().unwrap().hacker_news_id(), Some(42));
+    }
+
+    #[test]
+    fn invalid_video_ids_are_rejected() {
+        for id in [
+            "",
+            "abc",
+            "-abc",
+            "lobsters-",
+            "Lobsters-abc",
+            "lobsters-a/b",
+            "../x-1",
+        ] {
+            assert!(id.parse::<VideoId>().is_err(), "{:?} was accepted", id);
+        }
+    }
+
+    #[test]
+    fn hacker_news_ids_are_serialized_as_numbers() {
+        let ids = [
+            VideoId::new(hacker_news::NAME, "42".to_string()),
+            VideoId::new("lobsters", "abc".to_string()),
+        ];
+        assert_eq!(
+            serde_json::to_string(&ids).unwrap(),
+            r#"[42,"lobsters-abc"]"#
+        );
+    }
+}
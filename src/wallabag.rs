@@ -0,0 +1,97 @@
+/// Saving videos to a Wallabag account.
+///
+/// The API takes OAuth tokens, which are requested with the password of the account and reused
+/// until shortly before they expire.
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use crate::{config::WallabagConfig, read_later::ReadLater, video::Video};
+
+/// How long before it expires a token is no longer used, to allow for slow requests.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+pub struct Wallabag {
+    client: Client,
+    config: WallabagConfig,
+    /// The current token, with when it stops being used.
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    /// Seconds until the token expires.
+    expires_in: u64,
+}
+
+impl Wallabag {
+    pub fn new(config: WallabagConfig) -> Self {
+        Self {
+            client: Client::new(),
+            config,
+            token: Mutex::new(None),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.config.url.trim_end_matches('/'), path)
+    }
+
+    /// A valid token, requesting a new one if needed.
+    async fn token(&self) -> anyhow::Result<String> {
+        let mut token = self.token.lock().await;
+        if let Some((token, expiry)) = &*token {
+            if Instant::now() < *expiry {
+                return Ok(token.clone());
+            }
+        }
+
+        let response: TokenResponse = self
+            .client
+            .post(self.url("/oauth/v2/token"))
+            .form(&[
+                ("grant_type", "password"),
+                ("client_id", &self.config.client_id),
+                ("client_secret", &self.config.client_secret),
+                ("username", &self.config.username),
+                ("password", &self.config.password),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let expiry =
+            Instant::now() + Duration::from_secs(response.expires_in).saturating_sub(EXPIRY_MARGIN);
+        *token = Some((response.access_token.clone(), expiry));
+        Ok(response.access_token)
+    }
+}
+
+#[async_trait]
+impl ReadLater for Wallabag {
+    fn name(&self) -> &'static str {
+        "wallabag"
+    }
+
+    fn label(&self) -> &'static str {
+        "Wallabag"
+    }
+
+    async fn save(&self, video: &Video) -> anyhow::Result<()> {
+        let token = self.token().await?;
+        self.client
+            .post(self.url("/api/entries.json"))
+            .bearer_auth(token)
+            .json(&json!({ "url": video.url, "title": video.title }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
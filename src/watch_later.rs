@@ -0,0 +1,56 @@
+/// Moving watch-later lists in and out of the site.
+///
+/// A list is exported as JSON, and imported from such an export, from the `Watch later.csv` of a
+/// YouTube Takeout, or from plain text with a URL per line. Entries are matched against the
+/// recorded videos by their canonical ID: the YouTube ID of YouTube videos, which is all a
+/// Takeout has, and the URL of the others. Entries that were never listed here are skipped.
+use serde::{Deserialize, Serialize};
+
+use crate::video::canonical_id;
+
+/// A saved video, as exported.
+#[derive(Deserialize, Serialize)]
+pub struct Entry {
+    /// The ID of the video on this site.
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    pub url: String,
+}
+
+/// The canonical IDs of the videos in an imported list, in order.
+pub fn parse(list: &str) -> Vec<String> {
+    let list = list.trim_start_matches('\u{feff}').trim();
+    if list.starts_with('[') {
+        if let Ok(entries) = serde_json::from_str::<Vec<Entry>>(list) {
+            return entries
+                .iter()
+                .map(|entry| canonical_id(&entry.url))
+                .collect();
+        }
+    }
+
+    // Takeout CSVs start with a header and have the video ID in the first column, followed by
+    // when it was saved. Anything that isn't a URL or an ID, like the header, is skipped.
+    list.lines()
+        .filter_map(|line| {
+            let field = line.split(',').next()?.trim().trim_matches('"');
+            if field.starts_with("http://") || field.starts_with("https://") {
+                Some(canonical_id(field))
+            } else if is_youtube_id(field) {
+                Some(field.to_string())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Whether a field looks like a bare YouTube video ID.
+fn is_youtube_id(field: &str) -> bool {
+    field.len() == 11
+        && field
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
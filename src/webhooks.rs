@@ -0,0 +1,271 @@
+/// Outgoing webhooks, which tell other services about events such as new videos.
+///
+/// Subscribers register a URL and the events they want through the admin API. Every event is
+/// POSTed to them as JSON like `{"id", "event", "created_at", "data"}`, with these headers:
+///
+/// - `X-Hnv-Event`: the name of the event, e.g. `video.detected`.
+/// - `X-Hnv-Delivery`: the ID of the delivery, the same as in the body.
+/// - `X-Hnv-Signature`: `sha256=` followed by the hex HMAC-SHA256 of the body, keyed with the
+///   secret returned when subscribing, so subscribers can check the payload comes from here.
+///
+/// Deliveries that fail on the network, a server error or rate limiting are retried a few times
+/// with growing pauses. The outcome of every delivery is logged in the store.
+use std::time::Duration;
+
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use reqwest::{header::CONTENT_TYPE, Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::warn;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    config::NotifyRule,
+    notify::Notifier,
+    store::{Delivery, Store, Webhook},
+    video::Video,
+};
+
+/// A video was listed for the first time.
+pub const VIDEO_DETECTED: &str = "video.detected";
+/// A refresh finished, the list of videos is up to date.
+pub const REFRESH_COMPLETED: &str = "refresh.completed";
+/// The events subscribers can subscribe to.
+pub const EVENTS: [&str; 2] = [VIDEO_DETECTED, REFRESH_COMPLETED];
+
+/// The pauses before the retries of a failed delivery.
+const RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_secs(10),
+    Duration::from_secs(60),
+    Duration::from_secs(300),
+];
+
+/// How long a subscriber has to answer.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An event to deliver.
+pub struct Event {
+    name: &'static str,
+    data: Value,
+}
+
+impl Event {
+    pub fn video_detected(video: &Video) -> Self {
+        Self {
+            name: VIDEO_DETECTED,
+            data: json!(video),
+        }
+    }
+
+    pub fn refresh_completed(videos: usize) -> Self {
+        Self {
+            name: REFRESH_COMPLETED,
+            data: json!({ "videos": videos }),
+        }
+    }
+}
+
+/// A request to subscribe to events.
+#[derive(Deserialize, ToSchema)]
+pub struct Subscription {
+    /// Where the events are POSTed.
+    pub url: String,
+    /// The names of the events, e.g. `video.detected`.
+    pub events: Vec<String>,
+}
+
+impl Subscription {
+    /// Check the URL and the events, explaining what is wrong otherwise.
+    pub fn validate(&self) -> Result<(), String> {
+        match reqwest::Url::parse(&self.url) {
+            Ok(url) if ["http", "https"].contains(&url.scheme()) => {}
+            _ => return Err(format!("{} is not an HTTP URL", self.url)),
+        }
+        if self.events.is_empty() {
+            return Err("No events to subscribe to".to_string());
+        }
+        if let Some(event) = self.events.iter().find(|e| !EVENTS.contains(&e.as_str())) {
+            return Err(format!(
+                "Unknown event {}, expected any of {}",
+                event,
+                EVENTS.join(", ")
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A subscription to events, as shown by the admin API.
+#[derive(Serialize, ToSchema)]
+pub struct WebhookInfo {
+    pub id: i64,
+    pub url: String,
+    pub events: Vec<String>,
+    /// When the subscription was made, in seconds since the Unix epoch.
+    pub created_at: i64,
+    /// The key the payloads are signed with, only shown when subscribing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+impl From<Webhook> for WebhookInfo {
+    fn from(webhook: Webhook) -> Self {
+        Self {
+            id: webhook.id,
+            url: webhook.url,
+            events: webhook.events,
+            created_at: webhook.created_at,
+            secret: None,
+        }
+    }
+}
+
+/// Delivers the events to the subscribers in the store.
+#[derive(Clone)]
+pub struct Webhooks {
+    client: Client,
+    store: Store,
+}
+
+impl Webhooks {
+    pub fn new(store: Store) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: Client::builder().timeout(TIMEOUT).build()?,
+            store,
+        })
+    }
+
+    /// Subscribe to events, returning the subscription with its newly made secret.
+    pub async fn subscribe(&self, subscription: &Subscription) -> anyhow::Result<WebhookInfo> {
+        let secret = hex::encode(rand::thread_rng().gen::<[u8; 32]>());
+        let id = self
+            .store
+            .add_webhook(&subscription.url, &secret, &subscription.events)
+            .await?;
+        let webhook = self.store.webhook(id).await?.expect("just added");
+        Ok(WebhookInfo {
+            secret: Some(secret),
+            ..webhook.into()
+        })
+    }
+
+    /// Deliver an event to its subscribers in the background.
+    pub async fn emit(&self, event: Event) -> anyhow::Result<()> {
+        let webhooks = self.store.webhooks().await?;
+        for webhook in webhooks {
+            if !webhook.events.iter().any(|name| name == event.name) {
+                continue;
+            }
+            let id = Uuid::new_v4().to_string();
+            let created_at = OffsetDateTime::now_utc().format(&Rfc3339)?;
+            let body = json!({
+                "id": id,
+                "event": event.name,
+                "created_at": created_at,
+                "data": event.data,
+            });
+            tokio::spawn(
+                self.clone()
+                    .deliver(webhook, event.name, id, body.to_string()),
+            );
+        }
+        Ok(())
+    }
+
+    /// Send an event to a subscriber until it is accepted or the retries run out, then log the
+    /// outcome.
+    async fn deliver(self, webhook: Webhook, event: &'static str, id: String, body: String) {
+        let signature = sign(&webhook.secret, body.as_bytes());
+        let mut attempts = 0;
+        let (status, error) = loop {
+            attempts += 1;
+            let response = self
+                .client
+                .post(&webhook.url)
+                .header(CONTENT_TYPE, "application/json")
+                .header("X-Hnv-Event", event)
+                .header("X-Hnv-Delivery", &id)
+                .header("X-Hnv-Signature", &signature)
+                .body(body.clone())
+                .send()
+                .await;
+            let (status, error, retry) = match response {
+                Ok(response) if response.status().is_success() => {
+                    (Some(response.status()), None, false)
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let retry = status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS;
+                    (
+                        Some(status),
+                        Some(format!("The subscriber answered {}", status)),
+                        retry,
+                    )
+                }
+                Err(err) => (None, Some(format!("{:#}", anyhow::Error::from(err))), true),
+            };
+            match RETRY_DELAYS.get(attempts - 1) {
+                Some(delay) if retry => tokio::time::sleep(*delay).await,
+                _ => break (status, error),
+            }
+        };
+
+        if let Some(error) = &error {
+            warn!(
+                webhook = webhook.id,
+                event,
+                delivery = %id,
+                "Failed to deliver an event after {} attempts: {}",
+                attempts,
+                error
+            );
+        }
+        let delivery = Delivery {
+            id,
+            event: event.to_string(),
+            attempts: attempts as u32,
+            status: status.map(|status| status.as_u16()),
+            error,
+            delivered_at: OffsetDateTime::now_utc().unix_timestamp(),
+        };
+        if let Err(err) = self.store.record_delivery(webhook.id, delivery).await {
+            warn!(webhook = webhook.id, "Failed to log a delivery: {:#}", err);
+        }
+    }
+}
+
+/// New videos are delivered like the posts of the notifiers, so each is only detected once.
+#[async_trait]
+impl Notifier for Webhooks {
+    fn name(&self) -> &'static str {
+        "webhooks"
+    }
+
+    fn rules(&self) -> &[NotifyRule] {
+        &[]
+    }
+
+    async fn send(&self, video: &Video) -> anyhow::Result<()> {
+        self.emit(Event::video_detected(video)).await
+    }
+
+    async fn send_all(&self, videos: &[&Video]) -> anyhow::Result<()> {
+        for video in videos {
+            self.send(video).await?;
+        }
+        Ok(())
+    }
+}
+
+/// The signature of a payload, as sent in the `X-Hnv-Signature` header.
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
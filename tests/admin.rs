@@ -0,0 +1,324 @@
+//! End-to-end tests of the admin pages, for the operators of the instance.
+mod common;
+
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use common::{fake_hacker_news, free_port, Server, ADMIN_CONFIG};
+
+#[tokio::test]
+async fn stories_and_domains_are_blocked() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start_with(&fake.uri(), ADMIN_CONFIG);
+    server
+        .get_when("/search?q=talk", |body| body.contains("Another talk"))
+        .await;
+    let client = reqwest::Client::new();
+    let block = |kind: &'static str, value: &'static str| {
+        client
+            .post(format!("{}/admin/blocklist", server.url))
+            .basic_auth("admin", Some("admin"))
+            .json(&json!({ "kind": kind, "value": value }))
+            .send()
+    };
+
+    let response = block("domain", "www.YOUTU.BE").await.unwrap();
+    assert_eq!(response.status(), 201);
+    let domain: Value = response.json().await.unwrap();
+    assert_eq!(domain["value"], "youtu.be");
+    assert_eq!(block("domain", "youtu.be").await.unwrap().status(), 409);
+    assert_eq!(block("user", "pg").await.unwrap().status(), 400);
+
+    // Gone from the published videos and the archive alike.
+    let index = server.get_when("/", |_| true).await;
+    assert!(index.contains("A talk about Rust"));
+    assert!(!index.contains("Another talk"));
+    let search = server.get_when("/search?q=talk", |_| true).await;
+    assert!(!search.contains("Another talk"));
+    let response = reqwest::get(format!("{}/watch/3", server.url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    // Stories are blocked from the admin page too.
+    let response = client
+        .post(format!("{}/admin/blocklist/add", server.url))
+        .basic_auth("admin", Some("admin"))
+        .form(&[("kind", "story"), ("value", "1")])
+        .send()
+        .await
+        .unwrap();
+    let admin = response.text().await.unwrap();
+    assert!(admin.contains("<td>youtu.be</td>"));
+    let feed = server.get_when("/feed.xml", |_| true).await;
+    assert!(!feed.contains("A talk about Rust"));
+    let videos = server.get_when("/api/v1/videos", |_| true).await;
+    assert_eq!(videos, "[]");
+
+    let response = client
+        .delete(format!("{}/admin/blocklist/{}", server.url, domain["id"]))
+        .basic_auth("admin", Some("admin"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+    let blocks: Value = client
+        .get(format!("{}/admin/blocklist", server.url))
+        .basic_auth("admin", Some("admin"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(blocks.as_array().unwrap().len(), 1);
+    assert_eq!(blocks[0]["value"], "1");
+    let index = server.get_when("/", |_| true).await;
+    assert!(index.contains("Another talk"));
+}
+
+#[tokio::test]
+async fn cache_is_inspected_through_the_admin_api() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start_with(&fake.uri(), ADMIN_CONFIG);
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+    let client = reqwest::Client::new();
+    let get = |path: String| {
+        client
+            .get(format!("{}/admin/cache{}", server.url, path))
+            .basic_auth("admin", Some("admin"))
+            .send()
+    };
+
+    let entries: Value = get("?url=/item/1.json".into())
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let entries = entries.as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    let entry = &entries[0];
+    assert_eq!(entry["status"], "ok");
+    assert!(entry["fetched_at"].as_i64().unwrap() > 0);
+    assert!(entry["size"].as_u64().unwrap() > 0);
+
+    let described: Value = get(format!("/{}", entry["id"]))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(&described, entry);
+
+    let url = entry["url"].as_str().unwrap();
+    let response = get(format!("/body?url={}", url)).await.unwrap();
+    assert_eq!(response.headers()["content-type"], "application/json");
+    let item: Value = response.json().await.unwrap();
+    assert_eq!(item["title"], "A talk about Rust");
+
+    let response = get("/body?url=https://example.com/".into()).await.unwrap();
+    assert_eq!(response.status(), 404);
+    assert_eq!(get("/0".into()).await.unwrap().status(), 404);
+    let response = reqwest::get(format!("{}/admin/cache", server.url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+}
+
+#[tokio::test]
+async fn detection_outcomes_are_counted() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start_with(&fake.uri(), ADMIN_CONFIG);
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+
+    let client = reqwest::Client::new();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let counts = loop {
+        let counts: Vec<Value> = client
+            .get(format!("{}/admin/detections", server.url))
+            .basic_auth("admin", Some("admin"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if !counts.is_empty() || Instant::now() > deadline {
+            break counts;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    };
+    let outcomes: Vec<_> = counts
+        .iter()
+        .map(|count| {
+            assert_eq!(count["source"], "hacker-news");
+            (
+                count["rule"].as_str().unwrap(),
+                count["platform"].as_str().unwrap(),
+                count["count"].as_i64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(outcomes, [("rejected", "", 1), ("youtube", "youtube", 2)]);
+}
+
+#[tokio::test]
+async fn admin_dashboard_shows_the_refreshes() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start_with(&fake.uri(), ADMIN_CONFIG);
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+
+    let response = reqwest::get(format!("{}/admin", server.url)).await.unwrap();
+    assert_eq!(response.status(), 401);
+    let page = reqwest::Client::new()
+        .get(format!("{}/admin", server.url))
+        .basic_auth("admin", Some("admin"))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("Last refresh"));
+    assert!(page.contains("Items fetched"));
+    assert!(page.contains("Database size"));
+    // The initial refresh has finished, so it has a time.
+    let last_refresh = page.split("Last refresh").nth(1).unwrap();
+    let last_refresh = last_refresh.split("</tr>").next().unwrap();
+    assert!(!last_refresh.contains("never"), "{}", last_refresh);
+}
+
+#[tokio::test]
+async fn refreshes_are_started_and_followed_by_operators() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start_with(&fake.uri(), ADMIN_CONFIG);
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+    // Hold up the next refresh, so that it is still running when another is asked for.
+    Mock::given(method("GET"))
+        .and(path("/topstories.json"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!([1, 2, 3]))
+                .set_delay(Duration::from_secs(1)),
+        )
+        .with_priority(1)
+        .mount(&fake)
+        .await;
+    let client = reqwest::Client::new();
+    let refresh = || {
+        client
+            .post(format!("{}/admin/refresh", server.url))
+            .basic_auth("admin", Some("admin"))
+            .send()
+    };
+
+    let response = refresh().await.unwrap();
+    assert_eq!(response.status(), 202);
+    let job: Value = response.json().await.unwrap();
+    assert_eq!(job["status"], "running");
+    let response = refresh().await.unwrap();
+    assert_eq!(response.status(), 409);
+    let running: Value = response.json().await.unwrap();
+    assert_eq!(running["id"], job["id"]);
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let status = loop {
+        let status: Value = client
+            .get(format!("{}/admin/refresh/{}", server.url, job["id"]))
+            .basic_auth("admin", Some("admin"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if status["status"] != "running" || Instant::now() > deadline {
+            break status;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    };
+    assert_eq!(status["status"], "finished");
+    assert_eq!(status["done"], status["total"]);
+    assert_eq!(status["total"], json!(3));
+}
+
+#[tokio::test]
+async fn admin_pages_are_served_on_their_own_addresses() {
+    let fake = fake_hacker_news().await;
+    let admin_port = free_port();
+    let config = format!("admin_listen = [\"127.0.0.1:{admin_port}\"]\n{ADMIN_CONFIG}");
+    let server = Server::start_with(&fake.uri(), &config);
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/admin", server.url))
+        .basic_auth("admin", Some("admin"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    let response = reqwest::Client::new()
+        .get(format!("http://127.0.0.1:{admin_port}/admin"))
+        .basic_auth("admin", Some("admin"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn private_instances_ask_for_credentials() {
+    let fake = fake_hacker_news().await;
+    let config = "[private]\nusername = \"friend\"\npassword = \"letmein\"";
+    let server = Server::start_with(&fake.uri(), config);
+    let client = reqwest::Client::new();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let response = client
+            .get(&server.url)
+            .basic_auth("friend", Some("letmein"))
+            .send()
+            .await;
+        if let Ok(response) = response {
+            if response.text().await.unwrap().contains("Another talk") {
+                break;
+            }
+        }
+        assert!(
+            Instant::now() < deadline,
+            "the index never listed the videos"
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let response = client.get(&server.url).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+    assert!(response.headers()["www-authenticate"]
+        .to_str()
+        .unwrap()
+        .starts_with("Basic "));
+    let response = client
+        .get(format!("{}/feed.xml", server.url))
+        .basic_auth("friend", Some("wrong"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+}
@@ -0,0 +1,338 @@
+//! End-to-end tests of the JSON API and the assistants talking to it.
+mod common;
+
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use common::{fake_hacker_news, run, run_with_input, Server, ADMIN_CONFIG};
+
+#[tokio::test]
+async fn api_lists_the_videos() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+
+    let body = server
+        .get_when("/api/v1/videos", |body| body.contains("A talk about Rust"))
+        .await;
+    let videos: Vec<Value> = serde_json::from_str(&body).unwrap();
+
+    let ids: Vec<_> = videos.iter().map(|video| video["id"].clone()).collect();
+    assert_eq!(ids, [json!(1), json!(3)]);
+}
+
+#[tokio::test]
+async fn api_lists_the_score_history_of_a_video() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+
+    let body = server
+        .get_when("/api/v1/videos/3/history", |body| body.starts_with("[{"))
+        .await;
+    let points: Vec<Value> = serde_json::from_str(&body).unwrap();
+    assert!(points.iter().all(|point| point["score"] == json!(80)));
+    assert!(points[0]["time"].as_i64().unwrap() > 0);
+
+    let response = reqwest::get(format!("{}/api/v1/videos/999/history", server.url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn videos_are_submitted_by_hand() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start_with(&fake.uri(), ADMIN_CONFIG);
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    let client = reqwest::Client::new();
+    let api = format!("{}/api/v1/videos", server.url);
+    let submission = json!({
+        "url": "https://www.youtube.com/watch?v=9bZkp7q19f0",
+        "title": "A talk that never made it",
+    });
+
+    let response = client.post(&api).json(&submission).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+    let response = client
+        .post(&api)
+        .basic_auth("admin", Some("admin"))
+        .json(&json!({ "url": "https://example.com/article" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let response = client
+        .post(&api)
+        .basic_auth("admin", Some("admin"))
+        .json(&submission)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+    let video: Value = response.json().await.unwrap();
+    assert_eq!(video["source"], "submitted");
+    let id = video["id"].as_str().unwrap();
+    assert!(id.starts_with("submitted-"));
+
+    // The video gets a watch page, but stays off the front page.
+    let watch = server
+        .get_when(&format!("/watch/{}", id), |body| {
+            body.contains("A talk that never made it")
+        })
+        .await;
+    assert!(watch.contains("9bZkp7q19f0"));
+    let index = reqwest::get(format!("{}/", server.url))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(!index.contains("A talk that never made it"));
+    let videos: Value = reqwest::get(&api).await.unwrap().json().await.unwrap();
+    assert_eq!(videos.as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn videos_are_fetched_as_ndjson() {
+    let hacker_news = fake_hacker_news().await;
+
+    let output = run(&hacker_news.uri(), ":memory:", &["fetch", "--ndjson"]).await;
+    let mut videos: Vec<Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    // Found concurrently, so in any order.
+    videos.sort_by_key(|video| video["rank"].as_u64());
+    assert_eq!(videos.len(), 2);
+    assert_eq!(videos[0]["rank"], 1);
+    assert_eq!(videos[0]["title"], "A talk about Rust");
+    assert_eq!(videos[0]["score"], 120);
+    assert_eq!(videos[1]["rank"], 3);
+    assert_eq!(videos[1]["url"], "https://youtu.be/oHg5SJYRHA0");
+
+    let output = run(&hacker_news.uri(), ":memory:", &["fetch"]).await;
+    assert!(output.contains("1\tA talk about Rust\thttps://www.youtube.com/watch?v=dQw4w9WgXcQ\n"));
+}
+
+#[tokio::test]
+async fn assistants_get_videos_over_mcp() {
+    let hacker_news = fake_hacker_news().await;
+    let story = json!({
+        "id": 1,
+        "type": "story",
+        "title": "A talk about Rust",
+        "url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        "kids": [10, 11],
+    });
+    Mock::given(method("GET"))
+        .and(path("/item/1.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(story))
+        .with_priority(1)
+        .mount(&hacker_news)
+        .await;
+    for (id, comment) in [
+        (
+            10,
+            json!({ "id": 10, "by": "alice", "text": "Great talk", "time": 1 }),
+        ),
+        (11, json!({ "id": 11, "deleted": true })),
+    ] {
+        Mock::given(method("GET"))
+            .and(path(format!("/item/{}.json", id)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(comment))
+            .mount(&hacker_news)
+            .await;
+    }
+
+    let requests = [
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+        json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }),
+        json!({ "jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {
+            "name": "get_front_page", "arguments": {},
+        }}),
+        json!({ "jsonrpc": "2.0", "id": 4, "method": "tools/call", "params": {
+            "name": "search_videos", "arguments": { "query": "another" },
+        }}),
+        json!({ "jsonrpc": "2.0", "id": 5, "method": "tools/call", "params": {
+            "name": "get_discussion", "arguments": { "id": "1" },
+        }}),
+    ];
+    let input: String = requests
+        .iter()
+        .map(|request| format!("{}\n", request))
+        .collect();
+    let output = run_with_input(&hacker_news.uri(), ":memory:", &["mcp"], &input).await;
+
+    // The notification isn't answered.
+    let responses: Vec<Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(responses.len(), 5);
+    assert_eq!(responses[0]["result"]["serverInfo"]["name"], "hnv");
+    assert_eq!(responses[1]["result"]["tools"].as_array().unwrap().len(), 3);
+    let text = |response: &Value| -> Value {
+        let text = response["result"]["content"][0]["text"].as_str().unwrap();
+        serde_json::from_str(text).unwrap()
+    };
+    let front_page = text(&responses[2]);
+    assert_eq!(front_page.as_array().unwrap().len(), 2);
+    let found = text(&responses[3]);
+    assert_eq!(found[0]["title"], "Another talk");
+    assert_eq!(found.as_array().unwrap().len(), 1);
+    let discussion = text(&responses[4]);
+    assert_eq!(discussion["video"]["title"], "A talk about Rust");
+    assert_eq!(
+        discussion["comments"],
+        json!([{ "id": 10, "by": "alice", "text": "Great talk", "time": 1 }])
+    );
+}
+
+#[tokio::test]
+async fn a_single_story_is_refreshed_through_the_api() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start_with(&fake.uri(), ADMIN_CONFIG);
+    server
+        .get_when("/api/v1/videos", |body| body.contains("A talk about Rust"))
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/item/1.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": 1,
+            "type": "story",
+            "title": "A talk about Rust [video]",
+            "url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "score": 130,
+            "descendants": 42,
+        })))
+        .with_priority(1)
+        .mount(&fake)
+        .await;
+    let client = reqwest::Client::new();
+    let refresh = |id: &str| {
+        client
+            .post(format!("{}/api/v1/refresh/{}", server.url, id))
+            .basic_auth("admin", Some("admin"))
+            .send()
+    };
+
+    let response = refresh("1").await.unwrap();
+    assert_eq!(response.status(), 200);
+    let video: Value = response.json().await.unwrap();
+    assert_eq!(video["title"], "A talk about Rust [video]");
+    assert_eq!(video["score"], 130);
+    let api = reqwest::get(format!("{}/api/v1/videos", server.url))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(api.contains("A talk about Rust [video]"));
+
+    // Not a video, or not on Hacker News.
+    assert_eq!(refresh("2").await.unwrap().status(), 404);
+    assert_eq!(refresh("lobsters-abc").await.unwrap().status(), 404);
+    let response = client
+        .post(format!("{}/api/v1/refresh/1", server.url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+}
+
+#[tokio::test]
+async fn the_api_times_out_sooner_than_the_pages() {
+    let fake = fake_hacker_news().await;
+    let config = format!("timeouts.api = 1\n{}", ADMIN_CONFIG);
+    let server = Server::start_with(&fake.uri(), &config);
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/item/1.json"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(3)))
+        .with_priority(1)
+        .mount(&fake)
+        .await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/api/v1/refresh/1", server.url))
+        .basic_auth("admin", Some("admin"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 408);
+    let page = reqwest::get(format!("{}/watch/1", server.url))
+        .await
+        .unwrap();
+    assert_eq!(page.status(), 200);
+}
+
+#[tokio::test]
+async fn api_is_described_by_an_openapi_document() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+
+    let spec = server.get_when("/api/v1/openapi.json", |_| true).await;
+    let spec: Value = serde_json::from_str(&spec).unwrap();
+    assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+    for path in ["/api/v1/videos", "/version"] {
+        assert!(
+            spec["paths"].get(path).is_some(),
+            "{} is not described",
+            path
+        );
+    }
+
+    let page = server.get_when("/api/docs", |_| true).await;
+    assert!(page.contains("/api/v1/openapi.json"));
+}
+
+#[tokio::test]
+async fn api_tokens_are_required_where_configured() {
+    let fake = fake_hacker_news().await;
+    let config = "[api]\ntokens = [\"secret\"]\nprivate = true";
+    let server = Server::start_with(&fake.uri(), config);
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(format!("{}/api/v1/videos", server.url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    assert_eq!(response.headers()["www-authenticate"], "Bearer");
+    let response = client
+        .get(format!("{}/api/v1/videos", server.url))
+        .bearer_auth("wrong")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    let response = client
+        .get(format!("{}/api/v1/videos", server.url))
+        .bearer_auth("secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // Tokens open the admin endpoints as well.
+    let response = client
+        .get(format!("{}/admin/detections", server.url))
+        .bearer_auth("secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
@@ -0,0 +1,215 @@
+//! End-to-end tests of the archive: browsing it by period, searching it and its statistics.
+mod common;
+
+use common::{fake_hacker_news, Server};
+
+#[tokio::test]
+async fn archive_is_browsed_by_month() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    let today = time::OffsetDateTime::now_utc().date();
+    let month = format!("{:04}/{:02}", today.year(), u8::from(today.month()));
+
+    let archive = server
+        .get_when("/archive", |body| body.contains(&month))
+        .await;
+    assert!(archive.contains("2 videos"));
+    let page = server
+        .get_when(&format!("/archive/{}", month), |body| {
+            body.contains("A talk about Rust")
+        })
+        .await;
+    assert!(page.contains(&today.to_string()));
+    assert!(!page.contains("An article about Rust"));
+
+    let response = reqwest::get(format!("{}/archive/2024/13", server.url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn weeks_list_the_highest_scores() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+    let (year, week, _) = time::OffsetDateTime::now_utc().date().to_iso_week_date();
+
+    let archive = server.get_when("/archive", |_| true).await;
+    let path = format!("/week/{}/{}", year, week);
+    assert!(archive.contains(&path));
+    let page = server
+        .get_when(&path, |body| body.contains("Another talk"))
+        .await;
+    assert!(page.contains("120 points"));
+    assert!(page.find("A talk about Rust") < page.find("Another talk"));
+    assert!(!page.contains("An article about Rust"));
+
+    let response = reqwest::get(format!("{}/week/2024/54", server.url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    let feed = server.get_when("/weeks.xml", |_| true).await;
+    assert!(feed.contains("<rss"));
+}
+
+#[tokio::test]
+async fn yesterday_lists_the_videos_submitted_then() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+
+    let page = server
+        .get_when("/yesterday", |body| body.contains("Another talk"))
+        .await;
+    assert!(page.contains("80 points"));
+    // Without a submission time, videos count as submitted when they were first listed.
+    assert!(!page.contains("A talk about Rust"));
+}
+
+#[tokio::test]
+async fn days_start_in_the_chosen_time_zone() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start_with(&fake.uri(), "timezone = \"-12:00\"");
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let yesterday = |offset: i8| {
+        let offset = time::UtcOffset::from_hms(offset, 0, 0).unwrap();
+        let today = time::OffsetDateTime::now_utc().to_offset(offset).date();
+        (today - time::Duration::days(1)).to_string()
+    };
+
+    let page = server.get_when("/yesterday", |_| true).await;
+    assert!(page.contains(&yesterday(-12)));
+    assert!(page.contains("value=\"-12:00\""));
+
+    let response = client
+        .post(format!("{}/timezone", server.url))
+        .form(&[("timezone", "+14:00")])
+        .send()
+        .await
+        .unwrap();
+    let cookie = response.headers()["set-cookie"]
+        .to_str()
+        .unwrap()
+        .split(';')
+        .next()
+        .unwrap()
+        .to_string();
+    let page = client
+        .get(format!("{}/yesterday", server.url))
+        .header("cookie", cookie)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains(&yesterday(14)));
+}
+
+#[tokio::test]
+async fn archive_is_searched_between_dates() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    let today = time::OffsetDateTime::now_utc().date();
+
+    let found = server
+        .get_when(
+            &format!("/search?q=TALK&from={}&to={}", today, today),
+            |body| body.contains("A talk about Rust"),
+        )
+        .await;
+    assert!(found.contains(&format!("value=\"{}\"", today)));
+    let found = server
+        .get_when("/search?q=rust", |body| body.contains("A talk about Rust"))
+        .await;
+    assert!(!found.contains("An article about Rust"));
+    let none = server
+        .get_when("/search?q=talk&from=2023-03-01&to=2023-03-31", |body| {
+            body.contains("<h2>")
+        })
+        .await;
+    assert!(!none.contains("A talk about Rust"));
+    assert!(none.contains("No videos match."));
+}
+
+#[tokio::test]
+async fn archive_is_searched_with_filters() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+    let server = &server;
+    let search = |query: &str| {
+        let path = format!("/search?q={}", query.replace('+', "%2B").replace(' ', "+"));
+        async move { server.get_when(&path, |body| body.contains("<h2>")).await }
+    };
+
+    let found = search("platform:youtube score:>100").await;
+    assert!(found.contains("A talk about Rust"));
+    assert!(!found.contains("Another talk"));
+    let found = search("source:hacker-news score:<=80 \"another TALK\"").await;
+    assert!(found.contains("Another talk"));
+    assert!(!found.contains("A talk about Rust"));
+    let tomorrow = time::OffsetDateTime::now_utc().date().next_day().unwrap();
+    let found = search(&format!("talk before:{}", tomorrow)).await;
+    assert!(found.contains("Another talk"));
+    for query in [
+        "source:lobsters",
+        "platform:vimeo talk",
+        "talk after:2100-01-01",
+    ] {
+        assert!(search(query).await.contains("No videos match."));
+    }
+}
+
+#[tokio::test]
+async fn archive_statistics_are_shown() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+
+    // Aggregated after the first refresh.
+    let stats = server
+        .get_when("/stats", |body| body.contains("2 videos"))
+        .await;
+    assert!(stats.contains("<th>youtube</th>"));
+    assert_eq!(stats.matches("<rect").count(), 30);
+}
+
+#[tokio::test]
+async fn channels_are_ranked_over_a_period() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    server
+        .get_when("/stats", |body| body.contains("2 videos"))
+        .await;
+
+    // Channels come from oEmbed, which can't be reached here.
+    let page = server.get_when("/channels?period=week", |_| true).await;
+    assert!(page.contains("No channels are known yet."));
+    assert!(page.contains("href=\"?period=week\" aria-current=\"page\""));
+    let response = reqwest::get(format!("{}/channels?period=decade", server.url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
@@ -0,0 +1,350 @@
+//! End-to-end tests of the commands other than `serve`.
+mod common;
+
+use std::{
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use serde_json::Value;
+use wiremock::{
+    matchers::{method, path, path_regex, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use common::{fake_hacker_news, output_of, run, Server, ADMIN_CONFIG};
+
+#[tokio::test]
+async fn site_is_exported_to_static_files() {
+    let hacker_news = fake_hacker_news().await;
+    let dir = std::env::temp_dir().join(format!("hnv-e2e-static-{}", std::process::id()));
+
+    run(
+        &hacker_news.uri(),
+        ":memory:",
+        &["export", "--static", dir.to_str().unwrap()],
+    )
+    .await;
+
+    let index = std::fs::read_to_string(dir.join("index.html")).unwrap();
+    assert!(index.contains("A talk about Rust"));
+    assert!(index.contains("/watch/3"));
+    let watch = std::fs::read_to_string(dir.join("watch/3/index.html")).unwrap();
+    assert!(watch.contains("Another talk"));
+    let feed = std::fs::read_to_string(dir.join("feed/youtube.xml")).unwrap();
+    assert!(feed.contains("<title>Another talk</title>"));
+    assert!(dir.join("feed.xml").exists());
+    assert!(dir.join("assets/main.css").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn recorded_videos_are_dumped() {
+    let hacker_news = fake_hacker_news().await;
+    let database = std::env::temp_dir().join(format!("hnv-e2e-dump-{}.db", std::process::id()));
+    let database = database.to_str().unwrap();
+
+    run(&hacker_news.uri(), database, &["refresh"]).await;
+    let json = run(&hacker_news.uri(), database, &["dump"]).await;
+    let csv = run(&hacker_news.uri(), database, &["dump", "--format", "csv"]).await;
+
+    let videos: Vec<Value> = serde_json::from_str(&json).unwrap();
+    let titles: Vec<_> = videos.iter().map(|video| &video["title"]).collect();
+    assert_eq!(titles, ["A talk about Rust", "Another talk"]);
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next(),
+        Some("id,source,title,url,discussion_url,score,first_seen,last_seen")
+    );
+    assert!(lines
+        .next()
+        .unwrap()
+        .starts_with("1,hacker-news,A talk about Rust,"));
+
+    std::fs::remove_file(database).unwrap();
+}
+
+#[tokio::test]
+async fn database_is_backed_up_online() {
+    let hacker_news = fake_hacker_news().await;
+    let database = std::env::temp_dir().join(format!("hnv-e2e-backup-{}.db", std::process::id()));
+    let database = database.to_str().unwrap();
+    let backup =
+        std::env::temp_dir().join(format!("hnv-e2e-backup-{}.copy.db", std::process::id()));
+    let backup = backup.to_str().unwrap();
+
+    run(&hacker_news.uri(), database, &["refresh"]).await;
+    let output = run(&hacker_news.uri(), database, &["backup", backup]).await;
+    assert!(output.contains(&format!("to {}", backup)));
+    let json = run(&hacker_news.uri(), backup, &["dump"]).await;
+    let videos: Vec<Value> = serde_json::from_str(&json).unwrap();
+    assert_eq!(videos.len(), 2);
+
+    let server = Server::start_with(&hacker_news.uri(), ADMIN_CONFIG);
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    let response = reqwest::Client::new()
+        .post(format!("{}/admin/backup", server.url))
+        .basic_auth("admin", Some("admin"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["content-disposition"]
+        .to_str()
+        .unwrap()
+        .starts_with("attachment; filename=\"hnv-"));
+    let bytes = response.bytes().await.unwrap();
+    assert!(bytes.starts_with(b"SQLite format 3\0"));
+
+    std::fs::remove_file(database).unwrap();
+    std::fs::remove_file(backup).unwrap();
+}
+
+#[tokio::test]
+async fn backups_are_uploaded_and_expired() {
+    let hacker_news = fake_hacker_news().await;
+    let storage = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/bucket/backups/hnv-\d{8}-\d{6}\.db$"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&storage)
+        .await;
+    let listing = "<ListBucketResult><IsTruncated>false</IsTruncated>\
+        <Contents><Key>backups/hnv-20200102-000000.db</Key></Contents>\
+        <Contents><Key>backups/hnv-20200101-000000.db</Key></Contents>\
+        <Contents><Key>backups/hnv-29990101-000000.db</Key></Contents>\
+        </ListBucketResult>";
+    Mock::given(method("GET"))
+        .and(path("/bucket"))
+        .and(query_param("list-type", "2"))
+        .and(query_param("prefix", "backups/hnv-"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(listing))
+        .mount(&storage)
+        .await;
+    Mock::given(method("DELETE"))
+        .and(path("/bucket/backups/hnv-20200101-000000.db"))
+        .respond_with(ResponseTemplate::new(204))
+        .mount(&storage)
+        .await;
+
+    let _server = Server::start_with(
+        &hacker_news.uri(),
+        &format!(
+            r#"
+            [backup]
+            endpoint = "{}"
+            bucket = "bucket"
+            access_key_id = "key"
+            secret_access_key = "secret"
+            prefix = "backups/"
+            schedule = "* * * * * *"
+            keep = 2
+            "#,
+            storage.uri()
+        ),
+    );
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let requests = loop {
+        let requests = storage.received_requests().await.unwrap();
+        if requests
+            .iter()
+            .any(|request| request.method.as_str() == "DELETE")
+            || Instant::now() > deadline
+        {
+            break requests;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    };
+    let upload = requests
+        .iter()
+        .find(|request| request.method.as_str() == "PUT")
+        .expect("no backup was uploaded");
+    assert!(upload.body.starts_with(b"SQLite format 3\0"));
+    let authorization = upload.headers["authorization"].to_str().unwrap();
+    assert!(authorization.starts_with("AWS4-HMAC-SHA256 Credential=key/"));
+    assert!(authorization.contains("/us-east-1/s3/aws4_request"));
+    let deleted: Vec<_> = requests
+        .iter()
+        .filter(|request| request.method.as_str() == "DELETE")
+        .map(|request| request.url.path())
+        .collect();
+    assert_eq!(deleted[0], "/bucket/backups/hnv-20200101-000000.db");
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn database_defaults_to_the_data_directory() {
+    let hacker_news = fake_hacker_news().await;
+    let data = std::env::temp_dir().join(format!("hnv-e2e-data-{}", std::process::id()));
+    let config = data.with_extension("toml");
+    std::fs::write(
+        &config,
+        format!("hacker_news_url = \"{}\"\n", hacker_news.uri()),
+    )
+    .unwrap();
+
+    let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_hnv"))
+        .args(["--no-progress", "refresh"])
+        .env("HNV_CONFIG", &config)
+        .env("XDG_DATA_HOME", &data)
+        .output()
+        .await
+        .unwrap();
+    std::fs::remove_file(&config).unwrap();
+
+    assert!(output.status.success());
+    assert!(data.join("hnv/cache.db").is_file());
+    std::fs::remove_dir_all(data).unwrap();
+}
+
+#[test]
+fn invalid_settings_fail_at_startup() {
+    let config = std::env::temp_dir().join(format!("hnv-e2e-invalid-{}.toml", std::process::id()));
+    std::fs::write(
+        &config,
+        r#"
+        listen = ["127.0.0.1:0"]
+        public_url = "localhost:3000"
+        database = ":memory:"
+        refresh.cron = "every minute"
+        "#,
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_hnv"))
+        .arg("refresh")
+        .env("HNV_CONFIG", &config)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&config).unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    // Every problem is reported at once, by its key.
+    assert!(
+        stderr.contains("`listen`: 127.0.0.1:0 has no port"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("`public_url`: localhost:3000 isn't an HTTP URL"));
+    assert!(stderr.contains("`refresh.cron`: every minute isn't a cron expression"));
+}
+
+#[tokio::test]
+async fn doctor_checks_the_setup() {
+    let hacker_news = fake_hacker_news().await;
+    let database = std::env::temp_dir().join(format!("hnv-e2e-doctor-{}.db", std::process::id()));
+    let database = database.to_str().unwrap();
+
+    let output = run(&hacker_news.uri(), database, &["doctor"]).await;
+    assert!(output.contains("is created on the first start"));
+
+    run(&hacker_news.uri(), database, &["refresh"]).await;
+    let output = run(&hacker_news.uri(), database, &["doctor"]).await;
+    assert!(output.contains("has schema version 4"));
+    assert!(output.contains("lists 3 top stories"));
+    assert!(output.contains("embedded files"));
+    assert!(!output.contains("error"));
+
+    std::fs::remove_file(database).unwrap();
+}
+
+#[tokio::test]
+async fn videos_are_exported_as_a_library() {
+    let hacker_news = fake_hacker_news().await;
+    let dir = std::env::temp_dir().join(format!("hnv-e2e-strm-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("Gone [9].strm"), "https://example.com/gone").unwrap();
+    std::fs::write(dir.join("notes.txt"), "kept").unwrap();
+
+    let dir_arg = dir.to_str().unwrap();
+    run(
+        &hacker_news.uri(),
+        ":memory:",
+        &["export", "--strm", dir_arg],
+    )
+    .await;
+
+    let strm = std::fs::read_to_string(dir.join("Another talk [3].strm")).unwrap();
+    assert_eq!(strm, "https://youtu.be/oHg5SJYRHA0\n");
+    let nfo = std::fs::read_to_string(dir.join("Another talk [3].nfo")).unwrap();
+    assert!(nfo.contains("<title>Another talk</title>"));
+    assert!(nfo.contains("news.ycombinator.com/item?id=3"));
+    assert!(dir.join("A talk about Rust [1].strm").exists());
+    // Videos that are gone are removed, other files are left alone.
+    assert!(!dir.join("Gone [9].strm").exists());
+    assert!(dir.join("notes.txt").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn videos_are_searched_and_exported_from_the_command_line() {
+    let hacker_news = fake_hacker_news().await;
+
+    let found = run(&hacker_news.uri(), ":memory:", &["search", "RUST"]).await;
+    assert_eq!(
+        found,
+        "1\tA talk about Rust\thttps://www.youtube.com/watch?v=dQw4w9WgXcQ\n"
+    );
+
+    let exported = run(
+        &hacker_news.uri(),
+        ":memory:",
+        &["export", "--format", "json"],
+    )
+    .await;
+    let videos: Vec<Value> = serde_json::from_str(&exported).unwrap();
+    let titles: Vec<_> = videos.iter().map(|video| &video["title"]).collect();
+    assert_eq!(titles, ["A talk about Rust", "Another talk"]);
+}
+
+#[tokio::test]
+async fn progress_is_logged_as_lines_without_a_terminal() {
+    let hacker_news = fake_hacker_news().await;
+
+    // Without `--no-progress`, the bar is still left out when stderr is not a terminal.
+    let output = output_of(&hacker_news.uri(), "", &["refresh"]).await;
+    let log = String::from_utf8(output.stderr).unwrap();
+    assert!(log.contains("Refresh 1 finished"));
+    assert!(!log.contains('\r'));
+}
+
+#[tokio::test]
+async fn log_lines_are_written_as_json() {
+    let hacker_news = fake_hacker_news().await;
+
+    let output = output_of(&hacker_news.uri(), "", &["--log-format", "json", "refresh"]).await;
+    let log = String::from_utf8(output.stderr).unwrap();
+    let lines: Vec<Value> = log
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let finished = lines
+        .iter()
+        .find(|line| line["fields"]["message"] == "Refresh 1 finished")
+        .unwrap();
+    assert_eq!(finished["level"], "INFO");
+    assert!(finished["timestamp"].is_string());
+}
+
+#[tokio::test]
+async fn log_is_written_to_a_file() {
+    let hacker_news = fake_hacker_news().await;
+    let dir = std::env::temp_dir().join(format!("hnv-e2e-log-{}", std::process::id()));
+    let file = dir.join("logs/hnv.log");
+    let config = format!("[log]\nfile = {:?}\nrotation = \"never\"", file);
+
+    let output = output_of(&hacker_news.uri(), &config, &["refresh"]).await;
+    assert!(output.status.success());
+    let log = std::fs::read_to_string(&file).unwrap();
+    assert!(log.contains("Refresh 1 finished"));
+    // Unlike the terminal, the file gets no colors.
+    assert!(!log.contains('\x1b'));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
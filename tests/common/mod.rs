@@ -0,0 +1,385 @@
+//! What the end-to-end tests have in common, which run the `hnv` binary against a fake Hacker
+//! News API.
+//!
+//! Every test starts a wiremock server standing in for the API and the server with an in-memory
+//! database, then waits for the initial refresh to show up in the pages. Not every test uses
+//! every helper.
+#![allow(dead_code)]
+
+use std::{
+    net::TcpListener,
+    path::PathBuf,
+    process::{Child, Command, Output, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+/// Start a fake API with two videos and an article among the top stories.
+pub async fn fake_hacker_news() -> MockServer {
+    let server = MockServer::start().await;
+    let yesterday = time::OffsetDateTime::now_utc().unix_timestamp() - 24 * 60 * 60;
+
+    let items = [
+        json!({
+            "id": 1,
+            "type": "story",
+            "title": "A talk about Rust",
+            "url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "score": 120,
+            "descendants": 40,
+        }),
+        json!({
+            "id": 2,
+            "type": "story",
+            "title": "An article about Rust",
+            "url": "https://example.com/rust",
+        }),
+        json!({
+            "id": 3,
+            "type": "story",
+            "title": "Another talk",
+            "url": "https://youtu.be/oHg5SJYRHA0",
+            "score": 80,
+            "descendants": 150,
+            "time": yesterday,
+        }),
+    ];
+
+    Mock::given(method("GET"))
+        .and(path("/topstories.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([1, 2, 3])))
+        .mount(&server)
+        .await;
+    for item in items {
+        Mock::given(method("GET"))
+            .and(path(format!("/item/{}.json", item["id"])))
+            .respond_with(ResponseTemplate::new(200).set_body_json(item))
+            .mount(&server)
+            .await;
+    }
+
+    server
+}
+
+/// Add a fake Lobsters front page with a video and an article to a fake API.
+pub async fn fake_lobsters(server: &MockServer) {
+    let stories = json!([
+        {
+            "short_id": "abc123",
+            "title": "A Lobsters talk",
+            "url": "https://vimeo.com/123456",
+            "comments_url": "https://lobste.rs/s/abc123/a_lobsters_talk",
+            "tags": ["video", "rust"],
+        },
+        {
+            "short_id": "def456",
+            "title": "A Lobsters article",
+            "url": "https://example.com/lobsters",
+            "comments_url": "https://lobste.rs/s/def456/a_lobsters_article",
+            "tags": ["rust"],
+        },
+    ]);
+
+    Mock::given(method("GET"))
+        .and(path("/hottest.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(stories))
+        .mount(server)
+        .await;
+}
+
+/// Add a fake subreddit with a pinned post, a video and an article to a fake API.
+pub async fn fake_subreddit(server: &MockServer) {
+    let post = |id: &str, title: &str, url: &str, stickied: bool| {
+        json!({
+            "kind": "t3",
+            "data": {
+                "id": id,
+                "title": title,
+                "url": url,
+                "permalink": format!("/r/videos/comments/{}/post/", id),
+                "subreddit": "videos",
+                "is_video": false,
+                "stickied": stickied,
+            },
+        })
+    };
+    let listing = json!({
+        "kind": "Listing",
+        "data": {
+            "children": [
+                post("pin1", "Subreddit rules", "https://www.youtube.com/watch?v=aaaaaaaaaaa", true),
+                post("vid1", "A Reddit talk", "https://youtu.be/bbbbbbbbbbb", false),
+                post("art1", "A Reddit article", "https://example.com/reddit", false),
+            ],
+        },
+    });
+
+    Mock::given(method("GET"))
+        .and(path("/r/videos/hot.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(listing))
+        .mount(server)
+        .await;
+}
+
+/// Make the first request for the top stories only list the first video, so that the next
+/// refresh finds another.
+pub async fn fake_new_video(server: &MockServer) {
+    Mock::given(method("GET"))
+        .and(path("/topstories.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([1])))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(server)
+        .await;
+}
+
+/// The credentials of the admin pages, to be appended to the configuration.
+pub const ADMIN_CONFIG: &str = "[admin]\nusername = \"admin\"\npassword = \"admin\"";
+
+/// Wait for JSON requests to a path of a fake, returning their bodies.
+pub async fn requests_to(server: &MockServer, path: &str) -> Vec<Value> {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let bodies: Vec<Value> = server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|request| request.url.path() == path)
+            .map(|request| serde_json::from_slice(&request.body).unwrap())
+            .collect();
+        if !bodies.is_empty() || Instant::now() > deadline {
+            return bodies;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// A running `hnv serve`, killed when dropped.
+pub struct Server {
+    child: Child,
+    config: PathBuf,
+    pub url: String,
+}
+
+impl Server {
+    pub fn start(hacker_news_url: &str) -> Self {
+        Self::start_with(hacker_news_url, "")
+    }
+
+    /// Start with additional configuration, appended to the file.
+    pub fn start_with(hacker_news_url: &str, extra_config: &str) -> Self {
+        Self::start_with_env(hacker_news_url, extra_config, &[])
+    }
+
+    /// Start with additional configuration and environment variables.
+    pub fn start_with_env(hacker_news_url: &str, extra_config: &str, env: &[(&str, &str)]) -> Self {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let config = std::env::temp_dir().join(format!("hnv-e2e-{}.toml", port));
+        std::fs::write(
+            &config,
+            format!(
+                r#"
+                listen = ["127.0.0.1:{port}"]
+                database = ":memory:"
+                hacker_news_url = "{hacker_news_url}"
+                refresh.interval = 0
+
+                {extra_config}
+                "#
+            ),
+        )
+        .unwrap();
+
+        let child = Command::new(env!("CARGO_BIN_EXE_hnv"))
+            .arg("serve")
+            .env("HNV_CONFIG", &config)
+            .envs(env.iter().copied())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        Self {
+            child,
+            config,
+            url: format!("http://127.0.0.1:{}", port),
+        }
+    }
+
+    /// Get a page, retrying until `ready` accepts its body.
+    pub async fn get_when(&self, path: &str, ready: impl Fn(&str) -> bool) -> String {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut last = String::new();
+        while Instant::now() < deadline {
+            if let Ok(response) = reqwest::get(format!("{}{}", self.url, path)).await {
+                last = response.text().await.unwrap_or_default();
+                if ready(&last) {
+                    return last;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("{} never became ready, last response:\n{}", path, last);
+    }
+
+    /// Wait for the initial refresh to list the first video, then start another refresh through
+    /// the admin API, which needs [`ADMIN_CONFIG`].
+    pub async fn refresh_again(&self) {
+        self.get_when("/", |body| body.contains("A talk about Rust"))
+            .await;
+        // Let the notifiers record the initial list first.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let response = reqwest::Client::new()
+            .post(format!("{}/admin/refresh", self.url))
+            .basic_auth("admin", Some("admin"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 202);
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_file(&self.config);
+    }
+}
+
+/// Run a command of `hnv` to completion against a fake API and with a database, returning what
+/// it printed.
+pub async fn run(hacker_news_url: &str, database: &str, args: &[&str]) -> String {
+    run_with_input(hacker_news_url, database, args, "").await
+}
+
+/// Run a command of `hnv` like [`run`], writing `input` to its stdin.
+pub async fn run_with_input(
+    hacker_news_url: &str,
+    database: &str,
+    args: &[&str],
+    input: &str,
+) -> String {
+    run_with_config(hacker_news_url, database, "", args, input).await
+}
+
+/// Run a command of `hnv` like [`run_with_input`], with additional configuration.
+pub async fn run_with_config(
+    hacker_news_url: &str,
+    database: &str,
+    extra_config: &str,
+    args: &[&str],
+    input: &str,
+) -> String {
+    // Tests run concurrently, each needs a file of its own.
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+    let run = RUNS.fetch_add(1, Ordering::Relaxed);
+    let config =
+        std::env::temp_dir().join(format!("hnv-e2e-run-{}-{}.toml", std::process::id(), run));
+    std::fs::write(
+        &config,
+        format!(
+            "database = {database:?}\nhacker_news_url = \"{hacker_news_url}\"\n{extra_config}\n"
+        ),
+    )
+    .unwrap();
+
+    // The fake runs on the same thread, so waiting must not block it.
+    let mut child = tokio::process::Command::new(env!("CARGO_BIN_EXE_hnv"))
+        .arg("--no-progress")
+        .args(args)
+        .env("HNV_CONFIG", &config)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(input.as_bytes()).await.unwrap();
+    drop(stdin);
+    let output = child.wait_with_output().await.unwrap();
+    std::fs::remove_file(&config).unwrap();
+    assert!(output.status.success(), "hnv {:?} failed", args);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// A client that doesn't follow redirects, to look at them.
+pub fn no_redirects() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+/// The first cookie a response sets, as `name=value` to send back.
+pub fn cookie_of(response: &reqwest::Response) -> String {
+    let cookie = response.headers()["set-cookie"].to_str().unwrap();
+    cookie.split(';').next().unwrap().to_string()
+}
+
+/// A port nothing listens on, at least for now.
+pub fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// Run a command of `hnv` against a fake API with an in-memory database, returning its output
+/// whether or not it succeeded, to look at what it logged.
+pub async fn output_of(hacker_news_url: &str, extra_config: &str, args: &[&str]) -> Output {
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+    let run = RUNS.fetch_add(1, Ordering::Relaxed);
+    let config = std::env::temp_dir().join(format!(
+        "hnv-e2e-output-{}-{}.toml",
+        std::process::id(),
+        run
+    ));
+    std::fs::write(
+        &config,
+        format!(
+            "database = \":memory:\"\nhacker_news_url = \"{hacker_news_url}\"\n{extra_config}\n"
+        ),
+    )
+    .unwrap();
+
+    let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_hnv"))
+        .args(args)
+        .env("HNV_CONFIG", &config)
+        .output()
+        .await
+        .unwrap();
+    std::fs::remove_file(&config).unwrap();
+    output
+}
+
+/// Start a server with a notifier, answered by `notifier` on the fake API, and let it see a new
+/// video, returning the JSON bodies sent to `path`.
+///
+/// `config` configures the notifier, given the URL of the fake to point it at.
+pub async fn notified(
+    notifier: Mock,
+    config: impl FnOnce(&str) -> String,
+    path: &str,
+) -> Vec<Value> {
+    let fake = fake_hacker_news().await;
+    fake_new_video(&fake).await;
+    notifier.mount(&fake).await;
+    let config = format!("{ADMIN_CONFIG}\n{}", config(&fake.uri()));
+    let server = Server::start_with(&fake.uri(), &config);
+    server.refresh_again().await;
+    requests_to(&fake, path).await
+}
@@ -0,0 +1,152 @@
+//! End-to-end tests of downloading the new videos.
+mod common;
+
+use std::time::Duration;
+
+use common::{fake_hacker_news, fake_new_video, Server, ADMIN_CONFIG};
+
+#[tokio::test]
+async fn new_videos_are_downloaded() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let fake = fake_hacker_news().await;
+    fake_new_video(&fake).await;
+
+    // A stand-in for yt-dlp that writes a file named like the real one would.
+    let dir = std::env::temp_dir().join(format!("hnv-e2e-media-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let yt_dlp = dir.join("yt-dlp");
+    std::fs::write(
+        &yt_dlp,
+        r#"#!/bin/sh
+while [ $# -gt 0 ]; do
+  case "$1" in
+    --paths) dir="$2"; shift ;;
+    --output) name="$2"; shift ;;
+    --write-subs) subs=1 ;;
+  esac
+  shift
+done
+file="$dir/$(echo "$name" | sed 's/%(ext)s/mp4/')"
+echo video > "$file"
+[ -n "$subs" ] && echo WEBVTT > "${file%.mp4}.en.vtt"
+echo "$file"
+"#,
+    )
+    .unwrap();
+    std::fs::set_permissions(&yt_dlp, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let media = dir.join("media");
+    let config = format!(
+        "{ADMIN_CONFIG}\n[downloads]\ndir = {:?}\nyt_dlp = {:?}\n[[downloads.rules]]\nplatforms = [\"youtube\"]",
+        media, yt_dlp
+    );
+    let server = Server::start_with(&fake.uri(), &config);
+    server.refresh_again().await;
+
+    // Only the new video is downloaded, and then played from the media directory.
+    let page = server
+        .get_when("/watch/3", |body| body.contains("/media/3.mp4"))
+        .await;
+    assert!(page.contains("<video"));
+    assert!(page.contains(r#"src="/media/3.en.vtt" srclang="en""#));
+    let file = server.get_when("/media/3.mp4", |_| true).await;
+    assert_eq!(file, "video\n");
+    assert!(!media.join("1.mp4").exists());
+
+    // Podcast apps find the file in the feed.
+    let podcast = server.get_when("/podcast.xml", |_| true).await;
+    assert!(podcast.contains(r#"/media/3.mp4" length="6" type="video/mp4""#));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn downloads_are_toggled_at_runtime() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let fake = fake_hacker_news().await;
+    let dir = std::env::temp_dir().join(format!("hnv-e2e-features-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let yt_dlp = dir.join("yt-dlp");
+    std::fs::write(
+        &yt_dlp,
+        r#"#!/bin/sh
+while [ $# -gt 0 ]; do
+  case "$1" in
+    --paths) dir="$2"; shift ;;
+    --output) name="$2"; shift ;;
+  esac
+  shift
+done
+file="$dir/$(echo "$name" | sed 's/%(ext)s/mp4/')"
+echo video > "$file"
+echo "$file"
+"#,
+    )
+    .unwrap();
+    std::fs::set_permissions(&yt_dlp, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let media = dir.join("media");
+    let config = format!(
+        "{ADMIN_CONFIG}\n[features]\ndownloads = false\n[downloads]\ndir = {:?}\nyt_dlp = {:?}",
+        media, yt_dlp
+    );
+    let server = Server::start_with(&fake.uri(), &config);
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    let client = reqwest::Client::new();
+    let admin = || async {
+        client
+            .get(format!("{}/admin", server.url))
+            .basic_auth("admin", Some("admin"))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap()
+    };
+    assert!(admin()
+        .await
+        .contains(r#"action="/admin/features/downloads">"#));
+
+    // Queued videos wait while the downloads are disabled.
+    let response = client
+        .post(format!("{}/admin/downloads", server.url))
+        .basic_auth("admin", Some("admin"))
+        .form(&[("video", "1")])
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(!media.join("1.mp4").exists());
+
+    let response = client
+        .post(format!("{}/admin/features/downloads", server.url))
+        .basic_auth("admin", Some("admin"))
+        .form(&[("enabled", "true")])
+        .send()
+        .await
+        .unwrap();
+    // Back on the admin page, which offers to disable them again.
+    let page = response.text().await.unwrap();
+    assert!(page.contains(r#"name="enabled" value="false""#));
+    let file = server
+        .get_when("/media/1.mp4", |body| body == "video\n")
+        .await;
+    assert_eq!(file, "video\n");
+
+    let response = client
+        .post(format!("{}/admin/features/comments", server.url))
+        .basic_auth("admin", Some("admin"))
+        .form(&[("enabled", "true")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
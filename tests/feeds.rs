@@ -0,0 +1,216 @@
+//! End-to-end tests of the feeds and the files crawlers are pointed at.
+mod common;
+
+use serde_json::json;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use common::{fake_hacker_news, Server, ADMIN_CONFIG};
+
+#[tokio::test]
+async fn feeds_answer_conditional_requests() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+    server
+        .get_when("/feed/youtube.xml", |body| {
+            body.contains("A talk about Rust")
+        })
+        .await;
+    let client = reqwest::Client::new();
+    let url = format!("{}/feed/youtube.xml", server.url);
+
+    let response = client.get(&url).send().await.unwrap();
+    let last_modified = response.headers()["last-modified"].clone();
+
+    // Polling with the date of the copy at hand gets nothing new.
+    let response = client
+        .get(&url)
+        .header("if-modified-since", last_modified.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 304);
+    assert!(response.text().await.unwrap().is_empty());
+    let response = client
+        .get(&url)
+        .header("if-modified-since", "Thu, 01 Jan 1970 00:00:00 GMT")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client.head(&url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["last-modified"], last_modified);
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("application/xml"));
+}
+
+#[tokio::test]
+async fn private_feeds_leave_out_hidden_videos() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    let response = client
+        .post(format!("{}/hide/3", server.url))
+        .send()
+        .await
+        .unwrap();
+    let cookie = response.headers()["set-cookie"]
+        .to_str()
+        .unwrap()
+        .split(';')
+        .next()
+        .unwrap()
+        .to_string();
+    let save = |tags: &str| {
+        client
+            .post(format!("{}/feed/mine", server.url))
+            .header("cookie", &cookie)
+            .form(&[("source", ""), ("tags", tags)])
+            .send()
+    };
+    assert_eq!(save("").await.unwrap().status(), 303);
+
+    let page = client
+        .get(format!("{}/feed/mine", server.url))
+        .header("cookie", &cookie)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let start = page.find("/feed/u/").unwrap();
+    let path = &page[start..start + page[start..].find('"').unwrap()];
+    let feed = || async {
+        reqwest::get(format!("{}{}", server.url, path))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap()
+    };
+    let items = feed().await;
+    assert!(items.contains("<title>A talk about Rust</title>"));
+    assert!(!items.contains("<title>Another talk</title>"));
+
+    // None of the videos is tagged.
+    save("[gamedev]").await.unwrap();
+    assert!(!feed().await.contains("<item>"));
+
+    let response = reqwest::get(format!("{}/feed/u/unknown.xml", server.url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn the_sitemap_lists_the_archive() {
+    let fake = fake_hacker_news().await;
+    Mock::given(method("GET"))
+        .and(path("/item/4.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": 4,
+            "type": "story",
+            "title": "Making a game in a week [gamedev]",
+            "url": "https://www.youtube.com/watch?v=bbbbbbbbbbb",
+            "score": 10,
+        })))
+        .mount(&fake)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/topstories.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([1, 2, 3, 4])))
+        .with_priority(1)
+        .up_to_n_times(1)
+        .mount(&fake)
+        .await;
+    let server = Server::start_with(&fake.uri(), ADMIN_CONFIG);
+    server
+        .get_when("/", |body| body.contains("Making a game"))
+        .await;
+
+    // The next refresh finds the tagged video gone from the front page.
+    server.refresh_again().await;
+    server
+        .get_when("/", |body| !body.contains("Making a game"))
+        .await;
+
+    let sitemap = server
+        .get_when("/sitemap.xml", |body| body.contains("/watch/4"))
+        .await;
+    let today = time::OffsetDateTime::now_utc().date();
+    let (year, week, _) = today.to_iso_week_date();
+    for loc in [
+        "/archive</loc>".to_string(),
+        format!(
+            "/archive/{:04}/{:02}</loc>",
+            today.year(),
+            today.month() as u8
+        ),
+        format!("/week/{}/{}</loc>", year, week),
+        "/watch/1</loc>".to_string(),
+        "/search?q=tag%3Agamedev</loc>".to_string(),
+    ] {
+        assert!(
+            sitemap.contains(&loc),
+            "{} is missing from {}",
+            loc,
+            sitemap
+        );
+    }
+}
+
+#[tokio::test]
+async fn feeds_are_narrowed_to_a_tag() {
+    let hacker_news = fake_hacker_news().await;
+    Mock::given(method("GET"))
+        .and(path("/item/3.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": 3,
+            "type": "story",
+            "title": "Another talk [Rust]",
+            "url": "https://youtu.be/oHg5SJYRHA0",
+        })))
+        .with_priority(1)
+        .mount(&hacker_news)
+        .await;
+    let server = Server::start(&hacker_news.uri());
+
+    let feed = server
+        .get_when("/feed/tag/rust.xml", |body| body.contains("Another talk"))
+        .await;
+    assert!(!feed.contains("A talk about Rust"));
+    // Tags are matched whatever their case.
+    let feed = server.get_when("/feed/tag/RUST.xml", |_| true).await;
+    assert!(feed.contains("Another talk"));
+    let feed = server.get_when("/feed/tag/video.xml", |_| true).await;
+    assert!(!feed.contains("Another talk"));
+
+    let response = reqwest::get(format!("{}/feed/tag/rust", server.url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn robots_are_pointed_at_the_sitemap() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+
+    let robots = server.get_when("/robots.txt", |_| true).await;
+    assert!(robots.starts_with("User-agent: *\nAllow: /\n"));
+    assert!(robots.contains("Sitemap: http://localhost:3000/sitemap.xml\n"));
+}
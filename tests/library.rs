@@ -0,0 +1,46 @@
+//! Tests of the `hnv` library, used on its own without the server.
+mod common;
+
+use hnv::{
+    cache::Cache,
+    hacker_news::{self, Detection, HackerNews},
+};
+
+use common::fake_hacker_news;
+
+#[tokio::test]
+async fn top_videos_are_fetched_through_the_library() {
+    let fake = fake_hacker_news().await;
+    let hacker_news = HackerNews::with_base_url(Cache::new().await.unwrap(), &fake.uri());
+
+    let videos = hacker_news.get_top_videos(None, |_| {}).await.unwrap();
+    let titles: Vec<_> = videos
+        .iter()
+        .map(|item| item.title.as_deref().unwrap())
+        .collect();
+    assert_eq!(titles, ["A talk about Rust", "Another talk"]);
+
+    // The responses are kept in the cache, so the items are not fetched again.
+    hacker_news.get_top_videos(None, |_| {}).await.unwrap();
+    let requests = fake.received_requests().await.unwrap();
+    let items = requests
+        .iter()
+        .filter(|request| request.url.path() == "/item/1.json")
+        .count();
+    assert_eq!(items, 1);
+}
+
+#[test]
+fn items_are_classified_without_fetching_them() {
+    assert_eq!(
+        hacker_news::detect(r#"{"id": 1, "title": "A talk", "url": "https://youtu.be/x"}"#)
+            .unwrap(),
+        Detection::YouTube
+    );
+    assert_eq!(
+        hacker_news::detect(r#"{"id": 2, "title": "An article", "url": "https://example.com"}"#)
+            .unwrap(),
+        Detection::Rejected
+    );
+    assert_eq!(hacker_news::detect("null").unwrap(), Detection::Rejected);
+}
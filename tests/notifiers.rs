@@ -0,0 +1,431 @@
+//! End-to-end tests of the notifiers, which post the new videos of a refresh elsewhere.
+mod common;
+
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{header, method, path},
+    Mock, ResponseTemplate,
+};
+
+use common::{fake_hacker_news, fake_new_video, notified, requests_to, Server, ADMIN_CONFIG};
+
+#[tokio::test]
+async fn new_videos_are_posted_to_telegram() {
+    let notifier = Mock::given(method("POST"))
+        .and(path("/botsecret/sendMessage"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "ok": true })));
+    let config =
+        |url: &str| format!("[telegram]\ntoken = \"secret\"\nchat_id = -100\nurl = \"{url}\"");
+    let messages = notified(notifier, config, "/botsecret/sendMessage").await;
+
+    // The video listed when the notifier started is not posted.
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["chat_id"], json!(-100));
+    let text = messages[0]["text"].as_str().unwrap();
+    assert!(text.contains("Another talk"));
+    assert!(text.contains("https://youtu.be/oHg5SJYRHA0"));
+}
+
+#[tokio::test]
+async fn new_videos_are_posted_to_discord() {
+    let notifier = Mock::given(method("POST"))
+        .and(path("/webhooks/1/token"))
+        .respond_with(ResponseTemplate::new(204));
+    let config = |url: &str| format!("[discord]\nwebhook_url = \"{url}/webhooks/1/token\"");
+    let messages = notified(notifier, config, "/webhooks/1/token").await;
+
+    assert_eq!(messages.len(), 1);
+    let embed = &messages[0]["embeds"][0];
+    assert_eq!(embed["title"], "Another talk");
+    assert_eq!(embed["url"], "https://youtu.be/oHg5SJYRHA0");
+}
+
+#[tokio::test]
+async fn new_videos_are_posted_to_slack() {
+    let notifier = Mock::given(method("POST"))
+        .and(path("/services/T0/B0/secret"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("ok"));
+    let config = |url: &str| format!("[slack]\nwebhook_url = \"{url}/services/T0/B0/secret\"");
+    let messages = notified(notifier, config, "/services/T0/B0/secret").await;
+
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["text"], "1 new video");
+    let line = messages[0]["blocks"][0]["text"]["text"].as_str().unwrap();
+    assert!(line.starts_with("*<https://youtu.be/oHg5SJYRHA0|Another talk>*"));
+}
+
+#[tokio::test]
+async fn new_videos_are_tooted() {
+    let notifier = Mock::given(method("POST"))
+        .and(path("/api/v1/statuses"))
+        .and(header("authorization", "Bearer secret"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "1" })));
+    let config = |url: &str| format!("[mastodon]\nurl = \"{url}\"\ntoken = \"secret\"");
+    let toots = notified(notifier, config, "/api/v1/statuses").await;
+
+    assert_eq!(toots.len(), 1);
+    let status = toots[0]["status"].as_str().unwrap();
+    assert!(status.starts_with("Another talk\n\nhttps://youtu.be/oHg5SJYRHA0\n"));
+}
+
+#[tokio::test]
+async fn new_videos_are_posted_to_matrix() {
+    let send = "/_matrix/client/v3/rooms/!room:example.org/send/m.room.message/hnv-3";
+    let notifier = Mock::given(method("PUT"))
+        .and(path(send))
+        .and(header("authorization", "Bearer secret"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "event_id": "$1" })));
+    let config = |url: &str| {
+        format!(
+            "[matrix]\nhomeserver = \"{url}\"\naccess_token = \"secret\"\n\
+            room_id = \"!room:example.org\""
+        )
+    };
+    let messages = notified(notifier, config, send).await;
+
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["msgtype"], "m.text");
+    assert!(messages[0]["body"]
+        .as_str()
+        .unwrap()
+        .starts_with("Another talk\nhttps://youtu.be/oHg5SJYRHA0\n"));
+}
+
+#[tokio::test]
+async fn new_videos_are_published_to_followers() {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use openssl::{
+        hash::{hash, MessageDigest},
+        pkey::PKey,
+        rsa::Rsa,
+        sign::Signer,
+    };
+
+    let fake = fake_hacker_news().await;
+    fake_new_video(&fake).await;
+    let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let alice = format!("{}/users/alice", fake.uri());
+    Mock::given(method("GET"))
+        .and(path("/users/alice"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": alice,
+            "type": "Person",
+            "inbox": format!("{}/inbox", alice),
+            "publicKey": {
+                "id": format!("{}#main-key", alice),
+                "owner": alice,
+                "publicKeyPem": String::from_utf8(key.public_key_to_pem().unwrap()).unwrap(),
+            },
+        })))
+        .mount(&fake)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/users/alice/inbox"))
+        .respond_with(ResponseTemplate::new(202))
+        .mount(&fake)
+        .await;
+    let config =
+        format!("{ADMIN_CONFIG}\n[activitypub]\nallow_http = true\nallow_private_hosts = true");
+    let server = Server::start_with(&fake.uri(), &config);
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+
+    let webfinger = server
+        .get_when(
+            "/.well-known/webfinger?resource=acct:videos@localhost:3000",
+            |body| body.contains("/ap/actor"),
+        )
+        .await;
+    let webfinger: Value = serde_json::from_str(&webfinger).unwrap();
+    assert_eq!(
+        webfinger["links"][0]["href"],
+        "http://localhost:3000/ap/actor"
+    );
+
+    // A Follow signed by the remote actor, the way Mastodon sends it.
+    let follow = json!({
+        "id": format!("{}#follows/1", alice),
+        "type": "Follow",
+        "actor": alice,
+        "object": "http://localhost:3000/ap/actor",
+    })
+    .to_string();
+    let host = server.url.trim_start_matches("http://").to_string();
+    let date = httpdate::fmt_http_date(std::time::SystemTime::now());
+    let digest = format!(
+        "SHA-256={}",
+        STANDARD.encode(hash(MessageDigest::sha256(), follow.as_bytes()).unwrap())
+    );
+    let signing_string =
+        format!("(request-target): post /ap/inbox\nhost: {host}\ndate: {date}\ndigest: {digest}");
+    let mut signer = Signer::new(MessageDigest::sha256(), &key).unwrap();
+    signer.update(signing_string.as_bytes()).unwrap();
+    let signature = format!(
+        "keyId=\"{alice}#main-key\",algorithm=\"rsa-sha256\",\
+        headers=\"(request-target) host date digest\",signature=\"{}\"",
+        STANDARD.encode(signer.sign_to_vec().unwrap())
+    );
+    let client = reqwest::Client::new();
+    let inbox = format!("{}/ap/inbox", server.url);
+
+    // The signature doesn't cover another date.
+    let response = client
+        .post(&inbox)
+        .header("Date", "Thu, 01 Jan 1970 00:00:00 GMT")
+        .header("Digest", &digest)
+        .header("Signature", &signature)
+        .body(follow.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    let response = client
+        .post(&inbox)
+        .header("Date", &date)
+        .header("Digest", &digest)
+        .header("Signature", &signature)
+        .body(follow)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+    let activities = requests_to(&fake, "/users/alice/inbox").await;
+    assert_eq!(activities[0]["type"], "Accept");
+    assert_eq!(activities[0]["object"]["type"], "Follow");
+    let followers = server
+        .get_when("/ap/followers", |body| body.contains("totalItems"))
+        .await;
+    assert!(followers.contains(r#""totalItems":1"#));
+
+    server.refresh_again().await;
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let create = loop {
+        let activities = requests_to(&fake, "/users/alice/inbox").await;
+        if let Some(create) = activities.into_iter().find(|a| a["type"] == "Create") {
+            break create;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "the new video was never published"
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    };
+    assert_eq!(create["object"]["type"], "Note");
+    let content = create["object"]["content"].as_str().unwrap();
+    assert!(content.contains("Another talk"));
+    assert!(content.contains("https://youtu.be/oHg5SJYRHA0"));
+}
+
+#[tokio::test]
+async fn new_videos_are_delivered_to_webhooks() {
+    use hmac::{Hmac, Mac};
+
+    let fake = fake_hacker_news().await;
+    fake_new_video(&fake).await;
+    Mock::given(method("POST"))
+        .and(path("/hook"))
+        .respond_with(ResponseTemplate::new(204))
+        .mount(&fake)
+        .await;
+    let server = Server::start_with(&fake.uri(), ADMIN_CONFIG);
+    server.get_when("/", |_| true).await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("{}/admin/webhooks", server.url))
+        .basic_auth("admin", Some("admin"))
+        .json(&json!({ "url": format!("{}/hook", fake.uri()), "events": ["nope"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let response = client
+        .post(format!("{}/admin/webhooks", server.url))
+        .basic_auth("admin", Some("admin"))
+        .json(&json!({ "url": format!("{}/hook", fake.uri()), "events": ["video.detected"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+    let webhook: Value = response.json().await.unwrap();
+    let secret = webhook["secret"].as_str().unwrap().to_string();
+    server.refresh_again().await;
+
+    requests_to(&fake, "/hook").await;
+    let requests = fake.received_requests().await.unwrap();
+    let request = requests
+        .iter()
+        .find(|request| request.url.path() == "/hook")
+        .unwrap();
+    let payload: Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(payload["event"], "video.detected");
+    assert_eq!(payload["data"]["title"], "Another talk");
+
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(&request.body);
+    let signature: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    assert_eq!(
+        request.headers["x-hnv-signature"],
+        format!("sha256={}", signature).as_str()
+    );
+
+    // The delivery is logged once the response is in.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let deliveries: Vec<Value> = client
+        .get(format!(
+            "{}/admin/webhooks/{}/deliveries",
+            server.url, webhook["id"]
+        ))
+        .basic_auth("admin", Some("admin"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(deliveries[0]["status"], 204);
+}
+
+#[tokio::test]
+async fn new_videos_are_pushed_to_ntfy() {
+    let notifier = Mock::given(method("POST"))
+        .and(path("/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "1" })));
+    let config = |url: &str| format!("[ntfy]\ntopic_url = \"{url}/videos\"");
+    let messages = notified(notifier, config, "/").await;
+
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["topic"], "videos");
+    assert_eq!(messages[0]["message"], "Another talk");
+    assert_eq!(messages[0]["click"], "https://youtu.be/oHg5SJYRHA0");
+}
+
+#[tokio::test]
+async fn new_videos_with_subscribed_tags_are_pushed() {
+    let fake = fake_hacker_news().await;
+    fake_new_video(&fake).await;
+    Mock::given(method("GET"))
+        .and(path("/item/3.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": 3,
+            "type": "story",
+            "title": "Another talk [GameDev]",
+            "url": "https://youtu.be/oHg5SJYRHA0",
+        })))
+        .with_priority(1)
+        .mount(&fake)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "1" })))
+        .mount(&fake)
+        .await;
+    let config = format!(
+        "{ADMIN_CONFIG}\n[tag_push]\nserver_url = \"{}\"",
+        fake.uri()
+    );
+    let server = Server::start_with(&fake.uri(), &config);
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    for (tags, topic) in [
+        ("gamedev", "mine"),
+        ("rust", "theirs"),
+        ("gamedev", "not/a/topic"),
+    ] {
+        let response = client
+            .post(format!("{}/feed/mine", server.url))
+            .form(&[("source", ""), ("tags", tags), ("push_topic", topic)])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 303);
+    }
+    server.refresh_again().await;
+
+    let messages = requests_to(&fake, "/").await;
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["topic"], "mine");
+    assert_eq!(messages[0]["message"], "Another talk [GameDev]");
+}
+
+#[tokio::test]
+async fn saved_searches_alert_to_new_matches() {
+    let fake = fake_hacker_news().await;
+    fake_new_video(&fake).await;
+    Mock::given(method("POST"))
+        .and(path("/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "1" })))
+        .mount(&fake)
+        .await;
+    let config = format!(
+        "{ADMIN_CONFIG}\n[tag_push]\nserver_url = \"{}\"",
+        fake.uri()
+    );
+    let server = Server::start_with(&fake.uri(), &config);
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let response = client
+        .post(format!("{}/feed/mine", server.url))
+        .form(&[("source", ""), ("tags", ""), ("push_topic", "alerts")])
+        .send()
+        .await
+        .unwrap();
+    let cookie = response.headers()["set-cookie"]
+        .to_str()
+        .unwrap()
+        .split(';')
+        .next()
+        .unwrap()
+        .to_string();
+    for query in ["another platform:youtube", "rust", "  "] {
+        let response = client
+            .post(format!("{}/searches", server.url))
+            .header("cookie", &cookie)
+            .form(&[("q", query)])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 303);
+    }
+    server.refresh_again().await;
+
+    let messages = requests_to(&fake, "/").await;
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["topic"], "alerts");
+    assert_eq!(messages[0]["title"], "another platform:youtube");
+    let page = client
+        .get(format!("{}/searches", server.url))
+        .header("cookie", &cookie)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("1 matches"));
+    assert!(page.contains("0 matches"));
+    // The most recently saved come first.
+    let start = page.rfind("/searches/feed/").unwrap();
+    let path = &page[start..start + page[start..].find('"').unwrap()];
+    let feed = server.get_when(path, |_| true).await;
+    assert!(feed.contains("Another talk"));
+    assert!(!feed.contains("A talk about Rust"));
+}
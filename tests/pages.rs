@@ -0,0 +1,631 @@
+//! End-to-end tests of the pages of the site: the index, how pages are served and what they look like.
+mod common;
+
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use common::{cookie_of, fake_hacker_news, no_redirects, Server};
+
+#[tokio::test]
+async fn index_lists_the_videos() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+
+    let body = server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+
+    assert!(body.contains("Another talk"));
+    assert!(!body.contains("An article about Rust"));
+    assert!(body.contains("/watch/1"));
+    assert!(body.contains("/watch/3"));
+}
+
+#[tokio::test]
+async fn videos_are_sorted_by_comments() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+
+    let page = server
+        .get_when("/?sort=comments", |body| body.contains("A talk about Rust"))
+        .await;
+    let list = &page[page.find("<ul class=\"videos\">").unwrap()..];
+    assert!(list.find("Another talk") < list.find("A talk about Rust"));
+    assert!(list.contains("150 comments"));
+
+    // Only the video with more comments than points makes for a hot discussion.
+    let hot = &page[page.find("hot-discussions").unwrap()..page.find("<ul").unwrap()];
+    assert!(hot.contains("Another talk"));
+    assert!(!hot.contains("A talk about Rust"));
+}
+
+#[tokio::test]
+async fn index_is_served_as_json_when_asked() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+    let api = server
+        .get_when("/api/v1/videos", |body| body.contains("A talk about Rust"))
+        .await;
+    let client = reqwest::Client::new();
+    let get = |accept: &'static str| {
+        client
+            .get(format!("{}/", server.url))
+            .header("accept", accept)
+            .send()
+    };
+
+    let response = get("application/json").await.unwrap();
+    assert_eq!(response.headers()["content-type"], "application/json");
+    assert_eq!(response.headers()["vary"], "Accept");
+    let videos: Value = response.json().await.unwrap();
+    assert_eq!(videos, serde_json::from_str::<Value>(&api).unwrap());
+
+    // Browsers, and clients taking anything, get the page.
+    for accept in [
+        "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+        "*/*",
+        "text/html, application/json;q=0.5",
+    ] {
+        let response = get(accept).await.unwrap();
+        assert!(response.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html"));
+    }
+}
+
+#[tokio::test]
+async fn build_is_shown_for_bug_reports() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+
+    let body = server.get_when("/version", |_| true).await;
+    let build: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(build["version"], env!("CARGO_PKG_VERSION"));
+    let git_hash = build["git_hash"].as_str().unwrap();
+    assert!(!git_hash.is_empty());
+
+    let index = server.get_when("/", |_| true).await;
+    assert!(index.contains(&format!(
+        "hnv {} ({}, ",
+        env!("CARGO_PKG_VERSION"),
+        git_hash
+    )));
+}
+
+#[tokio::test]
+async fn index_is_shown_as_a_grid() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .get(format!("{}/?view=grid", server.url))
+        .send()
+        .await
+        .unwrap();
+    let cookie = response
+        .headers()
+        .get_all("set-cookie")
+        .iter()
+        .map(|value| value.to_str().unwrap().split(';').next().unwrap())
+        .find(|cookie| cookie.starts_with("view="))
+        .unwrap()
+        .to_string();
+    let page = response.text().await.unwrap();
+    assert!(page.contains("<ul class=\"videos grid\">"));
+    assert!(page.contains("https://i.ytimg.com/vi/dQw4w9WgXcQ/mqdefault.jpg"));
+
+    // The view is remembered.
+    let page = client
+        .get(format!("{}/", server.url))
+        .header("cookie", &cookie)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("<ul class=\"videos grid\">"));
+    let page = server.get_when("/", |_| true).await;
+    assert!(page.contains("<ul class=\"videos\">"));
+}
+
+#[tokio::test]
+async fn assets_are_cached_under_fingerprinted_names() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+    let page = server.get_when("/", |_| true).await;
+    let stylesheet = page
+        .split("<link href=\"")
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .unwrap();
+    assert!(stylesheet.starts_with("/assets/main."));
+    assert_ne!(stylesheet, "/assets/main.css");
+
+    let response = reqwest::get(format!("{}{}", server.url, stylesheet))
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["content-type"], "text/css");
+    assert!(response.headers()["cache-control"]
+        .to_str()
+        .unwrap()
+        .contains("immutable"));
+
+    // Names with other fingerprints and without one are served, but revalidated.
+    for path in ["/assets/main.css", "/assets/main.0123456789.css"] {
+        let response = reqwest::get(format!("{}{}", server.url, path))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert!(!response.headers()["cache-control"]
+            .to_str()
+            .unwrap()
+            .contains("immutable"));
+    }
+}
+
+#[tokio::test]
+async fn pages_are_styled_with_a_theme() {
+    let hacker_news = fake_hacker_news().await;
+    let themes = std::env::temp_dir().join(format!("hnv-e2e-themes-{}", std::process::id()));
+    std::fs::create_dir_all(themes.join("terminal/fonts")).unwrap();
+    let css = "body { font-family: monospace; }";
+    std::fs::write(themes.join("terminal/main.css"), css).unwrap();
+    std::fs::write(themes.join("terminal/fonts/mono.woff2"), "font").unwrap();
+    let config = format!("theme = \"terminal\"\nthemes_dir = {:?}", themes);
+    let server = Server::start_with(&hacker_news.uri(), &config);
+
+    let page = server.get_when("/", |_| true).await;
+    let stylesheet = page
+        .split("<link href=\"")
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .unwrap();
+    let response = reqwest::get(format!("{}{}", server.url, stylesheet))
+        .await
+        .unwrap();
+    assert!(response.headers()["cache-control"]
+        .to_str()
+        .unwrap()
+        .contains("immutable"));
+    assert_eq!(response.text().await.unwrap(), css);
+
+    // The theme can add files, and the embedded ones it doesn't replace are still served.
+    for path in ["/assets/fonts/mono.woff2", "/assets/history.js"] {
+        let response = reqwest::get(format!("{}{}", server.url, path))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    drop(server);
+    std::fs::remove_dir_all(themes).unwrap();
+}
+
+#[tokio::test]
+async fn pages_are_minified() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+    let body = server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    assert!(!body.contains("  "));
+    assert!(body.contains("<ul class=\"videos\">"));
+
+    let server = Server::start_with(&hacker_news.uri(), "minify_html = false");
+    let body = server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    assert!(body.contains("\n    "));
+}
+
+#[tokio::test]
+async fn pages_are_sent_with_security_headers() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start_with(
+        &hacker_news.uri(),
+        r#"
+        privacy.frontend = "https://yewtu.be/"
+        security.frame_hosts = ["https://player.vimeo.com"]
+        "#,
+    );
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+
+    let page = reqwest::get(format!("{}/", server.url)).await.unwrap();
+    let policy = page.headers()["content-security-policy"].to_str().unwrap();
+    assert!(policy
+        .contains("frame-src https://www.youtube.com https://yewtu.be https://player.vimeo.com;"));
+    assert!(policy.contains("frame-ancestors 'none'"));
+    assert_eq!(page.headers()["x-content-type-options"], "nosniff");
+    assert!(page.headers().contains_key("referrer-policy"));
+
+    // Other responses are left alone, and the API docs bring their own policy.
+    let api = reqwest::get(format!("{}/api/v1/videos", server.url))
+        .await
+        .unwrap();
+    assert!(!api.headers().contains_key("content-security-policy"));
+    let docs = reqwest::get(format!("{}/api/docs", server.url))
+        .await
+        .unwrap();
+    let policy = docs.headers()["content-security-policy"].to_str().unwrap();
+    assert!(policy.contains("https://unpkg.com"));
+}
+
+#[tokio::test]
+async fn hacker_news_is_watched_for_the_status_page() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start_with(&fake.uri(), "status.interval = 1");
+
+    // The fake doesn't know the newest item yet.
+    let status = server
+        .get_when("/status", |body| body.contains("class=\"health down\""))
+        .await;
+    assert!(status.contains("404 Not Found"));
+    assert_eq!(status.matches("<rect").count(), 24);
+
+    Mock::given(method("GET"))
+        .and(path("/maxitem.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(3)))
+        .mount(&fake)
+        .await;
+    let status = server
+        .get_when("/status", |body| body.contains("class=\"health up\""))
+        .await;
+    assert!(status.contains("Failed requests today"));
+}
+
+#[tokio::test]
+async fn index_is_sorted_by_local_clicks() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    let index = server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+    // The hot discussions come before the list.
+    let index = &index[index.find("<ul class=\"videos\">").unwrap()..];
+    assert!(index.find("A talk about Rust") < index.find("Another talk"));
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    for _ in 0..2 {
+        let response = client
+            .get(format!("{}/v/3", server.url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 302);
+    }
+
+    let sorted = server
+        .get_when("/?sort=clicks", |body| body.contains("2 clicks"))
+        .await;
+    assert!(sorted.find("Another talk") < sorted.find("A talk about Rust"));
+}
+
+#[tokio::test]
+async fn index_answers_conditional_requests() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    let response = client.get(&server.url).send().await.unwrap();
+    let etag = response.headers()["etag"].clone();
+    // The page depends on the session, so only the browser of the visitor may keep it.
+    assert_eq!(
+        response.headers()["cache-control"],
+        "private, max-age=60, must-revalidate"
+    );
+
+    let response = client
+        .get(&server.url)
+        .header("if-none-match", etag.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 304);
+
+    // Hiding a video changes the page of the visitor, and with it the tag.
+    let response = client
+        .post(format!("{}/hide/3", server.url))
+        .send()
+        .await
+        .unwrap();
+    let cookie = response.headers()["set-cookie"]
+        .to_str()
+        .unwrap()
+        .split(';')
+        .next()
+        .unwrap()
+        .to_string();
+    let response = client
+        .get(&server.url)
+        .header("cookie", cookie)
+        .header("if-none-match", etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn deeper_ranks_are_fetched_when_paged_to() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start_with(&hacker_news.uri(), "refresh.eager_ranks = 1");
+
+    let index = server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    assert!(!index.contains("Another talk"));
+    assert!(index.contains("?page=2"));
+    let fetches = || async {
+        let requests = hacker_news.received_requests().await.unwrap();
+        ["1", "2", "3"].map(|item| {
+            let path = format!("/item/{}.json", item);
+            requests
+                .iter()
+                .filter(|request| request.url.path() == path)
+                .count()
+        })
+    };
+    assert_eq!(fetches().await, [1, 0, 0]);
+
+    // Paging on fetches them, and lists them on the page they fall on.
+    let page = reqwest::get(format!("{}/?page=2", server.url))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("A talk about Rust"));
+    assert!(page.contains("Another talk"));
+    assert!(!page.contains("?page=2"));
+    assert_eq!(fetches().await, [1, 1, 1]);
+
+    // They are kept for the other visitors, like the rest.
+    let index = reqwest::get(&server.url)
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(index.contains("Another talk"));
+    assert_eq!(fetches().await, [1, 1, 1]);
+}
+
+#[tokio::test]
+async fn requests_are_tagged_with_an_id() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+    server.get_when("/", |_| true).await;
+
+    let response = reqwest::get(format!("{}/", server.url)).await.unwrap();
+    assert!(!response.headers()["x-request-id"].is_empty());
+
+    // The ID of a proxy in front is kept, so its logs and ours can be matched.
+    let response = reqwest::Client::new()
+        .get(format!("{}/", server.url))
+        .header("x-request-id", "from-the-proxy")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["x-request-id"], "from-the-proxy");
+}
+
+#[tokio::test]
+async fn unknown_pages_get_the_error_page() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+    server.get_when("/", |_| true).await;
+
+    let response = reqwest::get(format!("{}/no/such/page", server.url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("The page you are looking for does not exist."));
+    assert!(body.contains("Back to the video list"));
+}
+
+#[tokio::test]
+async fn upstream_failures_are_told_apart_without_their_details() {
+    let hacker_news = fake_hacker_news().await;
+    Mock::given(method("GET"))
+        .and(path("/item/99.json"))
+        .respond_with(ResponseTemplate::new(500).set_body_string("secret internals"))
+        .mount(&hacker_news)
+        .await;
+    let server = Server::start(&hacker_news.uri());
+    server.get_when("/", |_| true).await;
+
+    let response = reqwest::get(format!("{}/watch/99", server.url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 502);
+    let body = response.text().await.unwrap();
+    assert!(body.contains("Hacker News can not be reached right now"));
+    assert!(body.contains("Error ID: <code>"));
+    assert!(!body.contains("secret internals"));
+    assert!(!body.contains("/item/99.json"));
+}
+
+#[tokio::test]
+async fn dark_mode_is_chosen_on_the_server() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+    let page = server.get_when("/", |_| true).await;
+    assert!(page.contains("data-theme=\"light\""));
+
+    let response = no_redirects()
+        .post(format!("{}/theme", server.url))
+        .form(&[("theme", "dark")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 303);
+    let cookie = cookie_of(&response);
+    assert_eq!(cookie, "theme=dark");
+
+    let page = reqwest::Client::new()
+        .get(format!("{}/", server.url))
+        .header("cookie", cookie)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("data-theme=\"dark\""));
+    assert!(page.contains("light mode"));
+}
+
+#[tokio::test]
+async fn pages_are_translated() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+    let page = server.get_when("/", |_| true).await;
+    assert!(page.contains("<html lang=\"en\""));
+
+    let page = reqwest::Client::new()
+        .get(format!("{}/", server.url))
+        .header("accept-language", "fr;q=0.9, de;q=0.8")
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("<html lang=\"de\""));
+    assert!(page.contains("Hacker News Top-Videos"));
+
+    // The parameter wins over the header.
+    let page = reqwest::Client::new()
+        .get(format!("{}/?lang=zh", server.url))
+        .header("accept-language", "de")
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("Hacker News 熱門影片"));
+}
+
+#[tokio::test]
+async fn watch_pages_have_rich_previews() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+
+    let page = server.get_when("/watch/1", |_| true).await;
+    assert!(page.contains(r#"<meta property="og:title" content="A talk about Rust"/>"#));
+    assert!(page.contains(
+        r#"<meta property="og:video" content="https://www.youtube.com/embed/dQw4w9WgXcQ"/>"#
+    ));
+    assert!(page.contains(r#"<meta name="twitter:card" content="player"/>"#));
+}
+
+#[tokio::test]
+async fn youtube_links_go_through_the_privacy_frontend() {
+    let hacker_news = fake_hacker_news().await;
+    let config = "[privacy]\nfrontend = \"https://yewtu.be/\"\nenabled_by_default = true";
+    let server = Server::start_with(&hacker_news.uri(), config);
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    let client = no_redirects();
+
+    let response = client
+        .get(format!("{}/v/1", server.url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()["location"],
+        "https://yewtu.be/watch?v=dQw4w9WgXcQ"
+    );
+    let page = server.get_when("/watch/1", |_| true).await;
+    assert!(page.contains("https://yewtu.be/embed/dQw4w9WgXcQ"));
+
+    // Visitors can opt out.
+    let response = client
+        .post(format!("{}/privacy", server.url))
+        .form(&[("enabled", "false")])
+        .send()
+        .await
+        .unwrap();
+    let response = client
+        .get(format!("{}/v/1", server.url))
+        .header("cookie", cookie_of(&response))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()["location"],
+        "https://www.youtube.com/watch?v=dQw4w9WgXcQ"
+    );
+}
+
+#[tokio::test]
+async fn site_is_served_under_a_base_path() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start_with(
+        &hacker_news.uri(),
+        "public_url = \"https://videos.example/videos\"\nbase_path = \"/videos\"",
+    );
+
+    let page = server
+        .get_when("/videos", |body| body.contains("A talk about Rust"))
+        .await;
+    assert!(page.contains("href=\"/videos/watch/1\""));
+    assert!(page.contains("href=\"/videos/assets/main."));
+    let response = no_redirects()
+        .get(format!("{}/videos/", server.url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 308);
+    assert_eq!(response.headers()["location"], "/videos");
+    let response = reqwest::get(format!("{}/watch/1", server.url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn links_follow_the_prefix_of_the_proxy() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start(&hacker_news.uri());
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+
+    let page = reqwest::Client::new()
+        .get(format!("{}/", server.url))
+        .header("x-forwarded-prefix", "/hnv")
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("href=\"/hnv/watch/1\""));
+}
@@ -0,0 +1,350 @@
+//! End-to-end tests of refreshing the videos: when refreshes run and what they fetch.
+mod common;
+
+use std::{
+    process::Stdio,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde_json::json;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, Request, Respond, ResponseTemplate,
+};
+
+use common::{fake_hacker_news, run, run_with_config, Server};
+
+#[tokio::test]
+async fn refresh_metrics_are_pushed() {
+    let hacker_news = fake_hacker_news().await;
+    let pushgateway = MockServer::start().await;
+    Mock::given(method("PUT"))
+        .and(path("/metrics/job/cron"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&pushgateway)
+        .await;
+    let config = format!(
+        "[pushgateway]\nurl = \"{}\"\njob = \"cron\"",
+        pushgateway.uri()
+    );
+
+    run_with_config(&hacker_news.uri(), ":memory:", &config, &["refresh"], "").await;
+
+    let requests = pushgateway.received_requests().await.unwrap();
+    let body = String::from_utf8(requests[0].body.clone()).unwrap();
+    assert!(body.contains("# TYPE hnv_refresh_duration_seconds gauge"));
+    assert!(body.contains("\nhnv_refresh_items_fetched 3\n"));
+    assert!(body.contains("\nhnv_refresh_new_videos 2\n"));
+    assert!(body.contains("\nhnv_refresh_errors 0\n"));
+    assert!(body.contains("\nhnv_refresh_success 1\n"));
+}
+
+#[tokio::test]
+async fn an_interrupted_refresh_is_resumed() {
+    let hacker_news = fake_hacker_news().await;
+    // The first fetch of the last item hangs, long enough for the refresh to be killed.
+    Mock::given(method("GET"))
+        .and(path("/item/3.json"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(60)))
+        .with_priority(1)
+        .up_to_n_times(1)
+        .mount(&hacker_news)
+        .await;
+    let database = std::env::temp_dir().join(format!("hnv-e2e-resume-{}.db", std::process::id()));
+    let database = database.to_str().unwrap();
+    // Without resuming, every item would be fetched again.
+    let config = "[refresh]\nitem_ttl = 0";
+    let fetches = || async {
+        let requests = hacker_news.received_requests().await.unwrap();
+        ["1", "2", "3"].map(|item| {
+            requests
+                .iter()
+                .filter(|request| request.url.path() == format!("/item/{}.json", item))
+                .count()
+        })
+    };
+
+    let config_path =
+        std::env::temp_dir().join(format!("hnv-e2e-resume-{}.toml", std::process::id()));
+    std::fs::write(
+        &config_path,
+        format!(
+            "database = {database:?}\nhacker_news_url = \"{}\"\n{config}\n",
+            hacker_news.uri()
+        ),
+    )
+    .unwrap();
+    let mut crashing = tokio::process::Command::new(env!("CARGO_BIN_EXE_hnv"))
+        .args(["--no-progress", "refresh"])
+        .env("HNV_CONFIG", &config_path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    while fetches().await != [1, 1, 1] {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    // Let the cache write the processed items, then crash.
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    crashing.kill().await.unwrap();
+    std::fs::remove_file(&config_path).unwrap();
+    // The cache tells the age of items in seconds.
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+
+    run_with_config(&hacker_news.uri(), database, config, &["refresh"], "").await;
+    assert_eq!(fetches().await, [1, 1, 2]);
+
+    // A refresh that completed is not resumed.
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    run_with_config(&hacker_news.uri(), database, config, &["refresh"], "").await;
+    assert_eq!(fetches().await, [2, 2, 3]);
+
+    std::fs::remove_file(database).unwrap();
+}
+
+#[tokio::test]
+async fn pages_are_served_before_the_first_refresh_finishes() {
+    let fake = fake_hacker_news().await;
+    Mock::given(method("GET"))
+        .and(path("/topstories.json"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!([1, 2, 3]))
+                .set_delay(Duration::from_secs(3)),
+        )
+        .with_priority(1)
+        .mount(&fake)
+        .await;
+    let started = Instant::now();
+    let server = Server::start(&fake.uri());
+
+    let page = server.get_when("/", |_| true).await;
+    assert!(started.elapsed() < Duration::from_secs(3));
+    assert!(!page.contains("A talk about Rust"));
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+}
+
+#[tokio::test]
+async fn page_views_dont_fetch_the_top_stories() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+    for path in ["/", "/", "/api/v1/videos", "/feed.xml"] {
+        server.get_when(path, |_| true).await;
+    }
+
+    let requests = fake.received_requests().await.unwrap();
+    let fetches = |path: &str| {
+        requests
+            .iter()
+            .filter(|request| request.url.path() == path)
+            .count()
+    };
+    assert_eq!(fetches("/topstories.json"), 1);
+    assert_eq!(fetches("/item/1.json"), 1);
+}
+
+#[tokio::test]
+async fn refreshes_are_scheduled() {
+    let fake = fake_hacker_news().await;
+    let config = "refresh.cron = \"* * * * * *\"\nrefresh.jitter = 0";
+    let server = Server::start_with(&fake.uri(), config);
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let requests = fake.received_requests().await.unwrap();
+        let refreshes = requests
+            .iter()
+            .filter(|request| request.url.path() == "/topstories.json")
+            .count();
+        if refreshes >= 3 {
+            break;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "only {} refreshes ran",
+            refreshes
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+#[tokio::test]
+async fn videos_are_shown_as_soon_as_they_are_fetched() {
+    let fake = fake_hacker_news().await;
+    Mock::given(method("GET"))
+        .and(path("/item/3.json"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({
+                    "id": 3,
+                    "type": "story",
+                    "title": "Another talk",
+                    "url": "https://youtu.be/oHg5SJYRHA0",
+                }))
+                .set_delay(Duration::from_secs(5)),
+        )
+        .with_priority(1)
+        .mount(&fake)
+        .await;
+    let server = Server::start(&fake.uri());
+
+    let page = server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    assert!(!page.contains("Another talk"));
+}
+
+#[tokio::test]
+async fn slow_items_dont_hold_up_the_others() {
+    let fake = fake_hacker_news().await;
+    Mock::given(method("GET"))
+        .and(path("/item/1.json"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({
+                    "id": 1,
+                    "type": "story",
+                    "title": "A talk about Rust",
+                    "url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+                }))
+                .set_delay(Duration::from_secs(5)),
+        )
+        .with_priority(1)
+        .mount(&fake)
+        .await;
+    // With batches of two, the last item would wait for the first.
+    let server = Server::start_with(&fake.uri(), "refresh.concurrency = 2");
+
+    let page = server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+    assert!(!page.contains("A talk about Rust"));
+}
+
+#[tokio::test]
+async fn unchanged_items_are_not_fetched_again() {
+    let hacker_news = fake_hacker_news().await;
+    let database = std::env::temp_dir().join(format!("hnv-e2e-diff-{}.db", std::process::id()));
+    let database = database.to_str().unwrap();
+
+    run(&hacker_news.uri(), database, &["refresh"]).await;
+    run(&hacker_news.uri(), database, &["refresh"]).await;
+
+    let requests = hacker_news.received_requests().await.unwrap();
+    let fetches = |path: &str| {
+        requests
+            .iter()
+            .filter(|request| request.url.path() == path)
+            .count()
+    };
+    assert_eq!(fetches("/topstories.json"), 2);
+    assert_eq!(
+        (
+            fetches("/item/1.json"),
+            fetches("/item/2.json"),
+            fetches("/item/3.json")
+        ),
+        (1, 1, 1)
+    );
+
+    std::fs::remove_file(database).unwrap();
+}
+
+/// Answers with a story, noting when it was asked for.
+struct TimedStory {
+    id: usize,
+    delay: Duration,
+    requested: Arc<Mutex<Vec<(usize, Instant)>>>,
+}
+
+impl Respond for TimedStory {
+    fn respond(&self, _: &Request) -> ResponseTemplate {
+        self.requested
+            .lock()
+            .unwrap()
+            .push((self.id, Instant::now()));
+        ResponseTemplate::new(200)
+            .set_body_json(json!({
+                "id": self.id,
+                "type": "story",
+                "title": format!("Story {}", self.id),
+                "url": format!("https://example.com/{}", self.id),
+            }))
+            .set_delay(self.delay)
+    }
+}
+
+#[tokio::test]
+async fn the_front_page_is_fetched_first() {
+    let hacker_news = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/topstories.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json((1..=40).collect::<Vec<_>>()))
+        .mount(&hacker_news)
+        .await;
+    let requested = Arc::new(Mutex::new(Vec::new()));
+    for id in 1..=40 {
+        // The front page takes a while, which the deeper ranks have to wait for.
+        let story = TimedStory {
+            id,
+            delay: Duration::from_secs(if id == 1 { 1 } else { 0 }),
+            requested: requested.clone(),
+        };
+        Mock::given(method("GET"))
+            .and(path(format!("/item/{}.json", id)))
+            .respond_with(story)
+            .mount(&hacker_news)
+            .await;
+    }
+
+    run_with_config(
+        &hacker_news.uri(),
+        ":memory:",
+        "[refresh]\nconcurrency = 4",
+        &["refresh"],
+        "",
+    )
+    .await;
+
+    let requested = requested.lock().unwrap();
+    assert_eq!(requested.len(), 40);
+    let front_page_done =
+        requested.iter().find(|(id, _)| *id == 1).unwrap().1 + Duration::from_secs(1);
+    for (id, at) in requested.iter() {
+        assert_eq!(
+            *id > 30,
+            *at >= front_page_done,
+            "item {} was fetched out of turn",
+            id
+        );
+    }
+}
+
+#[tokio::test]
+async fn refresh_progress_is_streamed_to_visitors() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+
+    let response = reqwest::get(format!("{}/refresh/events", server.url))
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    // The refresh has finished, so the stream ends with its final status.
+    let events = response.text().await.unwrap();
+    assert!(events.contains("event: progress\n"));
+    assert!(events.contains("\"status\":\"finished\""));
+    assert!(!events.contains("\"error\":\""));
+}
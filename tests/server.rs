@@ -0,0 +1,273 @@
+//! End-to-end tests of running the server: its sockets, service manager and load.
+mod common;
+
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use common::{fake_hacker_news, free_port, Server, ADMIN_CONFIG};
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn systemd_is_notified_once_ready() {
+    let hacker_news = fake_hacker_news().await;
+    let path = std::env::temp_dir().join(format!("hnv-e2e-notify-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let socket = tokio::net::UnixDatagram::bind(&path).unwrap();
+    let server = Server::start_with_env(
+        &hacker_news.uri(),
+        "",
+        &[
+            ("NOTIFY_SOCKET", path.to_str().unwrap()),
+            ("WATCHDOG_USEC", "200000"),
+        ],
+    );
+
+    let mut received = Vec::new();
+    let mut buf = [0; 256];
+    while !received.contains(&"WATCHDOG=1".to_string())
+        || !received.contains(&"READY=1".to_string())
+    {
+        let len = tokio::time::timeout(Duration::from_secs(10), socket.recv(&mut buf))
+            .await
+            .expect("systemd was never notified")
+            .unwrap();
+        received.push(String::from_utf8_lossy(&buf[..len]).into_owned());
+    }
+    // Ready only once there is an index to serve.
+    let index = reqwest::get(format!("{}/", server.url))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(index.contains("A talk about Rust"));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn the_watchdog_starves_while_a_refresh_hangs() {
+    let hacker_news = fake_hacker_news().await;
+    Mock::given(method("GET"))
+        .and(path("/topstories.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([1, 2, 3])))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&hacker_news)
+        .await;
+    // The refreshes after the first never get the list.
+    Mock::given(method("GET"))
+        .and(path("/topstories.json"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!([1, 2, 3]))
+                .set_delay(Duration::from_secs(60)),
+        )
+        .with_priority(2)
+        .mount(&hacker_news)
+        .await;
+    let path = std::env::temp_dir().join(format!("hnv-e2e-starve-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let socket = tokio::net::UnixDatagram::bind(&path).unwrap();
+    let _server = Server::start_with_env(
+        &hacker_news.uri(),
+        "refresh.cron = \"* * * * * *\"\nrefresh.jitter = 0",
+        &[
+            ("NOTIFY_SOCKET", path.to_str().unwrap()),
+            ("WATCHDOG_USEC", "200000"),
+        ],
+    );
+
+    // Fed every 100ms while waiting, the watchdog goes quiet once the hanging refresh started.
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut buf = [0; 256];
+    let mut fed = 0;
+    while let Ok(len) = tokio::time::timeout(Duration::from_secs(2), socket.recv(&mut buf)).await {
+        if &buf[..len.unwrap()] == b"WATCHDOG=1" {
+            fed += 1;
+        }
+        assert!(Instant::now() < deadline, "the watchdog was fed all along");
+    }
+    assert!(fed > 0);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn saturated_pages_still_serve_the_assets() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start_with(&fake.uri(), "concurrency.pages = 1");
+    let page = server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    let stylesheet = page
+        .split("<link href=\"")
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .unwrap()
+        .to_owned();
+    Mock::given(method("GET"))
+        .and(path("/item/9.json"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(3)))
+        .with_priority(1)
+        .mount(&fake)
+        .await;
+
+    // The page of an unknown item waits on Hacker News, holding the only page slot.
+    let blocked = tokio::spawn(reqwest::get(format!("{}/watch/9", server.url)));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let page = reqwest::get(format!("{}/archive", server.url))
+        .await
+        .unwrap();
+    assert_eq!(page.status(), 503);
+    let asset = reqwest::get(format!("{}{}", server.url, stylesheet))
+        .await
+        .unwrap();
+    assert_eq!(asset.status(), 200);
+    blocked.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn shed_visitors_are_shown_the_index_snapshot() {
+    let fake = fake_hacker_news().await;
+    let config = format!(
+        "concurrency.pages = 1\noverload.serve_index = true\n{}",
+        ADMIN_CONFIG
+    );
+    let server = Server::start_with(&fake.uri(), &config);
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    // The snapshot is rendered once the refresh is done.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    Mock::given(method("GET"))
+        .and(path("/item/9.json"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(3)))
+        .with_priority(1)
+        .mount(&fake)
+        .await;
+
+    let blocked = tokio::spawn(reqwest::get(format!("{}/watch/9", server.url)));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let index = reqwest::get(format!("{}/", server.url)).await.unwrap();
+    assert_eq!(index.status(), 503);
+    assert_eq!(index.headers()["retry-after"], "5");
+    assert!(index.text().await.unwrap().contains("A talk about Rust"));
+    let archive = reqwest::get(format!("{}/archive", server.url))
+        .await
+        .unwrap();
+    assert_eq!(archive.status(), 503);
+    assert_eq!(archive.headers()["retry-after"], "5");
+    assert!(!archive.text().await.unwrap().contains("A talk about Rust"));
+    blocked.await.unwrap().unwrap();
+
+    let admin = reqwest::Client::new()
+        .get(format!("{}/admin", server.url))
+        .basic_auth("admin", Some("admin"))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(admin.contains("<th>Requests turned away</th><td>2</td>"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn sockets_are_inherited_from_the_service_manager() {
+    use std::os::fd::AsRawFd;
+
+    use socket2::{Domain, Socket, Type};
+
+    let hacker_news = fake_hacker_news().await;
+    // Stand in for systemd, which binds the socket and hands it down at startup.
+    let socket = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+    socket
+        .bind(
+            &"127.0.0.1:0"
+                .parse::<std::net::SocketAddr>()
+                .unwrap()
+                .into(),
+        )
+        .unwrap();
+    socket.listen(16).unwrap();
+    socket.set_cloexec(false).unwrap();
+    let port = socket.local_addr().unwrap().as_socket().unwrap().port();
+    let fd = socket.as_raw_fd().to_string();
+
+    let server = Server::start_with_env(
+        &hacker_news.uri(),
+        "",
+        &[("LISTEN_FDS", "1"), ("LISTEN_FDS_FIRST_FD", &fd)],
+    );
+    let url = format!("http://127.0.0.1:{}", port);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        // Connections made before the server started wait in the backlog of the socket.
+        let page = reqwest::get(&url).await.unwrap().text().await.unwrap();
+        if page.contains("A talk about Rust") {
+            break;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "the index never listed the videos"
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    // The inherited socket replaces the configured addresses.
+    assert!(reqwest::get(&server.url).await.is_err());
+}
+
+#[tokio::test]
+async fn pages_are_served_when_traces_cant_be_exported() {
+    let hacker_news = fake_hacker_news().await;
+    let collector = format!("http://127.0.0.1:{}", free_port());
+    let server = Server::start_with_env(
+        &hacker_news.uri(),
+        "",
+        &[("OTEL_EXPORTER_OTLP_ENDPOINT", &collector)],
+    );
+
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+}
+
+#[tokio::test]
+async fn failed_refreshes_are_reported_to_sentry() {
+    let hacker_news = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/topstories.json"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&hacker_news)
+        .await;
+    let sentry = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/1/envelope/"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&sentry)
+        .await;
+    let dsn = sentry.uri().replace("http://", "http://public@") + "/1";
+    let _server = Server::start_with_env(&hacker_news.uri(), "", &[("SENTRY_DSN", &dsn)]);
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let envelope = loop {
+        let requests = sentry.received_requests().await.unwrap();
+        if let Some(request) = requests.first() {
+            break String::from_utf8_lossy(&request.body).into_owned();
+        }
+        assert!(Instant::now() < deadline, "nothing was reported");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    };
+    assert!(envelope.contains("\"type\":\"event\""));
+    assert!(envelope.contains("Refresh 1 failed"));
+}
@@ -0,0 +1,210 @@
+//! End-to-end tests of the sources of videos and what is kept of them.
+mod common;
+
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use common::{
+    fake_hacker_news, fake_lobsters, fake_subreddit, run_with_config, Server, ADMIN_CONFIG,
+};
+
+#[tokio::test]
+async fn reposts_are_collapsed_into_one_video() {
+    let fake = fake_hacker_news().await;
+    Mock::given(method("GET"))
+        .and(path("/topstories.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([1, 2, 3, 4])))
+        .with_priority(1)
+        .mount(&fake)
+        .await;
+    // The first video again, under another URL.
+    Mock::given(method("GET"))
+        .and(path("/item/4.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": 4,
+            "type": "story",
+            "title": "A talk about Rust (2019)",
+            "url": "https://youtu.be/dQw4w9WgXcQ",
+            "score": 30,
+            "descendants": 5,
+        })))
+        .mount(&fake)
+        .await;
+    let server = Server::start(&fake.uri());
+
+    let api = server
+        .get_when("/api/v1/videos", |body| body.contains("A talk about Rust"))
+        .await;
+    let videos: Value = serde_json::from_str(&api).unwrap();
+    assert_eq!(videos.as_array().unwrap().len(), 2);
+    assert_eq!(videos[0]["reposts"][0]["id"], 4);
+    assert_eq!(videos[0]["reposts"][0]["score"], 30);
+    assert!(videos[1].get("reposts").is_none());
+
+    let index = server.get_when("/", |_| true).await;
+    assert!(!index.contains("A talk about Rust (2019)"));
+    assert!(index.contains("also discussed in"));
+    assert!(index.contains("/v/4/discussion\">30 points</a>"));
+    // The repost can still be watched on its own.
+    let watch = server.get_when("/watch/4", |_| true).await;
+    assert!(watch.contains("A talk about Rust (2019)"));
+}
+
+#[tokio::test]
+async fn lobsters_videos_are_merged_and_filterable() {
+    let fake = fake_hacker_news().await;
+    fake_lobsters(&fake).await;
+    let config = format!("[lobsters]\nenabled = true\nurl = \"{}\"", fake.uri());
+    let server = Server::start_with(&fake.uri(), &config);
+
+    let body = server
+        .get_when("/", |body| body.contains("A Lobsters talk"))
+        .await;
+    assert!(body.contains("A talk about Rust"));
+    assert!(!body.contains("A Lobsters article"));
+    assert!(body.contains("/watch/lobsters-abc123"));
+
+    let body = server
+        .get_when("/?source=lobsters", |body| body.contains("A Lobsters talk"))
+        .await;
+    assert!(!body.contains("A talk about Rust"));
+
+    let body = server
+        .get_when("/watch/lobsters-abc123", |body| {
+            body.contains("A Lobsters talk")
+        })
+        .await;
+    assert!(body.contains("/v/lobsters-abc123/discussion"));
+}
+
+#[tokio::test]
+async fn sources_are_merged_as_configured() {
+    let fake = fake_hacker_news().await;
+    fake_lobsters(&fake).await;
+    let config = format!(
+        "source_order = [\"lobsters\"]\n[hacker_news]\nweight = 2\n[lobsters]\nenabled = true\nurl = \"{}\"",
+        fake.uri()
+    );
+    let server = Server::start_with(&fake.uri(), &config);
+
+    // Lobsters takes the first turn, then Hacker News two videos.
+    let body = server
+        .get_when("/api/v1/videos", |body| {
+            body.contains("A Lobsters talk") && body.contains("Another talk")
+        })
+        .await;
+    let videos: Vec<Value> = serde_json::from_str(&body).unwrap();
+    let titles: Vec<_> = videos.iter().map(|video| &video["title"]).collect();
+    assert_eq!(
+        titles,
+        ["A Lobsters talk", "A talk about Rust", "Another talk"]
+    );
+}
+
+#[tokio::test]
+async fn reddit_videos_are_merged() {
+    let fake = fake_hacker_news().await;
+    fake_subreddit(&fake).await;
+    let config = format!(
+        "[reddit]\nenabled = true\nsubreddits = [\"videos\"]\nurl = \"{}\"",
+        fake.uri()
+    );
+    let server = Server::start_with(&fake.uri(), &config);
+
+    let body = server
+        .get_when("/", |body| body.contains("A Reddit talk"))
+        .await;
+    assert!(body.contains("A talk about Rust"));
+    assert!(!body.contains("Subreddit rules"));
+    assert!(!body.contains("A Reddit article"));
+    assert!(body.contains("/watch/reddit-vid1"));
+}
+
+#[tokio::test]
+async fn videos_are_capped() {
+    let hacker_news = fake_hacker_news().await;
+    // The video submitted yesterday ranks first, the other counts as submitted now.
+    Mock::given(method("GET"))
+        .and(path("/topstories.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([3, 2, 1])))
+        .with_priority(1)
+        .mount(&hacker_news)
+        .await;
+    let server = Server::start_with(&hacker_news.uri(), "[memory]\nmax_videos = 1");
+
+    server
+        .get_when("/api/v1/videos", |body| body.contains("A talk about Rust"))
+        .await;
+    // Give the refresh time to get to the next video, which must not show up.
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let body = server.get_when("/api/v1/videos", |_| true).await;
+    let videos: Vec<Value> = serde_json::from_str(&body).unwrap();
+
+    let ids: Vec<_> = videos.iter().map(|video| video["id"].clone()).collect();
+    assert_eq!(ids, [json!(1)]);
+}
+
+#[tokio::test]
+async fn a_cache_on_disk_is_not_trimmed() {
+    let hacker_news = fake_hacker_news().await;
+    let database = std::env::temp_dir().join(format!("hnv-e2e-trim-{}.db", std::process::id()));
+    let database = database.to_str().unwrap();
+    let config = "[memory]\nmax_cached = 1";
+
+    run_with_config(&hacker_news.uri(), database, config, &["refresh"], "").await;
+    run_with_config(&hacker_news.uri(), database, config, &["refresh"], "").await;
+
+    // The items of the first refresh are still cached for the second.
+    let requests = hacker_news.received_requests().await.unwrap();
+    let items = requests
+        .iter()
+        .filter(|request| request.url.path().starts_with("/item/"))
+        .count();
+    assert_eq!(items, 3);
+
+    std::fs::remove_file(database).unwrap();
+}
+
+#[tokio::test]
+async fn error_responses_are_not_cached_as_items() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start_with(&fake.uri(), ADMIN_CONFIG);
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/item/1.json"))
+        .respond_with(ResponseTemplate::new(503).set_body_string("{}"))
+        .with_priority(1)
+        .up_to_n_times(1)
+        .mount(&fake)
+        .await;
+    let refresh = || {
+        reqwest::Client::new()
+            .post(format!("{}/api/v1/refresh/1", server.url))
+            .basic_auth("admin", Some("admin"))
+            .send()
+    };
+
+    assert_eq!(refresh().await.unwrap().status(), 502);
+    // The item is fetched again rather than read back from the cache.
+    assert_eq!(refresh().await.unwrap().status(), 200);
+    let api = server.get_when("/api/v1/videos", |_| true).await;
+    assert!(api.contains("A talk about Rust"));
+}
+
+#[tokio::test]
+async fn sources_are_limited_to_their_top_videos() {
+    let hacker_news = fake_hacker_news().await;
+    let server = Server::start_with(&hacker_news.uri(), "[hacker_news]\nlimit = 1");
+
+    let page = server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    assert!(!page.contains("Another talk"));
+}
@@ -0,0 +1,625 @@
+//! End-to-end tests of what visitors keep: their lists, playlists, sessions and history.
+mod common;
+
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{header, method, path, path_regex},
+    Mock, ResponseTemplate,
+};
+
+use common::{
+    cookie_of, fake_hacker_news, no_redirects, requests_to, run_with_config, Server, ADMIN_CONFIG,
+};
+
+#[tokio::test]
+async fn videos_are_loved_by_visitors() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    // Every visitor without a cookie gets a session of their own.
+    let mut cookie = String::new();
+    for _ in 0..2 {
+        let response = client
+            .post(format!("{}/love/3", server.url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 303);
+        cookie = response.headers()["set-cookie"]
+            .to_str()
+            .unwrap()
+            .split(';')
+            .next()
+            .unwrap()
+            .to_string();
+    }
+
+    let index = client
+        .get(format!("{}/", server.url))
+        .header("cookie", &cookie)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(index.contains("♥ 2"));
+    assert!(index.contains("/love/3/undo"));
+    let loved = server
+        .get_when("/loved", |body| body.contains("Another talk"))
+        .await;
+    assert!(loved.contains("♥ 2"));
+    assert!(!loved.contains("A talk about Rust"));
+}
+
+#[tokio::test]
+async fn sessions_are_signed_and_ended_by_operators() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start_with(&fake.uri(), ADMIN_CONFIG);
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let loved = |cookie: String| {
+        let client = client.clone();
+        let url = format!("{}/", server.url);
+        async move {
+            let index = client
+                .get(url)
+                .header("cookie", cookie)
+                .send()
+                .await
+                .unwrap()
+                .text()
+                .await
+                .unwrap();
+            index.contains("/love/3/undo")
+        }
+    };
+
+    let response = client
+        .post(format!("{}/love/3", server.url))
+        .send()
+        .await
+        .unwrap();
+    let cookie = response.headers()["set-cookie"]
+        .to_str()
+        .unwrap()
+        .split(';')
+        .next()
+        .unwrap()
+        .to_string();
+    assert!(loved(cookie.clone()).await);
+
+    // Neither a forged or stripped signature nor an unknown session are accepted.
+    let (id, _) = cookie.rsplit_once('.').unwrap();
+    assert!(!loved(format!("{}.forged", id)).await);
+    assert!(!loved(id.to_string()).await);
+    assert!(!loved("session=00000000-0000-0000-0000-000000000000".to_string()).await);
+
+    let response = client
+        .post(format!("{}/admin/sessions/end", server.url))
+        .basic_auth("admin", Some("admin"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 303);
+    assert!(!loved(cookie).await);
+}
+
+#[tokio::test]
+async fn watch_history_is_resumed() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("{}/history/3", server.url))
+        .form(&[("position", "125")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+    let cookie = response.headers()["set-cookie"]
+        .to_str()
+        .unwrap()
+        .split(';')
+        .next()
+        .unwrap()
+        .to_string();
+    let get = |path: &str| {
+        client
+            .get(format!("{}{}", server.url, path))
+            .header("cookie", &cookie)
+            .send()
+    };
+
+    let index = get("/").await.unwrap().text().await.unwrap();
+    assert!(index.contains("Continue watching"));
+    assert!(index.contains("Stopped at 2:05"));
+    let watch = get("/watch/3").await.unwrap().text().await.unwrap();
+    assert!(watch.contains("start=125"));
+    assert!(watch.contains("data-history"));
+
+    get("/watch/1").await.unwrap();
+    // The history is read from the videos as listed, so it doesn't need Hacker News.
+    Mock::given(method("GET"))
+        .and(path_regex("^/item/"))
+        .respond_with(ResponseTemplate::new(500))
+        .with_priority(1)
+        .mount(&fake)
+        .await;
+    let history = get("/history").await.unwrap().text().await.unwrap();
+    let rust = history.find("A talk about Rust").unwrap();
+    assert!(rust < history.find("Another talk").unwrap());
+
+    // Watching to the end takes the video off the index.
+    client
+        .post(format!("{}/history/3", server.url))
+        .header("cookie", &cookie)
+        .form(&[("position", "0")])
+        .send()
+        .await
+        .unwrap();
+    let index = get("/").await.unwrap().text().await.unwrap();
+    assert!(!index.contains("Continue watching"));
+}
+
+#[tokio::test]
+async fn playlists_are_created_ordered_and_shared() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    let response = client
+        .post(format!("{}/playlists", server.url))
+        .form(&[("name", "Talks")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 303);
+    let cookie = response.headers()["set-cookie"]
+        .to_str()
+        .unwrap()
+        .split(';')
+        .next()
+        .unwrap()
+        .to_string();
+    let playlist = response.headers()["location"].to_str().unwrap().to_string();
+    let id = playlist.rsplit('/').next().unwrap().to_string();
+    let get = |path: &str| {
+        client
+            .get(format!("{}{}", server.url, path))
+            .header("cookie", &cookie)
+            .send()
+    };
+    let edit = |video: &str, edit: &str| {
+        client
+            .post(format!("{}/playlists/edit", server.url))
+            .header("cookie", &cookie)
+            .form(&[("playlist", id.as_str()), ("video", video), ("edit", edit)])
+            .send()
+    };
+
+    let watch = get("/watch/1").await.unwrap().text().await.unwrap();
+    assert!(watch.contains("Talks"));
+    for video in ["1", "3"] {
+        assert_eq!(edit(video, "add").await.unwrap().status(), 303);
+    }
+    // The videos are read as listed, so the playlist doesn't need Hacker News.
+    Mock::given(method("GET"))
+        .and(path_regex("^/item/"))
+        .respond_with(ResponseTemplate::new(500))
+        .with_priority(1)
+        .mount(&fake)
+        .await;
+    let page = get(&playlist).await.unwrap().text().await.unwrap();
+    assert!(page.contains("Talks"));
+    assert!(page.contains("/assets/playlist."));
+    assert!(page.find("A talk about Rust") < page.find("Another talk"));
+
+    edit("3", "up").await.unwrap();
+    let page = get(&playlist).await.unwrap().text().await.unwrap();
+    assert!(page.find("Another talk") < page.find("A talk about Rust"));
+
+    edit("1", "remove").await.unwrap();
+    let page = get(&playlist).await.unwrap().text().await.unwrap();
+    assert!(!page.contains("A talk about Rust"));
+    let playlists = get("/playlists").await.unwrap().text().await.unwrap();
+    assert!(playlists.contains("1 videos"));
+
+    // Other visitors can neither see nor change the playlist.
+    let response = reqwest::get(format!("{}{}", server.url, playlist))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    // Unless it is shared with them, then they can play it and subscribe to it.
+    let share = page
+        .split("href=\"")
+        .filter_map(|link| link.split('"').next())
+        .find_map(|link| link.find("/shared/").map(|start| &link[start..]))
+        .map(|path| format!("{}{}", server.url, path))
+        .unwrap();
+    let shared = reqwest::get(&share).await.unwrap().text().await.unwrap();
+    assert!(shared.contains("Another talk"));
+    assert!(!shared.contains("/playlists/edit"));
+    let feed = reqwest::get(format!("{}/feed.xml", share))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(feed.contains("<title>Another talk</title>"));
+
+    client
+        .post(format!("{}/playlists/{}/delete", server.url, id))
+        .header("cookie", &cookie)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(get(&playlist).await.unwrap().status(), 404);
+    assert_eq!(reqwest::get(&share).await.unwrap().status(), 404);
+}
+
+#[tokio::test]
+async fn watch_later_lists_are_imported_and_exported() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    // A YouTube Takeout, with a video that was never listed here.
+    let takeout = "Video ID,Playlist Video Creation Timestamp\n\
+        dQw4w9WgXcQ,2024-01-01T00:00:00+00:00\n\
+        aaaaaaaaaaa,2024-01-02T00:00:00+00:00\n";
+    let response = client
+        .post(format!("{}/later/import", server.url))
+        .form(&[("list", takeout)])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 303);
+    assert_eq!(
+        response.headers()["location"],
+        "/later?imported=1&unmatched=1"
+    );
+    let cookie = response.headers()["set-cookie"]
+        .to_str()
+        .unwrap()
+        .split(';')
+        .next()
+        .unwrap()
+        .to_string();
+
+    let exported: Value = client
+        .get(format!("{}/later/export.json", server.url))
+        .header("cookie", &cookie)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        exported,
+        json!([{
+            "id": "1",
+            "title": "A talk about Rust",
+            "url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        }])
+    );
+
+    // The export imports into another session, as does a list of URLs.
+    let response = client
+        .post(format!("{}/later/import", server.url))
+        .form(&[("list", exported.to_string())])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()["location"],
+        "/later?imported=1&unmatched=0"
+    );
+    let response = client
+        .post(format!("{}/later/import", server.url))
+        .form(&[(
+            "list",
+            "https://youtu.be/dQw4w9WgXcQ\nhttps://example.com/rust\n",
+        )])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()["location"],
+        "/later?imported=1&unmatched=1"
+    );
+}
+
+#[tokio::test]
+async fn videos_are_saved_to_read_later_services() {
+    let fake = fake_hacker_news().await;
+    Mock::given(method("POST"))
+        .and(path("/v3/add"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "status": 1 })))
+        .mount(&fake)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/oauth/v2/token"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "access_token": "wallabag-token", "expires_in": 3600 })),
+        )
+        .mount(&fake)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/entries.json"))
+        .and(header("authorization", "Bearer wallabag-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": 1 })))
+        .mount(&fake)
+        .await;
+
+    let config = format!(
+        "[pocket]\nconsumer_key = \"key\"\naccess_token = \"token\"\nurl = \"{0}\"\n\
+        [wallabag]\nurl = \"{0}\"\nclient_id = \"id\"\nclient_secret = \"secret\"\n\
+        username = \"me\"\npassword = \"password\"",
+        fake.uri()
+    );
+    let server = Server::start_with(&fake.uri(), &config);
+    let page = server
+        .get_when("/", |body| body.contains("A talk about Rust"))
+        .await;
+    assert!(page.contains("/save/pocket/1"));
+    assert!(page.contains("/save/wallabag/1"));
+
+    let client = reqwest::Client::new();
+    for service in ["pocket", "wallabag"] {
+        let response = client
+            .post(format!("{}/save/{}/1", server.url, service))
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+    }
+
+    let pocket = requests_to(&fake, "/v3/add").await;
+    assert_eq!(
+        pocket[0]["url"],
+        "https://www.youtube.com/watch?v=dQw4w9WgXcQ"
+    );
+    assert_eq!(pocket[0]["access_token"], "token");
+    let wallabag = requests_to(&fake, "/api/entries.json").await;
+    assert_eq!(wallabag[0]["title"], "A talk about Rust");
+}
+
+#[tokio::test]
+async fn videos_are_opened_in_the_player() {
+    let hacker_news = fake_hacker_news().await;
+    let played = std::env::temp_dir().join(format!("hnv-e2e-played-{}", std::process::id()));
+    let player = format!(
+        r#"player.command = ["sh", "-c", "echo \"$1 $2\" > {}", "sh", "{{title}}"]"#,
+        played.display()
+    );
+
+    run_with_config(&hacker_news.uri(), ":memory:", &player, &["open", "2"], "").await;
+
+    // The player runs on its own, after `hnv` exits.
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut line = String::new();
+    while !line.ends_with('\n') && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        line = std::fs::read_to_string(&played).unwrap_or_default();
+    }
+    assert_eq!(line, "Another talk https://youtu.be/oHg5SJYRHA0\n");
+    std::fs::remove_file(&played).unwrap();
+}
+
+#[tokio::test]
+async fn followed_users_videos_are_listed() {
+    let fake = fake_hacker_news().await;
+    Mock::given(method("GET"))
+        .and(path("/user/alice.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": "alice",
+            "submitted": [5, 1],
+        })))
+        .mount(&fake)
+        .await;
+    // Not on the front page, only among alice's submissions.
+    Mock::given(method("GET"))
+        .and(path("/item/5.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": 5,
+            "type": "story",
+            "by": "alice",
+            "title": "A talk only alice watched",
+            "url": "https://www.youtube.com/watch?v=aaaaaaaaaaa",
+            "score": 3,
+            "descendants": 0,
+        })))
+        .mount(&fake)
+        .await;
+    let server = Server::start_with(&fake.uri(), "[hacker_news]\nfollowed = [\"alice\"]");
+
+    let page = server
+        .get_when("/followed", |body| {
+            body.contains("A talk only alice watched")
+        })
+        .await;
+    assert!(page.contains("alice"));
+    assert!(page.contains("A talk about Rust"));
+    let feed = server
+        .get_when("/followed.xml", |body| {
+            body.contains("A talk only alice watched")
+        })
+        .await;
+    assert!(!feed.contains("Another talk"));
+
+    let unfollowed = Server::start(&fake.uri());
+    unfollowed.get_when("/", |_| true).await;
+    let response = reqwest::get(format!("{}/followed", unfollowed.url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn videos_are_saved_to_watch_later() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+    let client = no_redirects();
+
+    let response = client
+        .post(format!("{}/later/3", server.url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 303);
+    let cookie = cookie_of(&response);
+    let later = |client: &reqwest::Client| {
+        client
+            .get(format!("{}/later", server.url))
+            .header("cookie", &cookie)
+            .send()
+    };
+    let page = later(&client).await.unwrap().text().await.unwrap();
+    assert!(page.contains("Another talk"));
+    assert!(!page.contains("A talk about Rust"));
+    // Other visitors have lists of their own.
+    let page = server.get_when("/later", |_| true).await;
+    assert!(!page.contains("Another talk"));
+
+    client
+        .post(format!("{}/later/3/remove", server.url))
+        .header("cookie", &cookie)
+        .send()
+        .await
+        .unwrap();
+    let page = later(&client).await.unwrap().text().await.unwrap();
+    assert!(!page.contains("Another talk"));
+}
+
+#[tokio::test]
+async fn hidden_videos_are_left_out_of_the_index() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+    let client = no_redirects();
+
+    let response = client
+        .post(format!("{}/hide/3", server.url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 303);
+    let cookie = cookie_of(&response);
+    let index = |query: &'static str| {
+        client
+            .get(format!("{}/{}", server.url, query))
+            .header("cookie", &cookie)
+            .send()
+    };
+    let page = index("").await.unwrap().text().await.unwrap();
+    assert!(page.contains("A talk about Rust"));
+    assert!(!page.contains("Another talk"));
+    assert!(page.contains("?show_hidden=true"));
+    let page = index("?show_hidden=true")
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(page.contains("Another talk"));
+
+    client
+        .post(format!("{}/hide/3/undo", server.url))
+        .header("cookie", &cookie)
+        .send()
+        .await
+        .unwrap();
+    let page = index("").await.unwrap().text().await.unwrap();
+    assert!(page.contains("Another talk"));
+}
+
+#[tokio::test]
+async fn videos_are_queued_in_the_player() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+
+    let page = server.get_when("/playlist", |_| true).await;
+    assert!(page.contains("dQw4w9WgXcQ"));
+    assert!(page.contains("oHg5SJYRHA0"));
+    assert!(page.contains("/playlist.m3u"));
+
+    let response = reqwest::get(format!("{}/playlist.m3u", server.url))
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()["content-type"],
+        "audio/x-mpegurl; charset=utf-8"
+    );
+    let m3u = response.text().await.unwrap();
+    assert!(m3u.starts_with("#EXTM3U\n"));
+    assert!(
+        m3u.contains("#EXTINF:-1,A talk about Rust\nhttps://www.youtube.com/watch?v=dQw4w9WgXcQ\n")
+    );
+    assert!(m3u.contains("#EXTINF:-1,Another talk\nhttps://youtu.be/oHg5SJYRHA0\n"));
+}
+
+#[tokio::test]
+async fn outbound_links_dont_tell_the_referrer() {
+    let fake = fake_hacker_news().await;
+    let server = Server::start(&fake.uri());
+    server
+        .get_when("/", |body| body.contains("Another talk"))
+        .await;
+    let client = no_redirects();
+
+    for (path, location) in [
+        ("/v/3", "https://youtu.be/oHg5SJYRHA0"),
+        ("/v/3/discussion", "https://news.ycombinator.com/item?id=3"),
+    ] {
+        let response = client
+            .get(format!("{}{}", server.url, path))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 302);
+        assert_eq!(response.headers()["location"], location);
+        assert_eq!(response.headers()["referrer-policy"], "no-referrer");
+    }
+}